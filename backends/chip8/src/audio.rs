@@ -1,50 +1,154 @@
 use axwemulator_core::{
+    audio::{BlipBuffer, SquareOscillator},
     backend::{
-        Backend,
-        component::{Addressable, Steppable, Transmutable},
+        Backend, ExecutionEvent,
+        component::{Addressable, Resettable, Saveable, Steppable, Transmutable},
+        snapshot::{StateReader, StateWriter},
     },
     error::Error,
-    frontend::audio::AudioSender,
+    frontend::audio::{AudioSender, Sample},
 };
-use femtos::Duration;
+use femtos::{Duration, Instant};
 
 use crate::ST_TIMER;
 
 pub const AUDIO_SAMPLING_RATE: f32 = 48_000.0;
 pub const AUDIO_CLOCK_SPEED_NS: u64 = 1_000_000_000 / (AUDIO_SAMPLING_RATE as u64);
 
+const BUZZER_FREQUENCY: u64 = 440;
+const BUZZER_AMPLITUDE: Sample = 0.25;
+
 pub struct Audio {
-    sample_clock: f32,
+    buzzer: SquareOscillator,
+    blip: BlipBuffer,
     audio_sender: AudioSender,
+    // Every beep lasts at least this long, as a single timer tick is barely audible
+    min_duration: Duration,
+    last_st: u8,
+    hold_until: Instant,
 }
 
 impl Audio {
-    pub fn new(audio_sender: AudioSender) -> Self {
+    pub fn new(audio_sender: AudioSender, min_duration: Duration) -> Self {
         Self {
-            sample_clock: 0.0,
+            buzzer: SquareOscillator::new(BUZZER_FREQUENCY, AUDIO_SAMPLING_RATE as u64),
+            blip: BlipBuffer::new(),
             audio_sender,
+            min_duration,
+            last_st: 0,
+            hold_until: Instant::START,
+        }
+    }
+
+    // A beep starts whenever ST is raised, either from zero or by reloading it with a larger value
+    fn is_buzzing(&mut self, st: u8, clock: Instant) -> bool {
+        if st > self.last_st {
+            self.hold_until = clock + self.min_duration;
         }
+        self.last_st = st;
+        st != 0 || clock < self.hold_until
     }
 }
 
 impl Steppable for Audio {
     fn step(&mut self, backend: &Backend) -> Result<Duration, Error> {
         let st = backend.get_bus().read_u8(ST_TIMER)?;
+        // The buzzer line is the only signal of the machine
+        if st > self.last_st {
+            backend.signal(ExecutionEvent::Signal);
+        }
 
-        self.sample_clock = (self.sample_clock + 1.0) % AUDIO_SAMPLING_RATE;
-        let sample = if st > 0 {
-            (self.sample_clock * 440.0 * 2.0 * std::f32::consts::PI / AUDIO_SAMPLING_RATE).sin()
+        let amplitude = if self.is_buzzing(st, backend.get_current_clock()) {
+            BUZZER_AMPLITUDE
         } else {
             0.0
         };
-        self.audio_sender.add(backend.get_current_clock(), sample);
+        self.buzzer.set_amplitude(amplitude, &mut self.blip);
+        self.buzzer.advance(&mut self.blip);
+        self.audio_sender
+            .add(backend.get_current_clock(), self.blip.next_sample());
 
         Ok(Duration::from_nanos(AUDIO_CLOCK_SPEED_NS))
     }
 }
 
+// The phase of the oscillator is left out, it only affects the output and not the machine
+impl Saveable for Audio {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.last_st);
+        writer.write_instant(self.hold_until);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), Error> {
+        self.last_st = reader.read_u8()?;
+        self.hold_until = reader.read_instant()?;
+        Ok(())
+    }
+}
+
+impl Resettable for Audio {
+    fn reset(&mut self) {
+        self.last_st = 0;
+        self.hold_until = Instant::START;
+    }
+}
+
 impl Transmutable for Audio {
     fn as_steppable(&mut self) -> Option<&mut dyn Steppable> {
         Some(self)
     }
+
+    fn as_saveable(&mut self) -> Option<&mut dyn Saveable> {
+        Some(self)
+    }
+
+    fn as_resettable(&mut self) -> Option<&mut dyn Resettable> {
+        Some(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axwemulator_core::{frontend::audio::build_audio_channel, testing::at_ms};
+
+    use super::*;
+
+    fn audio(min_duration_ms: u64) -> Audio {
+        let (audio_sender, _) = build_audio_channel(AUDIO_SAMPLING_RATE, 16);
+        Audio::new(audio_sender, Duration::from_millis(min_duration_ms))
+    }
+
+    #[test]
+    fn short_beep_is_held_for_min_duration() {
+        let mut audio = audio(50);
+        assert!(audio.is_buzzing(1, at_ms(0)));
+        assert!(audio.is_buzzing(0, at_ms(17)));
+        assert!(audio.is_buzzing(0, at_ms(49)));
+        assert!(!audio.is_buzzing(0, at_ms(50)));
+    }
+
+    #[test]
+    fn long_beep_follows_the_timer() {
+        let mut audio = audio(50);
+        assert!(audio.is_buzzing(6, at_ms(0)));
+        assert!(audio.is_buzzing(1, at_ms(83)));
+        assert!(!audio.is_buzzing(0, at_ms(100)));
+    }
+
+    #[test]
+    fn reloading_the_timer_starts_a_new_beep() {
+        let mut audio = audio(50);
+        audio.is_buzzing(1, at_ms(0));
+        audio.is_buzzing(0, at_ms(17));
+        assert!(audio.is_buzzing(1, at_ms(60)));
+        assert!(audio.is_buzzing(0, at_ms(77)));
+        assert!(!audio.is_buzzing(0, at_ms(110)));
+    }
+
+    #[test]
+    fn zero_min_duration_keeps_raw_timer() {
+        let mut audio = audio(0);
+        assert!(audio.is_buzzing(1, at_ms(0)));
+        assert!(!audio.is_buzzing(0, at_ms(1)));
+    }
 }
@@ -0,0 +1,187 @@
+use std::{
+    collections::BTreeSet,
+    fmt::Write,
+    sync::{Mutex, OnceLock, PoisonError},
+};
+
+use crate::cpu::{CpuQuirks, Instruction};
+
+// Names the file the report is written to, e.g.
+// CHIP8_COVERAGE_REPORT=coverage.txt cargo test -p axwemulator-backends-chip8
+const REPORT_PATH_VARIABLE: &str = "CHIP8_COVERAGE_REPORT";
+
+// An instruction executed with a quirk set one way, None for the instruction itself
+type Branch = (&'static str, Option<(&'static str, bool)>);
+
+// Everything executed by the tests of the crate so far. They run in parallel threads of one
+// process, so the report is rewritten whenever something new was executed.
+static EXECUTED: Mutex<BTreeSet<Branch>> = Mutex::new(BTreeSet::new());
+
+fn instruction_name(instruction: &Instruction) -> &'static str {
+    let name = format!("{:?}", instruction);
+    let name = name.split('(').next().unwrap_or_default();
+    variants()
+        .iter()
+        .find(|(known, _)| *known == name)
+        .map_or("Unknown", |(known, _)| known)
+}
+
+// Every variant which can be decoded with the quirks it has branches for, in the order of their
+// opcodes
+fn variants() -> &'static [(&'static str, Vec<&'static str>)] {
+    static VARIANTS: OnceLock<Vec<(&'static str, Vec<&'static str>)>> = OnceLock::new();
+    VARIANTS.get_or_init(|| {
+        let mut variants: Vec<(&'static str, Vec<&'static str>)> = vec![];
+        for opcode in 0..=u16::MAX {
+            let instruction = Instruction::from(opcode);
+            let name = format!("{:?}", instruction);
+            let name = name.split('(').next().unwrap_or_default();
+            if variants.iter().any(|(known, _)| *known == name) {
+                continue;
+            }
+            let quirks = quirk_branches(&instruction, &CpuQuirks::default())
+                .into_iter()
+                .map(|(quirk, _)| quirk)
+                .collect();
+            variants.push((String::leak(name.to_string()), quirks));
+        }
+        variants
+    })
+}
+
+// The quirks the instruction takes a different branch for
+fn quirk_branches(instruction: &Instruction, quirks: &CpuQuirks) -> Vec<(&'static str, bool)> {
+    match instruction {
+        Instruction::Or(..) | Instruction::And(..) | Instruction::Xor(..) => vec![(
+            "logic leaves flag unmodified",
+            quirks.quirks_logic_leaves_flag_unmodified,
+        )],
+        Instruction::ShiftRight(..) | Instruction::ShiftLeft(..) => vec![(
+            "shift takes x instead of y",
+            quirks.quirks_shift_takes_x_instead_of_y,
+        )],
+        Instruction::JumpV0(..) => vec![("jump uses x", quirks.quirks_jump_uses_x)],
+        Instruction::Draw(..) => vec![
+            (
+                "draw not waiting for vblank",
+                quirks.quirks_draw_not_waiting_for_vblank,
+            ),
+            (
+                "draw counts colliding rows",
+                quirks.quirks_draw_counts_colliding_rows,
+            ),
+        ],
+        Instruction::StoreAllV(..) | Instruction::LoadAllV(..) => vec![
+            (
+                "loadstore leaves i unmodified",
+                quirks.quirks_loadstore_leaves_i_unmodified,
+            ),
+            (
+                "loadstore modifies i one less",
+                quirks.quirks_loadstore_modifies_i_one_less,
+            ),
+        ],
+        Instruction::ScrollDown(..)
+        | Instruction::ScrollUp(..)
+        | Instruction::ScrollRight
+        | Instruction::ScrollLeft => vec![(
+            "lores scroll half pixel",
+            quirks.quirks_lores_scroll_half_pixel,
+        )],
+        _ => vec![],
+    }
+}
+
+// Called by the cpu for every instruction it executes in tests
+pub(crate) fn record(instruction: &Instruction, quirks: &CpuQuirks) {
+    let name = instruction_name(instruction);
+    let mut executed = EXECUTED.lock().unwrap_or_else(PoisonError::into_inner);
+    let mut changed = executed.insert((name, None));
+    for quirk in quirk_branches(instruction, quirks) {
+        changed |= executed.insert((name, Some(quirk)));
+    }
+    if !changed {
+        return;
+    }
+    if let Ok(path) = std::env::var(REPORT_PATH_VARIABLE) {
+        if let Err(error) = std::fs::write(&path, report(&executed)) {
+            eprintln!("unable to write coverage report to {}: {}", path, error);
+        }
+    }
+}
+
+// Lists every instruction, the ones never executed and the quirk branches never taken are marked
+fn report(executed: &BTreeSet<Branch>) -> String {
+    let branches: Vec<Branch> = variants()
+        .iter()
+        .flat_map(|(name, quirks)| {
+            quirks.iter().flat_map(move |quirk| {
+                [true, false].map(|enabled| (*name, Some((*quirk, enabled))))
+            })
+        })
+        .collect();
+    let mut report = format!(
+        "{} of {} instructions and {} of {} quirk branches executed, missing ones marked with -\n",
+        variants()
+            .iter()
+            .filter(|(name, _)| executed.contains(&(*name, None)))
+            .count(),
+        variants().len(),
+        branches
+            .iter()
+            .filter(|branch| executed.contains(branch))
+            .count(),
+        branches.len()
+    );
+    for (name, quirks) in variants() {
+        let marker = if executed.contains(&(*name, None)) {
+            ' '
+        } else {
+            '-'
+        };
+        let mut line = format!("{} {:<20}", marker, name);
+        for quirk in quirks {
+            let states: Vec<String> = [(true, "on"), (false, "off")]
+                .into_iter()
+                .map(|(enabled, state)| {
+                    if executed.contains(&(*name, Some((*quirk, enabled)))) {
+                        state.to_string()
+                    } else {
+                        format!("-{}", state)
+                    }
+                })
+                .collect();
+            let _ = write!(line, "  {}: {}", quirk, states.join("/"));
+        }
+        report.push_str(line.trim_end());
+        report.push('\n');
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_marks_what_was_not_executed() {
+        let mut executed = BTreeSet::new();
+        executed.insert(("Cls", None));
+        executed.insert(("Or", None));
+        executed.insert(("Or", Some(("logic leaves flag unmodified", true))));
+        let report = report(&executed);
+
+        assert!(report.starts_with("2 of "));
+        assert!(report.contains("\n  Cls\n"));
+        assert!(report.contains("\n- Return\n"));
+        assert!(report.contains("  Or                    logic leaves flag unmodified: on/-off\n"));
+        assert!(report.contains("- Draw"));
+    }
+
+    #[test]
+    fn names_the_variants_of_opcodes() {
+        assert_eq!(instruction_name(&Instruction::from(0x00E0)), "Cls");
+        assert_eq!(instruction_name(&Instruction::from(0xD015)), "Draw");
+        assert!(variants().iter().any(|(name, _)| *name == "Unknown"));
+    }
+}
@@ -1,35 +1,79 @@
-use std::fmt::Display;
+use std::{fmt::Display, ops::Range};
 
 use axwemulator_core::{
     backend::{
-        Backend,
-        component::{Addressable, Inspectable, MemoryAddress, Steppable, Transmutable},
+        Backend, ExecutionEvent,
+        component::{
+            Addressable, Debuggable, Disassemblable, DisassembledInstruction, Inspectable, Keypad,
+            MemoryAddress, MemorySize, Resettable, Saveable, StackFrame, Steppable, Traceable,
+            Transmutable,
+        },
+        options::OptionValues,
+        snapshot::{StateReader, StateWriter},
     },
     error::Error,
     frontend::{
-        graphics::{Frame, FrameSender},
-        input::{ButtonState, InputEvent, InputReceiver},
+        debug::DebugSender,
+        graphics::{Frame, FrameSender, Pixel},
+        input::{ButtonState, InputEvent, InputReceiver, KeyboardEventKey},
+        trace::{TraceEntry, TraceSender, register_delta},
     },
 };
-use femtos::Duration;
+use femtos::{Duration, Instant};
 
 use crate::{
-    DT_TIMER, FONT_BASE, Platform, ST_TIMER,
+    DT_TIMER, FONT_BASE, OPTION_COLOR_BACKGROUND, OPTION_COLOR_FOREGROUND,
+    OPTION_INSTRUCTIONS_PER_SECOND, OPTION_PHOSPHOR_PERSISTENCE, OPTION_QUIRK_COLLISION_ROWS,
+    OPTION_QUIRK_JUMP, OPTION_QUIRK_LOADSTORE_I_ONE_LESS, OPTION_QUIRK_LOADSTORE_I_UNMODIFIED,
+    OPTION_QUIRK_LOGIC, OPTION_QUIRK_LORES_HALF_SCROLL, OPTION_QUIRK_SHIFT, OPTION_QUIRK_VBLANK,
+    Platform, ST_TIMER,
     input::{InputButton, KeypadState},
 };
 
-pub const CLOCK_SPEED_NS: u64 = 1_000_000_000 / 700;
 pub const VBLANK_CLOCK_SPEED_NS: u64 = 1_000_000_000 / 60;
-pub const FRAME_DIMENSIONS: (usize, usize) = (64, 32);
+// The frame buffer always has the hires resolution, lores pixels cover 2x2 buffer pixels
+pub const FRAME_DIMENSIONS: (usize, usize) = (128, 64);
+pub const LORES_DIMENSIONS: (usize, usize) = (64, 32);
 
 #[derive(Default)]
 pub struct CpuQuirks {
-    quirks_shift_takes_x_instead_of_y: bool,
-    quirks_loadstore_leaves_i_unmodified: bool,
-    quirks_loadstore_modifies_i_one_less: bool,
-    quirks_jump_uses_x: bool,
-    quirks_draw_not_waiting_for_vblank: bool,
-    quirks_logic_leaves_flag_unmodified: bool,
+    pub quirks_shift_takes_x_instead_of_y: bool,
+    pub quirks_loadstore_leaves_i_unmodified: bool,
+    pub quirks_loadstore_modifies_i_one_less: bool,
+    pub quirks_jump_uses_x: bool,
+    pub quirks_draw_not_waiting_for_vblank: bool,
+    pub quirks_logic_leaves_flag_unmodified: bool,
+    pub quirks_lores_scroll_half_pixel: bool,
+    pub quirks_draw_counts_colliding_rows: bool,
+}
+
+impl From<&OptionValues> for CpuQuirks {
+    fn from(value: &OptionValues) -> Self {
+        Self {
+            quirks_shift_takes_x_instead_of_y: value
+                .get_bool(OPTION_QUIRK_SHIFT)
+                .unwrap_or_default(),
+            quirks_loadstore_leaves_i_unmodified: value
+                .get_bool(OPTION_QUIRK_LOADSTORE_I_UNMODIFIED)
+                .unwrap_or_default(),
+            quirks_loadstore_modifies_i_one_less: value
+                .get_bool(OPTION_QUIRK_LOADSTORE_I_ONE_LESS)
+                .unwrap_or_default(),
+            quirks_jump_uses_x: value.get_bool(OPTION_QUIRK_JUMP).unwrap_or_default(),
+            quirks_draw_not_waiting_for_vblank: value
+                .get_bool(OPTION_QUIRK_VBLANK)
+                .unwrap_or_default(),
+            quirks_logic_leaves_flag_unmodified: value
+                .get_bool(OPTION_QUIRK_LOGIC)
+                .unwrap_or_default(),
+            quirks_lores_scroll_half_pixel: value
+                .get_bool(OPTION_QUIRK_LORES_HALF_SCROLL)
+                .unwrap_or_default(),
+            quirks_draw_counts_colliding_rows: value
+                .get_bool(OPTION_QUIRK_COLLISION_ROWS)
+                .unwrap_or_default(),
+        }
+    }
 }
 
 impl From<Platform> for CpuQuirks {
@@ -42,6 +86,8 @@ impl From<Platform> for CpuQuirks {
                 quirks_jump_uses_x: false,
                 quirks_draw_not_waiting_for_vblank: false,
                 quirks_logic_leaves_flag_unmodified: false,
+                quirks_lores_scroll_half_pixel: false,
+                quirks_draw_counts_colliding_rows: false,
             },
             Platform::SuperChip => Self {
                 quirks_shift_takes_x_instead_of_y: true,
@@ -50,6 +96,8 @@ impl From<Platform> for CpuQuirks {
                 quirks_jump_uses_x: true,
                 quirks_draw_not_waiting_for_vblank: true,
                 quirks_logic_leaves_flag_unmodified: true,
+                quirks_lores_scroll_half_pixel: true,
+                quirks_draw_counts_colliding_rows: true,
             },
         }
     }
@@ -61,9 +109,12 @@ pub struct CpuState {
     pc: u16,
     sp: u8,
     stack: [u16; 16],
+    // Subroutine each stack entry called, only kept for debuggers
+    call_targets: [u16; 16],
     paused: bool,
     waiting_for_key: Option<usize>,
     waiting_for_vblank: bool,
+    hires: bool,
     frame_buffer: [bool; FRAME_DIMENSIONS.0 * FRAME_DIMENSIONS.1],
     keypad_state: KeypadState,
 }
@@ -76,9 +127,11 @@ impl Default for CpuState {
             pc: Default::default(),
             sp: Default::default(),
             stack: Default::default(),
+            call_targets: Default::default(),
             paused: Default::default(),
             waiting_for_key: Default::default(),
             waiting_for_vblank: Default::default(),
+            hires: Default::default(),
             frame_buffer: [false; FRAME_DIMENSIONS.0 * FRAME_DIMENSIONS.1],
             keypad_state: KeypadState::new(),
         }
@@ -92,6 +145,48 @@ impl CpuState {
             ..Default::default()
         }
     }
+
+    fn resolution(&self) -> (usize, usize) {
+        if self.hires {
+            FRAME_DIMENSIONS
+        } else {
+            LORES_DIMENSIONS
+        }
+    }
+
+    fn pixel_scale(&self) -> usize {
+        FRAME_DIMENSIONS.0 / self.resolution().0
+    }
+
+    // Toggles the pixel at the given position in the current resolution and returns whether it
+    // was set before
+    fn toggle_pixel(&mut self, x: usize, y: usize) -> bool {
+        let scale = self.pixel_scale();
+        let mut collision = false;
+        for buffer_y in y * scale..(y + 1) * scale {
+            for buffer_x in x * scale..(x + 1) * scale {
+                let index = buffer_y * FRAME_DIMENSIONS.0 + buffer_x;
+                collision |= self.frame_buffer[index];
+                self.frame_buffer[index] = !self.frame_buffer[index];
+            }
+        }
+        collision
+    }
+
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let (width, height) = (FRAME_DIMENSIONS.0 as isize, FRAME_DIMENSIONS.1 as isize);
+        let mut frame_buffer = [false; FRAME_DIMENSIONS.0 * FRAME_DIMENSIONS.1];
+        for y in 0..height {
+            for x in 0..width {
+                let (source_x, source_y) = (x - dx, y - dy);
+                if (0..width).contains(&source_x) && (0..height).contains(&source_y) {
+                    frame_buffer[(y * width + x) as usize] =
+                        self.frame_buffer[(source_y * width + source_x) as usize];
+                }
+            }
+        }
+        self.frame_buffer = frame_buffer;
+    }
 }
 
 impl Display for CpuState {
@@ -115,56 +210,172 @@ impl Display for CpuState {
 #[derive(Default)]
 pub struct Cpu {
     state: CpuState,
+    platform: Platform,
     quirks: CpuQuirks,
+    clock_speed_ns: u64,
+    foreground_color: Pixel,
+    background_color: Pixel,
+    // Percentage of brightness an unlit pixel keeps per vblank, 0 disables the phosphor effect
+    phosphor_persistence: u8,
+    phosphor: Vec<u8>,
+    last_vblank_idx: u64,
+    frame_pending: bool,
+    // Debugger controls, not part of the machine state
+    debug_paused: bool,
+    single_step: bool,
+    // The breakpoint the run stopped at, its instruction runs once the run continues
+    breakpoint_hit: Option<MemoryAddress>,
     frame_sender: Option<FrameSender>,
     input_receiver: Option<InputReceiver>,
+    debug_sender: Option<DebugSender>,
+    trace_sender: Option<TraceSender>,
 }
 
 impl Cpu {
     pub fn new(
         platform: Platform,
+        options: &OptionValues,
         frame_sender: FrameSender,
         input_receiver: InputReceiver,
+        debug_sender: DebugSender,
+        trace_sender: TraceSender,
     ) -> Self {
+        let instructions_per_second = options
+            .get_int(OPTION_INSTRUCTIONS_PER_SECOND)
+            .unwrap_or(700)
+            .max(1) as u64;
         Self {
             state: CpuState::new(),
-            quirks: platform.into(),
+            platform,
+            quirks: options.into(),
+            clock_speed_ns: 1_000_000_000 / instructions_per_second,
+            foreground_color: options
+                .get_color(OPTION_COLOR_FOREGROUND)
+                .unwrap_or((255, 255, 255, 255)),
+            background_color: options
+                .get_color(OPTION_COLOR_BACKGROUND)
+                .unwrap_or((0, 0, 0, 255)),
+            phosphor_persistence: options
+                .get_int(OPTION_PHOSPHOR_PERSISTENCE)
+                .unwrap_or_default()
+                .clamp(0, 100) as u8,
+            phosphor: vec![0; FRAME_DIMENSIONS.0 * FRAME_DIMENSIONS.1],
+            last_vblank_idx: 0,
+            // The blank screen is shown right away, like after a reset
+            frame_pending: true,
+            debug_paused: false,
+            single_step: false,
+            breakpoint_hit: None,
             frame_sender: Some(frame_sender),
             input_receiver: Some(input_receiver),
+            debug_sender: Some(debug_sender),
+            trace_sender: Some(trace_sender),
+        }
+    }
+
+    fn send_debug_values(&self, backend: &Backend) {
+        let Some(sender) = self.debug_sender.as_ref() else {
+            return;
+        };
+        let clock = backend.get_current_clock();
+        sender.plot(clock, "Stack depth", self.state.sp as f64);
+        let mut delay = [0];
+        if backend.get_bus().peek(DT_TIMER, &mut delay).is_ok() {
+            sender.plot(clock, "Delay timer", delay[0] as f64);
         }
+        sender.value(
+            clock,
+            "Waiting for key",
+            self.state.waiting_for_key.is_some() as u8 as f64,
+        );
     }
 
-    fn handle_input(&mut self) {
-        while let Some(ie) = self.input_receiver.as_ref().unwrap().pop() {
+    fn handle_input(&mut self, clock: Instant) {
+        while let Some(ie) = self.input_receiver.as_ref().unwrap().pop_due(clock) {
             self.state.keypad_state.parse_input_event(ie);
 
             if let Some(x) = self.state.waiting_for_key {
-                match ie {
-                    InputEvent::Keyboard(keyboard_event_key, ButtonState::Released) => {
-                        if let Ok(button) = InputButton::try_from(keyboard_event_key) {
-                            self.state.v[x] = button.into();
-                            self.state.waiting_for_key = None;
-                        }
+                if let InputEvent::Keyboard(keyboard_event_key, ButtonState::Released) = ie {
+                    if let Ok(button) = InputButton::try_from(keyboard_event_key) {
+                        self.state.v[x] = button.into();
+                        self.state.waiting_for_key = None;
                     }
-                    _ => (),
                 }
             }
         }
     }
 
-    fn send_frame(&self, backend: &Backend) {
+    // Single steps of the debugger are not stopped, they are already meant to run the instruction
+    fn stops_at_breakpoint(&mut self, backend: &Backend) -> bool {
+        let pc = self.state.pc as MemoryAddress;
+        if self.debug_paused || self.breakpoint_hit.take() == Some(pc) {
+            return false;
+        }
+        let hit = backend.check_breakpoint(pc);
+        if hit {
+            self.breakpoint_hit = Some(pc);
+        }
+        hit
+    }
+
+    // Scroll distances are given in hires pixels. Modern interpreters scale them in lores, while
+    // the original SCHIP scrolls by half a lores pixel.
+    fn scroll_distance(&self, distance: usize) -> isize {
+        if self.state.hires || self.quirks.quirks_lores_scroll_half_pixel {
+            distance as isize
+        } else {
+            (distance * self.state.pixel_scale()) as isize
+        }
+    }
+
+    fn blend_color(&self, intensity: u8) -> Pixel {
+        let blend = |background: u8, foreground: u8| {
+            ((background as u16 * (255 - intensity) as u16 + foreground as u16 * intensity as u16)
+                / 255) as u8
+        };
+        (
+            blend(self.background_color.0, self.foreground_color.0),
+            blend(self.background_color.1, self.foreground_color.1),
+            blend(self.background_color.2, self.foreground_color.2),
+            blend(self.background_color.3, self.foreground_color.3),
+        )
+    }
+
+    // Lit pixels charge the phosphor fully, unlit ones fade out over the next vblanks
+    fn decay_phosphor(&mut self) {
+        for (intensity, lit) in self.phosphor.iter_mut().zip(self.state.frame_buffer.iter()) {
+            *intensity = if *lit {
+                255
+            } else {
+                (*intensity as u16 * self.phosphor_persistence as u16 / 100) as u8
+            };
+        }
+    }
+
+    fn send_frame(&mut self, backend: &Backend) {
         if self.frame_sender.is_none() {
             return;
         }
+        backend.signal(ExecutionEvent::Frame);
+
+        if !backend.is_render_enabled() {
+            self.frame_pending = true;
+            return;
+        }
+        self.frame_pending = false;
 
         let mut frame = Frame::new(FRAME_DIMENSIONS);
 
         for y in 0..frame.height {
             for x in 0..frame.width {
                 let index = y * frame.width + x;
-                if self.state.frame_buffer[index] {
-                    frame.data[index] = (255, 255, 255, 255);
-                }
+                frame.data[index] = if self.state.frame_buffer[index] {
+                    self.foreground_color
+                } else if self.phosphor_persistence > 0 {
+                    self.blend_color(self.phosphor[index])
+                } else {
+                    self.background_color
+                };
             }
         }
 
@@ -177,20 +388,55 @@ impl Cpu {
 
 impl Steppable for Cpu {
     fn step(&mut self, backend: &Backend) -> Result<Duration, Error> {
-        self.handle_input();
+        self.handle_input(backend.get_current_clock());
+
+        let debug_allows = !self.debug_paused || std::mem::take(&mut self.single_step);
+        if debug_allows && !self.state.paused && self.state.waiting_for_key.is_none() {
+            if self.stops_at_breakpoint(backend) {
+                return Ok(Duration::ZERO);
+            }
+            let tracing = self
+                .trace_sender
+                .as_ref()
+                .is_some_and(|sender| sender.is_enabled());
+            let before = tracing.then(|| self.trace_registers());
 
-        if !self.state.paused && self.state.waiting_for_key.is_none() {
             // fetch
-            let opcode = backend
-                .get_bus()
-                .read_u16_be(self.state.pc as MemoryAddress)?;
+            let pc = self.state.pc as MemoryAddress;
+            let opcode = backend.get_bus().read_u16_be(pc)?;
             self.state.pc += 2;
 
             // decode
-            let instruction = Instruction::from(opcode);
+            let instruction = Instruction::decode(opcode, self.platform);
 
             // execute
             instruction.execute(self, backend)?;
+            if let (Some(before), Some(sender)) = (before, self.trace_sender.as_ref()) {
+                sender.add(
+                    backend.get_current_clock(),
+                    TraceEntry {
+                        pc,
+                        opcode: opcode.to_be_bytes().to_vec(),
+                        disassembly: instruction.to_string(),
+                        register_delta: register_delta(&before, &self.trace_registers()),
+                    },
+                );
+            }
+        }
+
+        let vblank_idx =
+            backend.get_current_clock().as_duration() / Duration::from_nanos(VBLANK_CLOCK_SPEED_NS);
+        if vblank_idx != self.last_vblank_idx {
+            self.last_vblank_idx = vblank_idx;
+            self.send_debug_values(backend);
+            if self.phosphor_persistence > 0 {
+                self.decay_phosphor();
+                self.send_frame(backend);
+            }
+        }
+
+        if self.frame_pending {
+            self.send_frame(backend);
         }
 
         if !self.quirks.quirks_draw_not_waiting_for_vblank && self.state.waiting_for_vblank {
@@ -203,7 +449,7 @@ impl Steppable for Cpu {
             self.state.waiting_for_vblank = false;
             Ok(next_cpu_clock)
         } else {
-            Ok(Duration::from_nanos(CLOCK_SPEED_NS))
+            Ok(Duration::from_nanos(self.clock_speed_ns))
         }
     }
 }
@@ -224,6 +470,198 @@ impl Inspectable for Cpu {
     }
 }
 
+const REGISTER_NAMES: [&str; 16] = [
+    "V0", "V1", "V2", "V3", "V4", "V5", "V6", "V7", "V8", "V9", "VA", "VB", "VC", "VD", "VE", "VF",
+];
+
+impl Traceable for Cpu {
+    fn trace_registers(&self) -> Vec<(&'static str, u64)> {
+        let mut result = vec![
+            ("PC", self.state.pc as u64),
+            ("I", self.state.i as u64),
+            ("SP", self.state.sp as u64),
+        ];
+        for (name, value) in REGISTER_NAMES.iter().zip(self.state.v.iter()) {
+            result.push((name, *value as u64));
+        }
+        result
+    }
+}
+
+impl Keypad for Cpu {
+    fn pressed_keys(&self) -> Vec<KeyboardEventKey> {
+        self.state.keypad_state.pressed_keys()
+    }
+}
+
+impl Debuggable for Cpu {
+    fn get_pc(&self) -> MemoryAddress {
+        self.state.pc as MemoryAddress
+    }
+
+    fn pause(&mut self) {
+        self.debug_paused = true;
+    }
+
+    fn resume(&mut self) {
+        self.debug_paused = false;
+    }
+
+    fn is_paused(&self) -> bool {
+        self.debug_paused
+    }
+
+    fn step_instruction(&mut self) {
+        self.single_step = true;
+    }
+
+    fn stack_frames(&self) -> Vec<StackFrame> {
+        (0..self.state.sp as usize)
+            .rev()
+            .map(|index| StackFrame {
+                function: Some(self.state.call_targets[index] as MemoryAddress),
+                return_address: self.state.stack[index] as MemoryAddress,
+            })
+            .collect()
+    }
+}
+
+impl Disassemblable for Cpu {
+    fn disassemble(&self, address: MemoryAddress, bytes: &[u8]) -> Option<DisassembledInstruction> {
+        let opcode = u16::from_be_bytes([*bytes.first()?, *bytes.get(1)?]);
+        let (mnemonic, operands) = Instruction::decode(opcode, self.platform).parts();
+        Some(DisassembledInstruction {
+            address,
+            length: Instruction::LENGTH,
+            mnemonic: mnemonic.to_string(),
+            operands,
+        })
+    }
+}
+
+// The screen is cleared on the next step, debugger controls stay as they are
+impl Resettable for Cpu {
+    fn reset(&mut self) {
+        self.state = CpuState::new();
+        self.phosphor.fill(0);
+        self.last_vblank_idx = 0;
+        self.frame_pending = true;
+    }
+}
+
+// The registers, the stack pointer and the stack, which come before the call targets in the state
+fn write_registers(state: &CpuState, writer: &mut StateWriter) {
+    writer.write_bytes(&state.v);
+    writer.write_u16(state.i);
+    writer.write_u16(state.pc);
+    writer.write_u8(state.sp);
+    for address in state.stack {
+        writer.write_u16(address);
+    }
+}
+
+// Where the call targets lie in the state of the cpu, measured with the same writer
+pub(crate) fn call_targets_state_range() -> Range<usize> {
+    let state = CpuState::new();
+    let mut writer = StateWriter::new();
+    write_registers(&state, &mut writer);
+    let start = writer.len();
+    for address in state.call_targets {
+        writer.write_u16(address);
+    }
+    start..writer.len()
+}
+
+// States from before the call targets were saved, the stack panel then shows no callee
+pub(crate) fn migrate_state_without_call_targets(state: &[u8]) -> Result<Vec<u8>, Error> {
+    let range = call_targets_state_range();
+    if state.len() < range.start {
+        return Err(Error::new("state is truncated"));
+    }
+    let mut migrated = state.to_vec();
+    migrated.splice(range.start..range.start, vec![0; range.len()]);
+    Ok(migrated)
+}
+
+// The quirks, colors and speed come from the options and are not part of the state
+impl Saveable for Cpu {
+    fn save_state(&self, writer: &mut StateWriter) {
+        let state = &self.state;
+        write_registers(state, writer);
+        for address in state.call_targets {
+            writer.write_u16(address);
+        }
+        writer.write_bool(state.paused);
+        writer.write_bool(state.waiting_for_key.is_some());
+        writer.write_u8(state.waiting_for_key.unwrap_or_default() as u8);
+        writer.write_bool(state.waiting_for_vblank);
+        writer.write_bool(state.hires);
+        let frame_buffer: Vec<u8> = state
+            .frame_buffer
+            .chunks(8)
+            .map(|pixels| {
+                pixels
+                    .iter()
+                    .enumerate()
+                    .fold(0, |byte, (bit, lit)| byte | ((*lit as u8) << bit))
+            })
+            .collect();
+        writer.write_bytes(&frame_buffer);
+        writer.write_u16(state.keypad_state.pressed_mask());
+        writer.write_bytes(&self.phosphor);
+        writer.write_u64(self.last_vblank_idx);
+        writer.write_bool(self.frame_pending);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), Error> {
+        let mut state = CpuState::new();
+        state.v = reader
+            .read_bytes()?
+            .try_into()
+            .map_err(|_| Error::new("state has the wrong number of registers"))?;
+        state.i = reader.read_u16()?;
+        state.pc = reader.read_u16()?;
+        state.sp = reader.read_u8()?;
+        if state.sp as usize > state.stack.len() {
+            return Err(Error::new("state has a stack pointer beyond the stack"));
+        }
+        for address in state.stack.iter_mut() {
+            *address = reader.read_u16()?;
+        }
+        for address in state.call_targets.iter_mut() {
+            *address = reader.read_u16()?;
+        }
+        state.paused = reader.read_bool()?;
+        let waiting = reader.read_bool()?;
+        let register = reader.read_u8()? as usize;
+        if register >= state.v.len() {
+            return Err(Error::new("state waits for a key in an unknown register"));
+        }
+        state.waiting_for_key = waiting.then_some(register);
+        state.waiting_for_vblank = reader.read_bool()?;
+        state.hires = reader.read_bool()?;
+        let frame_buffer = reader.read_bytes()?;
+        if frame_buffer.len() * 8 != state.frame_buffer.len() {
+            return Err(Error::new("state has a frame buffer of the wrong size"));
+        }
+        for (index, lit) in state.frame_buffer.iter_mut().enumerate() {
+            *lit = frame_buffer[index / 8] & (1 << (index % 8)) != 0;
+        }
+        state.keypad_state = KeypadState::from_pressed_mask(reader.read_u16()?);
+        let phosphor = reader.read_bytes()?;
+        if phosphor.len() != self.phosphor.len() {
+            return Err(Error::new("state has a phosphor buffer of the wrong size"));
+        }
+
+        self.state = state;
+        self.breakpoint_hit = None;
+        self.phosphor.copy_from_slice(phosphor);
+        self.last_vblank_idx = reader.read_u64()?;
+        self.frame_pending = reader.read_bool()?;
+        Ok(())
+    }
+}
+
 impl Transmutable for Cpu {
     fn as_steppable(&mut self) -> Option<&mut dyn Steppable> {
         Some(self)
@@ -232,13 +670,45 @@ impl Transmutable for Cpu {
     fn as_inspectable(&mut self) -> Option<&mut dyn Inspectable> {
         Some(self)
     }
+
+    fn as_traceable(&mut self) -> Option<&mut dyn Traceable> {
+        Some(self)
+    }
+
+    fn as_keypad(&mut self) -> Option<&mut dyn Keypad> {
+        Some(self)
+    }
+
+    fn as_saveable(&mut self) -> Option<&mut dyn Saveable> {
+        Some(self)
+    }
+
+    fn as_debuggable(&mut self) -> Option<&mut dyn Debuggable> {
+        Some(self)
+    }
+
+    fn as_disassemblable(&mut self) -> Option<&mut dyn Disassemblable> {
+        Some(self)
+    }
+
+    fn as_resettable(&mut self) -> Option<&mut dyn Resettable> {
+        Some(self)
+    }
 }
 
+#[derive(Debug, PartialEq)]
 pub enum Instruction {
     // 0XXX
     Sys(MemoryAddress),
+    ScrollDown(usize),
+    ScrollUp(usize),
     Cls,
     Return,
+    ScrollRight,
+    ScrollLeft,
+    Exit,
+    LowRes,
+    HighRes,
     // 1XXX
     Jump(MemoryAddress),
     // 2XXX
@@ -290,12 +760,40 @@ pub enum Instruction {
     Unknown(u16),
 }
 
+impl Instruction {
+    // The original interpreter knows none of the SuperChip instructions, its DXY0 draws nothing
+    pub fn decode(value: u16, platform: Platform) -> Self {
+        let instruction = Self::from(value);
+        match instruction {
+            Self::ScrollDown(_)
+            | Self::ScrollUp(_)
+            | Self::ScrollRight
+            | Self::ScrollLeft
+            | Self::Exit
+            | Self::LowRes
+            | Self::HighRes
+                if platform == Platform::Chip8 =>
+            {
+                Self::Unknown(value)
+            }
+            _ => instruction,
+        }
+    }
+}
+
 impl From<u16> for Instruction {
     fn from(value: u16) -> Self {
         match value >> 12 {
             0x0 => match value {
+                0x00C0..=0x00CF => Self::ScrollDown((value & 0x000F) as usize),
+                0x00D0..=0x00DF => Self::ScrollUp((value & 0x000F) as usize),
                 0x00E0 => Self::Cls,
                 0x00EE => Self::Return,
+                0x00FB => Self::ScrollRight,
+                0x00FC => Self::ScrollLeft,
+                0x00FD => Self::Exit,
+                0x00FE => Self::LowRes,
+                0x00FF => Self::HighRes,
                 _ => Self::Sys((value & 0x0FFF) as MemoryAddress),
             },
             0x1 => Self::Jump((value & 0x0FFF) as MemoryAddress),
@@ -385,54 +883,96 @@ impl From<u16> for Instruction {
 
 impl Display for Instruction {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Instruction::Sys(x) => write!(f, "SYS[{:#03x}]", x),
-            Instruction::Cls => write!(f, "CLS"),
-            Instruction::Return => write!(f, "RET"),
-            Instruction::Jump(x) => write!(f, "JMP[{:#03x}]", x),
-            Instruction::Call(x) => write!(f, "CAL[{:#03x}]", x),
-            Instruction::SkipIfVImmediate(x, y) => write!(f, "SKI[{:#03x},{:#03x}]", x, y),
-            Instruction::SkipIfNotVImmediate(x, y) => write!(f, "SNI[{:#03x},{:#03x}]", x, y),
-            Instruction::SkipIfCmp(x, y) => write!(f, "SKC[{:#03x},{:#03x}]", x, y),
-            Instruction::LoadVImmediate(x, y) => write!(f, "LVI[{:#03x},{:#03x}]", x, y),
-            Instruction::AddVImmediate(x, y) => write!(f, "AVI[{:#03x},{:#03x}]", x, y),
-            Instruction::LoadV(x, y) => write!(f, "LDV[{:#03x},{:#03x}]", x, y),
-            Instruction::Or(x, y) => write!(f, "ORV[{:#03x},{:#03x}]", x, y),
-            Instruction::And(x, y) => write!(f, "AND[{:#03x},{:#03x}]", x, y),
-            Instruction::Xor(x, y) => write!(f, "XOR[{:#03x},{:#03x}]", x, y),
-            Instruction::Add(x, y) => write!(f, "ADD[{:#03x},{:#03x}]", x, y),
-            Instruction::Sub(x, y) => write!(f, "SUB[{:#03x},{:#03x}]", x, y),
-            Instruction::ShiftRight(x, y) => write!(f, "SHR[{:#03x},{:#03x}]", x, y),
-            Instruction::SubN(x, y) => write!(f, "SUN[{:#03x},{:#03x}]", x, y),
-            Instruction::ShiftLeft(x, y) => write!(f, "SHL[{:#03x},{:#03x}]", x, y),
-            Instruction::SkipIfNotCmp(x, y) => write!(f, "SKC[{:#03x},{:#03x}]", x, y),
-            Instruction::LoadIImmediate(x) => write!(f, "LDI[{:#03x}]", x),
-            Instruction::JumpV0(x) => write!(f, "JPV[{:#03x}]", x),
-            Instruction::Random(x, y) => write!(f, "RND[{:#03x},{:#03x}]", x, y),
-            Instruction::Draw(x, y, z) => write!(f, "DRW[{:#03x},{:#03x},{:#03x}]", x, y, z),
-            Instruction::SkipIfKey(x) => write!(f, "SKP[{:#03x}]", x),
-            Instruction::SkipIfNotKey(x) => write!(f, "SNP[{:#03x}]", x),
-            Instruction::LoadVDT(x) => write!(f, "LVD[{:#03x}]", x),
-            Instruction::WaitAndLoadKeypress(x) => write!(f, "LVK[{:#03x}]", x),
-            Instruction::LoadDTV(x) => write!(f, "LDV[{:#03x}]", x),
-            Instruction::LoadSTV(x) => write!(f, "LSV[{:#03x}]", x),
-            Instruction::AddIV(x) => write!(f, "AIV[{:#03x}]", x),
-            Instruction::LoadFontV(x) => write!(f, "LFV[{:#03x}]", x),
-            Instruction::StoreBCDV(x) => write!(f, "LBV[{:#03x}]", x),
-            Instruction::StoreAllV(x) => write!(f, "LIA[{:#03x}]", x),
-            Instruction::LoadAllV(x) => write!(f, "LAI[{:#03x}]", x),
-            Instruction::Unknown(x) => write!(f, "UWN[{:#03x}]", x),
+        let (mnemonic, operands) = self.parts();
+        write!(f, "{}", mnemonic)?;
+        if !operands.is_empty() {
+            write!(f, "[{}]", operands.join(","))?;
         }
+        Ok(())
+    }
+}
+
+impl Instruction {
+    // Every instruction is a single opcode
+    pub const LENGTH: MemorySize = 2;
+
+    fn parts(&self) -> (&'static str, Vec<String>) {
+        let (mnemonic, operands) = match self {
+            Instruction::Sys(x) => ("SYS", vec![*x]),
+            Instruction::ScrollDown(x) => ("SCD", vec![*x]),
+            Instruction::ScrollUp(x) => ("SCU", vec![*x]),
+            Instruction::Cls => ("CLS", vec![]),
+            Instruction::Return => ("RET", vec![]),
+            Instruction::ScrollRight => ("SCR", vec![]),
+            Instruction::ScrollLeft => ("SCL", vec![]),
+            Instruction::Exit => ("EXT", vec![]),
+            Instruction::LowRes => ("LOW", vec![]),
+            Instruction::HighRes => ("HIG", vec![]),
+            Instruction::Jump(x) => ("JMP", vec![*x]),
+            Instruction::Call(x) => ("CAL", vec![*x]),
+            Instruction::SkipIfVImmediate(x, y) => ("SKI", vec![*x, *y as usize]),
+            Instruction::SkipIfNotVImmediate(x, y) => ("SNI", vec![*x, *y as usize]),
+            Instruction::SkipIfCmp(x, y) => ("SKC", vec![*x, *y]),
+            Instruction::LoadVImmediate(x, y) => ("LVI", vec![*x, *y as usize]),
+            Instruction::AddVImmediate(x, y) => ("AVI", vec![*x, *y as usize]),
+            Instruction::LoadV(x, y) => ("LDV", vec![*x, *y]),
+            Instruction::Or(x, y) => ("ORV", vec![*x, *y]),
+            Instruction::And(x, y) => ("AND", vec![*x, *y]),
+            Instruction::Xor(x, y) => ("XOR", vec![*x, *y]),
+            Instruction::Add(x, y) => ("ADD", vec![*x, *y]),
+            Instruction::Sub(x, y) => ("SUB", vec![*x, *y]),
+            Instruction::ShiftRight(x, y) => ("SHR", vec![*x, *y]),
+            Instruction::SubN(x, y) => ("SUN", vec![*x, *y]),
+            Instruction::ShiftLeft(x, y) => ("SHL", vec![*x, *y]),
+            Instruction::SkipIfNotCmp(x, y) => ("SKC", vec![*x, *y]),
+            Instruction::LoadIImmediate(x) => ("LDI", vec![*x]),
+            Instruction::JumpV0(x) => ("JPV", vec![*x]),
+            Instruction::Random(x, y) => ("RND", vec![*x, *y as usize]),
+            Instruction::Draw(x, y, z) => ("DRW", vec![*x, *y, *z]),
+            Instruction::SkipIfKey(x) => ("SKP", vec![*x]),
+            Instruction::SkipIfNotKey(x) => ("SNP", vec![*x]),
+            Instruction::LoadVDT(x) => ("LVD", vec![*x]),
+            Instruction::WaitAndLoadKeypress(x) => ("LVK", vec![*x]),
+            Instruction::LoadDTV(x) => ("LDV", vec![*x]),
+            Instruction::LoadSTV(x) => ("LSV", vec![*x]),
+            Instruction::AddIV(x) => ("AIV", vec![*x]),
+            Instruction::LoadFontV(x) => ("LFV", vec![*x]),
+            Instruction::StoreBCDV(x) => ("LBV", vec![*x]),
+            Instruction::StoreAllV(x) => ("LIA", vec![*x]),
+            Instruction::LoadAllV(x) => ("LAI", vec![*x]),
+            Instruction::Unknown(x) => ("UWN", vec![*x as usize]),
+        };
+        (
+            mnemonic,
+            operands
+                .into_iter()
+                .map(|operand| format!("{:#03x}", operand))
+                .collect(),
+        )
     }
 }
 
 impl Instruction {
     fn execute(&self, cpu: &mut Cpu, backend: &Backend) -> Result<(), Error> {
+        #[cfg(test)]
+        crate::coverage::record(self, &cpu.quirks);
         match self {
             Instruction::Sys(address) => {
                 cpu.state.pc = *address as u16;
                 Ok(())
             }
+            Instruction::ScrollDown(n) => {
+                let distance = cpu.scroll_distance(*n);
+                cpu.state.scroll(0, distance);
+                cpu.send_frame(backend);
+                Ok(())
+            }
+            Instruction::ScrollUp(n) => {
+                let distance = cpu.scroll_distance(*n);
+                cpu.state.scroll(0, -distance);
+                cpu.send_frame(backend);
+                Ok(())
+            }
             Instruction::Cls => {
                 cpu.state.frame_buffer = [false; FRAME_DIMENSIONS.0 * FRAME_DIMENSIONS.1];
                 cpu.send_frame(backend);
@@ -443,12 +983,44 @@ impl Instruction {
                 cpu.state.pc = cpu.state.stack[cpu.state.sp as usize];
                 Ok(())
             }
+            Instruction::ScrollRight => {
+                let distance = cpu.scroll_distance(4);
+                cpu.state.scroll(distance, 0);
+                cpu.send_frame(backend);
+                Ok(())
+            }
+            Instruction::ScrollLeft => {
+                let distance = cpu.scroll_distance(4);
+                cpu.state.scroll(-distance, 0);
+                cpu.send_frame(backend);
+                Ok(())
+            }
+            Instruction::Exit => {
+                cpu.state.paused = true;
+                Ok(())
+            }
+            Instruction::LowRes => {
+                cpu.state.hires = false;
+                Ok(())
+            }
+            Instruction::HighRes => {
+                cpu.state.hires = true;
+                Ok(())
+            }
             Instruction::Jump(address) => {
                 cpu.state.pc = *address as u16;
                 Ok(())
             }
             Instruction::Call(address) => {
+                // Runaway recursion is common under the wrong quirks, it ends the run like a crash
+                if cpu.state.sp as usize >= cpu.state.stack.len() {
+                    return Err(Error::new(format!(
+                        "stack overflow calling {:#05x}",
+                        address
+                    )));
+                }
                 cpu.state.stack[cpu.state.sp as usize] = cpu.state.pc;
+                cpu.state.call_targets[cpu.state.sp as usize] = *address as u16;
                 cpu.state.sp = cpu.state.sp.saturating_add(1);
                 cpu.state.pc = *address as u16;
                 Ok(())
@@ -552,7 +1124,7 @@ impl Instruction {
             }
             Instruction::JumpV0(x) => {
                 if cpu.quirks.quirks_jump_uses_x {
-                    let register = *x & 0xF00;
+                    let register = (*x & 0xF00) >> 8;
                     cpu.state.pc = (cpu.state.v[register] as u16).wrapping_add(*x as u16);
                 } else {
                     cpu.state.pc = (cpu.state.v[0x0] as u16).wrapping_add(*x as u16);
@@ -560,39 +1132,63 @@ impl Instruction {
                 Ok(())
             }
             Instruction::Random(x, y) => {
-                let random: u8 = rand::random();
+                let random = backend.get_rng().next_u8();
                 cpu.state.v[*x] = random & *y;
                 Ok(())
             }
             Instruction::Draw(vx, vy, n) => {
+                let (width, height) = cpu.state.resolution();
                 let (start_x, start_y) = (
-                    cpu.state.v[*vx] as usize % FRAME_DIMENSIONS.0,
-                    cpu.state.v[*vy] as usize % FRAME_DIMENSIONS.1,
+                    cpu.state.v[*vx] as usize % width,
+                    cpu.state.v[*vy] as usize % height,
                 );
-                cpu.state.v[0xF] = 0;
-                for y in 0..*n {
-                    if start_y + y >= FRAME_DIMENSIONS.1 {
+                // DXY0 draws a 16x16 sprite made of two bytes per row on SuperChip
+                let (sprite_width, sprite_height) = match (*n, cpu.platform) {
+                    (0, Platform::SuperChip) => (16, 16),
+                    _ => (8, *n),
+                };
+                // SCHIP reports the number of colliding rows in hires, rows clipped at the bottom
+                // count as colliding as well
+                let count_rows = cpu.state.hires && cpu.quirks.quirks_draw_counts_colliding_rows;
+                let mut colliding_rows = 0;
+                for y in 0..sprite_height {
+                    if start_y + y >= height {
+                        if count_rows {
+                            colliding_rows += 1;
+                        }
                         continue;
                     }
-                    let pixeldata = backend
-                        .get_bus()
-                        .read_u8((cpu.state.i as usize + y) as MemoryAddress)?;
-                    for x in 0..8 {
-                        if start_x + x >= FRAME_DIMENSIONS.0 {
+                    let pixeldata = if sprite_width == 16 {
+                        backend
+                            .get_bus()
+                            .read_u16_be((cpu.state.i as usize + y * 2) as MemoryAddress)?
+                    } else {
+                        (backend
+                            .get_bus()
+                            .read_u8((cpu.state.i as usize + y) as MemoryAddress)?
+                            as u16)
+                            << 8
+                    };
+                    let mut row_collision = false;
+                    for x in 0..sprite_width {
+                        if start_x + x >= width {
                             break;
                         }
-                        let index = (start_y + y) * FRAME_DIMENSIONS.0 + start_x + x;
-
-                        let current_pixel = cpu.state.frame_buffer[index];
-                        let new_pixel = ((pixeldata >> (7 - x)) & 0b1) > 0;
+                        let new_pixel = ((pixeldata >> (15 - x)) & 0b1) > 0;
 
-                        cpu.state.frame_buffer[index] = new_pixel != current_pixel;
-
-                        if new_pixel && current_pixel {
-                            cpu.state.v[0xF] = 1;
+                        if new_pixel && cpu.state.toggle_pixel(start_x + x, start_y + y) {
+                            row_collision = true;
                         }
                     }
+                    if row_collision {
+                        colliding_rows += 1;
+                    }
                 }
+                cpu.state.v[0xF] = if count_rows {
+                    colliding_rows as u8
+                } else {
+                    (colliding_rows > 0) as u8
+                };
                 cpu.send_frame(backend);
                 if !cpu.quirks.quirks_draw_not_waiting_for_vblank {
                     cpu.state.waiting_for_vblank = true;
@@ -643,7 +1239,8 @@ impl Instruction {
                 Ok(())
             }
             Instruction::LoadFontV(x) => {
-                cpu.state.i = FONT_BASE as u16 + (*x as u16) * 5;
+                // The digit is the low nibble of VX, not the register index
+                cpu.state.i = FONT_BASE as u16 + (cpu.state.v[*x] & 0xF) as u16 * 5;
                 Ok(())
             }
             Instruction::StoreBCDV(x) => {
@@ -693,3 +1290,303 @@ impl Instruction {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use axwemulator_core::testing::MockBackend;
+
+    use super::*;
+
+    fn draw(cpu: &mut Cpu, opcode: u16, sprite: &[u8], count_rows: bool) {
+        cpu.quirks.quirks_draw_counts_colliding_rows = count_rows;
+        cpu.quirks.quirks_draw_not_waiting_for_vblank = true;
+        cpu.state.i = 0;
+        let backend = MockBackend::new().with_data(0, sprite.to_vec());
+        Instruction::from(opcode)
+            .execute(cpu, backend.backend())
+            .unwrap();
+    }
+
+    fn cpu(hires: bool, half_pixel_scroll: bool) -> Cpu {
+        let mut cpu = Cpu::default();
+        cpu.state.hires = hires;
+        cpu.quirks.quirks_lores_scroll_half_pixel = half_pixel_scroll;
+        cpu
+    }
+
+    fn set_pixel(cpu: &mut Cpu, x: usize, y: usize) {
+        cpu.state.frame_buffer[y * FRAME_DIMENSIONS.0 + x] = true;
+    }
+
+    fn set_pixels(cpu: &Cpu) -> Vec<(usize, usize)> {
+        (0..FRAME_DIMENSIONS.0 * FRAME_DIMENSIONS.1)
+            .filter(|index| cpu.state.frame_buffer[*index])
+            .map(|index| (index % FRAME_DIMENSIONS.0, index / FRAME_DIMENSIONS.0))
+            .collect()
+    }
+
+    fn execute(cpu: &mut Cpu, opcode: u16) {
+        Instruction::from(opcode)
+            .execute(cpu, &Backend::default())
+            .unwrap();
+    }
+
+    fn reload(cpu: &Cpu) -> Result<(), Error> {
+        let mut writer = StateWriter::new();
+        cpu.save_state(&mut writer);
+        let data = writer.into_data();
+        Cpu::default().load_state(&mut StateReader::new(&data))
+    }
+
+    #[test]
+    fn states_out_of_range_fail_to_load() {
+        let mut cpu = Cpu::default();
+        cpu.state.sp = 16;
+        cpu.state.waiting_for_key = Some(0xF);
+        assert!(reload(&cpu).is_ok());
+
+        cpu.state.sp = 17;
+        assert!(reload(&cpu).is_err());
+        cpu.state.sp = 0;
+        cpu.state.waiting_for_key = Some(0x10);
+        assert!(reload(&cpu).is_err());
+    }
+
+    #[test]
+    fn scroll_down_hires() {
+        let mut cpu = cpu(true, false);
+        set_pixel(&mut cpu, 10, 10);
+        execute(&mut cpu, 0x00C3);
+        assert_eq!(set_pixels(&cpu), vec![(10, 13)]);
+    }
+
+    #[test]
+    fn scroll_down_lores() {
+        let mut cpu = cpu(false, false);
+        cpu.state.toggle_pixel(5, 5);
+        execute(&mut cpu, 0x00C3);
+        assert_eq!(
+            set_pixels(&cpu),
+            vec![(10, 16), (11, 16), (10, 17), (11, 17)]
+        );
+    }
+
+    #[test]
+    fn scroll_down_lores_half_pixel() {
+        let mut cpu = cpu(false, true);
+        cpu.state.toggle_pixel(5, 5);
+        execute(&mut cpu, 0x00C3);
+        assert_eq!(
+            set_pixels(&cpu),
+            vec![(10, 13), (11, 13), (10, 14), (11, 14)]
+        );
+    }
+
+    #[test]
+    fn scroll_down_discards_bottom_rows() {
+        let mut cpu = cpu(true, false);
+        set_pixel(&mut cpu, 0, FRAME_DIMENSIONS.1 - 1);
+        execute(&mut cpu, 0x00C1);
+        assert_eq!(set_pixels(&cpu), vec![]);
+    }
+
+    #[test]
+    fn scroll_up_hires() {
+        let mut cpu = cpu(true, false);
+        set_pixel(&mut cpu, 10, 10);
+        set_pixel(&mut cpu, 20, 1);
+        execute(&mut cpu, 0x00D2);
+        assert_eq!(set_pixels(&cpu), vec![(10, 8)]);
+    }
+
+    #[test]
+    fn scroll_up_lores() {
+        let mut cpu = cpu(false, false);
+        cpu.state.toggle_pixel(5, 5);
+        execute(&mut cpu, 0x00D2);
+        assert_eq!(set_pixels(&cpu), vec![(10, 6), (11, 6), (10, 7), (11, 7)]);
+    }
+
+    #[test]
+    fn scroll_right_hires() {
+        let mut cpu = cpu(true, false);
+        set_pixel(&mut cpu, 10, 10);
+        set_pixel(&mut cpu, FRAME_DIMENSIONS.0 - 2, 0);
+        execute(&mut cpu, 0x00FB);
+        assert_eq!(set_pixels(&cpu), vec![(14, 10)]);
+    }
+
+    #[test]
+    fn scroll_right_lores() {
+        let mut cpu = cpu(false, false);
+        cpu.state.toggle_pixel(5, 5);
+        execute(&mut cpu, 0x00FB);
+        assert_eq!(
+            set_pixels(&cpu),
+            vec![(18, 10), (19, 10), (18, 11), (19, 11)]
+        );
+    }
+
+    #[test]
+    fn scroll_right_lores_half_pixel() {
+        let mut cpu = cpu(false, true);
+        cpu.state.toggle_pixel(5, 5);
+        execute(&mut cpu, 0x00FB);
+        assert_eq!(
+            set_pixels(&cpu),
+            vec![(14, 10), (15, 10), (14, 11), (15, 11)]
+        );
+    }
+
+    #[test]
+    fn scroll_left_hires() {
+        let mut cpu = cpu(true, false);
+        set_pixel(&mut cpu, 10, 10);
+        set_pixel(&mut cpu, 1, 0);
+        execute(&mut cpu, 0x00FC);
+        assert_eq!(set_pixels(&cpu), vec![(6, 10)]);
+    }
+
+    #[test]
+    fn scroll_left_lores() {
+        let mut cpu = cpu(false, false);
+        cpu.state.toggle_pixel(5, 5);
+        execute(&mut cpu, 0x00FC);
+        assert_eq!(set_pixels(&cpu), vec![(2, 10), (3, 10), (2, 11), (3, 11)]);
+    }
+
+    #[test]
+    fn switching_resolution_keeps_frame_buffer() {
+        let mut cpu = cpu(false, false);
+        cpu.state.toggle_pixel(1, 1);
+        execute(&mut cpu, 0x00FF);
+        assert!(cpu.state.hires);
+        execute(&mut cpu, 0x00FE);
+        assert!(!cpu.state.hires);
+        assert_eq!(set_pixels(&cpu), vec![(2, 2), (3, 2), (2, 3), (3, 3)]);
+    }
+
+    #[test]
+    fn draw_collision_sets_flag_to_one_without_quirk() {
+        let mut cpu = cpu(true, false);
+        draw(&mut cpu, 0xD013, &[0xFF, 0xFF, 0xFF], false);
+        assert_eq!(cpu.state.v[0xF], 0);
+        draw(&mut cpu, 0xD013, &[0xFF, 0xFF, 0xFF], false);
+        assert_eq!(cpu.state.v[0xF], 1);
+    }
+
+    #[test]
+    fn draw_collision_counts_rows_in_hires() {
+        let mut cpu = cpu(true, false);
+        draw(&mut cpu, 0xD013, &[0x80, 0x00, 0x80], true);
+        assert_eq!(cpu.state.v[0xF], 0);
+        draw(&mut cpu, 0xD013, &[0x80, 0x80, 0x80], true);
+        assert_eq!(cpu.state.v[0xF], 2);
+    }
+
+    #[test]
+    fn draw_collision_counts_rows_clipped_at_bottom() {
+        let mut cpu = cpu(true, false);
+        cpu.state.v[1] = (FRAME_DIMENSIONS.1 - 2) as u8;
+        draw(&mut cpu, 0xD014, &[0x80, 0x80, 0x80, 0x80], true);
+        assert_eq!(cpu.state.v[0xF], 2);
+    }
+
+    #[test]
+    fn draw_collision_counts_rows_only_in_hires() {
+        let mut cpu = cpu(false, false);
+        draw(&mut cpu, 0xD013, &[0x80, 0x80, 0x80], true);
+        draw(&mut cpu, 0xD013, &[0x80, 0x80, 0x80], true);
+        assert_eq!(cpu.state.v[0xF], 1);
+    }
+
+    #[test]
+    fn draw_of_sixteen_by_sixteen_sprite_only_on_superchip() {
+        let mut superchip = cpu(true, false);
+        superchip.platform = Platform::SuperChip;
+        draw(&mut superchip, 0xD010, &[0xFF; 32], false);
+        assert_eq!(set_pixels(&superchip).len(), 16 * 16);
+
+        let mut chip8 = cpu(false, false);
+        draw(&mut chip8, 0xD010, &[0xFF; 32], false);
+        assert!(set_pixels(&chip8).is_empty());
+        assert_eq!(chip8.state.v[0xF], 0);
+    }
+
+    #[test]
+    fn superchip_instructions_are_unknown_on_chip8() {
+        for opcode in [0x00C1, 0x00D1, 0x00FB, 0x00FC, 0x00FD, 0x00FE, 0x00FF] {
+            assert_eq!(
+                Instruction::decode(opcode, Platform::Chip8),
+                Instruction::Unknown(opcode)
+            );
+            assert_ne!(
+                Instruction::decode(opcode, Platform::SuperChip),
+                Instruction::Unknown(opcode)
+            );
+        }
+        assert_eq!(
+            Instruction::decode(0x00E0, Platform::Chip8),
+            Instruction::Cls
+        );
+    }
+
+    #[test]
+    fn stack_frames_list_innermost_call_first() {
+        let mut cpu = Cpu::default();
+        let backend = MockBackend::new();
+        cpu.state.pc = 0x204;
+        Instruction::from(0x2300)
+            .execute(&mut cpu, backend.backend())
+            .unwrap();
+        cpu.state.pc = 0x306;
+        Instruction::from(0x2400)
+            .execute(&mut cpu, backend.backend())
+            .unwrap();
+        assert_eq!(
+            cpu.stack_frames(),
+            vec![
+                StackFrame {
+                    function: Some(0x400),
+                    return_address: 0x306,
+                },
+                StackFrame {
+                    function: Some(0x300),
+                    return_address: 0x204,
+                },
+            ]
+        );
+        Instruction::from(0x00EE)
+            .execute(&mut cpu, backend.backend())
+            .unwrap();
+        assert_eq!(cpu.stack_frames().len(), 1);
+    }
+
+    #[test]
+    fn load_font_points_at_the_digit_in_vx() {
+        let mut cpu = Cpu::default();
+        cpu.state.v[0] = 0x1A;
+        execute(&mut cpu, 0xF029);
+        assert_eq!(cpu.state.i, FONT_BASE as u16 + 0xA * 5);
+    }
+
+    #[test]
+    fn jump_with_x_quirk_adds_vx() {
+        let mut cpu = Cpu::default();
+        cpu.quirks.quirks_jump_uses_x = true;
+        cpu.state.v[3] = 0x10;
+        execute(&mut cpu, 0xB300);
+        assert_eq!(cpu.state.pc, 0x310);
+    }
+
+    #[test]
+    fn call_on_a_full_stack_fails() {
+        let mut cpu = Cpu::default();
+        cpu.state.sp = cpu.state.stack.len() as u8;
+        assert!(
+            Instruction::from(0x2300)
+                .execute(&mut cpu, &Backend::default())
+                .is_err()
+        );
+    }
+}
@@ -1,6 +1,89 @@
 use std::collections::HashMap;
 
-use axwemulator_core::frontend::input::{ButtonState, InputEvent, KeyboardEventKey};
+use axwemulator_core::{
+    backend::controls::{Control, ControlScheme},
+    frontend::input::{ButtonState, InputEvent, KeyboardEventKey},
+};
+
+// The hexadecimal keypad of the COSMAC VIP, mapped onto the left side of a QWERTZ keyboard
+pub const KEYPAD_CONTROLS: ControlScheme = ControlScheme {
+    rows: &[
+        &[
+            Control {
+                label: "1",
+                key: KeyboardEventKey::Number1,
+            },
+            Control {
+                label: "2",
+                key: KeyboardEventKey::Number2,
+            },
+            Control {
+                label: "3",
+                key: KeyboardEventKey::Number3,
+            },
+            Control {
+                label: "C",
+                key: KeyboardEventKey::Number4,
+            },
+        ],
+        &[
+            Control {
+                label: "4",
+                key: KeyboardEventKey::Q,
+            },
+            Control {
+                label: "5",
+                key: KeyboardEventKey::W,
+            },
+            Control {
+                label: "6",
+                key: KeyboardEventKey::E,
+            },
+            Control {
+                label: "D",
+                key: KeyboardEventKey::R,
+            },
+        ],
+        &[
+            Control {
+                label: "7",
+                key: KeyboardEventKey::A,
+            },
+            Control {
+                label: "8",
+                key: KeyboardEventKey::S,
+            },
+            Control {
+                label: "9",
+                key: KeyboardEventKey::D,
+            },
+            Control {
+                label: "E",
+                key: KeyboardEventKey::F,
+            },
+        ],
+        &[
+            Control {
+                label: "A",
+                key: KeyboardEventKey::Y,
+            },
+            Control {
+                label: "0",
+                key: KeyboardEventKey::X,
+            },
+            Control {
+                label: "B",
+                key: KeyboardEventKey::C,
+            },
+            Control {
+                label: "F",
+                key: KeyboardEventKey::V,
+            },
+        ],
+    ],
+    help: "Most games only use a few keys of the keypad, often 2/4/6/8 or 5/7/8/9 for directions. \
+           FX0A waits for a key to be released before the game continues.",
+};
 
 #[derive(Debug, PartialEq, Eq, Hash)]
 pub enum InputButton {
@@ -115,4 +198,75 @@ impl KeypadState {
     pub fn get_state_for_button(&self, button: InputButton) -> ButtonState {
         *self.0.get(&button).unwrap_or(&ButtonState::Released)
     }
+
+    // One bit per button, button 0 in the lowest bit
+    pub fn pressed_mask(&self) -> u16 {
+        (0..16u8)
+            .filter(|index| {
+                InputButton::try_from(*index)
+                    .is_ok_and(|button| self.get_state_for_button(button) == ButtonState::Pressed)
+            })
+            .fold(0, |mask, index| mask | (1 << index))
+    }
+
+    pub fn from_pressed_mask(mask: u16) -> Self {
+        let mut state = Self::new();
+        for index in 0..16u8 {
+            if let Ok(button) = InputButton::try_from(index) {
+                let button_state = if mask & (1 << index) != 0 {
+                    ButtonState::Pressed
+                } else {
+                    ButtonState::Released
+                };
+                state.0.insert(button, button_state);
+            }
+        }
+        state
+    }
+
+    pub fn pressed_keys(&self) -> Vec<KeyboardEventKey> {
+        KEYPAD_CONTROLS
+            .controls()
+            .map(|control| control.key)
+            .filter(|key| {
+                InputButton::try_from(*key)
+                    .is_ok_and(|button| self.get_state_for_button(button) == ButtonState::Pressed)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keypad_controls_match_button_mapping() {
+        for control in KEYPAD_CONTROLS.controls() {
+            let button = InputButton::try_from(control.key).unwrap();
+            assert_eq!(
+                u8::from(button),
+                u8::from_str_radix(control.label, 16).unwrap()
+            );
+        }
+        assert_eq!(KEYPAD_CONTROLS.controls().count(), 16);
+    }
+
+    #[test]
+    fn reports_pressed_keys() {
+        let mut keypad = KeypadState::new();
+        keypad.parse_input_event(InputEvent::Keyboard(
+            KeyboardEventKey::W,
+            ButtonState::Pressed,
+        ));
+        keypad.parse_input_event(InputEvent::Keyboard(
+            KeyboardEventKey::V,
+            ButtonState::Pressed,
+        ));
+        keypad.parse_input_event(InputEvent::Keyboard(
+            KeyboardEventKey::V,
+            ButtonState::Released,
+        ));
+        assert_eq!(keypad.pressed_keys(), vec![KeyboardEventKey::W]);
+    }
 }
@@ -1,27 +1,59 @@
 mod audio;
+#[cfg(test)]
+mod coverage;
 mod cpu;
 mod input;
+mod quirk_detection;
+pub mod quirks_test;
 mod timer;
 
 use audio::{AUDIO_SAMPLING_RATE, Audio};
 use axwemulator_core::{
     backend::{
-        Backend,
+        Backend, BackendInfo, DisplayConfig,
         component::{Addressable, Component, MemoryAddress},
         memory::MemoryBlock,
+        options::{OptionValues, OptionsAnalysis, OptionsSchema},
+        registry::{BackendDescriptor, BackendOptions, BackendRegistry},
+        rtc::migrate_payload_without_rtc,
+        selftest::SelfTest,
+        snapshot::migrate_component_state,
     },
     error::Error,
     frontend::{
-        Frontend, audio::build_audio_channel, graphics::build_frame_channel,
-        input::build_input_channel,
+        Frontend, audio::build_audio_channel, debug::build_debug_channel,
+        graphics::build_frame_channel, input::build_input_channel, text::build_text_channel,
+        trace::build_trace_channel,
     },
+    rom::{DetectionConfidence, Rom, RomIssue},
 };
-use cpu::{Cpu, FRAME_DIMENSIONS};
+use cpu::{Cpu, CpuQuirks, FRAME_DIMENSIONS};
+use femtos::Duration;
+use input::KEYPAD_CONTROLS;
 use timer::Timer;
 
 const TIMER_BASE: MemoryAddress = 0x100;
 const DT_TIMER: MemoryAddress = TIMER_BASE;
 const ST_TIMER: MemoryAddress = TIMER_BASE + 1;
+// More than a frame of instructions at the highest speed, frontends drain the trace every frame
+const TRACE_BUFFER_SIZE: usize = 4096;
+// The ram ends one byte short of the address space, so the last byte is free for the debug port
+const DEBUG_PORT_ADDRESS: MemoryAddress = 0xFFF;
+
+const OPTION_INSTRUCTIONS_PER_SECOND: &str = "instructions_per_second";
+const OPTION_QUIRK_SHIFT: &str = "quirk_shift";
+const OPTION_QUIRK_LOADSTORE_I_UNMODIFIED: &str = "quirk_loadstore_i_unmodified";
+const OPTION_QUIRK_LOADSTORE_I_ONE_LESS: &str = "quirk_loadstore_i_one_less";
+const OPTION_QUIRK_JUMP: &str = "quirk_jump";
+const OPTION_QUIRK_VBLANK: &str = "quirk_vblank";
+const OPTION_QUIRK_LOGIC: &str = "quirk_logic";
+const OPTION_QUIRK_LORES_HALF_SCROLL: &str = "quirk_lores_half_scroll";
+const OPTION_QUIRK_COLLISION_ROWS: &str = "quirk_collision_rows";
+const OPTION_COLOR_FOREGROUND: &str = "color_foreground";
+const OPTION_COLOR_BACKGROUND: &str = "color_background";
+const OPTION_PHOSPHOR_PERSISTENCE: &str = "phosphor_persistence";
+const OPTION_BUZZER_MIN_DURATION: &str = "buzzer_min_duration";
+const OPTION_DEBUG_PORT: &str = "debug_port";
 
 const FONT_BASE: MemoryAddress = 0x50;
 // From http://devernay.free.fr/hacks/chip8/C8TECH10.HTM#2.5
@@ -45,7 +77,9 @@ const FONT_SET: [u8; 80]  = [
     0xF0, 0x80, 0xF0, 0x80, 0x80  // F
 ];
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub enum Platform {
+    #[default]
     Chip8,
     SuperChip,
 }
@@ -53,38 +87,808 @@ pub enum Platform {
 pub struct Chip8Options {
     pub rom_data: Vec<u8>,
     pub platform: Platform,
+    pub options: OptionValues,
+}
+
+pub fn options_schema(platform: Platform) -> OptionsSchema {
+    let quirks = CpuQuirks::from(platform);
+    OptionsSchema::new()
+        .int(
+            OPTION_INSTRUCTIONS_PER_SECOND,
+            "Instructions per second",
+            1,
+            100_000,
+            700,
+        )
+        .bool(
+            OPTION_QUIRK_SHIFT,
+            "Shift operates on VX instead of VY",
+            quirks.quirks_shift_takes_x_instead_of_y,
+        )
+        .bool(
+            OPTION_QUIRK_LOADSTORE_I_UNMODIFIED,
+            "Load/store leaves I unmodified",
+            quirks.quirks_loadstore_leaves_i_unmodified,
+        )
+        .bool(
+            OPTION_QUIRK_LOADSTORE_I_ONE_LESS,
+            "Load/store increments I by X only",
+            quirks.quirks_loadstore_modifies_i_one_less,
+        )
+        .bool(
+            OPTION_QUIRK_JUMP,
+            "Jump with offset uses VX",
+            quirks.quirks_jump_uses_x,
+        )
+        .bool(
+            OPTION_QUIRK_VBLANK,
+            "Draw does not wait for vblank",
+            quirks.quirks_draw_not_waiting_for_vblank,
+        )
+        .bool(
+            OPTION_QUIRK_LOGIC,
+            "Logic ops leave VF unmodified",
+            quirks.quirks_logic_leaves_flag_unmodified,
+        )
+        .bool(
+            OPTION_QUIRK_LORES_HALF_SCROLL,
+            "Scroll by half a pixel in lores",
+            quirks.quirks_lores_scroll_half_pixel,
+        )
+        .bool(
+            OPTION_QUIRK_COLLISION_ROWS,
+            "Hires draw sets VF to colliding rows",
+            quirks.quirks_draw_counts_colliding_rows,
+        )
+        .color(
+            OPTION_COLOR_FOREGROUND,
+            "Foreground color",
+            (255, 255, 255, 255),
+        )
+        .color(OPTION_COLOR_BACKGROUND, "Background color", (0, 0, 0, 255))
+        .palette(
+            "Classic",
+            &[
+                (OPTION_COLOR_FOREGROUND, (255, 255, 255, 255)),
+                (OPTION_COLOR_BACKGROUND, (0, 0, 0, 255)),
+            ],
+        )
+        .palette(
+            "High contrast",
+            &[
+                (OPTION_COLOR_FOREGROUND, (255, 255, 0, 255)),
+                (OPTION_COLOR_BACKGROUND, (0, 0, 0, 255)),
+            ],
+        )
+        .palette(
+            "Inverted",
+            &[
+                (OPTION_COLOR_FOREGROUND, (0, 0, 0, 255)),
+                (OPTION_COLOR_BACKGROUND, (255, 255, 255, 255)),
+            ],
+        )
+        // Yellow on blue of the Okabe-Ito palette, which stay apart with every color blindness
+        .palette(
+            "Color-blind safe",
+            &[
+                (OPTION_COLOR_FOREGROUND, (240, 228, 66, 255)),
+                (OPTION_COLOR_BACKGROUND, (0, 114, 178, 255)),
+            ],
+        )
+        .int(
+            OPTION_PHOSPHOR_PERSISTENCE,
+            "Phosphor persistence (%)",
+            0,
+            95,
+            0,
+        )
+        .int(
+            OPTION_BUZZER_MIN_DURATION,
+            "Minimum beep length (ms)",
+            0,
+            200,
+            30,
+        )
+        .int(
+            OPTION_DEBUG_PORT,
+            "Debug output port (4095 by convention, 0 is off)",
+            0,
+            DEBUG_PORT_ADDRESS as i64,
+            0,
+        )
+}
+
+// Version 1 was written before the cpu saved the call targets, version 2 before the backend saved
+// its rtc
+fn migrate_snapshot(from_version: u32, payload: Vec<u8>) -> Result<Vec<u8>, Error> {
+    match from_version {
+        1 => migrate_component_state(
+            payload,
+            false,
+            "cpu",
+            cpu::migrate_state_without_call_targets,
+        ),
+        2 => migrate_payload_without_rtc(payload),
+        _ => Err(Error::new(format!(
+            "no migration from snapshot version {}",
+            from_version
+        ))),
+    }
+}
+
+pub fn register(registry: &mut BackendRegistry) {
+    registry.register(BackendDescriptor {
+        name: "Chip8",
+        extensions: &["ch8"],
+        detect: Some(detect_chip8),
+        known_dumps: &[],
+        header_check: Some(check_rom_size),
+        options: || options_schema(Platform::Chip8),
+        firmware: &[],
+        snapshot_version: 3,
+        snapshot_migration: Some(migrate_snapshot),
+        self_test: Some(SelfTest {
+            label: "Quirks test",
+            run: |rom, options| quirks_test::run_quirks_test(rom, options, Platform::Chip8),
+        }),
+        options_analysis: Some(OptionsAnalysis {
+            label: "Quirk detection",
+            run: |rom, options| quirk_detection::detect_quirks(rom, options, Platform::Chip8),
+        }),
+        constructor: |frontend, options| create_from_options(frontend, options, Platform::Chip8),
+    });
+    registry.register(BackendDescriptor {
+        name: "SuperChip",
+        extensions: &["sc8", "ch8"],
+        detect: Some(detect_superchip),
+        known_dumps: &[],
+        header_check: Some(check_rom_size),
+        options: || options_schema(Platform::SuperChip),
+        firmware: &[],
+        snapshot_version: 3,
+        snapshot_migration: Some(migrate_snapshot),
+        self_test: Some(SelfTest {
+            label: "Quirks test",
+            run: |rom, options| quirks_test::run_quirks_test(rom, options, Platform::SuperChip),
+        }),
+        options_analysis: Some(OptionsAnalysis {
+            label: "Quirk detection",
+            run: |rom, options| quirk_detection::detect_quirks(rom, options, Platform::SuperChip),
+        }),
+        constructor: |frontend, options| {
+            create_from_options(frontend, options, Platform::SuperChip)
+        },
+    });
+}
+
+// Roms carry no header, so the only hints are the size and instructions only SuperChip knows
+fn uses_superchip_instructions(rom: &Rom) -> bool {
+    rom.data.chunks_exact(2).any(|opcode| {
+        matches!(
+            (opcode[0], opcode[1]),
+            (0x00, 0xFB..=0xFF) | (0x00, 0xC1..=0xCF) | (0xF0..=0xFF, 0x30 | 0x75 | 0x85)
+        )
+    })
+}
+
+const MAX_ROM_SIZE: usize = 0xFFF - 0x200;
+
+fn fits_into_ram(rom: &Rom) -> bool {
+    !rom.data.is_empty() && rom.data.len() <= MAX_ROM_SIZE
+}
+
+// Roms have no header, but cannot be larger than the memory behind the program start
+fn check_rom_size(rom: &Rom) -> Vec<RomIssue> {
+    if rom.data.len() <= MAX_ROM_SIZE {
+        return vec![];
+    }
+    vec![RomIssue::Overdump {
+        expected: MAX_ROM_SIZE,
+        actual: rom.data.len(),
+    }]
+}
+
+fn detect_chip8(rom: &Rom) -> DetectionConfidence {
+    match (fits_into_ram(rom), uses_superchip_instructions(rom)) {
+        (false, _) => DetectionConfidence::None,
+        (true, false) => DetectionConfidence::Medium,
+        (true, true) => DetectionConfidence::Low,
+    }
+}
+
+fn detect_superchip(rom: &Rom) -> DetectionConfidence {
+    match (fits_into_ram(rom), uses_superchip_instructions(rom)) {
+        (false, _) => DetectionConfidence::None,
+        (true, false) => DetectionConfidence::Low,
+        (true, true) => DetectionConfidence::Medium,
+    }
 }
 
-pub fn create_chip8_backend<F: Frontend>(
+fn create_from_options(
+    frontend: &mut dyn Frontend<Error = Error>,
+    options: BackendOptions,
+    platform: Platform,
+) -> Result<Backend, Error> {
+    create_chip8_backend(
+        frontend,
+        Chip8Options {
+            rom_data: options.rom_data,
+            platform,
+            options: options.options,
+        },
+    )
+}
+
+pub fn create_chip8_backend<F: Frontend + ?Sized>(
     frontend: &mut F,
     options: Chip8Options,
 ) -> Result<Backend, Error> {
+    let values = options_schema(options.platform).resolve(&options.options);
     let mut backend = Backend::default();
+    backend.set_info(BackendInfo {
+        refresh_rate: 60.0,
+        display: DisplayConfig {
+            integer_scaling: true,
+            aspect_ratio: None,
+        },
+        controls: KEYPAD_CONTROLS,
+        players: 1,
+    });
     let (frame_sender, frame_receiver) =
         build_frame_channel(FRAME_DIMENSIONS.0, FRAME_DIMENSIONS.1);
     let (input_sender, input_receiver) = build_input_channel();
     let (audio_sender, audio_receiver) = build_audio_channel(AUDIO_SAMPLING_RATE, 5000);
+    let (debug_sender, debug_receiver) = build_debug_channel(600);
+    let (trace_sender, trace_receiver) = build_trace_channel(TRACE_BUFFER_SIZE);
 
     let mut interpreter_memory: MemoryBlock = vec![].into();
     interpreter_memory.resize(0x200);
     interpreter_memory.write(FONT_BASE, &FONT_SET)?;
-    backend.add_addressable_component("mem_interpreter", 0x0, Component::new(interpreter_memory));
+    interpreter_memory.capture_power_on();
+    backend.add_addressable_component(
+        "mem_interpreter",
+        0x0,
+        Component::new(interpreter_memory),
+    )?;
 
     let mut ram: MemoryBlock = options.rom_data.into();
     ram.resize(0xFFF - 0x200);
-    backend.add_addressable_component("mem_ram", 0x200, Component::new(ram));
+    // The interpreter loads the rom again on a reset
+    ram.capture_power_on();
+    backend.add_addressable_component("mem_ram", 0x200, Component::new(ram))?;
 
     let timer = Timer::new();
     backend.add_component("timer", Component::new(timer));
 
-    let cpu = Cpu::new(options.platform, frame_sender, input_receiver);
+    let cpu = Cpu::new(
+        options.platform,
+        &values,
+        frame_sender,
+        input_receiver,
+        debug_sender,
+        trace_sender,
+    );
     backend.add_component("cpu", Component::new(cpu));
     frontend.register_input_sender(input_sender)?;
     frontend.register_graphics_receiver(frame_receiver)?;
+    frontend.register_debug_receiver(debug_receiver)?;
+    frontend.register_trace_receiver(trace_receiver)?;
 
-    let audio = Audio::new(audio_sender);
+    let min_beep_ms = values
+        .get_int(OPTION_BUZZER_MIN_DURATION)
+        .unwrap_or_default()
+        .max(0) as u64;
+    let audio = Audio::new(audio_sender, Duration::from_millis(min_beep_ms));
     backend.add_component("audio", Component::new(audio));
     frontend.register_audio_receiver(audio_receiver)?;
 
+    // Homebrew roms print by storing characters at the port, e.g. with FX55
+    let debug_port = values.get_int(OPTION_DEBUG_PORT).unwrap_or_default() as MemoryAddress;
+    if debug_port != 0 {
+        if backend.get_bus().get_component_at(debug_port, 1).is_err() {
+            let port_memory: MemoryBlock = vec![0].into();
+            backend.add_addressable_component(
+                "mem_debug_port",
+                debug_port,
+                Component::new(port_memory),
+            )?;
+        }
+        let (text_sender, text_receiver) = build_text_channel();
+        backend.set_debug_port(debug_port, text_sender);
+        frontend.register_text_receiver(text_receiver)?;
+    }
+
     Ok(backend)
 }
+
+#[cfg(test)]
+mod tests {
+    use axwemulator_core::{
+        backend::options::OptionValue,
+        backend::{ExecutionEvent, rewind::RewindBuffer, rtc::rtc_state_range},
+        diagnostic::capture_failure,
+        frontend::{
+            input::{ButtonState, InputEvent, KeyboardEventKey},
+            movie::{InputMovie, MoviePlayer},
+        },
+        statediff::{StateDifference, diff_states},
+        testing::{MockFrontend, at_ms},
+        trace::{TraceComparison, compare_trace, replay_trace},
+    };
+    use femtos::Instant;
+
+    use super::*;
+
+    // LD V0, 0x05; ADD V0, 0x01; JP 0x202
+    const ROM: [u8; 6] = [0x60, 0x05, 0x70, 0x01, 0x12, 0x02];
+
+    fn backend() -> Backend {
+        backend_with_rom(&ROM, &mut MockFrontend::new())
+    }
+
+    fn backend_with_rom(rom: &[u8], frontend: &mut MockFrontend) -> Backend {
+        create_chip8_backend(
+            frontend,
+            Chip8Options {
+                rom_data: rom.to_vec(),
+                platform: Platform::Chip8,
+                options: OptionValues::default(),
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn trace_matches_reference_log() {
+        let reference =
+            "PC:0200 V0:00 PCMEM:60,05\nPC:0202 V0:05\n# loop\nPC:0204 V0:06\nPC:0202 V0:06";
+        let result = compare_trace(&mut backend(), "cpu", reference.lines()).unwrap();
+        assert!(matches!(result, TraceComparison::Matched { lines: 4 }));
+    }
+
+    #[test]
+    fn trace_stops_at_first_divergence() {
+        let reference = "PC:0200 V0:00\nPC:0202 V0:05\nPC:0204 V0:07\nPC:0202 V0:07";
+        let TraceComparison::Diverged(divergence) =
+            compare_trace(&mut backend(), "cpu", reference.lines()).unwrap()
+        else {
+            panic!("trace did not diverge");
+        };
+        assert_eq!(divergence.line_number, 3);
+        assert_eq!(divergence.mismatches.len(), 1);
+        assert_eq!(divergence.mismatches[0].name, "V0");
+        assert_eq!(divergence.mismatches[0].actual, "0x6");
+        assert!(
+            divergence
+                .to_string()
+                .contains("last matching: PC:0202 V0:05")
+        );
+    }
+
+    #[test]
+    fn reports_stack_depth_every_frame() {
+        // CALL 0x204; JP 0x202 (never reached); JP 0x204
+        let rom = [0x22, 0x04, 0x12, 0x02, 0x12, 0x04];
+        let mut frontend = MockFrontend::new();
+        let mut backend = backend_with_rom(&rom, &mut frontend);
+        backend.run_for(Duration::from_millis(110)).unwrap();
+        let depths = frontend.debug_samples("Stack depth");
+        assert_eq!(depths.len(), 6);
+        assert!(depths.iter().all(|depth| *depth == 1.0));
+    }
+
+    #[test]
+    fn demo_movie_drives_the_game() {
+        // LD V0, K; LD I, 0x300; LD [I], V0; JP 0x206
+        let rom = [0xF0, 0x0A, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x06];
+        let mut movie = InputMovie::new(0);
+        movie.record(
+            at_ms(100),
+            0,
+            InputEvent::Keyboard(KeyboardEventKey::E, ButtonState::Pressed),
+        );
+        movie.record(
+            at_ms(150),
+            0,
+            InputEvent::Keyboard(KeyboardEventKey::E, ButtonState::Released),
+        );
+
+        let mut frontend = MockFrontend::new();
+        let mut backend = backend_with_rom(&rom, &mut frontend);
+        let mut player = MoviePlayer::new(movie);
+        for _ in 0..10 {
+            frontend.feed_movie(&mut player);
+            backend.run_for(Duration::from_millis(10)).unwrap();
+        }
+        assert_eq!(backend.get_bus().read_u8(0x300).unwrap(), 0);
+        for _ in 0..10 {
+            frontend.feed_movie(&mut player);
+            backend.run_for(Duration::from_millis(10)).unwrap();
+        }
+        assert_eq!(backend.get_bus().read_u8(0x300).unwrap(), 6);
+    }
+
+    #[test]
+    fn runs_to_timer_expiry() {
+        // LD V0, 0x03; LD DT, V0; JP 0x204
+        let rom = [0x60, 0x03, 0xF0, 0x15, 0x12, 0x04];
+        let mut backend = backend_with_rom(&rom, &mut MockFrontend::new());
+        let limit = Instant::START + Duration::from_secs(1);
+        assert!(backend.run_to(ExecutionEvent::TimerExpiry, limit).unwrap());
+        assert_eq!(backend.get_bus().read_u8(DT_TIMER).unwrap(), 0);
+        let clock = backend.get_current_clock().as_duration();
+        assert!(clock <= Duration::from_millis(4 * 1000 / 60));
+
+        assert!(!backend.run_to(ExecutionEvent::TimerExpiry, limit).unwrap());
+        assert!(backend.get_current_clock() >= limit);
+    }
+
+    #[test]
+    fn counts_frames_in_emulated_time() {
+        // CLS; JP 0x202
+        let rom = [0x00, 0xE0, 0x12, 0x02];
+        let mut backend = backend_with_rom(&rom, &mut MockFrontend::new());
+        backend.run_for(Duration::from_millis(500)).unwrap();
+        assert!(backend.get_elapsed_time() >= Duration::from_millis(500));
+        assert_eq!(backend.get_frame_count(), 1);
+    }
+
+    #[test]
+    fn loaded_state_continues_the_same_way() {
+        let mut backend = backend_with_rom(&ROM, &mut MockFrontend::new());
+        backend.run_for(Duration::from_millis(50)).unwrap();
+        let state = backend.save_state().unwrap();
+        let saved_hash = backend.state_hash().unwrap();
+        backend.run_for(Duration::from_millis(50)).unwrap();
+        let expected = backend.state_hash().unwrap();
+
+        backend.load_state(&state).unwrap();
+        assert_eq!(backend.state_hash().unwrap(), saved_hash);
+        backend.run_for(Duration::from_millis(50)).unwrap();
+        assert_eq!(backend.state_hash().unwrap(), expected);
+    }
+
+    #[test]
+    fn diffs_two_states_by_field() {
+        let mut backend = backend();
+        backend.run_for(Duration::from_millis(50)).unwrap();
+        let left = backend.save_state().unwrap();
+        backend.run_for(Duration::from_millis(10)).unwrap();
+        backend.get_bus().write(0x300, &[0xAB]).unwrap();
+        let right = backend.save_state().unwrap();
+        let hash = backend.state_hash().unwrap();
+
+        let differences = diff_states(&mut backend, &left, &right).unwrap();
+        assert_eq!(backend.state_hash().unwrap(), hash);
+        let names: Vec<&str> = differences
+            .iter()
+            .map(|difference| match difference {
+                StateDifference::Field { name, .. } | StateDifference::Bytes { name, .. } => {
+                    name.as_str()
+                }
+            })
+            .collect();
+        assert!(names.contains(&"clock"));
+        assert!(names.contains(&"cpu V0"));
+        assert!(differences.contains(&StateDifference::Bytes {
+            name: "mem_ram".to_string(),
+            offset: 0x100,
+            left: vec![0],
+            right: vec![0xAB],
+        }));
+        assert!(diff_states(&mut backend, &left, &left).unwrap().is_empty());
+    }
+
+    #[test]
+    fn components_can_be_swapped_while_running() {
+        let mut backend = backend();
+        backend.run_for(Duration::from_millis(50)).unwrap();
+        let ram = backend.remove_component("mem_ram").unwrap();
+        assert!(backend.get_bus().peek(0x200, &mut [0]).is_err());
+        assert!(backend.remove_component("mem_ram").is_err());
+        // Without its rom the cpu fails on the next fetch
+        assert!(backend.run_for(Duration::from_millis(10)).is_err());
+        assert_eq!(backend.failed_component(), Some("cpu"));
+
+        backend
+            .add_addressable_component("mem_ram", 0x200, ram)
+            .unwrap();
+        let audio = backend.remove_component("audio").unwrap();
+        backend.add_component("audio", audio);
+        let clock = backend.get_current_clock();
+        assert!(
+            backend
+                .scheduler_snapshot()
+                .wakeups
+                .iter()
+                .all(|wakeup| wakeup.clock >= clock)
+        );
+        backend.run_for(Duration::from_millis(10)).unwrap();
+        assert!(backend.get_current_clock() > clock);
+    }
+
+    #[test]
+    fn prints_characters_stored_at_the_debug_port() {
+        // LD I, 0xFFF; LD V0, c; LD [I], V0 for each character of the line, then JP 0x212
+        let mut rom = vec![];
+        for character in b"ok\n" {
+            rom.extend_from_slice(&[0xAF, 0xFF, 0x60, *character, 0xF0, 0x55]);
+        }
+        rom.extend_from_slice(&[0x12, 0x12]);
+        let mut options = OptionValues::default();
+        options.set(
+            OPTION_DEBUG_PORT,
+            OptionValue::Int(DEBUG_PORT_ADDRESS as i64),
+        );
+        let mut frontend = MockFrontend::new();
+        let mut backend = create_chip8_backend(
+            &mut frontend,
+            Chip8Options {
+                rom_data: rom,
+                platform: Platform::Chip8,
+                options,
+            },
+        )
+        .unwrap();
+        backend.run_for(Duration::from_millis(50)).unwrap();
+        assert_eq!(frontend.text_lines(), vec!["ok".to_string()]);
+    }
+
+    #[test]
+    fn states_of_older_versions_are_migrated() {
+        let mut backend = backend_with_rom(&ROM, &mut MockFrontend::new());
+        backend.run_for(Duration::from_millis(50)).unwrap();
+        let state = backend.save_state().unwrap();
+
+        // Version 2 lacks the rtc, version 1 the call targets as well
+        let mut version_2 = state.clone();
+        version_2.drain(rtc_state_range());
+        let version_1 = migrate_component_state(version_2.clone(), false, "cpu", |cpu| {
+            let mut cpu = cpu.to_vec();
+            cpu.drain(cpu::call_targets_state_range());
+            Ok(cpu)
+        })
+        .unwrap();
+
+        assert_eq!(migrate_snapshot(2, version_2.clone()).unwrap(), state);
+        assert_eq!(migrate_snapshot(1, version_1).unwrap(), version_2);
+    }
+
+    #[test]
+    fn failed_load_keeps_the_state() {
+        let mut backend = backend_with_rom(&ROM, &mut MockFrontend::new());
+        backend.run_for(Duration::from_millis(50)).unwrap();
+        let hash = backend.state_hash().unwrap();
+        let state = backend.save_state().unwrap();
+        assert!(backend.load_state(&state[..state.len() - 1]).is_err());
+        assert_eq!(backend.state_hash().unwrap(), hash);
+    }
+
+    #[test]
+    fn rewound_state_replays_the_same_way() {
+        let mut backend = backend_with_rom(&ROM, &mut MockFrontend::new());
+        backend.set_rewind(Some(RewindBuffer::new(Duration::from_millis(10), 100)));
+        backend.run_for(Duration::from_millis(100)).unwrap();
+        let clock = backend.get_current_clock();
+        let hash = backend.state_hash().unwrap();
+
+        assert!(backend.rewind(Duration::from_millis(30)).unwrap());
+        let rewound = clock.duration_since(backend.get_current_clock());
+        assert!(rewound >= Duration::from_millis(30) && rewound < Duration::from_millis(40));
+        backend.run_until(clock).unwrap();
+        assert_eq!(backend.get_current_clock(), clock);
+        assert_eq!(backend.state_hash().unwrap(), hash);
+    }
+
+    #[test]
+    fn breakpoint_stops_before_the_instruction() {
+        let mut backend = backend();
+        backend.add_breakpoint("cpu", 0x204).unwrap();
+        backend.run_for(Duration::from_millis(100)).unwrap();
+        let events = backend.take_breakpoint_events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].address, 0x204);
+        assert_eq!(events[0].clock, backend.get_current_clock());
+        // Only LD V0 and ADD V0 ran so far
+        let cpu = backend.get_component("cpu").unwrap();
+        let registers = cpu.borrow_mut().as_traceable().unwrap().trace_registers();
+        assert!(registers.contains(&("V0", 6)));
+
+        backend.remove_breakpoint("cpu", 0x204);
+        backend.run_for(Duration::from_millis(100)).unwrap();
+        assert!(backend.take_breakpoint_events().is_empty());
+        assert!(backend.add_breakpoint("missing", 0x204).is_err());
+    }
+
+    #[test]
+    fn breakpoint_at_the_first_instruction() {
+        let mut backend = backend();
+        backend.add_breakpoint("cpu", 0x200).unwrap();
+        backend.run_for(Duration::from_millis(100)).unwrap();
+        let events = backend.take_breakpoint_events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].address, 0x200);
+        assert_eq!(backend.get_pc("cpu").unwrap(), 0x200);
+
+        // Continuing runs the instruction instead of stopping at it again
+        backend.run_for(Duration::from_millis(100)).unwrap();
+        assert!(backend.take_breakpoint_events().is_empty());
+        assert_ne!(backend.get_pc("cpu").unwrap(), 0x200);
+    }
+
+    #[test]
+    fn replays_the_steps_before_a_breakpoint() {
+        let mut backend = backend();
+        backend.set_rewind(Some(RewindBuffer::new(Duration::from_millis(10), 100)));
+        backend.run_for(Duration::from_millis(50)).unwrap();
+        backend.add_breakpoint("cpu", 0x204).unwrap();
+        backend.run_for(Duration::from_millis(50)).unwrap();
+        let hit = backend.take_breakpoint_events().remove(0);
+        let hash = backend.state_hash().unwrap();
+
+        let start = hit.clock.checked_sub(Duration::from_millis(20)).unwrap();
+        let lines = replay_trace(&mut backend, "cpu", start, hit.clock).unwrap();
+        assert!(lines.len() >= 14);
+        // Ends with the step which stopped at the breakpoint
+        assert!(lines.last().unwrap().starts_with("PC:0204 "));
+        assert_eq!(backend.get_current_clock(), hit.clock);
+        assert_eq!(backend.state_hash().unwrap(), hash);
+        assert_eq!(backend.take_breakpoint_events(), vec![hit]);
+    }
+
+    #[test]
+    fn paused_cpu_only_runs_single_steps() {
+        let mut backend = backend();
+        backend.pause_component("cpu").unwrap();
+        backend.run_for(Duration::from_millis(50)).unwrap();
+        assert_eq!(backend.get_pc("cpu").unwrap(), 0x200);
+
+        backend.step_instruction("cpu").unwrap();
+        backend.step_instruction("cpu").unwrap();
+        assert_eq!(backend.get_pc("cpu").unwrap(), 0x204);
+        assert!(backend.is_component_paused("cpu").unwrap());
+
+        backend.resume_component("cpu").unwrap();
+        backend.run_for(Duration::from_millis(50)).unwrap();
+        assert!(!backend.is_component_paused("cpu").unwrap());
+        assert!(backend.step_instruction("timer").is_err());
+    }
+
+    #[test]
+    fn state_hash_follows_the_state() {
+        let run = |rom: &[u8]| {
+            let mut backend = backend_with_rom(rom, &mut MockFrontend::new());
+            backend.run_for(Duration::from_millis(50)).unwrap();
+            backend.state_hash().unwrap()
+        };
+        assert_eq!(run(&ROM), run(&ROM));
+        // LD V0, 0x06 instead of 0x05
+        assert_ne!(run(&ROM), run(&[0x60, 0x06, 0x70, 0x01, 0x12, 0x02]));
+        // Draws the glyph of the digit, then sets the digit in the rom and I back to 0, so the
+        // registers and the memory end alike and only the frame buffer differs.
+        // LD V0, digit; LD F, V0; LD V0, 0; DRW V0, V0, 5; LD I, 0x201; LD [I], V0; LD I, 0;
+        // JP 0x20E
+        let draw = |digit: u8| {
+            [
+                0x60, digit, 0xF0, 0x29, 0x60, 0x00, 0xD0, 0x05, 0xA2, 0x01, 0xF0, 0x55, 0xA0,
+                0x00, 0x12, 0x0E,
+            ]
+        };
+        assert_ne!(run(&draw(0)), run(&draw(1)));
+    }
+
+    #[test]
+    fn disassembles_the_rom_from_the_bus() {
+        let backend = backend();
+        let instructions = backend.disassemble("cpu", 0x200, 0x205).unwrap();
+        let lines: Vec<String> = instructions
+            .iter()
+            .map(|instruction| {
+                format!(
+                    "{:04X} {} {}",
+                    instruction.address,
+                    instruction.mnemonic,
+                    instruction.operands.join(",")
+                )
+            })
+            .collect();
+        assert_eq!(
+            lines,
+            vec!["0200 LVI 0x0,0x5", "0202 AVI 0x0,0x1", "0204 JMP 0x202"]
+        );
+        assert!(backend.disassemble("timer", 0x200, 0x202).is_err());
+    }
+
+    #[test]
+    fn traces_executed_instructions_once_enabled() {
+        let mut frontend = MockFrontend::new();
+        let mut backend = backend_with_rom(&ROM, &mut frontend);
+        backend.step_instruction("cpu").unwrap();
+        frontend.enable_trace();
+        backend.step_instruction("cpu").unwrap();
+        backend.step_instruction("cpu").unwrap();
+        let lines: Vec<String> = frontend
+            .trace_entries()
+            .iter()
+            .map(|entry| entry.to_string())
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "0202 7001 AVI[0x0,0x1]         PC=204 V0=6",
+                "0204 1202 JMP[0x202]           PC=202",
+            ]
+        );
+    }
+
+    #[test]
+    fn failure_report_replays_up_to_the_failing_step() {
+        // LD V0, 0x05; ADD V0, 0x01; an unknown opcode
+        let rom = [0x60, 0x05, 0x70, 0x01, 0x80, 0x0F];
+        let mut backend = backend_with_rom(&rom, &mut MockFrontend::new());
+        backend.set_rewind(Some(RewindBuffer::new(Duration::from_millis(10), 100)));
+        let error = backend.run_for(Duration::from_millis(100)).unwrap_err();
+        let clock = backend.get_current_clock();
+
+        let report = capture_failure(&mut backend, &error, Duration::from_secs(1)).unwrap();
+        assert_eq!(report.component, "cpu");
+        assert_eq!(report.clock, clock);
+        assert!(report.trace.last().unwrap().starts_with("PC:0204"));
+        assert!(report.to_string().contains("unknown opcode"));
+        assert_eq!(backend.get_pc("cpu").unwrap(), 0x204);
+
+        let mut replayed = backend_with_rom(&rom, &mut MockFrontend::new());
+        replayed.load_state(&report.state).unwrap();
+        assert!(replayed.step().is_err());
+        assert_eq!(replayed.get_current_clock(), clock);
+    }
+
+    #[test]
+    fn reset_starts_the_rom_over() {
+        // LD V0, 0x05; LD I, 0x300; LD [I], V0; JP 0x206
+        let rom = [0x60, 0x05, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x06];
+        let mut backend = backend_with_rom(&rom, &mut MockFrontend::new());
+        backend.run_for(Duration::from_millis(100)).unwrap();
+        let hash = backend.state_hash().unwrap();
+        assert_eq!(backend.get_bus().read_u8(0x300).unwrap(), 0x05);
+
+        backend.reset().unwrap();
+        assert_eq!(backend.get_current_clock(), Instant::START);
+        assert_eq!(backend.get_pc("cpu").unwrap(), 0x200);
+        assert_eq!(backend.get_bus().read_u8(0x300).unwrap(), 0x00);
+        assert_eq!(backend.get_bus().read_u8(FONT_BASE).unwrap(), FONT_SET[0]);
+        backend.run_for(Duration::from_millis(100)).unwrap();
+        assert_eq!(backend.state_hash().unwrap(), hash);
+    }
+
+    #[test]
+    fn paused_backend_only_takes_explicit_steps() {
+        let mut backend = backend();
+        backend.pause();
+        backend.run_for(Duration::from_millis(50)).unwrap();
+        assert_eq!(backend.get_current_clock(), Instant::START);
+
+        backend.step_instruction("cpu").unwrap();
+        assert_eq!(backend.get_pc("cpu").unwrap(), 0x202);
+        assert!(backend.is_paused());
+
+        backend.resume();
+        backend.run_for(Duration::from_millis(50)).unwrap();
+        assert!(backend.get_current_clock().as_duration() >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn speed_scales_the_emulated_time_of_a_run() {
+        let mut backend = backend();
+        backend.set_speed(0.25).unwrap();
+        backend.run_for(Duration::from_millis(100)).unwrap();
+        let slow = backend.get_current_clock().as_duration();
+        assert!(slow >= Duration::from_millis(25) && slow < Duration::from_millis(30));
+
+        backend.set_speed(4.0).unwrap();
+        backend.run_for(Duration::from_millis(100)).unwrap();
+        let fast = backend.get_current_clock().as_duration();
+        assert!(fast >= Duration::from_millis(425) && fast < Duration::from_millis(430));
+        assert!(backend.set_speed(0.0).is_err());
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::BTreeSet;
+
+use axwemulator_core::{
+    backend::{
+        Backend,
+        component::{Addressable, MemoryAddress},
+        options::{OptionSuggestion, OptionValue, OptionValues},
+    },
+    error::Error,
+};
+use femtos::Duration;
+
+use crate::{
+    Chip8Options, OPTION_QUIRK_JUMP, OPTION_QUIRK_LOADSTORE_I_ONE_LESS,
+    OPTION_QUIRK_LOADSTORE_I_UNMODIFIED, OPTION_QUIRK_LOGIC, OPTION_QUIRK_SHIFT, Platform,
+    create_chip8_backend, options_schema, quirks_test::HeadlessFrontend,
+};
+
+// Three seconds, long enough to get through the start of most roms without any input
+const RUN_FRAMES: usize = 180;
+const FRAME_DURATION: Duration = Duration::from_micros(16_667);
+
+// The quirks which change the flow of a program, the others only change what it draws
+const DETECTED_QUIRKS: [&str; 5] = [
+    OPTION_QUIRK_SHIFT,
+    OPTION_QUIRK_JUMP,
+    OPTION_QUIRK_LOGIC,
+    OPTION_QUIRK_LOADSTORE_I_UNMODIFIED,
+    OPTION_QUIRK_LOADSTORE_I_ONE_LESS,
+];
+
+// Ordered from worst to best
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Outcome {
+    // An unknown opcode or an access outside of the memory
+    Crashed,
+    // Roms halt by jumping to themselves, which a wrong quirk often leads to early
+    Halted,
+    Running,
+}
+
+struct Trial {
+    quirks: OptionValues,
+    outcome: Outcome,
+    // Frames until the rom crashed or halted
+    frames: usize,
+    error: Option<String>,
+    distinct_pcs: usize,
+    // Quirks set differently than in the options the analysis started with
+    changes: Vec<&'static str>,
+}
+
+impl Trial {
+    fn summary(&self) -> String {
+        let seconds = self.frames as f32 / 60.0;
+        match (self.outcome, &self.error) {
+            (Outcome::Crashed, Some(error)) => format!("crash after {:.1}s: {}", seconds, error),
+            (Outcome::Crashed, None) => format!("crash after {:.1}s", seconds),
+            (Outcome::Halted, _) => format!("halt after {:.1}s", seconds),
+            (Outcome::Running, _) => "keep running".to_string(),
+        }
+    }
+}
+
+// Every combination of the detected quirks, the two load/store quirks exclude each other
+fn combinations() -> Vec<OptionValues> {
+    (0..24)
+        .map(|index| {
+            let (unmodified, one_less) = [(false, false), (true, false), (false, true)][index % 3];
+            let flags = index / 3;
+            let mut quirks = OptionValues::default();
+            quirks.set(OPTION_QUIRK_SHIFT, OptionValue::Bool(flags & 1 != 0));
+            quirks.set(OPTION_QUIRK_JUMP, OptionValue::Bool(flags & 2 != 0));
+            quirks.set(OPTION_QUIRK_LOGIC, OptionValue::Bool(flags & 4 != 0));
+            quirks.set(
+                OPTION_QUIRK_LOADSTORE_I_UNMODIFIED,
+                OptionValue::Bool(unmodified),
+            );
+            quirks.set(
+                OPTION_QUIRK_LOADSTORE_I_ONE_LESS,
+                OptionValue::Bool(one_less),
+            );
+            quirks
+        })
+        .collect()
+}
+
+fn halts(backend: &Backend, pc: MemoryAddress) -> bool {
+    backend
+        .get_bus()
+        .read_u16_be(pc)
+        .is_ok_and(|opcode| opcode as MemoryAddress == 0x1000 | pc)
+}
+
+fn run_trial(
+    rom: &[u8],
+    options: &OptionValues,
+    quirks: OptionValues,
+    platform: Platform,
+) -> Result<Trial, Error> {
+    let mut values = options.clone();
+    values.apply(&quirks);
+    let changes = DETECTED_QUIRKS
+        .into_iter()
+        .filter(|key| options.get(key) != quirks.get(key))
+        .collect();
+    let mut frontend = HeadlessFrontend::default();
+    let mut backend = create_chip8_backend(
+        &mut frontend,
+        Chip8Options {
+            rom_data: rom.to_vec(),
+            platform,
+            options: values,
+        },
+    )?;
+
+    let mut pcs = BTreeSet::new();
+    let mut trial = Trial {
+        quirks,
+        outcome: Outcome::Running,
+        frames: RUN_FRAMES,
+        error: None,
+        distinct_pcs: 0,
+        changes,
+    };
+    for frame in 0..RUN_FRAMES {
+        if let Err(error) = backend.run_for(FRAME_DURATION) {
+            trial.outcome = Outcome::Crashed;
+            trial.frames = frame;
+            trial.error = Some(error.to_string());
+            break;
+        }
+        let pc = backend.get_pc("cpu")?;
+        pcs.insert(pc);
+        if halts(&backend, pc) {
+            trial.outcome = Outcome::Halted;
+            trial.frames = frame;
+            break;
+        }
+    }
+    trial.distinct_pcs = pcs.len();
+    Ok(trial)
+}
+
+// Runs the rom briefly under every combination of the quirks which change its flow. The one
+// which runs the longest without crashing or halting wins, ties go to the fewest changes to the
+// given options and then to the one which reached the most code.
+pub fn detect_quirks(
+    rom: &[u8],
+    options: OptionValues,
+    platform: Platform,
+) -> Result<OptionSuggestion, Error> {
+    let schema = options_schema(platform);
+    let options = schema.resolve(&options);
+    let trials = combinations()
+        .into_iter()
+        .map(|quirks| run_trial(rom, &options, quirks, platform))
+        .collect::<Result<Vec<Trial>, Error>>()?;
+    let best = trials
+        .iter()
+        .max_by(|a, b| {
+            (a.outcome, a.frames)
+                .cmp(&(b.outcome, b.frames))
+                .then(b.changes.len().cmp(&a.changes.len()))
+                .then(a.distinct_pcs.cmp(&b.distinct_pcs))
+        })
+        .ok_or_else(|| Error::new("no quirk combination to try"))?;
+
+    let count = |outcome| trials.iter().filter(|t| t.outcome == outcome).count();
+    let mut notes = vec![format!(
+        "{} of {} combinations crashed, {} halted within {}s",
+        count(Outcome::Crashed),
+        trials.len(),
+        count(Outcome::Halted),
+        RUN_FRAMES / 60
+    )];
+    if let Some(current) = trials.iter().find(|t| t.changes.is_empty()) {
+        notes.push(format!("The current quirks {}", current.summary()));
+    }
+    if best.outcome == Outcome::Crashed {
+        notes.push("Every combination crashed, the rom may not be a chip8 program".to_string());
+    } else if best.changes.is_empty() {
+        notes.push("No combination did better than the current quirks".to_string());
+    } else {
+        for key in &best.changes {
+            let label = schema
+                .options()
+                .iter()
+                .find(|option| option.key == *key)
+                .map_or(*key, |option| option.label);
+            let state = if best.quirks.get_bool(key).unwrap_or_default() {
+                "on"
+            } else {
+                "off"
+            };
+            notes.push(format!("Suggests {}: {}", label, state));
+        }
+        notes.push(format!("With them the rom would {}", best.summary()));
+    }
+
+    Ok(OptionSuggestion {
+        values: best.quirks.clone(),
+        notes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suggests_the_quirk_which_avoids_a_crash() {
+        // LD V0, 4; LD V1, 0; SHR V0, V1; SE V0, 2; an unknown opcode; ADD V2, 1; JP 0x20A
+        let rom = [
+            0x60, 0x04, 0x61, 0x00, 0x80, 0x16, 0x30, 0x02, 0x80, 0x08, 0x72, 0x01, 0x12, 0x0A,
+        ];
+        let suggestion = detect_quirks(&rom, OptionValues::default(), Platform::Chip8).unwrap();
+
+        assert_eq!(suggestion.values.get_bool(OPTION_QUIRK_SHIFT), Some(true));
+        for key in &DETECTED_QUIRKS[1..] {
+            assert_eq!(suggestion.values.get_bool(key), Some(false));
+        }
+        assert!(
+            suggestion
+                .notes
+                .iter()
+                .any(|note| note.starts_with("The current quirks crash"))
+        );
+    }
+
+    #[test]
+    fn jumps_and_recursion_crash_the_trial_instead_of_the_app() {
+        // LD V0, 0; JP V0, 0x300, with the jump quirk to V3 + 0x300
+        let jump = [0x60, 0x00, 0xB3, 0x00];
+        // CALL 0x200, which recurses until the stack overflows
+        let call = [0x22, 0x00];
+        for rom in [&jump[..], &call[..]] {
+            assert!(detect_quirks(rom, OptionValues::default(), Platform::Chip8).is_ok());
+        }
+        let suggestion = detect_quirks(&call, OptionValues::default(), Platform::Chip8).unwrap();
+        assert!(suggestion.notes.contains(
+            &"Every combination crashed, the rom may not be a chip8 program".to_string()
+        ));
+    }
+
+    #[test]
+    fn keeps_the_quirks_of_a_rom_which_runs_with_them() {
+        // LD V0, 5; ADD V0, 1; JP 0x202
+        let rom = [0x60, 0x05, 0x70, 0x01, 0x12, 0x02];
+        let options = options_schema(Platform::SuperChip).defaults();
+        let suggestion = detect_quirks(&rom, options.clone(), Platform::SuperChip).unwrap();
+
+        for key in DETECTED_QUIRKS {
+            assert_eq!(suggestion.values.get(key), options.get(key));
+        }
+        assert!(
+            suggestion
+                .notes
+                .contains(&"No combination did better than the current quirks".to_string())
+        );
+    }
+}
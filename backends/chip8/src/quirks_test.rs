@@ -0,0 +1,287 @@
+use axwemulator_core::{
+    backend::{
+        component::Addressable,
+        options::{OptionValue, OptionValues},
+        selftest::{SelfTestReport, SelfTestResult, SelfTestVerdict},
+    },
+    error::Error,
+    frontend::{
+        Frontend,
+        audio::AudioReceiver,
+        error::FrontendError,
+        graphics::{Frame, FrameReceiver},
+        input::InputSender,
+    },
+};
+use femtos::Duration;
+
+use crate::{
+    Chip8Options, OPTION_COLOR_BACKGROUND, OPTION_COLOR_FOREGROUND, OPTION_PHOSPHOR_PERSISTENCE,
+    Platform, create_chip8_backend,
+};
+
+// Rows of the result screen of the quirks test from Timendus' chip8 test suite, top to bottom
+pub const QUIRKS: [&str; 6] = [
+    "VF reset",
+    "Memory",
+    "Display wait",
+    "Clipping",
+    "Shifting",
+    "Jumping",
+];
+
+// The test rom skips its platform menu when this byte is set before it starts
+const PLATFORM_SELECTION: usize = 0x1FF;
+const TEST_DURATION_MS: u64 = 10_000;
+
+// Verdict glyphs in lores pixels, the screen may show them scaled up
+const CHECK_GLYPH: [&str; 5] = ["....#", "...#.", "#.#..", ".#...", "....."];
+const CROSS_GLYPH: [&str; 5] = ["#...#", ".#.#.", "..#..", ".#.#.", "#...#"];
+
+// Only keeps the graphics, headless runs need neither input nor audio
+#[derive(Default)]
+pub(crate) struct HeadlessFrontend {
+    graphics: Option<FrameReceiver>,
+}
+
+impl Frontend for HeadlessFrontend {
+    type Error = Error;
+
+    fn register_graphics_receiver(
+        &mut self,
+        receiver: FrameReceiver,
+    ) -> Result<(), FrontendError<Self::Error>> {
+        self.graphics = Some(receiver);
+        Ok(())
+    }
+
+    fn register_audio_receiver(
+        &mut self,
+        _receiver: AudioReceiver,
+    ) -> Result<(), FrontendError<Self::Error>> {
+        Ok(())
+    }
+
+    fn register_input_sender(
+        &mut self,
+        _sender: InputSender,
+    ) -> Result<(), FrontendError<Self::Error>> {
+        Ok(())
+    }
+}
+
+pub fn run_quirks_test(
+    rom: &[u8],
+    mut options: OptionValues,
+    platform: Platform,
+) -> Result<SelfTestReport, Error> {
+    // The screen is parsed by brightness, so it must not depend on the configured look
+    options.set(
+        OPTION_COLOR_FOREGROUND,
+        OptionValue::Color((255, 255, 255, 255)),
+    );
+    options.set(OPTION_COLOR_BACKGROUND, OptionValue::Color((0, 0, 0, 255)));
+    options.set(OPTION_PHOSPHOR_PERSISTENCE, OptionValue::Int(0));
+
+    let mut frontend = HeadlessFrontend::default();
+    let mut backend = create_chip8_backend(
+        &mut frontend,
+        Chip8Options {
+            rom_data: rom.to_vec(),
+            platform,
+            options,
+        },
+    )?;
+    let selection = match platform {
+        Platform::Chip8 => 1,
+        // The original SCHIP 1.1 behaviour, which the SuperChip defaults follow
+        Platform::SuperChip => 4,
+    };
+    backend.get_bus().write_u8(PLATFORM_SELECTION, selection)?;
+    backend.run_for(Duration::from_millis(TEST_DURATION_MS))?;
+
+    let frame = frontend
+        .graphics
+        .as_ref()
+        .and_then(FrameReceiver::latest)
+        .map(|(_, frame)| frame)
+        .ok_or_else(|| Error::new("the test rom did not draw anything"))?;
+    Ok(parse_quirks_screen(&frame))
+}
+
+// Finds the verdict glyph in each row of text of the result screen. Rows without a recognizable
+// glyph are reported as unknown, instead of guessing.
+pub fn parse_quirks_screen(frame: &Frame) -> SelfTestReport {
+    let lit = |x: usize, y: usize| frame.data[y * frame.width + x].0 > 127;
+    let row_lit = |y: usize| (0..frame.width).any(|x| lit(x, y));
+
+    let mut bands = vec![];
+    let mut start = None;
+    for y in 0..=frame.height {
+        match (start, y < frame.height && row_lit(y)) {
+            (None, true) => start = Some(y),
+            (Some(top), false) => {
+                bands.push(top..y);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    let bands = bands.split_off(bands.len().saturating_sub(QUIRKS.len()));
+    QUIRKS
+        .iter()
+        .enumerate()
+        .map(|(index, name)| {
+            let verdict = if bands.len() == QUIRKS.len() {
+                let band = bands[index].clone();
+                find_verdict(frame.width, &|x| band.clone().map(|y| lit(x, y)).collect())
+            } else {
+                SelfTestVerdict::Unknown
+            };
+            SelfTestResult {
+                name: name.to_string(),
+                verdict,
+            }
+        })
+        .collect()
+}
+
+// Splits a row of text into glyphs at empty columns and compares each of them with the verdicts
+fn find_verdict(width: usize, column: &dyn Fn(usize) -> Vec<bool>) -> SelfTestVerdict {
+    let columns: Vec<Vec<bool>> = (0..width).map(column).collect();
+    let mut x = 0;
+    while x < width {
+        if !columns[x].iter().any(|lit| *lit) {
+            x += 1;
+            continue;
+        }
+        let glyph_start = x;
+        while x < width && columns[x].iter().any(|lit| *lit) {
+            x += 1;
+        }
+        let glyph = &columns[glyph_start..x];
+        if matches_glyph(glyph, &CHECK_GLYPH) {
+            return SelfTestVerdict::Pass;
+        }
+        if matches_glyph(glyph, &CROSS_GLYPH) {
+            return SelfTestVerdict::Fail;
+        }
+    }
+    SelfTestVerdict::Unknown
+}
+
+fn matches_glyph(columns: &[Vec<bool>], template: &[&str]) -> bool {
+    let rows: Vec<usize> = (0..columns[0].len())
+        .filter(|y| columns.iter().any(|column| column[*y]))
+        .collect();
+    let (Some(top), Some(bottom)) = (rows.first(), rows.last()) else {
+        return false;
+    };
+    let template_width = template[0].len();
+    let template_rows: Vec<usize> = (0..template.len())
+        .filter(|y| template[*y].contains('#'))
+        .collect();
+    let template_top = template_rows[0];
+    let template_height = template_rows[template_rows.len() - 1] - template_top + 1;
+
+    (1..=2).any(|scale| {
+        columns.len() == template_width * scale
+            && bottom - top + 1 == template_height * scale
+            && columns.iter().enumerate().all(|(x, column)| {
+                (0..template_height * scale).all(|y| {
+                    let expected = template[template_top + y / scale].as_bytes()[x / scale] == b'#';
+                    column[top + y] == expected
+                })
+            })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draw_glyph(frame: &mut Frame, glyph: &[&str], x: usize, y: usize, scale: usize) {
+        for (row, line) in glyph.iter().enumerate() {
+            for (column, pixel) in line.bytes().enumerate() {
+                if pixel != b'#' {
+                    continue;
+                }
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        let index = (y + row * scale + dy) * frame.width + x + column * scale + dx;
+                        frame.data[index] = (255, 255, 255, 255);
+                    }
+                }
+            }
+        }
+    }
+
+    const LABEL: [&str; 5] = ["###", "#.#", "###", "#..", "#.."];
+
+    #[test]
+    fn reads_verdict_of_each_quirk() {
+        let mut frame = Frame::new((128, 64));
+        for row in 0..QUIRKS.len() {
+            // Lores text is drawn with doubled pixels
+            let (y, scale) = if row < 3 {
+                (row * 7, 1)
+            } else {
+                (row * 7 + (row - 3) * 5, 2)
+            };
+            draw_glyph(&mut frame, &LABEL, 2, y, scale);
+            let verdict = if row == 4 { &CROSS_GLYPH } else { &CHECK_GLYPH };
+            draw_glyph(&mut frame, verdict, 40, y, scale);
+            draw_glyph(&mut frame, &LABEL, 60, y, scale);
+        }
+
+        let report = parse_quirks_screen(&frame);
+        let verdicts: Vec<SelfTestVerdict> = report.iter().map(|r| r.verdict).collect();
+        assert_eq!(report[4].name, "Shifting");
+        assert_eq!(
+            verdicts,
+            vec![
+                SelfTestVerdict::Pass,
+                SelfTestVerdict::Pass,
+                SelfTestVerdict::Pass,
+                SelfTestVerdict::Pass,
+                SelfTestVerdict::Fail,
+                SelfTestVerdict::Pass,
+            ]
+        );
+    }
+
+    #[test]
+    fn unfinished_screen_is_unknown() {
+        let mut frame = Frame::new((128, 64));
+        draw_glyph(&mut frame, &CHECK_GLYPH, 10, 10, 1);
+        let report = parse_quirks_screen(&frame);
+        assert!(
+            report
+                .iter()
+                .all(|result| result.verdict == SelfTestVerdict::Unknown)
+        );
+    }
+
+    // Runs the actual test rom, which is not part of the repository. Point CHIP8_QUIRKS_ROM at
+    // 5-quirks.ch8 of https://github.com/Timendus/chip8-test-suite to enable it.
+    #[test]
+    fn quirks_rom_passes_with_default_options() {
+        let Ok(path) = std::env::var("CHIP8_QUIRKS_ROM") else {
+            return;
+        };
+        let rom = std::fs::read(path).unwrap();
+        for platform in [Platform::Chip8, Platform::SuperChip] {
+            let report = run_quirks_test(&rom, OptionValues::default(), platform).unwrap();
+            for result in report {
+                assert_eq!(
+                    result.verdict,
+                    SelfTestVerdict::Pass,
+                    "{} on {:?}",
+                    result.name,
+                    platform
+                );
+            }
+        }
+    }
+}
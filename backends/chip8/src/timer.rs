@@ -1,6 +1,6 @@
 use axwemulator_core::{
     backend::{
-        Backend,
+        Backend, ExecutionEvent,
         component::{Addressable, Steppable, Transmutable},
     },
     error::Error,
@@ -33,6 +33,10 @@ impl Steppable for Timer {
             backend.get_bus().write_u8(ST_TIMER, st.saturating_sub(1))?;
         }
 
+        if dt == 1 || st == 1 {
+            backend.signal(ExecutionEvent::TimerExpiry);
+        }
+
         Ok(Duration::from_nanos(TIMER_CLOCK_SPEED_NS))
     }
 }
@@ -42,3 +46,43 @@ impl Transmutable for Timer {
         Some(self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use axwemulator_core::testing::MockBackend;
+
+    use super::*;
+    use crate::TIMER_BASE;
+
+    fn backend(dt: u8, st: u8) -> MockBackend {
+        MockBackend::new().with_data(TIMER_BASE, vec![dt, st])
+    }
+
+    #[test]
+    fn decrements_both_timers_at_60hz() {
+        let mut backend = backend(3, 2);
+        let mut timer = Timer::new();
+        let duration = backend.step(&mut timer);
+        assert_eq!(duration, Duration::from_nanos(TIMER_CLOCK_SPEED_NS));
+        backend.assert_memory(DT_TIMER, &[2, 1]);
+    }
+
+    #[test]
+    fn stops_at_zero() {
+        let mut backend = backend(2, 1);
+        let mut timer = Timer::new();
+        backend.step_n(&mut timer, 5);
+        backend.assert_memory(DT_TIMER, &[0, 0]);
+    }
+
+    #[test]
+    fn timers_count_down_independently() {
+        let mut backend = backend(0, 0);
+        let mut timer = Timer::new();
+        backend.step(&mut timer);
+        backend.write_u8(ST_TIMER, 60);
+        backend.step_n(&mut timer, 59);
+        assert_eq!(backend.read_u8(DT_TIMER), 0);
+        assert_eq!(backend.read_u8(ST_TIMER), 1);
+    }
+}
@@ -2,8 +2,12 @@ use std::f32::consts::PI;
 
 use axwemulator_core::{
     backend::{
-        Backend,
-        component::{Component, Steppable, Transmutable},
+        Backend, BackendInfo, ExecutionEvent,
+        component::{Component, Resettable, Saveable, Steppable, Transmutable},
+        options::OptionsSchema,
+        registry::{BackendDescriptor, BackendRegistry},
+        rtc::migrate_payload_without_rtc,
+        snapshot::{StateReader, StateWriter},
     },
     error::Error,
     frontend::{
@@ -21,6 +25,8 @@ struct SimpleCpu {
 }
 
 impl Steppable for SimpleCpu {
+    // the colors are purely cosmetic output and never fed back into the state
+    #[allow(clippy::disallowed_methods)]
     fn step(&mut self, backend: &Backend) -> Result<Duration, Error> {
         self.counter += 1;
         self.text_sender.add(
@@ -28,31 +34,91 @@ impl Steppable for SimpleCpu {
             format!("Counter: {}", self.counter),
         );
 
-        let frame = Frame {
-            width: 100,
-            height: 100,
-            data: [(
-                (((self.counter as f32 * PI / 40.0).sin() + 1.0) * 255.0) as u8,
-                ((((self.counter as f32 + 0.5) * PI / 40.0).sin() + 1.0) * 255.0) as u8,
-                ((((self.counter as f32 + 1.0) * PI / 40.0).sin() + 1.0) * 255.0) as u8,
-                255,
-            ); 100 * 100]
-                .to_vec(),
-        };
-        self.frame_sender.add(backend.get_current_clock(), frame);
+        backend.signal(ExecutionEvent::Frame);
+        if backend.is_render_enabled() {
+            let frame = Frame {
+                width: 100,
+                height: 100,
+                data: [(
+                    (((self.counter as f32 * PI / 40.0).sin() + 1.0) * 255.0) as u8,
+                    ((((self.counter as f32 + 0.5) * PI / 40.0).sin() + 1.0) * 255.0) as u8,
+                    ((((self.counter as f32 + 1.0) * PI / 40.0).sin() + 1.0) * 255.0) as u8,
+                    255,
+                ); 100 * 100]
+                    .to_vec(),
+            };
+            self.frame_sender.add(backend.get_current_clock(), frame);
+        }
 
         Ok(Duration::from_millis(20))
     }
 }
 
+impl Saveable for SimpleCpu {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u64(self.counter);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), Error> {
+        self.counter = reader.read_u64()?;
+        Ok(())
+    }
+}
+
+impl Resettable for SimpleCpu {
+    fn reset(&mut self) {
+        self.counter = 0;
+    }
+}
+
 impl Transmutable for SimpleCpu {
     fn as_steppable(&mut self) -> Option<&mut dyn Steppable> {
         Some(self)
     }
+
+    fn as_saveable(&mut self) -> Option<&mut dyn Saveable> {
+        Some(self)
+    }
+
+    fn as_resettable(&mut self) -> Option<&mut dyn Resettable> {
+        Some(self)
+    }
+}
+
+// Version 1 was written before the backend saved its rtc
+fn migrate_snapshot(from_version: u32, payload: Vec<u8>) -> Result<Vec<u8>, Error> {
+    match from_version {
+        1 => migrate_payload_without_rtc(payload),
+        _ => Err(Error::new(format!(
+            "no migration from snapshot version {}",
+            from_version
+        ))),
+    }
+}
+
+pub fn register(registry: &mut BackendRegistry) {
+    registry.register(BackendDescriptor {
+        name: "Simple",
+        extensions: &[],
+        detect: None,
+        known_dumps: &[],
+        header_check: None,
+        options: OptionsSchema::new,
+        firmware: &[],
+        snapshot_version: 2,
+        snapshot_migration: Some(migrate_snapshot),
+        self_test: None,
+        options_analysis: None,
+        constructor: |frontend, _options| create_simple_backend(frontend),
+    });
 }
 
-pub fn create_simple_backend<F: Frontend>(frontend: &mut F) -> Result<Backend, Error> {
+pub fn create_simple_backend<F: Frontend + ?Sized>(frontend: &mut F) -> Result<Backend, Error> {
     let mut backend = Backend::default();
+    backend.set_info(BackendInfo {
+        refresh_rate: 50.0,
+        ..Default::default()
+    });
 
     let (text_sender, text_receiver) = build_text_channel();
     let (frame_sender, frame_receiver) = build_frame_channel(100, 100);
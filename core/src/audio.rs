@@ -0,0 +1,363 @@
+// Building blocks for sound generators. Everything runs on integer or fixed-point state and only
+// converts to samples at the very end, so the output is identical on every target.
+
+use crate::frontend::audio::Sample;
+
+// Offsets inside a sample are given in 1/65536 of a sample
+pub const SAMPLE_FRACTION_BITS: u32 = 16;
+
+// Places amplitude changes with sub-sample precision and spreads each of them over three samples
+// with a polynomial band-limited step, which removes most of the aliasing of ideal square waves.
+// The output lags one sample behind, as steps within a sample also affect the one before.
+// Sums are kept in fixed-point, so the level never drifts away from the sum of all steps.
+#[derive(Debug, Clone, Default)]
+pub struct BlipBuffer {
+    pending: [i64; 3],
+    level: i64,
+}
+
+const BLIP_AMPLITUDE_SCALE: Sample = (1 << 15) as Sample;
+const BLIP_ONE: i64 = 1 << SAMPLE_FRACTION_BITS;
+
+impl BlipBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_step(&mut self, offset: u32, delta: Sample) {
+        let delta = (delta * BLIP_AMPLITUDE_SCALE).round() as i64;
+        let t = (offset as i64).min(BLIP_ONE);
+        let before = ((BLIP_ONE - t) * (BLIP_ONE - t)) >> (SAMPLE_FRACTION_BITS + 1);
+        let after = (t * t) >> (SAMPLE_FRACTION_BITS + 1);
+        self.pending[0] += delta * before;
+        self.pending[1] += delta * (BLIP_ONE - before - after);
+        self.pending[2] += delta * after;
+    }
+
+    pub fn next_sample(&mut self) -> Sample {
+        self.level += self.pending[0];
+        self.pending = [self.pending[1], self.pending[2], 0];
+        self.level as Sample / (BLIP_AMPLITUDE_SCALE * BLIP_ONE as Sample)
+    }
+}
+
+fn phase_increment(frequency: u64, sample_rate: u64) -> u32 {
+    ((frequency << 32) / sample_rate).min(u32::MAX as u64) as u32
+}
+
+// Bipolar square wave, the phase is a 32 bit fixed-point fraction of one period
+#[derive(Debug, Clone)]
+pub struct SquareOscillator {
+    phase: u32,
+    increment: u32,
+    duty: u32,
+    amplitude: Sample,
+}
+
+impl SquareOscillator {
+    pub fn new(frequency: u64, sample_rate: u64) -> Self {
+        Self {
+            phase: 0,
+            increment: phase_increment(frequency, sample_rate),
+            duty: 1 << 31,
+            amplitude: 0.0,
+        }
+    }
+
+    pub fn set_frequency(&mut self, frequency: u64, sample_rate: u64) {
+        self.increment = phase_increment(frequency, sample_rate);
+    }
+
+    // Share of the period the wave stays high, in eighths like most PSGs
+    pub fn set_duty_eighths(&mut self, eighths: u8, blip: &mut BlipBuffer) {
+        let level = self.level();
+        self.duty = ((eighths.min(8) as u64) << 29).min(u32::MAX as u64) as u32;
+        blip.add_step(0, self.level() - level);
+    }
+
+    pub fn set_amplitude(&mut self, amplitude: Sample, blip: &mut BlipBuffer) {
+        let level = self.level();
+        self.amplitude = amplitude;
+        blip.add_step(0, self.level() - level);
+    }
+
+    fn is_high(&self) -> bool {
+        self.phase < self.duty
+    }
+
+    pub fn level(&self) -> Sample {
+        if self.is_high() {
+            self.amplitude
+        } else {
+            -self.amplitude
+        }
+    }
+
+    // Advances by one sample and adds all edges within it to the buffer
+    pub fn advance(&mut self, blip: &mut BlipBuffer) {
+        let start = self.phase as u64;
+        let end = start + self.increment as u64;
+        let mut period_start = 0u64;
+        while period_start <= end {
+            for edge in [period_start + self.duty as u64, period_start + (1 << 32)] {
+                if edge > start && edge <= end {
+                    let offset = ((edge - start) << SAMPLE_FRACTION_BITS) / self.increment as u64;
+                    let level = self.level();
+                    self.phase = edge as u32;
+                    blip.add_step(offset as u32, self.level() - level);
+                }
+            }
+            period_start += 1 << 32;
+        }
+        self.phase = end as u32;
+    }
+}
+
+// Bipolar triangle wave, which has no steps and therefore needs no band-limiting
+#[derive(Debug, Clone)]
+pub struct TriangleOscillator {
+    phase: u32,
+    increment: u32,
+}
+
+impl TriangleOscillator {
+    pub fn new(frequency: u64, sample_rate: u64) -> Self {
+        Self {
+            phase: 0,
+            increment: phase_increment(frequency, sample_rate),
+        }
+    }
+
+    pub fn set_frequency(&mut self, frequency: u64, sample_rate: u64) {
+        self.increment = phase_increment(frequency, sample_rate);
+    }
+
+    pub fn next_sample(&mut self, amplitude: Sample) -> Sample {
+        // fold the phase into a ramp going up in the first and down in the second half
+        let ramp = if self.phase < 1 << 31 {
+            self.phase << 1
+        } else {
+            u32::MAX - (self.phase << 1)
+        };
+        self.phase = self.phase.wrapping_add(self.increment);
+        ((ramp >> 16) as i32 - (1 << 15)) as Sample / (1 << 15) as Sample * amplitude
+    }
+}
+
+// Linear feedback shift register as found in the noise channels of most PSGs
+#[derive(Debug, Clone)]
+pub struct NoiseLfsr {
+    state: u16,
+    // Feeds back into bit 6 as well, which shortens the period to 127 steps
+    short: bool,
+}
+
+impl Default for NoiseLfsr {
+    fn default() -> Self {
+        Self {
+            state: 0x7FFF,
+            short: false,
+        }
+    }
+}
+
+impl NoiseLfsr {
+    pub fn new(short: bool) -> Self {
+        Self {
+            short,
+            ..Default::default()
+        }
+    }
+
+    pub fn set_short(&mut self, short: bool) {
+        self.short = short;
+    }
+
+    pub fn reset(&mut self) {
+        self.state = 0x7FFF;
+    }
+
+    pub fn output(&self) -> bool {
+        self.state & 1 == 0
+    }
+
+    // Shifts once and returns the new output
+    pub fn clock(&mut self) -> bool {
+        let feedback = (self.state ^ (self.state >> 1)) & 1;
+        self.state = (self.state >> 1) | (feedback << 14);
+        if self.short {
+            self.state = (self.state & !(1 << 6)) | (feedback << 6);
+        }
+        self.output()
+    }
+}
+
+// Volume envelope with 16 levels that moves one level every period clocks, 0 disables it
+#[derive(Debug, Clone, Default)]
+pub struct Envelope {
+    initial_volume: u8,
+    increase: bool,
+    period: u8,
+    volume: u8,
+    timer: u8,
+}
+
+impl Envelope {
+    pub const MAX_VOLUME: u8 = 15;
+
+    pub fn new(initial_volume: u8, increase: bool, period: u8) -> Self {
+        let mut envelope = Self {
+            initial_volume: initial_volume.min(Self::MAX_VOLUME),
+            increase,
+            period,
+            ..Default::default()
+        };
+        envelope.trigger();
+        envelope
+    }
+
+    pub fn trigger(&mut self) {
+        self.volume = self.initial_volume;
+        self.timer = self.period;
+    }
+
+    pub fn clock(&mut self) {
+        if self.period == 0 {
+            return;
+        }
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer > 0 {
+            return;
+        }
+        self.timer = self.period;
+        if self.increase && self.volume < Self::MAX_VOLUME {
+            self.volume += 1;
+        } else if !self.increase && self.volume > 0 {
+            self.volume -= 1;
+        }
+    }
+
+    pub fn volume(&self) -> u8 {
+        self.volume
+    }
+
+    pub fn amplitude(&self, max_amplitude: Sample) -> Sample {
+        self.volume as Sample / Self::MAX_VOLUME as Sample * max_amplitude
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blip_step_settles_at_delta() {
+        let mut blip = BlipBuffer::new();
+        blip.add_step(1 << 15, 1.0);
+        let samples: Vec<Sample> = (0..4).map(|_| blip.next_sample()).collect();
+        assert_eq!(samples, vec![0.125, 0.875, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn blip_step_at_sample_start_is_not_spread() {
+        let mut blip = BlipBuffer::new();
+        blip.add_step(0, 1.0);
+        assert_eq!(blip.next_sample(), 0.5);
+        assert_eq!(blip.next_sample(), 1.0);
+    }
+
+    #[test]
+    fn square_wave_has_requested_frequency() {
+        let mut blip = BlipBuffer::new();
+        let mut square = SquareOscillator::new(440, 48_000);
+        square.set_amplitude(1.0, &mut blip);
+        let mut rising_edges = 0;
+        let mut high = square.level() > 0.0;
+        // the increment is rounded down, so one second is slightly too short for the last period
+        for _ in 0..48_010 {
+            square.advance(&mut blip);
+            if !high && square.level() > 0.0 {
+                rising_edges += 1;
+            }
+            high = square.level() > 0.0;
+        }
+        assert_eq!(rising_edges, 440);
+    }
+
+    #[test]
+    fn square_wave_duty() {
+        let mut blip = BlipBuffer::new();
+        let mut square = SquareOscillator::new(1, 8);
+        square.set_amplitude(1.0, &mut blip);
+        square.set_duty_eighths(2, &mut blip);
+        let mut high_samples = 0;
+        for _ in 0..8 {
+            if square.level() > 0.0 {
+                high_samples += 1;
+            }
+            square.advance(&mut blip);
+        }
+        assert_eq!(high_samples, 2);
+    }
+
+    #[test]
+    fn square_wave_settles_to_amplitude() {
+        let mut blip = BlipBuffer::new();
+        let mut square = SquareOscillator::new(100, 48_000);
+        square.set_amplitude(0.5, &mut blip);
+        for _ in 0..10 {
+            square.advance(&mut blip);
+            blip.next_sample();
+        }
+        assert_eq!(blip.next_sample(), 0.5);
+    }
+
+    #[test]
+    fn triangle_wave_range() {
+        let mut triangle = TriangleOscillator::new(1, 4);
+        let samples: Vec<Sample> = (0..4).map(|_| triangle.next_sample(1.0)).collect();
+        assert_eq!(samples[0], -1.0);
+        assert!(samples[1].abs() < 0.001);
+        assert!(samples[2] > 0.999);
+        assert!(samples[3].abs() < 0.001);
+    }
+
+    #[test]
+    fn lfsr_periods() {
+        for (short, period) in [(false, 32767), (true, 127)] {
+            let mut lfsr = NoiseLfsr::new(short);
+            for _ in 0..200 {
+                lfsr.clock();
+            }
+            let start = lfsr.state;
+            let mut steps = 0;
+            loop {
+                lfsr.clock();
+                steps += 1;
+                if lfsr.state == start {
+                    break;
+                }
+            }
+            assert_eq!(steps, period);
+        }
+    }
+
+    #[test]
+    fn envelope_decays_and_stops_at_zero() {
+        let mut envelope = Envelope::new(2, false, 2);
+        let volumes: Vec<u8> = (0..6)
+            .map(|_| {
+                envelope.clock();
+                envelope.volume()
+            })
+            .collect();
+        assert_eq!(volumes, vec![2, 1, 1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn envelope_without_period_holds_volume() {
+        let mut envelope = Envelope::new(9, true, 0);
+        envelope.clock();
+        assert_eq!(envelope.volume(), 9);
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::{BTreeSet, HashMap};
+
+use femtos::Instant;
+
+use super::component::MemoryAddress;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BreakpointEvent {
+    pub component: String,
+    pub address: MemoryAddress,
+    pub clock: Instant,
+}
+
+// Program counter addresses per component, which stop the run before the instruction there
+#[derive(Default)]
+pub(crate) struct Breakpoints {
+    addresses: HashMap<String, BTreeSet<MemoryAddress>>,
+    events: Vec<BreakpointEvent>,
+}
+
+impl Breakpoints {
+    pub(crate) fn add(&mut self, component: &str, address: MemoryAddress) {
+        self.addresses
+            .entry(component.to_string())
+            .or_default()
+            .insert(address);
+    }
+
+    pub(crate) fn remove(&mut self, component: &str, address: MemoryAddress) {
+        if let Some(addresses) = self.addresses.get_mut(component) {
+            addresses.remove(&address);
+            if addresses.is_empty() {
+                self.addresses.remove(component);
+            }
+        }
+    }
+
+    pub(crate) fn remove_component(&mut self, component: &str) {
+        self.addresses.remove(component);
+        self.events.retain(|event| event.component != component);
+    }
+
+    pub(crate) fn list(&self, component: &str) -> Vec<MemoryAddress> {
+        self.addresses
+            .get(component)
+            .map_or(vec![], |addresses| addresses.iter().copied().collect())
+    }
+
+    pub(crate) fn check(
+        &mut self,
+        component: &str,
+        address: MemoryAddress,
+        clock: Instant,
+    ) -> bool {
+        let hit = self
+            .addresses
+            .get(component)
+            .is_some_and(|addresses| addresses.contains(&address));
+        if hit {
+            self.events.push(BreakpointEvent {
+                component: component.to_string(),
+                address,
+                clock,
+            });
+        }
+        hit
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.addresses.is_empty()
+    }
+
+    pub(crate) fn take_events(&mut self) -> Vec<BreakpointEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    pub(crate) fn discard_events_before(&mut self, clock: Instant) {
+        self.events.retain(|event| event.clock >= clock);
+    }
+
+    pub(crate) fn has_events(&self) -> bool {
+        !self.events.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hits_only_the_component_of_the_breakpoint() {
+        let mut breakpoints = Breakpoints::default();
+        breakpoints.add("cpu", 0x204);
+        breakpoints.check("apu", 0x204, Instant::START);
+        breakpoints.check("cpu", 0x202, Instant::START);
+        assert!(!breakpoints.has_events());
+        breakpoints.check("cpu", 0x204, Instant::START);
+        assert_eq!(
+            breakpoints.take_events(),
+            vec![BreakpointEvent {
+                component: "cpu".to_string(),
+                address: 0x204,
+                clock: Instant::START,
+            }]
+        );
+    }
+
+    #[test]
+    fn removing_the_last_breakpoint_empties_the_registry() {
+        let mut breakpoints = Breakpoints::default();
+        breakpoints.add("cpu", 0x204);
+        breakpoints.add("cpu", 0x200);
+        assert_eq!(breakpoints.list("cpu"), vec![0x200, 0x204]);
+        breakpoints.remove("cpu", 0x200);
+        breakpoints.remove("cpu", 0x204);
+        assert!(breakpoints.is_empty());
+    }
+}
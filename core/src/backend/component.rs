@@ -6,27 +6,87 @@ use std::{
 
 use femtos::Duration;
 
-use crate::{backend::Backend, error::Error};
+use crate::{
+    backend::{
+        Backend,
+        snapshot::{StateReader, StateWriter},
+    },
+    error::Error,
+    frontend::input::KeyboardEventKey,
+};
 
 pub type MemoryAddress = usize;
 pub type MemorySize = MemoryAddress;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteOrder {
+    Little,
+    Big,
+}
+
+// Integers the generic accessors of Addressable read and write
+pub trait MemoryValue: Copy {
+    const SIZE: usize;
+    fn from_bytes(bytes: &[u8], order: ByteOrder) -> Self;
+    fn to_bytes(self, order: ByteOrder, bytes: &mut [u8]);
+}
+
+macro_rules! impl_memory_value {
+    ($($value:ty),*) => {
+        $(
+            impl MemoryValue for $value {
+                const SIZE: usize = size_of::<$value>();
+
+                fn from_bytes(bytes: &[u8], order: ByteOrder) -> Self {
+                    let bytes = bytes.try_into().expect("buffer has the size of the value");
+                    match order {
+                        ByteOrder::Little => <$value>::from_le_bytes(bytes),
+                        ByteOrder::Big => <$value>::from_be_bytes(bytes),
+                    }
+                }
+
+                fn to_bytes(self, order: ByteOrder, bytes: &mut [u8]) {
+                    match order {
+                        ByteOrder::Little => bytes.copy_from_slice(&self.to_le_bytes()),
+                        ByteOrder::Big => bytes.copy_from_slice(&self.to_be_bytes()),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_memory_value!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+// Longest value the generic accessors handle
+const MAX_VALUE_SIZE: usize = 8;
+
 pub trait Addressable {
     fn size(&self) -> MemorySize;
-    fn read(&self, address: MemoryAddress, buffer: &mut [u8]) -> Result<(), Error>;
+
+    // Reads for debugging purposes, e.g. by the memory view and the inspectors. Must not change the
+    // state of the component, so inspecting memory never changes the emulation.
+    fn peek(&self, address: MemoryAddress, buffer: &mut [u8]) -> Result<(), Error>;
+
+    // Reads by the emulated machine. Components whose reads have side effects, like status
+    // registers that clear on read or FIFOs, override this, plain memory reads like a peek.
+    fn read(&mut self, address: MemoryAddress, buffer: &mut [u8]) -> Result<(), Error> {
+        self.peek(address, buffer)
+    }
+
     fn write(&mut self, address: MemoryAddress, buffer: &[u8]) -> Result<(), Error>;
 
-    fn read_u8(&self, address: MemoryAddress) -> Result<u8, Error> {
+    fn read_u8(&mut self, address: MemoryAddress) -> Result<u8, Error> {
         let mut buffer: [u8; 1] = Default::default();
         self.read(address, &mut buffer)?;
         Ok(buffer[0])
     }
-    fn read_u16_le(&self, address: MemoryAddress) -> Result<u16, Error> {
+    fn read_u16_le(&mut self, address: MemoryAddress) -> Result<u16, Error> {
         let mut buffer: [u8; 2] = Default::default();
         self.read(address, &mut buffer)?;
         Ok(u16::from_le_bytes(buffer))
     }
-    fn read_u16_be(&self, address: MemoryAddress) -> Result<u16, Error> {
+    fn read_u16_be(&mut self, address: MemoryAddress) -> Result<u16, Error> {
         let mut buffer: [u8; 2] = Default::default();
         self.read(address, &mut buffer)?;
         Ok(u16::from_be_bytes(buffer))
@@ -41,6 +101,126 @@ pub trait Addressable {
     fn write_u16_be(&mut self, address: MemoryAddress, value: u16) -> Result<(), Error> {
         self.write(address, &value.to_be_bytes())
     }
+
+    fn read_u32_le(&mut self, address: MemoryAddress) -> Result<u32, Error> {
+        self.read_value(address, ByteOrder::Little)
+    }
+    fn read_u32_be(&mut self, address: MemoryAddress) -> Result<u32, Error> {
+        self.read_value(address, ByteOrder::Big)
+    }
+    fn read_u64_le(&mut self, address: MemoryAddress) -> Result<u64, Error> {
+        self.read_value(address, ByteOrder::Little)
+    }
+    fn read_u64_be(&mut self, address: MemoryAddress) -> Result<u64, Error> {
+        self.read_value(address, ByteOrder::Big)
+    }
+    fn read_i8(&mut self, address: MemoryAddress) -> Result<i8, Error> {
+        self.read_value(address, ByteOrder::Little)
+    }
+    fn read_i16_le(&mut self, address: MemoryAddress) -> Result<i16, Error> {
+        self.read_value(address, ByteOrder::Little)
+    }
+    fn read_i16_be(&mut self, address: MemoryAddress) -> Result<i16, Error> {
+        self.read_value(address, ByteOrder::Big)
+    }
+    fn read_i32_le(&mut self, address: MemoryAddress) -> Result<i32, Error> {
+        self.read_value(address, ByteOrder::Little)
+    }
+    fn read_i32_be(&mut self, address: MemoryAddress) -> Result<i32, Error> {
+        self.read_value(address, ByteOrder::Big)
+    }
+    fn read_i64_le(&mut self, address: MemoryAddress) -> Result<i64, Error> {
+        self.read_value(address, ByteOrder::Little)
+    }
+    fn read_i64_be(&mut self, address: MemoryAddress) -> Result<i64, Error> {
+        self.read_value(address, ByteOrder::Big)
+    }
+
+    fn write_u32_le(&mut self, address: MemoryAddress, value: u32) -> Result<(), Error> {
+        self.write_value(address, value, ByteOrder::Little)
+    }
+    fn write_u32_be(&mut self, address: MemoryAddress, value: u32) -> Result<(), Error> {
+        self.write_value(address, value, ByteOrder::Big)
+    }
+    fn write_u64_le(&mut self, address: MemoryAddress, value: u64) -> Result<(), Error> {
+        self.write_value(address, value, ByteOrder::Little)
+    }
+    fn write_u64_be(&mut self, address: MemoryAddress, value: u64) -> Result<(), Error> {
+        self.write_value(address, value, ByteOrder::Big)
+    }
+    fn write_i8(&mut self, address: MemoryAddress, value: i8) -> Result<(), Error> {
+        self.write_value(address, value, ByteOrder::Little)
+    }
+    fn write_i16_le(&mut self, address: MemoryAddress, value: i16) -> Result<(), Error> {
+        self.write_value(address, value, ByteOrder::Little)
+    }
+    fn write_i16_be(&mut self, address: MemoryAddress, value: i16) -> Result<(), Error> {
+        self.write_value(address, value, ByteOrder::Big)
+    }
+    fn write_i32_le(&mut self, address: MemoryAddress, value: i32) -> Result<(), Error> {
+        self.write_value(address, value, ByteOrder::Little)
+    }
+    fn write_i32_be(&mut self, address: MemoryAddress, value: i32) -> Result<(), Error> {
+        self.write_value(address, value, ByteOrder::Big)
+    }
+    fn write_i64_le(&mut self, address: MemoryAddress, value: i64) -> Result<(), Error> {
+        self.write_value(address, value, ByteOrder::Little)
+    }
+    fn write_i64_be(&mut self, address: MemoryAddress, value: i64) -> Result<(), Error> {
+        self.write_value(address, value, ByteOrder::Big)
+    }
+}
+
+// Accessors for any integer in either byte order, e.g. for cores which switch their endianness.
+// Generic methods cannot be called on dyn Addressable, so they live in a trait of their own which
+// every addressable implements.
+pub trait AddressableExt: Addressable {
+    fn read_value<T: MemoryValue>(
+        &mut self,
+        address: MemoryAddress,
+        order: ByteOrder,
+    ) -> Result<T, Error> {
+        let mut buffer = [0; MAX_VALUE_SIZE];
+        self.read(address, &mut buffer[..T::SIZE])?;
+        Ok(T::from_bytes(&buffer[..T::SIZE], order))
+    }
+
+    fn peek_value<T: MemoryValue>(
+        &self,
+        address: MemoryAddress,
+        order: ByteOrder,
+    ) -> Result<T, Error> {
+        let mut buffer = [0; MAX_VALUE_SIZE];
+        self.peek(address, &mut buffer[..T::SIZE])?;
+        Ok(T::from_bytes(&buffer[..T::SIZE], order))
+    }
+
+    fn write_value<T: MemoryValue>(
+        &mut self,
+        address: MemoryAddress,
+        value: T,
+        order: ByteOrder,
+    ) -> Result<(), Error> {
+        let mut buffer = [0; MAX_VALUE_SIZE];
+        value.to_bytes(order, &mut buffer[..T::SIZE]);
+        self.write(address, &buffer[..T::SIZE])
+    }
+}
+
+impl<A: Addressable + ?Sized> AddressableExt for A {}
+
+// Translates the addresses of a bus window before they reach the mounted component, like the mapper
+// of a cartridge. Accesses are translated by their first byte, so the mapping has to be contiguous
+// within one access.
+pub trait Mapper {
+    // The address inside the component for an address relative to the start of the window
+    fn translate(&self, address: MemoryAddress) -> MemoryAddress;
+
+    // Sees every write to the window before the component does. Returns whether the write went to
+    // the control registers of the mapper, it then does not reach the component.
+    fn write_control(&mut self, _address: MemoryAddress, _data: &[u8]) -> bool {
+        false
+    }
 }
 
 pub trait Steppable {
@@ -51,6 +231,67 @@ pub trait Inspectable {
     fn inspect(&self) -> Vec<String>;
 }
 
+// Register state of a cpu, compared against reference logs of other emulators. Names should follow
+// the usual naming of the architecture, like PC or V0.
+pub trait Traceable {
+    fn trace_registers(&self) -> Vec<(&'static str, u64)>;
+}
+
+// The buttons the emulated machine currently sees pressed, given by the keys of its control scheme.
+// Unlike the input sent by the frontend this shows what actually arrived in the machine.
+pub trait Keypad {
+    fn pressed_keys(&self) -> Vec<KeyboardEventKey>;
+}
+
+// Machine state of a component for save states. Loading gets exactly the data the component saved
+// before, configuration from options and channels to the frontend are not part of it.
+pub trait Saveable {
+    fn save_state(&self, writer: &mut StateWriter);
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), Error>;
+}
+
+// Power-on state for Backend::reset. Like loading a state, configuration from options and the
+// channels to the frontend are kept.
+pub trait Resettable {
+    fn reset(&mut self);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StackFrame {
+    // Start of the called subroutine, if the component knows it
+    pub function: Option<MemoryAddress>,
+    // Where the caller continues once the subroutine returns
+    pub return_address: MemoryAddress,
+}
+
+// Execution control of a cpu for debuggers. A paused cpu is still stepped by the backend, so the
+// rest of the machine keeps running, but it does not execute instructions.
+pub trait Debuggable {
+    fn get_pc(&self) -> MemoryAddress;
+    fn pause(&mut self);
+    fn resume(&mut self);
+    fn is_paused(&self) -> bool;
+    // Executes exactly one instruction on the next step, also while paused
+    fn step_instruction(&mut self);
+    // The innermost call comes first
+    fn stack_frames(&self) -> Vec<StackFrame>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisassembledInstruction {
+    pub address: MemoryAddress,
+    pub length: MemorySize,
+    pub mnemonic: String,
+    pub operands: Vec<String>,
+}
+
+// Decoding of the instruction set of a cpu for disassembly views
+pub trait Disassemblable {
+    // Decodes the instruction at the start of the bytes, which were read from the address. None if
+    // the bytes end before the instruction does.
+    fn disassemble(&self, address: MemoryAddress, bytes: &[u8]) -> Option<DisassembledInstruction>;
+}
+
 pub trait Transmutable {
     fn as_steppable(&mut self) -> Option<&mut dyn Steppable> {
         None
@@ -61,6 +302,27 @@ pub trait Transmutable {
     fn as_inspectable(&mut self) -> Option<&mut dyn Inspectable> {
         None
     }
+    fn as_traceable(&mut self) -> Option<&mut dyn Traceable> {
+        None
+    }
+    fn as_keypad(&mut self) -> Option<&mut dyn Keypad> {
+        None
+    }
+    fn as_saveable(&mut self) -> Option<&mut dyn Saveable> {
+        None
+    }
+    fn as_resettable(&mut self) -> Option<&mut dyn Resettable> {
+        None
+    }
+    fn as_debuggable(&mut self) -> Option<&mut dyn Debuggable> {
+        None
+    }
+    fn as_disassemblable(&mut self) -> Option<&mut dyn Disassemblable> {
+        None
+    }
+    fn as_mapper(&mut self) -> Option<&mut dyn Mapper> {
+        None
+    }
 }
 
 type TransmutableBox = Rc<RefCell<Box<dyn Transmutable>>>;
@@ -0,0 +1,30 @@
+use crate::frontend::input::KeyboardEventKey;
+
+// A logical input of the emulated machine together with the host key it is mapped to by default
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Control {
+    pub label: &'static str,
+    pub key: KeyboardEventKey,
+}
+
+// Describes how the emulated machine is controlled, shown by frontends as quick help
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ControlScheme {
+    // Controls in the order of the physical layout of the machine, row by row
+    pub rows: &'static [&'static [Control]],
+    pub help: &'static str,
+}
+
+impl ControlScheme {
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty() && self.help.is_empty()
+    }
+
+    pub fn controls(&self) -> impl Iterator<Item = &Control> {
+        self.rows.iter().flat_map(|row| row.iter())
+    }
+
+    pub fn control_for_key(&self, key: KeyboardEventKey) -> Option<&Control> {
+        self.controls().find(|control| control.key == key)
+    }
+}
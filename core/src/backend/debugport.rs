@@ -0,0 +1,73 @@
+use super::{
+    component::MemoryAddress,
+    memory::{BusAccess, BusHook},
+};
+
+// Longest line kept before it is sent without waiting for the newline
+pub const MAX_LINE_LENGTH: usize = 256;
+
+// An address homebrew roms write characters to, to print to the text channel of the frontend. The
+// hook collects the bytes written there, the backend sends the complete lines after each step.
+pub(crate) struct DebugPort {
+    address: MemoryAddress,
+    line: Vec<u8>,
+    lines: Vec<String>,
+}
+
+impl DebugPort {
+    pub(crate) fn new(address: MemoryAddress) -> Self {
+        Self {
+            address,
+            line: vec![],
+            lines: vec![],
+        }
+    }
+
+    pub(crate) fn take_lines(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.lines)
+    }
+
+    fn end_line(&mut self) {
+        self.lines
+            .push(String::from_utf8_lossy(&self.line).trim_end().to_string());
+        self.line.clear();
+    }
+}
+
+impl BusHook for DebugPort {
+    fn on_access(&mut self, access: BusAccess, address: MemoryAddress, data: &[u8]) {
+        if access != BusAccess::Write || !(address..address + data.len()).contains(&self.address) {
+            return;
+        }
+        match data[self.address - address] {
+            b'\n' => self.end_line(),
+            byte => {
+                self.line.push(byte);
+                if self.line.len() == MAX_LINE_LENGTH {
+                    self.end_line();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collects_lines_written_to_the_port() {
+        let mut port = DebugPort::new(0x10);
+        for byte in b"ok\r\n" {
+            port.on_access(BusAccess::Write, 0x0F, &[0, *byte]);
+        }
+        port.on_access(BusAccess::Read, 0x10, b"\n");
+        port.on_access(BusAccess::Write, 0x11, b"\n");
+        assert_eq!(port.take_lines(), vec!["ok".to_string()]);
+
+        for _ in 0..MAX_LINE_LENGTH {
+            port.on_access(BusAccess::Write, 0x10, b"x");
+        }
+        assert_eq!(port.take_lines(), vec!["x".repeat(MAX_LINE_LENGTH)]);
+    }
+}
@@ -0,0 +1,268 @@
+use femtos::Duration;
+
+use super::{
+    Backend, ExecutionEvent,
+    component::{
+        Addressable, AddressableExt, ByteOrder, Inspectable, MemoryAddress, MemorySize, Resettable,
+        Saveable, Steppable, Transmutable,
+    },
+    snapshot::{StateReader, StateWriter},
+};
+use crate::error::Error;
+
+// Offsets of the registers, which live in memory on the bus like any other io register. The
+// addresses and the length are 32 bit wide.
+pub const DMA_SOURCE: MemoryAddress = 0;
+pub const DMA_DESTINATION: MemoryAddress = 4;
+pub const DMA_LENGTH: MemoryAddress = 8;
+pub const DMA_CONTROL: MemoryAddress = 12;
+pub const DMA_REGISTERS_SIZE: MemorySize = 13;
+
+// Set by the program to start a transfer, the engine clears it once it took the registers over
+pub const DMA_CONTROL_START: u8 = 0x01;
+pub const DMA_CONTROL_BUSY: u8 = 0x02;
+// Set when the last byte was copied, next to the signal the engine sends
+pub const DMA_CONTROL_DONE: u8 = 0x04;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Transfer {
+    source: MemoryAddress,
+    destination: MemoryAddress,
+    remaining: MemorySize,
+}
+
+// Copies a block between two bus addresses over emulated time, a few bytes each cycle, like the
+// OAM DMA of the Game Boy or the blitter of an arcade board. The program sets up the registers
+// and writes the start bit, the engine signals ExecutionEvent::Signal when the copy is done.
+pub struct Dma {
+    registers: MemoryAddress,
+    order: ByteOrder,
+    bytes_per_cycle: usize,
+    cycle: Duration,
+    transfer: Option<Transfer>,
+}
+
+impl Dma {
+    pub fn new(
+        registers: MemoryAddress,
+        order: ByteOrder,
+        bytes_per_cycle: usize,
+        cycle: Duration,
+    ) -> Self {
+        Self {
+            registers,
+            order,
+            bytes_per_cycle: bytes_per_cycle.max(1),
+            cycle,
+            transfer: None,
+        }
+    }
+
+    pub fn is_busy(&self) -> bool {
+        self.transfer.is_some()
+    }
+
+    // Takes the registers over once the start bit is set. Polls with peeks, so an idle engine does
+    // not show up as bus accesses of the machine.
+    fn start(&mut self, backend: &Backend) -> Result<(), Error> {
+        let mut bus = backend.get_bus();
+        let control: u8 = bus.peek_value(self.registers + DMA_CONTROL, self.order)?;
+        if control & DMA_CONTROL_START == 0 {
+            return Ok(());
+        }
+        let register = |offset| bus.peek_value::<u32>(self.registers + offset, self.order);
+        let transfer = Transfer {
+            source: register(DMA_SOURCE)? as MemoryAddress,
+            destination: register(DMA_DESTINATION)? as MemoryAddress,
+            remaining: register(DMA_LENGTH)? as MemorySize,
+        };
+        bus.write_u8(self.registers + DMA_CONTROL, DMA_CONTROL_BUSY)?;
+        self.transfer = Some(transfer);
+        Ok(())
+    }
+
+    fn finish(&mut self, backend: &Backend) -> Result<(), Error> {
+        self.transfer = None;
+        backend
+            .get_bus()
+            .write_u8(self.registers + DMA_CONTROL, DMA_CONTROL_DONE)?;
+        backend.signal(ExecutionEvent::Signal);
+        Ok(())
+    }
+}
+
+impl Steppable for Dma {
+    fn step(&mut self, backend: &Backend) -> Result<Duration, Error> {
+        let Some(mut transfer) = self.transfer else {
+            self.start(backend)?;
+            return Ok(self.cycle);
+        };
+        // Byte by byte, so the block may span several components
+        {
+            let mut bus = backend.get_bus();
+            for _ in 0..self.bytes_per_cycle.min(transfer.remaining) {
+                let byte = bus.read_u8(transfer.source)?;
+                bus.write_u8(transfer.destination, byte)?;
+                transfer.source += 1;
+                transfer.destination += 1;
+                transfer.remaining -= 1;
+            }
+        }
+        self.transfer = Some(transfer);
+        if transfer.remaining == 0 {
+            self.finish(backend)?;
+        }
+        Ok(self.cycle)
+    }
+}
+
+impl Inspectable for Dma {
+    fn inspect(&self) -> Vec<String> {
+        match self.transfer {
+            Some(transfer) => vec![
+                format!("source: {:#06x}", transfer.source),
+                format!("destination: {:#06x}", transfer.destination),
+                format!("remaining: {}", transfer.remaining),
+            ],
+            None => vec!["idle".to_string()],
+        }
+    }
+}
+
+impl Saveable for Dma {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bool(self.transfer.is_some());
+        let transfer = self.transfer.unwrap_or(Transfer {
+            source: 0,
+            destination: 0,
+            remaining: 0,
+        });
+        writer.write_u64(transfer.source as u64);
+        writer.write_u64(transfer.destination as u64);
+        writer.write_u64(transfer.remaining as u64);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), Error> {
+        let busy = reader.read_bool()?;
+        let transfer = Transfer {
+            source: reader.read_u64()? as MemoryAddress,
+            destination: reader.read_u64()? as MemoryAddress,
+            remaining: reader.read_u64()? as MemorySize,
+        };
+        self.transfer = busy.then_some(transfer);
+        Ok(())
+    }
+}
+
+impl Resettable for Dma {
+    fn reset(&mut self) {
+        self.transfer = None;
+    }
+}
+
+impl Transmutable for Dma {
+    fn as_steppable(&mut self) -> Option<&mut dyn Steppable> {
+        Some(self)
+    }
+
+    fn as_inspectable(&mut self) -> Option<&mut dyn Inspectable> {
+        Some(self)
+    }
+
+    fn as_saveable(&mut self) -> Option<&mut dyn Saveable> {
+        Some(self)
+    }
+
+    fn as_resettable(&mut self) -> Option<&mut dyn Resettable> {
+        Some(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::MockBackend;
+
+    use super::*;
+
+    const REGISTERS: MemoryAddress = 0x00;
+    const CYCLE: Duration = Duration::from_micros(1);
+
+    fn backend() -> MockBackend {
+        MockBackend::new()
+            .with_memory(REGISTERS, DMA_REGISTERS_SIZE)
+            .with_data(0x100, (1..=10).collect())
+            .with_memory(0x200, 0x10)
+    }
+
+    fn start(backend: &MockBackend, source: u32, destination: u32, length: u32) {
+        let mut bus = backend.backend().get_bus();
+        for (offset, value) in [
+            (DMA_SOURCE, source),
+            (DMA_DESTINATION, destination),
+            (DMA_LENGTH, length),
+        ] {
+            bus.write_value(REGISTERS + offset, value, ByteOrder::Little)
+                .unwrap();
+        }
+        bus.write_u8(REGISTERS + DMA_CONTROL, DMA_CONTROL_START)
+            .unwrap();
+    }
+
+    fn signalled(backend: &MockBackend) -> bool {
+        backend
+            .backend()
+            .signalled
+            .take()
+            .contains(&ExecutionEvent::Signal)
+    }
+
+    #[test]
+    fn copies_the_block_over_several_cycles() {
+        let mut backend = backend();
+        let mut dma = Dma::new(REGISTERS, ByteOrder::Little, 4, CYCLE);
+        start(&backend, 0x100, 0x200, 10);
+
+        backend.step(&mut dma);
+        assert!(dma.is_busy());
+        backend.assert_memory(REGISTERS + DMA_CONTROL, &[DMA_CONTROL_BUSY]);
+
+        backend.step_n(&mut dma, 2);
+        backend.assert_memory(0x200, &[1, 2, 3, 4, 5, 6, 7, 8, 0, 0]);
+        assert!(!signalled(&backend));
+
+        backend.step(&mut dma);
+        backend.assert_memory(0x200, &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 0]);
+        backend.assert_memory(REGISTERS + DMA_CONTROL, &[DMA_CONTROL_DONE]);
+        assert!(signalled(&backend));
+        assert!(!dma.is_busy());
+    }
+
+    #[test]
+    fn waits_for_the_start_bit() {
+        let mut backend = backend();
+        let mut dma = Dma::new(REGISTERS, ByteOrder::Little, 4, CYCLE);
+        assert_eq!(backend.step_n(&mut dma, 3), CYCLE * 3u32);
+        assert!(!dma.is_busy());
+        backend.assert_memory(0x200, &[0; 4]);
+    }
+
+    #[test]
+    fn state_keeps_a_running_transfer() {
+        let mut backend = backend();
+        let mut dma = Dma::new(REGISTERS, ByteOrder::Little, 4, CYCLE);
+        start(&backend, 0x100, 0x200, 10);
+        backend.step_n(&mut dma, 2);
+
+        let mut writer = StateWriter::new();
+        dma.save_state(&mut writer);
+        let data = writer.into_data();
+        let mut loaded = Dma::new(REGISTERS, ByteOrder::Little, 4, CYCLE);
+        let mut reader = StateReader::new(&data);
+        loaded.load_state(&mut reader).unwrap();
+        reader.finish().unwrap();
+
+        assert_eq!(loaded.transfer, dma.transfer);
+        backend.step_n(&mut loaded, 2);
+        backend.assert_memory(0x200, &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+    }
+}
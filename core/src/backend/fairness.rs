@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+
+use femtos::{Duration, Instant};
+
+// Every step advances the clock of its component by at least this much
+pub const MIN_STEP_ADVANCE: Duration = Duration::from_nanos(1);
+// Older warnings are dropped when nobody takes them
+const MAX_WARNINGS: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchedulerWarning {
+    // The component returned a step duration below the minimum advance
+    NoAdvance {
+        component: String,
+        clock: Instant,
+    },
+    // The component exceeded its step limit and was deferred to the end of the run
+    StepLimit {
+        component: String,
+        clock: Instant,
+        limit: usize,
+    },
+}
+
+impl SchedulerWarning {
+    pub fn component(&self) -> &str {
+        match self {
+            SchedulerWarning::NoAdvance { component, .. } => component,
+            SchedulerWarning::StepLimit { component, .. } => component,
+        }
+    }
+}
+
+// Keeps a single component from monopolizing the scheduler. Limits count the steps of a component
+// within one run, violations are reported once per component and run.
+#[derive(Default)]
+pub(crate) struct Fairness {
+    step_limits: HashMap<String, usize>,
+    run_end: Option<Instant>,
+    steps: HashMap<String, usize>,
+    warned: Vec<(String, bool)>,
+    warnings: Vec<SchedulerWarning>,
+}
+
+impl Fairness {
+    pub(crate) fn set_step_limit(&mut self, component: &str, limit: Option<usize>) {
+        match limit {
+            Some(limit) => self.step_limits.insert(component.to_string(), limit),
+            None => self.step_limits.remove(component),
+        };
+    }
+
+    pub(crate) fn step_limit(&self, component: &str) -> Option<usize> {
+        self.step_limits.get(component).copied()
+    }
+
+    pub(crate) fn remove_component(&mut self, component: &str) {
+        self.step_limits.remove(component);
+        self.steps.remove(component);
+    }
+
+    pub(crate) fn start_run(&mut self, end: Instant) {
+        self.run_end = Some(end);
+        self.steps.clear();
+        self.warned.clear();
+    }
+
+    pub(crate) fn end_run(&mut self) {
+        self.run_end = None;
+    }
+
+    // Returns the clock the component is stepped at next
+    pub(crate) fn next_clock(
+        &mut self,
+        component: &str,
+        clock: Instant,
+        advance: Duration,
+    ) -> Instant {
+        let mut next = clock + advance;
+        if advance < MIN_STEP_ADVANCE {
+            next = clock + MIN_STEP_ADVANCE;
+            self.warn(SchedulerWarning::NoAdvance {
+                component: component.to_string(),
+                clock,
+            });
+        }
+
+        let (Some(run_end), Some(limit)) = (self.run_end, self.step_limit(component)) else {
+            return next;
+        };
+        let steps = self.steps.entry(component.to_string()).or_default();
+        *steps += 1;
+        if *steps >= limit && next < run_end {
+            next = run_end;
+            self.warn(SchedulerWarning::StepLimit {
+                component: component.to_string(),
+                clock,
+                limit,
+            });
+        }
+        next
+    }
+
+    pub(crate) fn take_warnings(&mut self) -> Vec<SchedulerWarning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    fn warn(&mut self, warning: SchedulerWarning) {
+        let key = (
+            warning.component().to_string(),
+            matches!(warning, SchedulerWarning::StepLimit { .. }),
+        );
+        if self.warned.contains(&key) {
+            return;
+        }
+        // Outside of a run every violation would be reported again
+        if self.run_end.is_some() {
+            self.warned.push(key);
+        }
+        if self.warnings.len() >= MAX_WARNINGS {
+            self.warnings.remove(0);
+        }
+        self.warnings.push(warning);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::at_ns;
+
+    use super::*;
+
+    #[test]
+    fn zero_advance_is_raised_to_minimum() {
+        let mut fairness = Fairness::default();
+        fairness.start_run(at_ns(100));
+        assert_eq!(
+            fairness.next_clock("cpu", at_ns(10), Duration::ZERO),
+            at_ns(10) + MIN_STEP_ADVANCE
+        );
+        fairness.next_clock("cpu", at_ns(11), Duration::ZERO);
+        assert_eq!(
+            fairness.take_warnings(),
+            vec![SchedulerWarning::NoAdvance {
+                component: "cpu".to_string(),
+                clock: at_ns(10)
+            }]
+        );
+    }
+
+    #[test]
+    fn component_over_limit_is_deferred_to_end_of_run() {
+        let mut fairness = Fairness::default();
+        fairness.set_step_limit("cpu", Some(2));
+        fairness.start_run(at_ns(100));
+        assert_eq!(
+            fairness.next_clock("cpu", at_ns(0), Duration::from_nanos(5)),
+            at_ns(5)
+        );
+        assert_eq!(
+            fairness.next_clock("cpu", at_ns(5), Duration::from_nanos(5)),
+            at_ns(100)
+        );
+        assert_eq!(
+            fairness.next_clock("timer", at_ns(5), Duration::from_nanos(5)),
+            at_ns(10)
+        );
+        assert_eq!(fairness.take_warnings().len(), 1);
+
+        fairness.start_run(at_ns(200));
+        assert_eq!(
+            fairness.next_clock("cpu", at_ns(100), Duration::from_nanos(5)),
+            at_ns(105)
+        );
+    }
+
+    #[test]
+    fn limits_only_apply_within_a_run() {
+        let mut fairness = Fairness::default();
+        fairness.set_step_limit("cpu", Some(1));
+        assert_eq!(
+            fairness.next_clock("cpu", at_ns(0), Duration::from_nanos(5)),
+            at_ns(5)
+        );
+        assert!(fairness.take_warnings().is_empty());
+    }
+}
@@ -0,0 +1,65 @@
+use std::collections::BTreeMap;
+
+use crate::{error::Error, utils::rom_hash};
+
+#[derive(Debug, Clone)]
+pub struct FirmwareDescriptor {
+    pub key: &'static str,
+    pub label: &'static str,
+    pub file_names: &'static [&'static str],
+    // SHA-1 hashes of all known good dumps, an empty list accepts any file
+    pub hashes: &'static [&'static str],
+    // Backends have to fall back to a high level emulated boot if optional firmware is missing
+    pub required: bool,
+}
+
+impl FirmwareDescriptor {
+    pub fn verify(&self, data: &[u8]) -> bool {
+        self.hashes.is_empty() || self.hashes.contains(&rom_hash(data).as_str())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FirmwareFiles(BTreeMap<String, Vec<u8>>);
+
+impl FirmwareFiles {
+    pub fn set(&mut self, key: &str, data: Vec<u8>) {
+        self.0.insert(key.to_string(), data);
+    }
+
+    pub fn get(&self, key: &str) -> Option<&[u8]> {
+        self.0.get(key).map(Vec::as_slice)
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Vec<u8>> {
+        self.0.remove(key)
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.0.contains_key(key)
+    }
+}
+
+pub fn verify_firmware(
+    descriptors: &[FirmwareDescriptor],
+    files: &FirmwareFiles,
+) -> Result<(), Error> {
+    for descriptor in descriptors {
+        match files.get(descriptor.key) {
+            Some(data) if !descriptor.verify(data) => {
+                return Err(Error::new(format!(
+                    "firmware {} does not match any known dump",
+                    descriptor.label
+                )));
+            }
+            None if descriptor.required => {
+                return Err(Error::new(format!(
+                    "missing required firmware {}",
+                    descriptor.label
+                )));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use std::collections::BTreeMap;
+
+use super::{
+    component::MemoryAddress,
+    memory::{BusAccess, BusHook},
+};
+
+// Memory pinned to fixed values. The hook notes writes to frozen addresses, the backend writes
+// the frozen values back after the step that caused them.
+#[derive(Default)]
+pub(crate) struct FrozenMemory {
+    values: BTreeMap<MemoryAddress, u8>,
+    dirty: Vec<MemoryAddress>,
+}
+
+impl FrozenMemory {
+    pub(crate) fn insert(&mut self, address: MemoryAddress, value: u8) {
+        self.values.insert(address, value);
+    }
+
+    pub(crate) fn remove(&mut self, address: MemoryAddress) {
+        self.values.remove(&address);
+        self.dirty.retain(|a| *a != address);
+    }
+
+    pub(crate) fn list(&self) -> Vec<(MemoryAddress, u8)> {
+        self.values.iter().map(|(a, v)| (*a, *v)).collect()
+    }
+
+    pub(crate) fn take_dirty(&mut self) -> Vec<(MemoryAddress, u8)> {
+        self.dirty
+            .drain(..)
+            .filter_map(|address| Some((address, *self.values.get(&address)?)))
+            .collect()
+    }
+}
+
+impl BusHook for FrozenMemory {
+    fn on_access(&mut self, access: BusAccess, address: MemoryAddress, data: &[u8]) {
+        if access != BusAccess::Write || self.values.is_empty() {
+            return;
+        }
+        for (frozen, _) in self.values.range(address..address + data.len()) {
+            self.dirty.push(*frozen);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use femtos::Duration;
+
+    use crate::{
+        backend::{
+            Backend,
+            component::{Addressable, Component, Steppable, Transmutable},
+            memory::MemoryBlock,
+        },
+        error::Error,
+    };
+
+    use super::*;
+
+    struct Incrementer;
+
+    impl Steppable for Incrementer {
+        fn step(&mut self, backend: &Backend) -> Result<Duration, Error> {
+            let mut bus = backend.get_bus();
+            let value = bus.read_u8(0x02)?;
+            bus.write(0x01, &[0xAA, value.wrapping_add(1)])?;
+            Ok(Duration::from_micros(1))
+        }
+    }
+
+    impl Transmutable for Incrementer {
+        fn as_steppable(&mut self) -> Option<&mut dyn Steppable> {
+            Some(self)
+        }
+    }
+
+    #[test]
+    fn hook_only_marks_frozen_addresses_written() {
+        let mut frozen = FrozenMemory::default();
+        frozen.insert(0x10, 7);
+        frozen.on_access(BusAccess::Read, 0x10, &[0]);
+        frozen.on_access(BusAccess::Write, 0x0E, &[0, 0]);
+        assert!(frozen.take_dirty().is_empty());
+        frozen.on_access(BusAccess::Write, 0x0F, &[0, 0]);
+        assert_eq!(frozen.take_dirty(), vec![(0x10, 7)]);
+    }
+
+    #[test]
+    fn frozen_value_is_restored_after_each_step() {
+        let mut backend = Backend::default();
+        let memory: MemoryBlock = vec![0; 4].into();
+        backend
+            .add_addressable_component("memory", 0, Component::new(memory))
+            .unwrap();
+        backend.add_component("incrementer", Component::new(Incrementer));
+
+        backend.freeze_memory(0x02, 0x40).unwrap();
+        for _ in 0..3 {
+            backend.step().unwrap();
+            assert_eq!(backend.get_bus().read_u8(0x02).unwrap(), 0x40);
+        }
+        assert_eq!(backend.get_bus().read_u8(0x01).unwrap(), 0xAA);
+
+        backend.unfreeze_memory(0x02);
+        backend.step().unwrap();
+        assert_eq!(backend.get_bus().read_u8(0x02).unwrap(), 0x41);
+        assert!(backend.get_frozen_memory().is_empty());
+    }
+}
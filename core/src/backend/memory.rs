@@ -1,11 +1,20 @@
+use std::{cell::RefCell, rc::Rc};
+
 use crate::error::{EmulatorErrorKind, Error};
 
-use super::component::{Addressable, Component, MemoryAddress, MemorySize, Transmutable};
+use super::{
+    component::{
+        Addressable, Component, MemoryAddress, MemorySize, Resettable, Saveable, Transmutable,
+    },
+    snapshot::{StateReader, StateWriter},
+};
 
 #[derive(Default)]
 pub struct MemoryBlock {
     read_only: bool,
     data: Vec<u8>,
+    // Contents restored by a reset, writable blocks without them are cleared
+    power_on: Option<Vec<u8>>,
 }
 
 impl From<Vec<u8>> for MemoryBlock {
@@ -25,6 +34,11 @@ impl MemoryBlock {
     pub fn resize(&mut self, size: MemorySize) {
         self.data.resize(size, 0);
     }
+
+    // Keeps the current contents for resets, e.g. a rom the machine loads into ram when booting
+    pub fn capture_power_on(&mut self) {
+        self.power_on = Some(self.data.clone());
+    }
 }
 
 impl Addressable for MemoryBlock {
@@ -32,7 +46,7 @@ impl Addressable for MemoryBlock {
         self.data.len()
     }
 
-    fn read(&self, address: MemoryAddress, buffer: &mut [u8]) -> Result<(), Error> {
+    fn peek(&self, address: MemoryAddress, buffer: &mut [u8]) -> Result<(), Error> {
         if address + buffer.len() > self.size() {
             return Err(Error::emulator(
                 EmulatorErrorKind::MemoryAccessOutOfBounds,
@@ -78,17 +92,246 @@ impl Addressable for MemoryBlock {
     }
 }
 
+// Read only blocks are saved as well, e.g. rom patched while loading
+impl Saveable for MemoryBlock {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bytes(&self.data);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), Error> {
+        let data = reader.read_bytes()?;
+        if data.len() != self.data.len() {
+            return Err(Error::new(format!(
+                "state has {:#x} bytes for a memory block of size {:#x}",
+                data.len(),
+                self.data.len()
+            )));
+        }
+        self.data.copy_from_slice(data);
+        Ok(())
+    }
+}
+
+impl Resettable for MemoryBlock {
+    fn reset(&mut self) {
+        match self.power_on.as_ref() {
+            Some(power_on) => self.data.clone_from(power_on),
+            None if !self.read_only => self.data.fill(0),
+            None => {}
+        }
+    }
+}
+
 impl Transmutable for MemoryBlock {
     fn as_addressable(&mut self) -> Option<&mut dyn Addressable> {
         Some(self)
     }
+
+    fn as_saveable(&mut self) -> Option<&mut dyn Saveable> {
+        Some(self)
+    }
+
+    fn as_resettable(&mut self) -> Option<&mut dyn Resettable> {
+        Some(self)
+    }
+}
+
+// Several banks of the same size behind one bus window, e.g. the switchable rom of a cartridge.
+// Only the selected bank is visible, the machine switches banks with select_bank or through the
+// control register.
+pub struct BankedMemoryBlock {
+    read_only: bool,
+    bank_size: MemorySize,
+    // All banks back to back
+    data: Vec<u8>,
+    selected: usize,
+    // Writes to this address of the window select the bank. Writable blocks also store the byte, in
+    // the newly selected bank.
+    control_register: Option<MemoryAddress>,
+    power_on: Option<Vec<u8>>,
+}
+
+impl BankedMemoryBlock {
+    pub fn new(bank_size: MemorySize, banks: usize) -> Self {
+        Self::from_data(bank_size, vec![0; bank_size * banks.max(1)])
+    }
+
+    // Splits the data into banks, the last one is padded with zeros
+    pub fn from_data(bank_size: MemorySize, mut data: Vec<u8>) -> Self {
+        let bank_size = bank_size.max(1);
+        data.resize(data.len().div_ceil(bank_size).max(1) * bank_size, 0);
+        Self {
+            read_only: false,
+            bank_size,
+            data,
+            selected: 0,
+            control_register: None,
+            power_on: None,
+        }
+    }
+
+    pub fn set_read_only(&mut self) {
+        self.read_only = true;
+    }
+
+    // Writing a value to the register selects the bank of that number, wrapped around the number of
+    // banks like mappers ignore the upper bits. It works on read only blocks as well.
+    pub fn set_control_register(&mut self, address: MemoryAddress) {
+        self.control_register = Some(address);
+    }
+
+    // Keeps the current contents of all banks for resets
+    pub fn capture_power_on(&mut self) {
+        self.power_on = Some(self.data.clone());
+    }
+
+    pub fn bank_count(&self) -> usize {
+        self.data.len() / self.bank_size
+    }
+
+    pub fn selected_bank(&self) -> usize {
+        self.selected
+    }
+
+    pub fn select_bank(&mut self, bank: usize) -> Result<(), Error> {
+        if bank >= self.bank_count() {
+            return Err(Error::emulator(
+                EmulatorErrorKind::MemoryAccessOutOfBounds,
+                format!(
+                    "selected bank {} of a memory block with {} banks",
+                    bank,
+                    self.bank_count()
+                ),
+            ));
+        }
+        self.selected = bank;
+        Ok(())
+    }
+
+    // Any bank, regardless of the selected one, e.g. for memory views
+    pub fn bank(&self, bank: usize) -> Option<&[u8]> {
+        self.data.chunks(self.bank_size).nth(bank)
+    }
+
+    fn check_bounds(
+        &self,
+        address: MemoryAddress,
+        length: usize,
+        access: &str,
+    ) -> Result<(), Error> {
+        if address + length > self.bank_size {
+            return Err(Error::emulator(
+                EmulatorErrorKind::MemoryAccessOutOfBounds,
+                format!(
+                    "memory bank of size {:#010x}, but {} {:#010x} - {:#010x}",
+                    self.bank_size,
+                    access,
+                    address,
+                    address + length
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Addressable for BankedMemoryBlock {
+    fn size(&self) -> MemorySize {
+        self.bank_size
+    }
+
+    fn peek(&self, address: MemoryAddress, buffer: &mut [u8]) -> Result<(), Error> {
+        self.check_bounds(address, buffer.len(), "read")?;
+        let start = self.selected * self.bank_size + address;
+        buffer.copy_from_slice(&self.data[start..start + buffer.len()]);
+        Ok(())
+    }
+
+    fn write(&mut self, address: MemoryAddress, buffer: &[u8]) -> Result<(), Error> {
+        self.check_bounds(address, buffer.len(), "wrote")?;
+        let register = self
+            .control_register
+            .and_then(|register| register.checked_sub(address))
+            .filter(|offset| *offset < buffer.len());
+        if let Some(offset) = register {
+            self.selected = buffer[offset] as usize % self.bank_count();
+            if buffer.len() == 1 && self.read_only {
+                return Ok(());
+            }
+        }
+        if self.read_only {
+            return Err(Error::emulator(
+                EmulatorErrorKind::MemoryAccessReadOnly,
+                format!(
+                    "memory bank of size {:#010x}, request {:#010x} - {:#010x}",
+                    self.bank_size,
+                    address,
+                    address + buffer.len()
+                ),
+            ));
+        }
+        let start = self.selected * self.bank_size + address;
+        self.data[start..start + buffer.len()].copy_from_slice(buffer);
+        Ok(())
+    }
+}
+
+impl Saveable for BankedMemoryBlock {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u32(self.selected as u32);
+        writer.write_bytes(&self.data);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), Error> {
+        let selected = reader.read_u32()? as usize;
+        let data = reader.read_bytes()?;
+        if data.len() != self.data.len() {
+            return Err(Error::new(format!(
+                "state has {:#x} bytes for banked memory of size {:#x}",
+                data.len(),
+                self.data.len()
+            )));
+        }
+        self.select_bank(selected)?;
+        self.data.copy_from_slice(data);
+        Ok(())
+    }
+}
+
+impl Resettable for BankedMemoryBlock {
+    fn reset(&mut self) {
+        self.selected = 0;
+        match self.power_on.as_ref() {
+            Some(power_on) => self.data.clone_from(power_on),
+            None if !self.read_only => self.data.fill(0),
+            None => {}
+        }
+    }
+}
+
+impl Transmutable for BankedMemoryBlock {
+    fn as_addressable(&mut self) -> Option<&mut dyn Addressable> {
+        Some(self)
+    }
+
+    fn as_saveable(&mut self) -> Option<&mut dyn Saveable> {
+        Some(self)
+    }
+
+    fn as_resettable(&mut self) -> Option<&mut dyn Resettable> {
+        Some(self)
+    }
 }
 
 #[derive(Clone)]
 pub struct BusMount {
     base: MemoryAddress,
     size: MemorySize,
+    // Start of the mounted window inside the component
+    offset: MemoryAddress,
     component: Component,
+    // Translates the addresses instead of the offset
+    mapper: Option<Component>,
 }
 
 impl BusMount {
@@ -97,21 +340,167 @@ impl BusMount {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusAccess {
+    Read,
+    Write,
+}
+
+pub trait BusHook {
+    // Data holds the bytes read or written by the access
+    fn on_access(&mut self, access: BusAccess, address: MemoryAddress, data: &[u8]);
+}
+
+pub type BusHookHandle = Rc<RefCell<dyn BusHook>>;
+
 #[derive(Clone, Default)]
 pub struct Bus {
     mounts: Vec<BusMount>,
+    hooks: Vec<BusHookHandle>,
+    size: Option<MemorySize>,
 }
 
 impl Bus {
-    pub fn insert(&mut self, base: MemoryAddress, component: Component) {
-        // TODO: Assert this memory space isnt used already
-        let size = component.borrow_mut().as_addressable().unwrap().size();
-        self.mounts.push(BusMount {
+    // A bus with a fixed size, e.g. a secondary bus behind a bridge that decodes a whole window
+    // of its parent bus regardless of what is mounted on it
+    pub fn with_size(size: MemorySize) -> Self {
+        Self {
+            size: Some(size),
+            ..Default::default()
+        }
+    }
+
+    // Hooks are notified after every successful read or write that goes through the bus, peeks
+    // are not reported
+    pub fn add_hook(&mut self, hook: BusHookHandle) {
+        self.hooks.push(hook);
+    }
+
+    pub fn remove_hook(&mut self, hook: &BusHookHandle) {
+        self.hooks.retain(|h| !Rc::ptr_eq(h, hook));
+    }
+
+    // Writes for debugging purposes, which are not reported to the hooks
+    pub fn poke(&self, address: MemoryAddress, buffer: &[u8]) -> Result<(), Error> {
+        let (component, relative_address) = self.get_component_at(address, buffer.len())?;
+        component
+            .borrow_mut()
+            .as_addressable()
+            .unwrap()
+            .write(relative_address, buffer)
+    }
+
+    fn notify_hooks(&self, access: BusAccess, address: MemoryAddress, data: &[u8]) {
+        for hook in &self.hooks {
+            hook.borrow_mut().on_access(access, address, data);
+        }
+    }
+
+    // Mounts can not overlap, reads of the shared addresses would silently go to only one of them
+    fn mount(&mut self, mount: BusMount) -> Result<(), Error> {
+        let end = mount.base + mount.size;
+        if let Some(other) = self
+            .mounts
+            .iter()
+            .find(|other| other.base < end && mount.base < other.base + other.size)
+        {
+            return Err(Error::new(format!(
+                "mount {:#010x} - {:#010x} overlaps the mount {:#010x} - {:#010x}",
+                mount.base,
+                end,
+                other.base,
+                other.base + other.size
+            )));
+        }
+        self.mounts.push(mount);
+        self.mounts.sort_by_key(|m| m.base);
+        Ok(())
+    }
+
+    pub fn insert(&mut self, base: MemoryAddress, component: Component) -> Result<(), Error> {
+        let size = component
+            .borrow_mut()
+            .as_addressable()
+            .ok_or_else(|| Error::new("component is not addressable"))?
+            .size();
+        self.mount(BusMount {
             base,
             size,
+            offset: 0,
             component,
+            mapper: None,
+        })
+    }
+
+    // Unmounts the window starting at the base, returns its component
+    pub fn remove(&mut self, base: MemoryAddress) -> Option<Component> {
+        let index = self.mounts.iter().position(|mount| mount.base == base)?;
+        Some(self.mounts.remove(index).component)
+    }
+
+    // Unmounts every window of the component and every window it maps, returns whether it was
+    // mounted at all
+    pub fn remove_component(&mut self, component: &Component) -> bool {
+        let mounts = self.mounts.len();
+        self.mounts.retain(|mount| {
+            mount.component != *component && mount.mapper.as_ref() != Some(component)
         });
-        self.mounts.sort_by_key(|m| m.base);
+        self.mounts.len() != mounts
+    }
+
+    // Mounts only the window offset..offset + size of the component, so the same component can
+    // appear at several places of the address space
+    pub fn insert_region(
+        &mut self,
+        base: MemoryAddress,
+        component: Component,
+        offset: MemoryAddress,
+        size: MemorySize,
+    ) -> Result<(), Error> {
+        let component_size = component
+            .borrow_mut()
+            .as_addressable()
+            .ok_or_else(|| Error::new("component is not addressable"))?
+            .size();
+        if offset + size > component_size {
+            return Err(Error::new(format!(
+                "region {:#010x} - {:#010x} exceeds component of size {:#010x}",
+                offset,
+                offset + size,
+                component_size
+            )));
+        }
+        self.mount(BusMount {
+            base,
+            size,
+            offset,
+            component,
+            mapper: None,
+        })
+    }
+
+    // Mounts the component behind a mapper, another component which translates the addresses of
+    // the window and may take writes to its control registers
+    pub fn insert_mapped(
+        &mut self,
+        base: MemoryAddress,
+        size: MemorySize,
+        component: Component,
+        mapper: Component,
+    ) -> Result<(), Error> {
+        if component.borrow_mut().as_addressable().is_none() {
+            return Err(Error::new("component is not addressable"));
+        }
+        if mapper.borrow_mut().as_mapper().is_none() {
+            return Err(Error::new("component is not a mapper"));
+        }
+        self.mount(BusMount {
+            base,
+            size,
+            offset: 0,
+            component,
+            mapper: Some(mapper),
+        })
     }
 
     pub fn get_component_at(
@@ -119,10 +508,24 @@ impl Bus {
         address: MemoryAddress,
         size: MemorySize,
     ) -> Result<(Component, MemoryAddress), Error> {
+        let mount = self.get_mount_at(address, size)?;
+        let relative_address = address - mount.base;
+        let relative_address = match mount.mapper.as_ref() {
+            Some(mapper) => mapper
+                .borrow_mut()
+                .as_mapper()
+                .unwrap()
+                .translate(relative_address),
+            None => relative_address + mount.offset,
+        };
+        Ok((mount.component.clone(), relative_address))
+    }
+
+    fn get_mount_at(&self, address: MemoryAddress, size: MemorySize) -> Result<&BusMount, Error> {
         if size > 0 {
             for mount in &self.mounts {
                 if mount.contains(address) && mount.contains(address + size - 1) {
-                    return Ok((mount.component.clone(), address - mount.base));
+                    return Ok(mount);
                 }
             }
         }
@@ -139,25 +542,241 @@ impl Bus {
 
 impl Addressable for Bus {
     fn size(&self) -> MemorySize {
-        let last_mount = self.mounts.last().unwrap();
-        last_mount.base + last_mount.size
+        self.size.unwrap_or_else(|| {
+            self.mounts
+                .last()
+                .map_or(0, |last_mount| last_mount.base + last_mount.size)
+        })
     }
 
-    fn read(&self, address: MemoryAddress, buffer: &mut [u8]) -> Result<(), Error> {
+    fn read(&mut self, address: MemoryAddress, buffer: &mut [u8]) -> Result<(), Error> {
         let (component, relative_address) = self.get_component_at(address, buffer.len())?;
         component
             .borrow_mut()
             .as_addressable()
             .unwrap()
-            .read(relative_address, buffer)
+            .read(relative_address, buffer)?;
+        self.notify_hooks(BusAccess::Read, address, buffer);
+        Ok(())
     }
 
-    fn write(&mut self, address: MemoryAddress, buffer: &[u8]) -> Result<(), Error> {
+    fn peek(&self, address: MemoryAddress, buffer: &mut [u8]) -> Result<(), Error> {
         let (component, relative_address) = self.get_component_at(address, buffer.len())?;
         component
             .borrow_mut()
             .as_addressable()
             .unwrap()
-            .write(relative_address, buffer)
+            .peek(relative_address, buffer)
+    }
+
+    fn write(&mut self, address: MemoryAddress, buffer: &[u8]) -> Result<(), Error> {
+        let mount = self.get_mount_at(address, buffer.len())?;
+        let control = mount.mapper.as_ref().is_some_and(|mapper| {
+            mapper
+                .borrow_mut()
+                .as_mapper()
+                .unwrap()
+                .write_control(address - mount.base, buffer)
+        });
+        if !control {
+            let (component, relative_address) = self.get_component_at(address, buffer.len())?;
+            component
+                .borrow_mut()
+                .as_addressable()
+                .unwrap()
+                .write(relative_address, buffer)?;
+        }
+        self.notify_hooks(BusAccess::Write, address, buffer);
+        Ok(())
+    }
+}
+
+// Allows mounting a bus into another bus, addresses are relative to the mount point
+impl Transmutable for Bus {
+    fn as_addressable(&mut self) -> Option<&mut dyn Addressable> {
+        Some(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::component::{AddressableExt, ByteOrder, Mapper};
+
+    fn memory(size: MemorySize) -> Component {
+        Component::new(MemoryBlock::from(vec![0; size]))
+    }
+
+    #[test]
+    fn rejects_overlapping_mounts() {
+        let mut bus = Bus::default();
+        let ram = memory(0x100);
+        bus.insert(0x100, ram.clone()).unwrap();
+        assert!(bus.insert(0x1FF, memory(1)).is_err());
+        assert!(bus.insert(0x80, memory(0x81)).is_err());
+        assert!(bus.insert_region(0x150, ram.clone(), 0, 0x10).is_err());
+        bus.insert(0x80, memory(0x80)).unwrap();
+        bus.insert_region(0x200, ram.clone(), 0, 0x10).unwrap();
+
+        assert!(bus.remove(0x100).is_some_and(|removed| removed == ram));
+        assert!(bus.remove(0x100).is_none());
+        bus.insert(0x100, memory(0x100)).unwrap();
+        assert!(bus.remove_component(&ram));
+        assert!(bus.peek(0x200, &mut [0]).is_err());
+    }
+
+    // Hands out its bytes one read at a time, like a receive FIFO
+    struct Fifo(Vec<u8>);
+
+    impl Addressable for Fifo {
+        fn size(&self) -> MemorySize {
+            1
+        }
+
+        fn peek(&self, _address: MemoryAddress, buffer: &mut [u8]) -> Result<(), Error> {
+            buffer[0] = self.0.first().copied().unwrap_or_default();
+            Ok(())
+        }
+
+        fn read(&mut self, address: MemoryAddress, buffer: &mut [u8]) -> Result<(), Error> {
+            self.peek(address, buffer)?;
+            if !self.0.is_empty() {
+                self.0.remove(0);
+            }
+            Ok(())
+        }
+
+        fn write(&mut self, _address: MemoryAddress, buffer: &[u8]) -> Result<(), Error> {
+            self.0.extend_from_slice(buffer);
+            Ok(())
+        }
+    }
+
+    impl Transmutable for Fifo {
+        fn as_addressable(&mut self) -> Option<&mut dyn Addressable> {
+            Some(self)
+        }
+    }
+
+    #[test]
+    fn peeks_leave_devices_with_read_side_effects_alone() {
+        let mut bus = Bus::default();
+        bus.insert(0x10, Component::new(Fifo(vec![1, 2]))).unwrap();
+        let mut byte = [0];
+        bus.peek(0x10, &mut byte).unwrap();
+        bus.peek(0x10, &mut byte).unwrap();
+        assert_eq!(byte, [1]);
+        assert_eq!(bus.read_u8(0x10).unwrap(), 1);
+        assert_eq!(bus.read_u8(0x10).unwrap(), 2);
+        assert_eq!(bus.read_u8(0x10).unwrap(), 0);
+    }
+
+    #[test]
+    fn banks_are_switched_through_the_control_register() {
+        let data = (0..10).collect::<Vec<u8>>();
+        let mut rom = BankedMemoryBlock::from_data(4, data);
+        rom.set_read_only();
+        rom.set_control_register(0);
+        assert_eq!(rom.bank_count(), 3);
+        assert_eq!(rom.bank(2), Some(&[8, 9, 0, 0][..]));
+
+        assert_eq!(rom.read_u8(1).unwrap(), 1);
+        rom.write_u8(0, 1).unwrap();
+        assert_eq!(rom.read_u8(1).unwrap(), 5);
+        // Mappers ignore the bits above the number of banks
+        rom.write_u8(0, 5).unwrap();
+        assert_eq!(rom.selected_bank(), 2);
+        assert!(rom.write_u8(1, 0).is_err());
+        assert!(rom.select_bank(3).is_err());
+        assert!(rom.read_u8(4).is_err());
+
+        let mut writer = StateWriter::new();
+        rom.save_state(&mut writer);
+        rom.reset();
+        assert_eq!(rom.selected_bank(), 0);
+        rom.load_state(&mut StateReader::new(&writer.into_data()))
+            .unwrap();
+        assert_eq!(rom.selected_bank(), 2);
+    }
+
+    #[test]
+    fn writes_go_to_the_selected_bank() {
+        let mut ram = BankedMemoryBlock::new(2, 2);
+        ram.write(0, &[1, 2]).unwrap();
+        ram.select_bank(1).unwrap();
+        assert_eq!(ram.read_u16_be(0).unwrap(), 0);
+        ram.write_u8(1, 3).unwrap();
+        assert_eq!(ram.bank(0), Some(&[1, 2][..]));
+        assert_eq!(ram.bank(1), Some(&[0, 3][..]));
+    }
+
+    // Shows one of two halves of the component, writes to its first address pick the half
+    #[derive(Default)]
+    struct HalfMapper(usize);
+
+    impl Mapper for HalfMapper {
+        fn translate(&self, address: MemoryAddress) -> MemoryAddress {
+            self.0 * 4 + address
+        }
+
+        fn write_control(&mut self, address: MemoryAddress, data: &[u8]) -> bool {
+            if address != 0 {
+                return false;
+            }
+            self.0 = data[0] as usize & 1;
+            true
+        }
+    }
+
+    impl Transmutable for HalfMapper {
+        fn as_mapper(&mut self) -> Option<&mut dyn Mapper> {
+            Some(self)
+        }
+    }
+
+    #[test]
+    fn mappers_translate_addresses_and_take_control_writes() {
+        let mut bus = Bus::default();
+        let rom = Component::new(MemoryBlock::from((0..8).collect::<Vec<u8>>()));
+        let mapper = Component::new(HalfMapper::default());
+        assert!(
+            bus.insert_mapped(0, 4, mapper.clone(), rom.clone())
+                .is_err()
+        );
+        bus.insert_mapped(0x10, 4, rom.clone(), mapper.clone())
+            .unwrap();
+
+        assert_eq!(bus.read_u8(0x11).unwrap(), 1);
+        bus.write_u8(0x10, 1).unwrap();
+        assert_eq!(bus.read_u16_be(0x12).unwrap(), 0x0607);
+        // Other writes go through to the component
+        bus.write_u8(0x11, 0xAA).unwrap();
+        assert_eq!(bus.read_u8(0x11).unwrap(), 0xAA);
+        assert_eq!(
+            rom.borrow_mut()
+                .as_addressable()
+                .unwrap()
+                .read_u8(5)
+                .unwrap(),
+            0xAA
+        );
+
+        assert!(bus.remove_component(&mapper));
+        assert!(bus.peek(0x10, &mut [0]).is_err());
+    }
+
+    #[test]
+    fn wide_and_signed_accessors_assemble_bytes_in_order() {
+        let mut memory = MemoryBlock::from(vec![0; 16]);
+        memory.write_u32_be(0, 0x0102_0304).unwrap();
+        assert_eq!(memory.read_u32_le(0).unwrap(), 0x0403_0201);
+        memory.write_i64_le(8, -2).unwrap();
+        assert_eq!(memory.read_u64_le(8).unwrap(), u64::MAX - 1);
+        assert_eq!(memory.read_i16_be(8).unwrap(), -257);
+        assert_eq!(memory.read_i8(15).unwrap(), -1);
+
+        memory.write_value(4, -3i32, ByteOrder::Big).unwrap();
+        assert_eq!(memory.peek_value::<i32>(4, ByteOrder::Big).unwrap(), -3);
+        assert!(memory.read_u64_be(12).is_err());
     }
 }
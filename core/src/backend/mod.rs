@@ -1,41 +1,365 @@
+pub mod breakpoint;
 pub mod component;
+pub mod controls;
+mod debugport;
+pub mod dma;
+pub mod fairness;
+pub mod firmware;
+mod freeze;
 pub mod memory;
+pub mod options;
+pub mod registry;
+pub mod rewind;
+pub mod rng;
+pub mod rtc;
+pub mod selftest;
+pub mod snapshot;
+pub mod trigger;
 
 use std::{
-    cell::{RefCell, RefMut},
+    cell::{Cell, RefCell, RefMut},
     collections::{BinaryHeap, HashMap},
     rc::Rc,
 };
 
-use component::{Component, MemoryAddress};
+use breakpoint::{BreakpointEvent, Breakpoints};
+use component::{
+    Addressable, Component, Debuggable, DisassembledInstruction, MemoryAddress, MemorySize,
+};
+use controls::ControlScheme;
+use debugport::DebugPort;
+use fairness::{Fairness, SchedulerWarning};
 use femtos::{Duration, Instant};
-use memory::Bus;
+use freeze::FrozenMemory;
+use memory::{Bus, BusHookHandle};
+use rewind::RewindBuffer;
+use rng::Rng;
+use rtc::{RtcPolicy, RtcProvider, RtcTime};
+use snapshot::{StatePayload, StateReader, StateWriter};
+use trigger::{Trigger, TriggerCondition, TriggerEvent, TriggerId, Triggers};
+
+use crate::frontend::{input::KeyboardEventKey, text::TextSender};
 
 use crate::error::Error;
 
+// Steps of other components before one stepped for debugging is considered stuck
+const MAX_FOREIGN_STEPS: usize = 1_000_000;
+// Bytes read past the end of a disassembled range, so the last instruction is complete
+const MAX_INSTRUCTION_LENGTH: MemorySize = 16;
+// From slow motion to fast forward
+pub const SPEED_RANGE: std::ops::RangeInclusive<f64> = 0.1..=8.0;
+// The bus every backend has, triggers, frozen memory and the debugger work on it
+pub const CPU_BUS: &str = "cpu";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DisplayConfig {
+    // Only scale the output by whole multiples, which keeps low resolution output sharp
+    pub integer_scaling: bool,
+    // Width divided by height of the displayed image, None keeps the aspect of the frames
+    pub aspect_ratio: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BackendInfo {
+    // Nominal number of frames per second the backend produces
+    pub refresh_rate: f64,
+    pub display: DisplayConfig,
+    pub controls: ControlScheme,
+    // Number of players the backend accepts input for
+    pub players: u8,
+}
+
+impl Default for BackendInfo {
+    fn default() -> Self {
+        Self {
+            refresh_rate: 60.0,
+            display: DisplayConfig::default(),
+            controls: ControlScheme::default(),
+            players: 1,
+        }
+    }
+}
+
+impl BackendInfo {
+    pub fn frame_duration(&self) -> Duration {
+        Duration::from_nanos((1_000_000_000.0 / self.refresh_rate) as u64)
+    }
+}
+
+// Points in the emulation components signal while stepping, the debugger can run up to them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutionEvent {
+    // A frame was emitted, also when rendering is skipped
+    Frame,
+    // A timer of the machine ran out
+    TimerExpiry,
+    // A signal line was asserted, e.g. an interrupt request
+    Signal,
+}
+
+// What happens to the time beyond the catch-up limit of a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CatchUpPolicy {
+    // The machine falls behind the wall clock for good, like it was paused during the stall
+    Drop,
+    // Following runs cover it on top of their own time, up to the limit each
+    Spread,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScheduledWakeup {
+    pub name: String,
+    pub clock: Instant,
+}
+
+#[derive(Debug, Clone)]
+pub struct SchedulerSnapshot {
+    pub clock: Instant,
+    // Sorted by the time the components are stepped next
+    pub wakeups: Vec<ScheduledWakeup>,
+}
+
 pub struct Backend {
+    info: BackendInfo,
     clock: Instant,
     components: HashMap<String, Component>,
     scheduler_queue: BinaryHeap<SchedulerEvent>,
     bus: Rc<RefCell<Bus>>,
+    // Separate address spaces like video memory or io ports, including the cpu bus
+    buses: HashMap<String, Rc<RefCell<Bus>>>,
+    rng: RefCell<Rng>,
+    rtc: RtcProvider,
+    triggers: Triggers,
+    breakpoints: RefCell<Breakpoints>,
+    // Name of the component currently stepping, lent from its scheduler event
+    stepping: String,
+    // Component whose step returned the last error
+    failed_component: Option<String>,
+    // Runs do nothing while paused, explicit steps still go through
+    paused: bool,
+    speed: f64,
+    max_catch_up: Option<(Duration, CatchUpPolicy)>,
+    // Time left over from runs that hit the catch-up limit with CatchUpPolicy::Spread
+    catch_up_backlog: Duration,
+    frozen_memory: Rc<RefCell<FrozenMemory>>,
+    frozen_memory_installed: bool,
+    debug_port: Option<(Rc<RefCell<DebugPort>>, TextSender)>,
+    render_enabled: bool,
+    fairness: Fairness,
+    // Events signalled during the current step
+    signalled: Cell<Vec<ExecutionEvent>>,
+    frame_count: Cell<u64>,
+    rewind: Option<RewindBuffer>,
+    // Components added so far, which orders the steps of components due at the same clock
+    added_components: u64,
 }
 
 impl Default for Backend {
     fn default() -> Self {
+        let bus = Rc::new(RefCell::new(Bus::default()));
         Self {
+            info: BackendInfo::default(),
             clock: Instant::START,
             components: HashMap::new(),
             scheduler_queue: BinaryHeap::new(),
-            bus: Rc::new(RefCell::new(Bus::default())),
+            buses: HashMap::from([(CPU_BUS.to_string(), bus.clone())]),
+            bus,
+            rng: RefCell::new(Rng::default()),
+            rtc: RtcProvider::default(),
+            triggers: Triggers::default(),
+            breakpoints: RefCell::new(Breakpoints::default()),
+            stepping: String::new(),
+            failed_component: None,
+            paused: false,
+            speed: 1.0,
+            max_catch_up: None,
+            catch_up_backlog: Duration::ZERO,
+            frozen_memory: Rc::new(RefCell::new(FrozenMemory::default())),
+            frozen_memory_installed: false,
+            debug_port: None,
+            render_enabled: true,
+            fairness: Fairness::default(),
+            signalled: Cell::new(vec![]),
+            frame_count: Cell::new(0),
+            rewind: None,
+            added_components: 0,
         }
     }
 }
 
 impl Backend {
+    pub fn get_info(&self) -> BackendInfo {
+        self.info
+    }
+
+    pub fn set_info(&mut self, info: BackendInfo) {
+        self.info = info;
+    }
+
     pub fn get_bus(&self) -> RefMut<'_, Bus> {
         self.bus.borrow_mut()
     }
 
+    pub fn get_named_bus(&self, name: &str) -> Result<RefMut<'_, Bus>, Error> {
+        self.buses
+            .get(name)
+            .map(|bus| bus.borrow_mut())
+            .ok_or_else(|| Error::new(format!("no bus named {}", name)))
+    }
+
+    pub fn add_bus(&mut self, name: &str) -> Result<(), Error> {
+        if self.buses.contains_key(name) {
+            return Err(Error::new(format!("bus {} exists already", name)));
+        }
+        self.buses
+            .insert(name.to_string(), Rc::new(RefCell::new(Bus::default())));
+        Ok(())
+    }
+
+    // Sorted, the cpu bus is always there
+    pub fn bus_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.buses.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    // Components must draw all randomness from here, so runs with the same seed are reproducible
+    pub fn get_rng(&self) -> RefMut<'_, Rng> {
+        self.rng.borrow_mut()
+    }
+
+    pub fn get_rng_seed(&self) -> u64 {
+        self.rng.borrow().seed()
+    }
+
+    pub fn set_rng_seed(&mut self, seed: u64) {
+        self.rng = RefCell::new(Rng::new(seed));
+    }
+
+    // Real-time clocks of the components take the time of day from here, see RtcPolicy
+    pub fn get_rtc_time(&self) -> RtcTime {
+        self.rtc.now(self.clock)
+    }
+
+    pub fn get_rtc_policy(&self) -> RtcPolicy {
+        self.rtc.policy()
+    }
+
+    pub fn set_rtc_policy(&mut self, policy: RtcPolicy) {
+        self.rtc = RtcProvider::new(policy, self.clock);
+    }
+
+    pub fn add_trigger(&mut self, condition: TriggerCondition) -> TriggerId {
+        // value triggers only fire on a change, not for a value that is already present
+        let matched = match condition {
+            TriggerCondition::Value(address, value) => {
+                peek_u8(&self.bus.borrow(), address) == Some(value)
+            }
+            _ => false,
+        };
+        let id = self.triggers.add(condition, matched);
+        if let Some(write_watch) = self.triggers.install_write_watch() {
+            self.bus.borrow_mut().add_hook(write_watch);
+        }
+        id
+    }
+
+    pub fn remove_trigger(&mut self, id: TriggerId) {
+        self.triggers.remove(id);
+    }
+
+    pub fn get_triggers(&self) -> &[Trigger] {
+        self.triggers.list()
+    }
+
+    // Events of triggers that fired since the last call, running stops right after a trigger fires
+    pub fn take_trigger_events(&mut self) -> Vec<TriggerEvent> {
+        self.triggers.take_events()
+    }
+
+    pub fn has_trigger_events(&self) -> bool {
+        self.triggers.has_events()
+    }
+
+    pub fn add_breakpoint(&mut self, component: &str, address: MemoryAddress) -> Result<(), Error> {
+        self.get_component(component)?;
+        self.breakpoints.borrow_mut().add(component, address);
+        Ok(())
+    }
+
+    pub fn remove_breakpoint(&mut self, component: &str, address: MemoryAddress) {
+        self.breakpoints.borrow_mut().remove(component, address);
+    }
+
+    pub fn get_breakpoints(&self, component: &str) -> Vec<MemoryAddress> {
+        self.breakpoints.borrow().list(component)
+    }
+
+    // Called by cpus with the address of the next instruction before they run it. On a breakpoint
+    // of the stepping component the cpu leaves the instruction for the next step, as running stops
+    // until the events are taken.
+    pub fn check_breakpoint(&self, address: MemoryAddress) -> bool {
+        let mut breakpoints = self.breakpoints.borrow_mut();
+        !breakpoints.is_empty() && breakpoints.check(&self.stepping, address, self.clock)
+    }
+
+    // Breakpoints hit since the last call
+    pub fn take_breakpoint_events(&mut self) -> Vec<BreakpointEvent> {
+        self.breakpoints.borrow_mut().take_events()
+    }
+
+    pub fn has_breakpoint_events(&self) -> bool {
+        self.breakpoints.borrow().has_events()
+    }
+
+    pub(crate) fn discard_breakpoint_events_before(&mut self, clock: Instant) {
+        self.breakpoints.borrow_mut().discard_events_before(clock);
+    }
+
+    // Pins the byte at the address of the bus to the value, until it is unfrozen again
+    pub fn freeze_memory(&mut self, address: MemoryAddress, value: u8) -> Result<(), Error> {
+        self.bus.borrow().poke(address, &[value])?;
+        self.frozen_memory.borrow_mut().insert(address, value);
+        if !self.frozen_memory_installed {
+            self.frozen_memory_installed = true;
+            self.bus.borrow_mut().add_hook(self.frozen_memory.clone());
+        }
+        Ok(())
+    }
+
+    pub fn unfreeze_memory(&mut self, address: MemoryAddress) {
+        self.frozen_memory.borrow_mut().remove(address);
+    }
+
+    pub fn get_frozen_memory(&self) -> Vec<(MemoryAddress, u8)> {
+        self.frozen_memory.borrow().list()
+    }
+
+    // Lines of characters written to the address of the bus are sent to the text channel, so
+    // homebrew roms can print. The address has to be writable, e.g. a byte of memory.
+    pub fn set_debug_port(&mut self, address: MemoryAddress, sender: TextSender) {
+        self.remove_debug_port();
+        let port = Rc::new(RefCell::new(DebugPort::new(address)));
+        self.bus.borrow_mut().add_hook(port.clone());
+        self.debug_port = Some((port, sender));
+    }
+
+    pub fn remove_debug_port(&mut self) {
+        if let Some((port, _)) = self.debug_port.take() {
+            let hook: BusHookHandle = port;
+            self.bus.borrow_mut().remove_hook(&hook);
+        }
+    }
+
+    fn send_debug_port_lines(&self) {
+        if let Some((port, sender)) = self.debug_port.as_ref() {
+            for line in port.borrow_mut().take_lines() {
+                sender.add(self.clock, line);
+            }
+        }
+    }
+
     pub fn get_component(&self, name: &str) -> Result<Component, Error> {
         self.components
             .get(name)
@@ -43,6 +367,86 @@ impl Backend {
             .ok_or_else(|| Error::new(format!("no component named {}", name)))
     }
 
+    fn with_debuggable<T>(
+        &self,
+        name: &str,
+        action: impl FnOnce(&mut dyn Debuggable) -> T,
+    ) -> Result<T, Error> {
+        let component = self.get_component(name)?;
+        let mut component = component.borrow_mut();
+        let debuggable = component
+            .as_debuggable()
+            .ok_or_else(|| Error::new(format!("component {} does not support debugging", name)))?;
+        Ok(action(debuggable))
+    }
+
+    pub fn get_pc(&self, component: &str) -> Result<MemoryAddress, Error> {
+        self.with_debuggable(component, |debuggable| debuggable.get_pc())
+    }
+
+    pub fn pause_component(&mut self, component: &str) -> Result<(), Error> {
+        self.with_debuggable(component, |debuggable| debuggable.pause())
+    }
+
+    pub fn resume_component(&mut self, component: &str) -> Result<(), Error> {
+        self.with_debuggable(component, |debuggable| debuggable.resume())
+    }
+
+    pub fn is_component_paused(&self, component: &str) -> Result<bool, Error> {
+        self.with_debuggable(component, |debuggable| debuggable.is_paused())
+    }
+
+    // Runs the machine until the component executed one instruction, also when it is paused
+    pub fn step_instruction(&mut self, component: &str) -> Result<(), Error> {
+        self.with_debuggable(component, |debuggable| debuggable.step_instruction())?;
+        let component = self.get_component(component)?;
+        for _ in 0..MAX_FOREIGN_STEPS {
+            let is_target = self.next_component().as_ref() == Some(&component);
+            self.step()?;
+            if is_target {
+                return Ok(());
+            }
+        }
+        Err(Error::new("the component is not stepped anymore"))
+    }
+
+    // The instructions starting between start and end, decoded by the component from the bus. The
+    // memory is peeked, so disassembling never changes the emulation. Unmapped memory ends it.
+    pub fn disassemble(
+        &self,
+        name: &str,
+        start: MemoryAddress,
+        end: MemoryAddress,
+    ) -> Result<Vec<DisassembledInstruction>, Error> {
+        let bytes: Vec<u8> = {
+            let bus = self.get_bus();
+            (start..end.saturating_add(MAX_INSTRUCTION_LENGTH).min(bus.size()))
+                .map_while(|address| {
+                    let mut byte = [0];
+                    bus.peek(address, &mut byte).ok().map(|_| byte[0])
+                })
+                .collect()
+        };
+        let component = self.get_component(name)?;
+        let mut component = component.borrow_mut();
+        let disassemblable = component.as_disassemblable().ok_or_else(|| {
+            Error::new(format!("component {} does not support disassembly", name))
+        })?;
+        let mut instructions = vec![];
+        let mut offset = 0;
+        while start + offset < end {
+            let Some(instruction) = bytes
+                .get(offset..)
+                .and_then(|bytes| disassemblable.disassemble(start + offset, bytes))
+            else {
+                break;
+            };
+            offset += instruction.length.max(1);
+            instructions.push(instruction);
+        }
+        Ok(instructions)
+    }
+
     pub fn get_all_components(&self) -> Vec<(String, Component)> {
         self.components
             .iter()
@@ -50,61 +454,546 @@ impl Backend {
             .collect::<Vec<(String, Component)>>()
     }
 
+    // Hash over the full save state payload. Two runs with the same rom, seed and input end with
+    // the same hash.
+    pub fn state_hash(&self) -> Result<String, Error> {
+        Ok(crate::utils::sha1_hex(&self.save_state()?))
+    }
+
+    // Captures the clock, the scheduler and all saveable components as snapshot payload
+    pub fn save_state(&self) -> Result<Vec<u8>, Error> {
+        let mut writer = StateWriter::new();
+        write_state_header(
+            &mut writer,
+            self.clock,
+            self.frame_count.get(),
+            &self.rng.borrow(),
+        );
+        self.rtc.save_state(&mut writer);
+
+        let mut wakeups: Vec<(&str, Instant)> = self
+            .scheduler_queue
+            .iter()
+            .map(|event| (event.name.as_str(), event.clock_cycle))
+            .collect();
+        wakeups.sort();
+        writer.write_u32(wakeups.len() as u32);
+        for (name, clock) in wakeups {
+            writer.write_string(name);
+            writer.write_instant(clock);
+        }
+
+        let mut components = self.get_all_components();
+        components.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut states = vec![];
+        for (name, component) in components {
+            let mut component = component
+                .try_borrow_mut()
+                .map_err(|_| Error::new(format!("component {} is busy", name)))?;
+            if let Some(saveable) = component.as_saveable() {
+                let mut component_writer = StateWriter::new();
+                saveable.save_state(&mut component_writer);
+                states.push((name, component_writer.into_data()));
+            }
+        }
+        writer.write_u32(states.len() as u32);
+        for (name, state) in states {
+            writer.write_string(&name);
+            writer.write_bytes(&state);
+        }
+        Ok(writer.into_data())
+    }
+
+    // Restores a payload of save_state of the same machine. Nothing changes when it fails.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), Error> {
+        let backup = self.save_state()?;
+        let result = self.apply_state(data);
+        if result.is_err() {
+            self.apply_state(&backup)?;
+        }
+        result
+    }
+
+    fn apply_state(&mut self, data: &[u8]) -> Result<(), Error> {
+        let payload = StatePayload::read(data)?;
+        let wakeups = &payload.wakeups;
+        let mut events = std::mem::take(&mut self.scheduler_queue).into_vec();
+        if wakeups.len() != events.len() {
+            self.scheduler_queue = events.into();
+            return Err(Error::new("state does not match the scheduled components"));
+        }
+        for event in events.iter_mut() {
+            match wakeups.get(&event.name) {
+                Some(clock) => event.clock_cycle = *clock,
+                None => {
+                    let name = event.name.clone();
+                    self.scheduler_queue = events.into();
+                    return Err(Error::new(format!("state has no wakeup of {}", name)));
+                }
+            }
+        }
+        self.scheduler_queue = events.into();
+
+        let mut loaded = 0;
+        for (name, state) in payload.states.iter() {
+            let component = self.get_component(name)?;
+            let mut component = component.borrow_mut();
+            let saveable = component
+                .as_saveable()
+                .ok_or_else(|| Error::new(format!("component {} has no state", name)))?;
+            let mut component_reader = StateReader::new(state);
+            saveable.load_state(&mut component_reader)?;
+            component_reader.finish()?;
+            loaded += 1;
+        }
+        let saveable = self
+            .components
+            .values()
+            .filter(|component| component.borrow_mut().as_saveable().is_some())
+            .count();
+        if loaded != saveable {
+            return Err(Error::new("state is missing components"));
+        }
+
+        self.clock = payload.clock;
+        self.frame_count.set(payload.frame_count);
+        *self.rng.borrow_mut() = Rng::restore(payload.rng_seed, payload.rng_state);
+        self.rtc = payload.rtc;
+        self.signalled.take();
+        Ok(())
+    }
+
+    // Snapshots are captured into the buffer while running, None stops capturing and drops them
+    pub fn set_rewind(&mut self, rewind: Option<RewindBuffer>) {
+        self.rewind = rewind;
+    }
+
+    pub fn get_rewind(&self) -> Option<&RewindBuffer> {
+        self.rewind.as_ref()
+    }
+
+    // While paused, running the backend leaves the clock where it is. Stepping it explicitly, e.g.
+    // a single instruction for the debugger, still works.
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    // Power cycles the machine without rebuilding it. The clock, the scheduler and the rng seeded
+    // like before start over, and every resettable component returns to its power-on state.
+    // Channels to the frontend, breakpoints, triggers, frozen memory and the time of day stay.
+    pub fn reset(&mut self) -> Result<(), Error> {
+        for (name, component) in self.get_all_components() {
+            let mut component = component
+                .try_borrow_mut()
+                .map_err(|_| Error::new(format!("component {} is busy", name)))?;
+            if let Some(resettable) = component.as_resettable() {
+                resettable.reset();
+            }
+        }
+        let mut events = std::mem::take(&mut self.scheduler_queue).into_vec();
+        for event in events.iter_mut() {
+            event.clock_cycle = Instant::START;
+        }
+        self.scheduler_queue = events.into();
+        self.rtc.rebase(self.clock);
+        self.clock = Instant::START;
+        self.frame_count.set(0);
+        self.catch_up_backlog = Duration::ZERO;
+        self.set_rng_seed(self.get_rng_seed());
+        self.signalled.take();
+        self.failed_component = None;
+        self.breakpoints.borrow_mut().take_events();
+        self.triggers.take_events();
+        // Snapshots of the time before the reset cannot be rewound to anymore
+        if let Some(rewind) = self.rewind.as_mut() {
+            rewind.clear();
+        }
+        self.restore_frozen_memory()
+    }
+
+    // Goes back to the newest snapshot at least the duration ago, or to the oldest one. Returns
+    // false when there is no snapshot to go back to.
+    pub fn rewind(&mut self, duration: Duration) -> Result<bool, Error> {
+        let target = self.clock.checked_sub(duration).unwrap_or(Instant::START);
+        let Some(rewind) = self.rewind.as_mut() else {
+            return Ok(false);
+        };
+        let Some((_, state)) = rewind.rewind_to(target)? else {
+            return Ok(false);
+        };
+        self.load_state(&state)?;
+        Ok(true)
+    }
+
+    fn capture_rewind(&mut self) -> Result<(), Error> {
+        if !self
+            .rewind
+            .as_ref()
+            .is_some_and(|rewind| rewind.is_due(self.clock))
+        {
+            return Ok(());
+        }
+        let state = self.save_state()?;
+        if let Some(rewind) = self.rewind.as_mut() {
+            rewind.push(self.clock, state);
+        }
+        Ok(())
+    }
+
+    pub fn pressed_keys(&self) -> Vec<KeyboardEventKey> {
+        self.components
+            .values()
+            .filter_map(|component| {
+                let mut component = component.try_borrow_mut().ok()?;
+                Some(component.as_keypad()?.pressed_keys())
+            })
+            .flatten()
+            .collect()
+    }
+
     pub fn get_current_clock(&self) -> Instant {
         self.clock
     }
 
+    // Emulated time since the machine was powered on, independent of the wall clock
+    pub fn get_elapsed_time(&self) -> Duration {
+        self.clock.as_duration()
+    }
+
+    // Frames emitted since the machine was powered on, also counting frames with rendering skipped
+    pub fn get_frame_count(&self) -> u64 {
+        self.frame_count.get()
+    }
+
+    #[cfg(any(test, feature = "testing"))]
+    pub(crate) fn set_clock(&mut self, clock: Instant) {
+        self.clock = clock;
+    }
+
+    pub fn scheduler_snapshot(&self) -> SchedulerSnapshot {
+        let mut events: Vec<&SchedulerEvent> = self.scheduler_queue.iter().collect();
+        events.sort_by(|a, b| b.cmp(a));
+        let wakeups = events
+            .into_iter()
+            .map(|event| ScheduledWakeup {
+                name: event.name.clone(),
+                clock: event.clock_cycle,
+            })
+            .collect();
+        SchedulerSnapshot {
+            clock: self.clock,
+            wakeups,
+        }
+    }
+
+    // Components producing video output should skip expensive rendering while this is false,
+    // but keep running their logic. Used by frontends to skip frames when falling behind.
+    pub fn is_render_enabled(&self) -> bool {
+        self.render_enabled
+    }
+
+    pub fn set_render_enabled(&mut self, render_enabled: bool) {
+        self.render_enabled = render_enabled;
+    }
+
+    // Limits how often the component is stepped within one run, None removes the limit. A component
+    // reaching it is deferred to the end of the run.
+    pub fn set_step_limit(&mut self, name: &str, limit: Option<usize>) {
+        self.fairness.set_step_limit(name, limit);
+    }
+
+    pub fn get_step_limit(&self, name: &str) -> Option<usize> {
+        self.fairness.step_limit(name)
+    }
+
+    // Violations of the scheduling limits since the last call
+    pub fn take_scheduler_warnings(&mut self) -> Vec<SchedulerWarning> {
+        self.fairness.take_warnings()
+    }
+
+    // Called by components while stepping
+    pub fn signal(&self, event: ExecutionEvent) {
+        if event == ExecutionEvent::Frame {
+            self.frame_count.set(self.frame_count.get() + 1);
+        }
+        let mut signalled = self.signalled.take();
+        signalled.push(event);
+        self.signalled.set(signalled);
+    }
+
+    // A component of the same name is removed first, so its mount does not count as an overlap
     pub fn add_addressable_component(
         &mut self,
         name: &str,
         address: MemoryAddress,
         component: Component,
-    ) {
-        self.bus.borrow_mut().insert(address, component.clone());
+    ) -> Result<(), Error> {
+        self.add_addressable_component_to(CPU_BUS, name, address, component)
+    }
+
+    pub fn add_addressable_component_to(
+        &mut self,
+        bus_name: &str,
+        name: &str,
+        address: MemoryAddress,
+        component: Component,
+    ) -> Result<(), Error> {
+        let bus = self
+            .buses
+            .get(bus_name)
+            .cloned()
+            .ok_or_else(|| Error::new(format!("no bus named {}", bus_name)))?;
+        if self.components.contains_key(name) {
+            self.remove_component(name)?;
+        }
+        bus.borrow_mut().insert(address, component.clone())?;
         self.add_component(name, component);
+        Ok(())
     }
 
+    pub fn mount_component_region(
+        &mut self,
+        name: &str,
+        address: MemoryAddress,
+        offset: MemoryAddress,
+        size: MemorySize,
+    ) -> Result<(), Error> {
+        let component = self.get_component(name)?;
+        self.bus
+            .borrow_mut()
+            .insert_region(address, component, offset, size)
+    }
+
+    // Mounts the component behind the mapper, both have to be added to the backend already
+    pub fn mount_mapped_component(
+        &mut self,
+        name: &str,
+        address: MemoryAddress,
+        size: MemorySize,
+        mapper_name: &str,
+    ) -> Result<(), Error> {
+        let component = self.get_component(name)?;
+        let mapper = self.get_component(mapper_name)?;
+        self.bus
+            .borrow_mut()
+            .insert_mapped(address, size, component, mapper)
+    }
+
+    // Components can also be added while the machine runs, they are first stepped at the current
+    // clock. A component of the same name is removed first, which swaps it.
     pub fn add_component(&mut self, name: &str, component: Component) {
-        self.try_queue_component(component.clone());
+        if self.components.contains_key(name) {
+            let _ = self.remove_component(name);
+        }
+        self.try_queue_component(name, component.clone());
         self.components.insert(name.to_string(), component);
     }
 
+    // Takes the component out of the machine, e.g. to unplug a cartridge. It is unscheduled and
+    // unmounted from all buses, its breakpoints are dropped. Rewind snapshots contain the component,
+    // so they are cleared like on a reset.
+    pub fn remove_component(&mut self, name: &str) -> Result<Component, Error> {
+        let component = self
+            .components
+            .remove(name)
+            .ok_or_else(|| Error::new(format!("no component named {}", name)))?;
+        self.scheduler_queue.retain(|event| event.name != name);
+        for bus in self.buses.values() {
+            bus.borrow_mut().remove_component(&component);
+        }
+        self.breakpoints.borrow_mut().remove_component(name);
+        self.fairness.remove_component(name);
+        if self.failed_component.as_deref() == Some(name) {
+            self.failed_component = None;
+        }
+        if let Some(rewind) = self.rewind.as_mut() {
+            rewind.clear();
+        }
+        Ok(component)
+    }
+
+    // The component the next call to step runs
+    pub fn next_component(&self) -> Option<Component> {
+        self.scheduler_queue
+            .peek()
+            .map(|event| event.component.clone())
+    }
+
+    // The failed step stays scheduled at the clock of the failure, so it is the next one again
+    pub fn failed_component(&self) -> Option<&str> {
+        self.failed_component.as_deref()
+    }
+
+    // When the next call to step runs
+    pub fn next_clock(&self) -> Option<Instant> {
+        self.scheduler_queue.peek().map(|event| event.clock_cycle)
+    }
+
     pub fn step(&mut self) -> Result<(), Error> {
-        let mut next_event = self.scheduler_queue.pop().unwrap();
+        self.signalled.take();
+        let Some(mut next_event) = self.scheduler_queue.pop() else {
+            return Err(Error::new("no component is scheduled to step"));
+        };
         self.clock = next_event.clock_cycle;
 
-        let result = match next_event
+        std::mem::swap(&mut self.stepping, &mut next_event.name);
+        let result = next_event
             .component
             .borrow_mut()
             .as_steppable()
             .unwrap()
-            .step(self)
-        {
+            .step(self);
+        std::mem::swap(&mut self.stepping, &mut next_event.name);
+        let result = match result {
+            // A cpu which stopped at a breakpoint runs the instruction at the same clock, once the
+            // run continues
+            Ok(Duration::ZERO) if self.has_breakpoint_events() => Ok(()),
             Ok(next_event_in) => {
-                next_event.clock_cycle = self.clock.checked_add(next_event_in).unwrap();
+                next_event.clock_cycle =
+                    self.fairness
+                        .next_clock(&next_event.name, self.clock, next_event_in);
                 Ok(())
             }
-            Err(err) => Err(err),
+            Err(err) => {
+                self.failed_component = Some(next_event.name.clone());
+                Err(err)
+            }
         };
         self.queue_event(next_event);
-        result
+        self.restore_frozen_memory()?;
+        self.send_debug_port_lines();
+        result?;
+        self.capture_rewind()
     }
 
     pub fn run_until(&mut self, clock: Instant) -> Result<(), Error> {
-        while self.clock < clock {
-            self.step()?;
+        self.fairness.start_run(clock);
+        let result = (|| {
+            while self.clock < clock && !self.is_stopped() {
+                self.step()?;
+                self.check_triggers();
+            }
+            Ok(())
+        })();
+        self.fairness.end_run();
+        result
+    }
+
+    // Scales the emulated time covered by wall clock time, e.g. 0.25 for slow motion
+    pub fn set_speed(&mut self, speed: f64) -> Result<(), Error> {
+        if !SPEED_RANGE.contains(&speed) {
+            return Err(Error::new(format!(
+                "speed {} is outside of {:?}",
+                speed, SPEED_RANGE
+            )));
         }
+        self.speed = speed;
         Ok(())
     }
 
+    pub fn get_speed(&self) -> f64 {
+        self.speed
+    }
+
+    // Emulated time passing in the wall clock duration at the current speed. Normal speed keeps
+    // the exact duration, which nanoseconds would round.
+    pub fn emulated_duration(&self, wall_clock: Duration) -> Duration {
+        if self.speed == 1.0 {
+            return wall_clock;
+        }
+        Duration::from_nanos((wall_clock.as_nanos() as f64 * self.speed) as u64)
+    }
+
+    // Limits the emulated time of a single run, so a stalled host, e.g. while dragging the window,
+    // is not caught up in one long run. None runs the whole duration, which is the default.
+    pub fn set_max_catch_up(&mut self, limit: Option<(Duration, CatchUpPolicy)>) {
+        self.max_catch_up = limit;
+        self.catch_up_backlog = Duration::ZERO;
+    }
+
+    pub fn get_max_catch_up(&self) -> Option<(Duration, CatchUpPolicy)> {
+        self.max_catch_up
+    }
+
+    // Emulated time the next run should cover for the wall clock duration, with the speed and
+    // the catch-up limit applied
+    pub fn catch_up_duration(&mut self, wall_clock: Duration) -> Duration {
+        let duration = self.emulated_duration(wall_clock) + self.catch_up_backlog;
+        self.catch_up_backlog = Duration::ZERO;
+        let Some((limit, policy)) = self.max_catch_up else {
+            return duration;
+        };
+        if duration <= limit {
+            return duration;
+        }
+        if policy == CatchUpPolicy::Spread {
+            self.catch_up_backlog = duration - limit;
+        }
+        limit
+    }
+
+    // Runs for the wall clock duration, which covers more or less emulated time with the speed
     pub fn run_for(&mut self, duration: Duration) -> Result<(), Error> {
-        let clock = self.clock + duration;
+        let clock = self.clock + self.catch_up_duration(duration);
         self.run_until(clock)
     }
 
-    fn try_queue_component(&mut self, component: Component) {
+    // Runs until a component signals the event, stopping right after the step that signalled it.
+    // Returns false when the clock or a trigger stopped the run first.
+    pub fn run_to(&mut self, event: ExecutionEvent, clock: Instant) -> Result<bool, Error> {
+        self.fairness.start_run(clock);
+        let result = (|| {
+            while self.clock < clock && !self.is_stopped() {
+                self.step()?;
+                self.check_triggers();
+                if self.signalled.take().contains(&event) {
+                    return Ok(true);
+                }
+            }
+            Ok(false)
+        })();
+        self.fairness.end_run();
+        result
+    }
+
+    // Paused, or a trigger fired or a breakpoint was hit, the run ends until the events are taken
+    fn is_stopped(&self) -> bool {
+        self.paused || self.triggers.has_events() || self.has_breakpoint_events()
+    }
+
+    fn check_triggers(&mut self) {
+        if !self.triggers.is_empty() {
+            let bus = self.bus.borrow();
+            self.triggers
+                .check(self.clock, |address| peek_u8(&bus, address));
+        }
+    }
+
+    fn restore_frozen_memory(&self) -> Result<(), Error> {
+        if !self.frozen_memory_installed {
+            return Ok(());
+        }
+        let dirty = self.frozen_memory.borrow_mut().take_dirty();
+        let bus = self.bus.borrow();
+        for (address, value) in dirty {
+            bus.poke(address, &[value])?;
+        }
+        Ok(())
+    }
+
+    fn try_queue_component(&mut self, name: &str, component: Component) {
+        self.added_components += 1;
         if component.borrow_mut().as_steppable().is_some() {
-            self.queue_event(SchedulerEvent::new(component));
+            let event = SchedulerEvent::new(name, component, self.clock, self.added_components);
+            self.queue_event(event);
         }
     }
 
@@ -113,16 +1002,38 @@ impl Backend {
     }
 }
 
+// The clock, the frame count and the rng, which start every payload of save_state
+fn write_state_header(writer: &mut StateWriter, clock: Instant, frame_count: u64, rng: &Rng) {
+    writer.write_instant(clock);
+    writer.write_u64(frame_count);
+    writer.write_u64(rng.seed());
+    for value in rng.state() {
+        writer.write_u64(value);
+    }
+}
+
+fn peek_u8(bus: &Bus, address: MemoryAddress) -> Option<u8> {
+    let mut buffer = [0u8; 1];
+    bus.peek(address, &mut buffer).ok()?;
+    Some(buffer[0])
+}
+
 #[derive(PartialEq, Eq)]
 struct SchedulerEvent {
     clock_cycle: Instant,
+    // Components due at the same clock step in the order they were added to the backend, which
+    // does not depend on the history of the queue and so also holds after loading a state
+    order: u64,
+    name: String,
     component: Component,
 }
 
 impl SchedulerEvent {
-    fn new(component: Component) -> Self {
+    fn new(name: &str, component: Component, clock_cycle: Instant, order: u64) -> Self {
         Self {
-            clock_cycle: Instant::START,
+            clock_cycle,
+            order,
+            name: name.to_string(),
             component,
         }
     }
@@ -131,7 +1042,7 @@ impl SchedulerEvent {
 // We flip the ordering on ScheduleEvent, such that scheduler_queue will be a min_heap
 impl Ord for SchedulerEvent {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        other.clock_cycle.cmp(&self.clock_cycle)
+        (other.clock_cycle, other.order).cmp(&(self.clock_cycle, self.order))
     }
 }
 
@@ -140,3 +1051,152 @@ impl PartialOrd for SchedulerEvent {
         Some(self.cmp(other))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use component::{Steppable, Transmutable};
+
+    struct Recorder {
+        name: &'static str,
+        steps: Rc<RefCell<Vec<&'static str>>>,
+    }
+
+    impl Steppable for Recorder {
+        fn step(&mut self, _backend: &Backend) -> Result<Duration, Error> {
+            self.steps.borrow_mut().push(self.name);
+            Ok(Duration::from_micros(10))
+        }
+    }
+
+    impl Transmutable for Recorder {
+        fn as_steppable(&mut self) -> Option<&mut dyn Steppable> {
+            Some(self)
+        }
+    }
+
+    #[test]
+    fn catch_up_limit_drops_or_spreads_the_excess() {
+        let mut backend = Backend::default();
+        let limit = Duration::from_millis(100);
+        assert_eq!(
+            backend.catch_up_duration(Duration::from_millis(250)),
+            Duration::from_millis(250)
+        );
+
+        backend.set_max_catch_up(Some((limit, CatchUpPolicy::Drop)));
+        assert_eq!(backend.catch_up_duration(Duration::from_millis(250)), limit);
+        assert_eq!(
+            backend.catch_up_duration(Duration::from_millis(16)),
+            Duration::from_millis(16)
+        );
+
+        backend.set_max_catch_up(Some((limit, CatchUpPolicy::Spread)));
+        assert_eq!(backend.catch_up_duration(Duration::from_millis(250)), limit);
+        assert_eq!(backend.catch_up_duration(Duration::from_millis(20)), limit);
+        assert_eq!(
+            backend.catch_up_duration(Duration::from_millis(20)),
+            Duration::from_millis(90)
+        );
+    }
+
+    #[test]
+    fn simultaneous_steps_follow_the_order_components_were_added() {
+        let steps = Rc::new(RefCell::new(vec![]));
+        let mut backend = Backend::default();
+        for name in ["video", "cpu", "audio"] {
+            let recorder = Recorder {
+                name,
+                steps: steps.clone(),
+            };
+            backend.add_component(name, Component::new(recorder));
+        }
+        let state = backend.save_state().unwrap();
+        backend.run_for(Duration::from_micros(30)).unwrap();
+        // The run ends with the first step at or past its end
+        let mut expected = ["video", "cpu", "audio"].repeat(3);
+        expected.push("video");
+        assert_eq!(*steps.borrow(), expected);
+        let names: Vec<String> = backend
+            .scheduler_snapshot()
+            .wakeups
+            .into_iter()
+            .map(|wakeup| wakeup.name)
+            .collect();
+        assert_eq!(names, ["cpu", "audio", "video"]);
+
+        steps.borrow_mut().clear();
+        backend.load_state(&state).unwrap();
+        backend.run_for(Duration::from_micros(30)).unwrap();
+        assert_eq!(*steps.borrow(), expected);
+    }
+
+    #[test]
+    fn running_without_components_fails() {
+        let mut backend = Backend::default();
+        let recorder = Recorder {
+            name: "cpu",
+            steps: Rc::new(RefCell::new(vec![])),
+        };
+        backend.add_component("cpu", Component::new(recorder));
+        backend.remove_component("cpu").unwrap();
+        assert!(backend.run_for(Duration::from_millis(1)).is_err());
+        assert_eq!(backend.get_current_clock(), Instant::START);
+    }
+
+    #[test]
+    fn states_keep_the_rtc() {
+        let mut backend = Backend::default();
+        let recorder = Recorder {
+            name: "cpu",
+            steps: Rc::new(RefCell::new(vec![])),
+        };
+        backend.add_component("cpu", Component::new(recorder));
+        backend.set_rtc_policy(RtcPolicy::Fixed(1000));
+        backend.run_for(Duration::from_secs(2)).unwrap();
+        let state = backend.save_state().unwrap();
+        backend.reset().unwrap();
+        assert_eq!(backend.get_rtc_time(), RtcTime::from_secs(1002));
+
+        backend.set_rtc_policy(RtcPolicy::Host);
+        backend.load_state(&state).unwrap();
+        assert_eq!(backend.get_rtc_policy(), RtcPolicy::Fixed(1000));
+        assert_eq!(backend.get_rtc_time(), RtcTime::from_secs(1002));
+
+        // States of before the rtc was saved follow the host
+        let mut old_state = state.clone();
+        old_state.drain(rtc::rtc_state_range());
+        let state = rtc::migrate_payload_without_rtc(old_state).unwrap();
+        backend.load_state(&state).unwrap();
+        assert_eq!(backend.get_rtc_policy(), RtcPolicy::Host);
+    }
+
+    #[test]
+    fn components_can_be_added_to_other_buses() {
+        let mut backend = Backend::default();
+        let ports = Component::new(memory::MemoryBlock::from(vec![7; 4]));
+        assert!(
+            backend
+                .add_addressable_component_to("io", "ports", 0, ports.clone())
+                .is_err()
+        );
+        backend.add_bus("io").unwrap();
+        assert!(backend.add_bus(CPU_BUS).is_err());
+        assert_eq!(backend.bus_names(), vec!["cpu", "io"]);
+
+        backend
+            .add_addressable_component_to("io", "ports", 0, ports)
+            .unwrap();
+        assert_eq!(backend.get_named_bus("io").unwrap().read_u8(2).unwrap(), 7);
+        assert!(backend.get_bus().peek(2, &mut [0]).is_err());
+
+        backend.remove_component("ports").unwrap();
+        assert!(
+            backend
+                .get_named_bus("io")
+                .unwrap()
+                .peek(2, &mut [0])
+                .is_err()
+        );
+    }
+}
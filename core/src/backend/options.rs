@@ -0,0 +1,358 @@
+use std::collections::BTreeMap;
+
+use crate::{error::Error, frontend::graphics::Pixel};
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OptionValue {
+    Bool(bool),
+    Int(i64),
+    Enum(String),
+    Color(Pixel),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OptionKind {
+    Bool,
+    Int { min: i64, max: i64 },
+    Enum { variants: Vec<&'static str> },
+    Color,
+    // A bank of eight switches read by the machine as one byte, stored as OptionValue::Int
+    DipSwitches { settings: Vec<DipSetting> },
+}
+
+// One setting on a bank of DIP switches, like the number of lives. Each choice is the value of the
+// switches under the mask, already shifted into place.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DipSetting {
+    pub label: &'static str,
+    pub mask: u8,
+    pub choices: Vec<(&'static str, u8)>,
+}
+
+impl DipSetting {
+    pub fn new(label: &'static str, mask: u8) -> Self {
+        Self {
+            label,
+            mask,
+            choices: vec![],
+        }
+    }
+
+    pub fn choice(mut self, label: &'static str, value: u8) -> Self {
+        self.choices.push((label, value & self.mask));
+        self
+    }
+
+    // None if the switches are in a position the manual does not describe
+    pub fn selected(&self, bank: u8) -> Option<&'static str> {
+        self.choices
+            .iter()
+            .find(|(_, value)| bank & self.mask == *value)
+            .map(|(label, _)| *label)
+    }
+
+    pub fn select(&self, bank: u8, value: u8) -> u8 {
+        (bank & !self.mask) | (value & self.mask)
+    }
+}
+
+// Values for several color options which are picked together, e.g. a high contrast palette
+#[derive(Debug, Clone, PartialEq)]
+pub struct PalettePreset {
+    pub label: &'static str,
+    pub colors: Vec<(&'static str, Pixel)>,
+}
+
+impl PalettePreset {
+    pub fn apply(&self, values: &mut OptionValues) {
+        for (key, color) in &self.colors {
+            values.set(key, OptionValue::Color(*color));
+        }
+    }
+
+    pub fn is_applied(&self, values: &OptionValues) -> bool {
+        self.colors
+            .iter()
+            .all(|(key, color)| values.get_color(key) == Some(*color))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OptionDescriptor {
+    pub key: &'static str,
+    pub label: &'static str,
+    pub kind: OptionKind,
+    pub default: OptionValue,
+}
+
+impl OptionDescriptor {
+    pub fn accepts(&self, value: &OptionValue) -> bool {
+        match (&self.kind, value) {
+            (OptionKind::Bool, OptionValue::Bool(_)) => true,
+            (OptionKind::Int { min, max }, OptionValue::Int(v)) => min <= v && v <= max,
+            (OptionKind::Enum { variants }, OptionValue::Enum(v)) => variants.contains(&v.as_str()),
+            (OptionKind::Color, OptionValue::Color(_)) => true,
+            (OptionKind::DipSwitches { .. }, OptionValue::Int(v)) => u8::try_from(*v).is_ok(),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OptionsSchema {
+    options: Vec<OptionDescriptor>,
+    palettes: Vec<PalettePreset>,
+}
+
+impl OptionsSchema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn bool(mut self, key: &'static str, label: &'static str, default: bool) -> Self {
+        self.options.push(OptionDescriptor {
+            key,
+            label,
+            kind: OptionKind::Bool,
+            default: OptionValue::Bool(default),
+        });
+        self
+    }
+
+    pub fn int(
+        mut self,
+        key: &'static str,
+        label: &'static str,
+        min: i64,
+        max: i64,
+        default: i64,
+    ) -> Self {
+        self.options.push(OptionDescriptor {
+            key,
+            label,
+            kind: OptionKind::Int { min, max },
+            default: OptionValue::Int(default),
+        });
+        self
+    }
+
+    pub fn enumeration(
+        mut self,
+        key: &'static str,
+        label: &'static str,
+        variants: &[&'static str],
+        default: &'static str,
+    ) -> Self {
+        self.options.push(OptionDescriptor {
+            key,
+            label,
+            kind: OptionKind::Enum {
+                variants: variants.to_vec(),
+            },
+            default: OptionValue::Enum(default.to_string()),
+        });
+        self
+    }
+
+    pub fn color(mut self, key: &'static str, label: &'static str, default: Pixel) -> Self {
+        self.options.push(OptionDescriptor {
+            key,
+            label,
+            kind: OptionKind::Color,
+            default: OptionValue::Color(default),
+        });
+        self
+    }
+
+    // Like on the real board, changed switches are only read when the machine starts
+    pub fn dip_switches(
+        mut self,
+        key: &'static str,
+        label: &'static str,
+        settings: Vec<DipSetting>,
+        default: u8,
+    ) -> Self {
+        self.options.push(OptionDescriptor {
+            key,
+            label,
+            kind: OptionKind::DipSwitches { settings },
+            default: OptionValue::Int(default as i64),
+        });
+        self
+    }
+
+    // The colors have to belong to color options of the schema
+    pub fn palette(mut self, label: &'static str, colors: &[(&'static str, Pixel)]) -> Self {
+        self.palettes.push(PalettePreset {
+            label,
+            colors: colors.to_vec(),
+        });
+        self
+    }
+
+    pub fn options(&self) -> &[OptionDescriptor] {
+        &self.options
+    }
+
+    pub fn palettes(&self) -> &[PalettePreset] {
+        &self.palettes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.options.is_empty()
+    }
+
+    pub fn defaults(&self) -> OptionValues {
+        let mut values = OptionValues::default();
+        for option in &self.options {
+            values.set(option.key, option.default.clone());
+        }
+        values
+    }
+
+    // Starts from the defaults and takes over every given value that is valid for this schema,
+    // so stale or foreign values never reach a backend.
+    pub fn resolve(&self, values: &OptionValues) -> OptionValues {
+        let mut result = self.defaults();
+        for option in &self.options {
+            if let Some(value) = values.get(option.key) {
+                if option.accepts(value) {
+                    result.set(option.key, value.clone());
+                }
+            }
+        }
+        result
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OptionValues(BTreeMap<String, OptionValue>);
+
+impl OptionValues {
+    pub fn set(&mut self, key: &str, value: OptionValue) {
+        self.0.insert(key.to_string(), value);
+    }
+
+    pub fn get(&self, key: &str) -> Option<&OptionValue> {
+        self.0.get(key)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut OptionValue> {
+        self.0.get_mut(key)
+    }
+
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        match self.get(key) {
+            Some(OptionValue::Bool(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_int(&self, key: &str) -> Option<i64> {
+        match self.get(key) {
+            Some(OptionValue::Int(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_enum(&self, key: &str) -> Option<&str> {
+        match self.get(key) {
+            Some(OptionValue::Enum(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_color(&self, key: &str) -> Option<Pixel> {
+        match self.get(key) {
+            Some(OptionValue::Color(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_dip_switches(&self, key: &str) -> Option<u8> {
+        self.get_int(key).and_then(|value| u8::try_from(value).ok())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &OptionValue)> {
+        self.0.iter()
+    }
+
+    // Takes over every value of the other, keeping the ones it does not have
+    pub fn apply(&mut self, other: &OptionValues) {
+        for (key, value) in other.iter() {
+            self.set(key, value.clone());
+        }
+    }
+}
+
+// The values an analysis suggests for a rom, with what it found out on the way
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OptionSuggestion {
+    pub values: OptionValues,
+    pub notes: Vec<String>,
+}
+
+// Tries the rom headless under different options to guess the ones it was written for, e.g. the
+// quirks of an unlabeled chip8 rom
+#[derive(Debug, Clone, Copy)]
+pub struct OptionsAnalysis {
+    pub label: &'static str,
+    pub run: fn(&[u8], OptionValues) -> Result<OptionSuggestion, Error>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lives() -> DipSetting {
+        DipSetting::new("Lives", 0x03)
+            .choice("3", 0x00)
+            .choice("4", 0x01)
+            .choice("5", 0x02)
+            .choice("6", 0x03)
+    }
+
+    #[test]
+    fn dip_settings_only_change_their_switches() {
+        let bonus = DipSetting::new("Bonus life", 0x08)
+            .choice("1500", 0x00)
+            .choice("1000", 0x08);
+        let bank = lives().select(0x08, 0x02);
+        assert_eq!(bank, 0x0A);
+        assert_eq!(lives().selected(bank), Some("5"));
+        assert_eq!(bonus.selected(bank), Some("1000"));
+        assert_eq!(bonus.selected(bonus.select(bank, 0x00)), Some("1500"));
+    }
+
+    #[test]
+    fn dip_switches_resolve_to_a_byte() {
+        let schema = OptionsSchema::new().dip_switches("dsw0", "DSW0", vec![lives()], 0x00);
+        let mut values = OptionValues::default();
+        values.set("dsw0", OptionValue::Int(0x100));
+        assert_eq!(schema.resolve(&values).get_dip_switches("dsw0"), Some(0x00));
+        values.set("dsw0", OptionValue::Int(0x81));
+        assert_eq!(schema.resolve(&values).get_dip_switches("dsw0"), Some(0x81));
+    }
+
+    #[test]
+    fn palette_sets_all_its_colors() {
+        let schema = OptionsSchema::new()
+            .color("foreground", "Foreground", (255, 255, 255, 255))
+            .color("background", "Background", (0, 0, 0, 255))
+            .palette(
+                "Inverted",
+                &[
+                    ("foreground", (0, 0, 0, 255)),
+                    ("background", (255, 255, 255, 255)),
+                ],
+            );
+        let mut values = schema.defaults();
+        let inverted = &schema.palettes()[0];
+        assert!(!inverted.is_applied(&values));
+        inverted.apply(&mut values);
+        assert!(inverted.is_applied(&values));
+        assert_eq!(values.get_color("background"), Some((255, 255, 255, 255)));
+    }
+}
@@ -0,0 +1,153 @@
+use crate::{
+    backend::{
+        Backend,
+        firmware::{FirmwareDescriptor, FirmwareFiles, verify_firmware},
+        options::{OptionValues, OptionsAnalysis, OptionsSchema},
+        rtc::RtcPolicy,
+        selftest::SelfTest,
+        snapshot::{SnapshotHeader, SnapshotMigration, upgrade_snapshot},
+    },
+    error::Error,
+    frontend::Frontend,
+    rom::{
+        Detection, DetectionConfidence, KnownDump, Rom, RomDetector, RomHeaderCheck, RomIntegrity,
+        check_integrity,
+    },
+};
+
+pub type BackendConstructor =
+    fn(&mut dyn Frontend<Error = Error>, BackendOptions) -> Result<Backend, Error>;
+
+#[derive(Debug, Clone, Default)]
+pub struct BackendOptions {
+    pub rom_data: Vec<u8>,
+    pub options: OptionValues,
+    pub firmware: FirmwareFiles,
+    pub rng_seed: Option<u64>,
+    pub rtc_policy: Option<RtcPolicy>,
+}
+
+#[derive(Clone)]
+pub struct BackendDescriptor {
+    pub name: &'static str,
+    pub extensions: &'static [&'static str],
+    pub detect: Option<RomDetector>,
+    // Good and bad dumps of the roms the backend knows, may be empty
+    pub known_dumps: &'static [KnownDump],
+    pub header_check: Option<RomHeaderCheck>,
+    pub options: fn() -> OptionsSchema,
+    pub firmware: &'static [FirmwareDescriptor],
+    // Has to be increased whenever the snapshot payload format of the backend changes
+    pub snapshot_version: u32,
+    pub snapshot_migration: Option<SnapshotMigration>,
+    pub self_test: Option<SelfTest>,
+    pub options_analysis: Option<OptionsAnalysis>,
+    pub constructor: BackendConstructor,
+}
+
+impl BackendDescriptor {
+    pub fn create(
+        &self,
+        frontend: &mut dyn Frontend<Error = Error>,
+        mut options: BackendOptions,
+    ) -> Result<Backend, Error> {
+        options.options = (self.options)().resolve(&options.options);
+        verify_firmware(self.firmware, &options.firmware)?;
+        let (rng_seed, rtc_policy) = (options.rng_seed, options.rtc_policy);
+        let mut backend = (self.constructor)(frontend, options)?;
+        if let Some(seed) = rng_seed {
+            backend.set_rng_seed(seed);
+        }
+        if let Some(policy) = rtc_policy {
+            backend.set_rtc_policy(policy);
+        }
+        Ok(backend)
+    }
+
+    pub fn snapshot_header(&self, rom_hash: &str) -> SnapshotHeader {
+        SnapshotHeader::new(self.name, self.snapshot_version, rom_hash)
+    }
+
+    pub fn upgrade_snapshot(&self, data: &[u8], rom_hash: &str) -> Result<Vec<u8>, Error> {
+        upgrade_snapshot(
+            data,
+            self.name,
+            self.snapshot_version,
+            rom_hash,
+            self.snapshot_migration,
+        )
+    }
+
+    pub fn check_integrity(&self, rom: &Rom) -> RomIntegrity {
+        check_integrity(rom, self.known_dumps, self.header_check)
+    }
+
+    pub fn supports_extension(&self, extension: &str) -> bool {
+        self.extensions
+            .iter()
+            .any(|e| e.eq_ignore_ascii_case(extension))
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct BackendRegistry {
+    descriptors: Vec<BackendDescriptor>,
+}
+
+impl BackendRegistry {
+    pub fn register(&mut self, descriptor: BackendDescriptor) {
+        self.descriptors.retain(|d| d.name != descriptor.name);
+        self.descriptors.push(descriptor);
+    }
+
+    pub fn get(&self, name: &str) -> Result<&BackendDescriptor, Error> {
+        self.descriptors
+            .iter()
+            .find(|d| d.name == name)
+            .ok_or_else(|| Error::new(format!("no backend named {}", name)))
+    }
+
+    pub fn descriptors(&self) -> &[BackendDescriptor] {
+        &self.descriptors
+    }
+
+    pub fn find_by_extension(&self, extension: &str) -> Vec<&BackendDescriptor> {
+        self.descriptors
+            .iter()
+            .filter(|d| d.supports_extension(extension))
+            .collect()
+    }
+
+    // Sorted by confidence, backends that can not run the rom at all are left out
+    pub fn detect(&self, rom: &Rom) -> Vec<Detection> {
+        let extension_matches = rom
+            .extension()
+            .map_or(vec![], |extension| self.find_by_extension(extension));
+        let extension_confidence = match extension_matches.len() {
+            1 => DetectionConfidence::Medium,
+            _ => DetectionConfidence::Low,
+        };
+
+        let mut detections: Vec<Detection> = self
+            .descriptors
+            .iter()
+            .map(|descriptor| {
+                let by_extension = if extension_matches.iter().any(|d| d.name == descriptor.name) {
+                    extension_confidence
+                } else {
+                    DetectionConfidence::None
+                };
+                let by_content = descriptor
+                    .detect
+                    .map_or(DetectionConfidence::None, |detect| detect(rom));
+                Detection {
+                    backend: descriptor.name,
+                    confidence: by_extension.max(by_content),
+                }
+            })
+            .filter(|detection| detection.confidence > DetectionConfidence::None)
+            .collect();
+        detections.sort_by(|a, b| b.confidence.cmp(&a.confidence));
+        detections
+    }
+}
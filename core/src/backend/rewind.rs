@@ -0,0 +1,363 @@
+use std::collections::VecDeque;
+
+use femtos::{Duration, Instant};
+
+use crate::error::Error;
+
+struct Snapshot {
+    clock: Instant,
+    // The newest snapshot is a full save state payload, the older ones are deltas against the
+    // snapshot taken after them
+    data: Vec<u8>,
+    // Length of the full payload
+    size: usize,
+}
+
+// How much memory the rewind history takes, e.g. to show it in the frontend
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RewindUsage {
+    pub snapshots: usize,
+    pub capacity: usize,
+    // Bytes the snapshots are stored in
+    pub stored_bytes: usize,
+    // Bytes they would take as full save states
+    pub full_bytes: usize,
+}
+
+// Snapshots of the recent past, taken while the backend runs. Successive states mostly differ in a
+// few bytes, so only the newest is kept in full and every older one as the bytes it differs in
+// from its successor. Rewinding discards the future anyway, so it walks back the chain from the
+// newest snapshot. The oldest ones are dropped once the capacity is reached.
+pub struct RewindBuffer {
+    interval: Duration,
+    capacity: usize,
+    snapshots: VecDeque<Snapshot>,
+    next_capture: Instant,
+    // Compresses the deltas with lz4 on top, which trades some time per capture for memory
+    #[cfg(feature = "compression")]
+    compression: bool,
+}
+
+impl RewindBuffer {
+    pub fn new(interval: Duration, capacity: usize) -> Self {
+        Self {
+            interval,
+            capacity: capacity.max(1),
+            snapshots: VecDeque::new(),
+            next_capture: Instant::START,
+            #[cfg(feature = "compression")]
+            compression: false,
+        }
+    }
+
+    #[cfg(feature = "compression")]
+    pub fn with_compression(mut self) -> Self {
+        self.compression = true;
+        self
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    pub fn usage(&self) -> RewindUsage {
+        RewindUsage {
+            snapshots: self.snapshots.len(),
+            capacity: self.capacity,
+            stored_bytes: self.snapshots.iter().map(|s| s.data.len()).sum(),
+            full_bytes: self.snapshots.iter().map(|s| s.size).sum(),
+        }
+    }
+
+    // Emulated time which can be rewound at most
+    pub fn available(&self, clock: Instant) -> Duration {
+        self.snapshots
+            .front()
+            .map_or(Duration::ZERO, |oldest| clock.duration_since(oldest.clock))
+    }
+
+    pub(crate) fn is_due(&self, clock: Instant) -> bool {
+        clock >= self.next_capture
+    }
+
+    pub(crate) fn push(&mut self, clock: Instant, state: Vec<u8>) {
+        if self.snapshots.len() >= self.capacity {
+            self.snapshots.pop_front();
+        }
+        let delta = self
+            .snapshots
+            .back()
+            .map(|newest| self.pack(encode_delta(&newest.data, &state)));
+        if let (Some(newest), Some(delta)) = (self.snapshots.back_mut(), delta) {
+            newest.data = delta;
+        }
+        self.snapshots.push_back(Snapshot {
+            clock,
+            size: state.len(),
+            data: state,
+        });
+        self.next_capture = clock + self.interval;
+    }
+
+    // The newest snapshot taken at or before the clock. Later snapshots belong to the future that
+    // is discarded by rewinding, so they are dropped. The oldest snapshot is returned when the
+    // clock lies before all of them.
+    pub(crate) fn rewind_to(
+        &mut self,
+        clock: Instant,
+    ) -> Result<Option<(Instant, Vec<u8>)>, Error> {
+        while self.snapshots.len() > 1
+            && self
+                .snapshots
+                .back()
+                .is_some_and(|newest| newest.clock > clock)
+        {
+            let Some(newest) = self.snapshots.pop_back() else {
+                break;
+            };
+            let Some(previous) = self.snapshots.back_mut() else {
+                break;
+            };
+            let delta = unpack(std::mem::take(&mut previous.data));
+            match delta.and_then(|delta| decode_delta(&delta, &newest.data)) {
+                Ok(state) => previous.data = state,
+                // The chain behind a broken delta cannot be rebuilt either
+                Err(error) => {
+                    self.clear();
+                    return Err(error);
+                }
+            }
+        }
+        let Some(newest) = self.snapshots.back() else {
+            return Ok(None);
+        };
+        self.next_capture = newest.clock + self.interval;
+        Ok(Some((newest.clock, newest.data.clone())))
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+        self.next_capture = Instant::START;
+    }
+
+    #[cfg(feature = "compression")]
+    fn pack(&self, delta: Vec<u8>) -> Vec<u8> {
+        if !self.compression {
+            return delta;
+        }
+        // A leading marker tells compressed deltas apart, so the setting may change at any time
+        let mut packed = vec![1];
+        packed.extend(lz4_flex::compress_prepend_size(&delta[1..]));
+        packed
+    }
+
+    #[cfg(not(feature = "compression"))]
+    fn pack(&self, delta: Vec<u8>) -> Vec<u8> {
+        delta
+    }
+}
+
+fn corrupt_delta() -> Error {
+    Error::new("rewind snapshot is corrupt")
+}
+
+#[cfg(feature = "compression")]
+fn unpack(data: Vec<u8>) -> Result<Vec<u8>, Error> {
+    if data.first() != Some(&1) {
+        return Ok(data);
+    }
+    let mut delta = vec![0];
+    delta.extend(lz4_flex::decompress_size_prepended(&data[1..]).map_err(|_| corrupt_delta())?);
+    Ok(delta)
+}
+
+#[cfg(not(feature = "compression"))]
+fn unpack(data: Vec<u8>) -> Result<Vec<u8>, Error> {
+    Ok(data)
+}
+
+fn write_number(output: &mut Vec<u8>, mut number: usize) {
+    while number >= 0x80 {
+        output.push(number as u8 | 0x80);
+        number >>= 7;
+    }
+    output.push(number as u8);
+}
+
+fn read_number(input: &[u8], position: &mut usize) -> Result<usize, Error> {
+    let mut number = 0usize;
+    let mut shift = 0;
+    loop {
+        let byte = *input.get(*position).ok_or_else(corrupt_delta)?;
+        *position += 1;
+        let bits = ((byte & 0x7F) as usize)
+            .checked_shl(shift)
+            .ok_or_else(corrupt_delta)?;
+        number |= bits;
+        if byte & 0x80 == 0 {
+            return Ok(number);
+        }
+        shift += 7;
+    }
+}
+
+// The state as runs of bytes which are equal in the newer one and literal runs which are not.
+// It starts with a marker byte, 0 for uncompressed deltas, and the length of the state.
+fn encode_delta(state: &[u8], newer: &[u8]) -> Vec<u8> {
+    let mut delta = vec![0];
+    write_number(&mut delta, state.len());
+    let mut position = 0;
+    while position < state.len() {
+        let equal = state[position..]
+            .iter()
+            .zip(newer.get(position..).unwrap_or_default())
+            .take_while(|(a, b)| a == b)
+            .count();
+        position += equal;
+        let literal = state[position..]
+            .iter()
+            .enumerate()
+            .take_while(|(offset, byte)| newer.get(position + offset) != Some(byte))
+            .count();
+        write_number(&mut delta, equal);
+        write_number(&mut delta, literal);
+        delta.extend_from_slice(&state[position..position + literal]);
+        position += literal;
+    }
+    delta
+}
+
+fn decode_delta(delta: &[u8], newer: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut position = 1;
+    let size = read_number(delta, &mut position)?;
+    let mut state = Vec::with_capacity(size.min(newer.len() + delta.len()));
+    while state.len() < size {
+        let equal = read_number(delta, &mut position)?;
+        let equal = state
+            .len()
+            .checked_add(equal)
+            .and_then(|end| newer.get(state.len()..end))
+            .ok_or_else(corrupt_delta)?;
+        state.extend_from_slice(equal);
+        let literal = read_number(delta, &mut position)?;
+        let literal = position
+            .checked_add(literal)
+            .and_then(|end| delta.get(position..end))
+            .ok_or_else(corrupt_delta)?;
+        state.extend_from_slice(literal);
+        position += literal.len();
+    }
+    Ok(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::at_ms;
+
+    use super::*;
+
+    #[test]
+    fn captures_once_per_interval() {
+        let mut buffer = RewindBuffer::new(Duration::from_millis(100), 10);
+        assert!(buffer.is_due(at_ms(0)));
+        buffer.push(at_ms(0), vec![0]);
+        assert!(!buffer.is_due(at_ms(99)));
+        assert!(buffer.is_due(at_ms(100)));
+    }
+
+    #[test]
+    fn drops_oldest_snapshot_when_full() {
+        let mut buffer = RewindBuffer::new(Duration::from_millis(100), 2);
+        for ms in [0, 100, 200] {
+            buffer.push(at_ms(ms), vec![ms as u8]);
+        }
+        assert_eq!(buffer.len(), 2);
+        assert_eq!(buffer.available(at_ms(250)), Duration::from_millis(150));
+    }
+
+    #[test]
+    fn rewinding_discards_the_future() {
+        let mut buffer = RewindBuffer::new(Duration::from_millis(100), 10);
+        for ms in [0, 100, 200, 300] {
+            buffer.push(at_ms(ms), vec![ms as u8]);
+        }
+        assert_eq!(
+            buffer.rewind_to(at_ms(150)).unwrap(),
+            Some((at_ms(100), vec![100]))
+        );
+        assert_eq!(buffer.len(), 2);
+        assert!(!buffer.is_due(at_ms(150)));
+        // Further back than the oldest snapshot
+        assert_eq!(
+            buffer.rewind_to(at_ms(0)).unwrap(),
+            Some((at_ms(0), vec![0]))
+        );
+        assert_eq!(buffer.len(), 1);
+    }
+
+    #[test]
+    fn older_snapshots_are_stored_as_deltas() {
+        let states: Vec<Vec<u8>> = (0..5u8)
+            .map(|step| {
+                let mut state = vec![0xAA; 1000];
+                state[10] = step;
+                // The state grows and shrinks, like one with a variable length part
+                state.resize(1000 + step as usize * (step as usize % 2), step);
+                state
+            })
+            .collect();
+        let mut buffer = RewindBuffer::new(Duration::from_millis(100), 10);
+        for (index, state) in states.iter().enumerate() {
+            buffer.push(at_ms(index as u64 * 100), state.clone());
+        }
+
+        let usage = buffer.usage();
+        assert_eq!(usage.snapshots, 5);
+        assert_eq!(usage.full_bytes, states.iter().map(Vec::len).sum::<usize>());
+        assert!(usage.stored_bytes < states[4].len() + 100);
+
+        for (index, state) in states.iter().enumerate().rev() {
+            let clock = at_ms(index as u64 * 100);
+            assert_eq!(
+                buffer.rewind_to(clock).unwrap(),
+                Some((clock, state.clone()))
+            );
+        }
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn compressed_deltas_rewind_to_the_same_states() {
+        let states: Vec<Vec<u8>> = (0..3u8)
+            .map(|step| (0..2000).map(|i| (i % 7) as u8 ^ step).collect())
+            .collect();
+        let mut buffer = RewindBuffer::new(Duration::from_millis(100), 10).with_compression();
+        for (index, state) in states.iter().enumerate() {
+            buffer.push(at_ms(index as u64 * 100), state.clone());
+        }
+        assert!(buffer.usage().stored_bytes < states[2].len() + 1000);
+        assert_eq!(
+            buffer.rewind_to(at_ms(0)).unwrap(),
+            Some((at_ms(0), states[0].clone()))
+        );
+    }
+
+    #[test]
+    fn corrupt_deltas_fail_to_rewind() {
+        let mut buffer = RewindBuffer::new(Duration::from_millis(100), 10);
+        buffer.push(at_ms(0), vec![1; 100]);
+        buffer.push(at_ms(100), vec![2; 100]);
+        let delta = &mut buffer.snapshots[0].data;
+        delta.truncate(delta.len() - 1);
+        assert!(buffer.rewind_to(at_ms(0)).is_err());
+        assert!(buffer.is_empty());
+    }
+}
@@ -0,0 +1,57 @@
+// xoshiro256** seeded through splitmix64, see https://prng.di.unimi.it/
+pub struct Rng {
+    seed: u64,
+    state: [u64; 4],
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Self::new(0x853C_49E6_748F_EA9B)
+    }
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        let mut splitmix = seed;
+        let mut state = [0; 4];
+        for s in state.iter_mut() {
+            splitmix = splitmix.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            let mut z = splitmix;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            *s = z ^ (z >> 31);
+        }
+        Self { seed, state }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn state(&self) -> [u64; 4] {
+        self.state
+    }
+
+    // Continues a sequence from a state taken earlier, e.g. when loading a save state
+    pub fn restore(seed: u64, state: [u64; 4]) -> Self {
+        Self { seed, state }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let result = self.state[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = self.state[1] << 17;
+
+        self.state[2] ^= self.state[0];
+        self.state[3] ^= self.state[1];
+        self.state[1] ^= self.state[2];
+        self.state[0] ^= self.state[3];
+        self.state[2] ^= t;
+        self.state[3] = self.state[3].rotate_left(45);
+
+        result
+    }
+
+    pub fn next_u8(&mut self) -> u8 {
+        (self.next_u64() >> 56) as u8
+    }
+}
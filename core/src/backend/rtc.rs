@@ -0,0 +1,177 @@
+use std::ops::Range;
+
+use femtos::Instant;
+use web_time::{SystemTime, UNIX_EPOCH};
+
+use super::rng::Rng;
+use super::snapshot::{StateReader, StateWriter};
+use super::write_state_header;
+use crate::error::Error;
+
+// Time since the unix epoch
+pub type RtcTime = std::time::Duration;
+
+// Where emulated real-time clocks, like the one of a cartridge, take the time of day from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RtcPolicy {
+    // The time of the host, whatever the emulation does
+    #[default]
+    Host,
+    // Starts at the unix time in seconds and advances with the emulated time, so runs with the
+    // same input always see the same time
+    Fixed(u64),
+    // Starts at the time of the host and advances with the emulated time. Save states keep the
+    // time of day they were taken at.
+    Anchored,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RtcProvider {
+    policy: RtcPolicy,
+    // Time of day at the start of the emulated clock, unused by the host policy
+    anchor: RtcTime,
+}
+
+impl Default for RtcProvider {
+    fn default() -> Self {
+        Self::new(RtcPolicy::default(), Instant::START)
+    }
+}
+
+impl RtcProvider {
+    // The policy starts at the given emulated clock
+    pub fn new(policy: RtcPolicy, clock: Instant) -> Self {
+        let start = match policy {
+            RtcPolicy::Host => return Self::host(),
+            RtcPolicy::Fixed(seconds) => RtcTime::from_secs(seconds),
+            RtcPolicy::Anchored => host_time(),
+        };
+        Self {
+            policy,
+            anchor: start.saturating_sub(emulated_time(clock)),
+        }
+    }
+
+    fn host() -> Self {
+        Self {
+            policy: RtcPolicy::Host,
+            anchor: RtcTime::ZERO,
+        }
+    }
+
+    pub fn policy(&self) -> RtcPolicy {
+        self.policy
+    }
+
+    pub fn now(&self, clock: Instant) -> RtcTime {
+        match self.policy {
+            RtcPolicy::Host => host_time(),
+            RtcPolicy::Fixed(_) | RtcPolicy::Anchored => self.anchor + emulated_time(clock),
+        }
+    }
+
+    // Keeps the time of day going when the emulated clock starts over, like a battery backed
+    // clock does through a power cycle
+    pub(crate) fn rebase(&mut self, clock: Instant) {
+        if self.policy != RtcPolicy::Host {
+            self.anchor += emulated_time(clock);
+        }
+    }
+
+    pub(crate) fn save_state(&self, writer: &mut StateWriter) {
+        let (tag, seconds) = match self.policy {
+            RtcPolicy::Host => (0, 0),
+            RtcPolicy::Fixed(seconds) => (1, seconds),
+            RtcPolicy::Anchored => (2, 0),
+        };
+        writer.write_u8(tag);
+        writer.write_u64(seconds);
+        writer.write_u64(self.anchor.as_secs());
+        writer.write_u32(self.anchor.subsec_nanos());
+    }
+
+    pub(crate) fn load_state(reader: &mut StateReader) -> Result<Self, Error> {
+        let tag = reader.read_u8()?;
+        let seconds = reader.read_u64()?;
+        let policy = match tag {
+            0 => RtcPolicy::Host,
+            1 => RtcPolicy::Fixed(seconds),
+            2 => RtcPolicy::Anchored,
+            _ => return Err(Error::new("state contains an unknown rtc policy")),
+        };
+        let anchor_seconds = reader.read_u64()?;
+        let anchor = RtcTime::new(anchor_seconds, reader.read_u32()?);
+        Ok(Self { policy, anchor })
+    }
+}
+
+// Brings a payload of Backend::save_state from before the rtc was saved to the current layout.
+// Backends use it in their snapshot migration, the states then follow the time of the host.
+pub fn migrate_payload_without_rtc(mut payload: Vec<u8>) -> Result<Vec<u8>, Error> {
+    let offset = rtc_state_range().start;
+    if payload.len() < offset {
+        return Err(Error::new("state is truncated"));
+    }
+    let mut writer = StateWriter::new();
+    RtcProvider::host().save_state(&mut writer);
+    payload.splice(offset..offset, writer.into_data());
+    Ok(payload)
+}
+
+// Where the rtc state lies in a payload of Backend::save_state, measured with the same writers
+pub fn rtc_state_range() -> Range<usize> {
+    let mut writer = StateWriter::new();
+    write_state_header(&mut writer, Instant::START, 0, &Rng::new(0));
+    let start = writer.len();
+    RtcProvider::host().save_state(&mut writer);
+    start..writer.len()
+}
+
+fn emulated_time(clock: Instant) -> RtcTime {
+    RtcTime::from_nanos(clock.as_duration().as_nanos())
+}
+
+fn host_time() -> RtcTime {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use femtos::Duration;
+
+    use super::*;
+
+    #[test]
+    fn fixed_time_follows_the_emulated_clock() {
+        let clock = Instant::START + Duration::from_secs(10);
+        let mut rtc = RtcProvider::new(RtcPolicy::Fixed(1000), clock);
+        assert_eq!(rtc.now(clock), RtcTime::from_secs(1000));
+        assert_eq!(
+            rtc.now(clock + Duration::from_millis(1500)),
+            RtcTime::from_millis(1_001_500)
+        );
+
+        rtc.rebase(clock);
+        assert_eq!(rtc.now(Instant::START), RtcTime::from_secs(1000));
+    }
+
+    #[test]
+    fn state_keeps_the_policy_and_the_time() {
+        let clock = Instant::START + Duration::from_secs(3);
+        for rtc in [
+            RtcProvider::default(),
+            RtcProvider::new(RtcPolicy::Fixed(1000), clock),
+            RtcProvider::new(RtcPolicy::Anchored, clock),
+        ] {
+            let mut writer = StateWriter::new();
+            rtc.save_state(&mut writer);
+            let data = writer.into_data();
+            let mut reader = StateReader::new(&data);
+            assert_eq!(RtcProvider::load_state(&mut reader).unwrap(), rtc);
+            reader.finish().unwrap();
+        }
+    }
+}
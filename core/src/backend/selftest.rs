@@ -0,0 +1,24 @@
+use crate::{backend::options::OptionValues, error::Error};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelfTestVerdict {
+    Pass,
+    Fail,
+    // The output could not be interpreted, e.g. because the test did not finish
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelfTestResult {
+    pub name: String,
+    pub verdict: SelfTestVerdict,
+}
+
+pub type SelfTestReport = Vec<SelfTestResult>;
+
+// A known test rom the backend runs headless with the given options and interprets the output of
+#[derive(Debug, Clone, Copy)]
+pub struct SelfTest {
+    pub label: &'static str,
+    pub run: fn(&[u8], OptionValues) -> Result<SelfTestReport, Error>,
+}
@@ -0,0 +1,494 @@
+use std::collections::BTreeMap;
+
+use femtos::{Duration, Femtos, Instant};
+
+use super::rtc::{RtcProvider, rtc_state_range};
+use crate::error::Error;
+
+const SNAPSHOT_MAGIC: &[u8; 4] = b"AXWS";
+pub const CORE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// Upgrades the payload of a snapshot from the given backend version to the next one
+pub type SnapshotMigration = fn(from_version: u32, payload: Vec<u8>) -> Result<Vec<u8>, Error>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotHeader {
+    pub backend: String,
+    pub backend_version: u32,
+    pub core_version: String,
+    pub rom_hash: String,
+}
+
+impl SnapshotHeader {
+    pub fn new(backend: &str, backend_version: u32, rom_hash: &str) -> Self {
+        Self {
+            backend: backend.to_string(),
+            backend_version,
+            core_version: CORE_VERSION.to_string(),
+            rom_hash: rom_hash.to_string(),
+        }
+    }
+
+    pub fn encode(&self, payload: &[u8]) -> Vec<u8> {
+        let mut result = SNAPSHOT_MAGIC.to_vec();
+        write_string(&mut result, &self.backend);
+        result.extend_from_slice(&self.backend_version.to_le_bytes());
+        write_string(&mut result, &self.core_version);
+        write_string(&mut result, &self.rom_hash);
+        result.extend_from_slice(payload);
+        result
+    }
+
+    pub fn decode(data: &[u8]) -> Result<(Self, &[u8]), Error> {
+        let data = data
+            .strip_prefix(SNAPSHOT_MAGIC)
+            .ok_or_else(|| Error::new("not a snapshot file"))?;
+        let (backend, data) = read_string(data)?;
+        let (backend_version, data) = read_u32(data)?;
+        let (core_version, data) = read_string(data)?;
+        let (rom_hash, payload) = read_string(data)?;
+        Ok((
+            Self {
+                backend,
+                backend_version,
+                core_version,
+                rom_hash,
+            },
+            payload,
+        ))
+    }
+}
+
+// Checks the header against the running backend and ROM and brings the payload up to the
+// current backend version, so backends only ever see payloads in their current format.
+pub fn upgrade_snapshot(
+    data: &[u8],
+    backend: &str,
+    backend_version: u32,
+    rom_hash: &str,
+    migration: Option<SnapshotMigration>,
+) -> Result<Vec<u8>, Error> {
+    let (header, payload) = SnapshotHeader::decode(data)?;
+    if header.backend != backend {
+        return Err(Error::new(format!(
+            "snapshot was created by backend {}, not {}",
+            header.backend, backend
+        )));
+    }
+    if header.rom_hash != rom_hash {
+        return Err(Error::new("snapshot was created for a different rom"));
+    }
+    if header.backend_version > backend_version {
+        return Err(Error::new(format!(
+            "snapshot has version {} but {} only supports up to version {} (created with core {})",
+            header.backend_version, backend, backend_version, header.core_version
+        )));
+    }
+
+    let mut payload = payload.to_vec();
+    for version in header.backend_version..backend_version {
+        let migration = migration.ok_or_else(|| {
+            Error::new(format!(
+                "snapshot has version {} and {} cannot upgrade it to version {}",
+                header.backend_version, backend, backend_version
+            ))
+        })?;
+        payload = migration(version, payload)?;
+    }
+    Ok(payload)
+}
+
+// Rewrites the state of one component in a payload of Backend::save_state, for the snapshot
+// migrations of backends. Payloads from before the rtc was saved lack its state.
+pub fn migrate_component_state(
+    payload: Vec<u8>,
+    with_rtc: bool,
+    component: &str,
+    migrate: impl FnOnce(&[u8]) -> Result<Vec<u8>, Error>,
+) -> Result<Vec<u8>, Error> {
+    let rtc = rtc_state_range();
+    let wakeups_offset = if with_rtc { rtc.end } else { rtc.start };
+    let mut reader = StateReader::new(
+        payload
+            .get(wakeups_offset..)
+            .ok_or_else(|| Error::new("state is truncated"))?,
+    );
+    for _ in 0..reader.read_u32()? {
+        reader.read_string()?;
+        reader.read_instant()?;
+    }
+    let components_offset = payload.len() - reader.data.len();
+
+    let mut writer = StateWriter::new();
+    let count = reader.read_u32()?;
+    writer.write_u32(count);
+    let mut migrate = Some(migrate);
+    for _ in 0..count {
+        let name = reader.read_string()?;
+        let state = reader.read_bytes()?;
+        writer.write_string(&name);
+        match migrate.take_if(|_| name == component) {
+            Some(migrate) => writer.write_bytes(&migrate(state)?),
+            None => writer.write_bytes(state),
+        }
+    }
+    reader.finish()?;
+    if migrate.is_some() {
+        return Err(Error::new(format!("state has no component {}", component)));
+    }
+    let mut migrated = payload[..components_offset].to_vec();
+    migrated.extend_from_slice(&writer.into_data());
+    Ok(migrated)
+}
+
+// A payload of Backend::save_state taken apart, with the component states still encoded
+pub(crate) struct StatePayload<'a> {
+    pub clock: Instant,
+    pub frame_count: u64,
+    pub rng_seed: u64,
+    pub rng_state: [u64; 4],
+    pub rtc: RtcProvider,
+    pub wakeups: BTreeMap<String, Instant>,
+    pub states: Vec<(String, &'a [u8])>,
+}
+
+impl<'a> StatePayload<'a> {
+    pub fn read(data: &'a [u8]) -> Result<Self, Error> {
+        let mut reader = StateReader::new(data);
+        let clock = reader.read_instant()?;
+        let frame_count = reader.read_u64()?;
+        let rng_seed = reader.read_u64()?;
+        let mut rng_state = [0; 4];
+        for value in rng_state.iter_mut() {
+            *value = reader.read_u64()?;
+        }
+        let rtc = RtcProvider::load_state(&mut reader)?;
+        let mut wakeups = BTreeMap::new();
+        for _ in 0..reader.read_u32()? {
+            let name = reader.read_string()?;
+            wakeups.insert(name, reader.read_instant()?);
+        }
+        let mut states = vec![];
+        for _ in 0..reader.read_u32()? {
+            let name = reader.read_string()?;
+            states.push((name, reader.read_bytes()?));
+        }
+        reader.finish()?;
+        Ok(Self {
+            clock,
+            frame_count,
+            rng_seed,
+            rng_state,
+            rtc,
+            wakeups,
+            states,
+        })
+    }
+
+    pub fn state(&self, name: &str) -> Option<&'a [u8]> {
+        self.states
+            .iter()
+            .find(|(state_name, _)| state_name == name)
+            .map(|(_, state)| *state)
+    }
+}
+
+// Binary encoding of machine state, used by components to write their part of the snapshot
+// payload. Integers are stored little endian, clocks in femtoseconds.
+#[derive(Default)]
+pub struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_u8(value as u8);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    // Femtos are 64 bit on wasm, so clocks are always stored with 128 bits
+    #[allow(clippy::useless_conversion)]
+    pub fn write_instant(&mut self, value: Instant) {
+        let femtos = u128::from(value.as_duration().as_femtos());
+        self.data.extend_from_slice(&femtos.to_le_bytes());
+    }
+
+    pub fn write_bytes(&mut self, value: &[u8]) {
+        write_bytes(&mut self.data, value);
+    }
+
+    pub fn write_string(&mut self, value: &str) {
+        write_string(&mut self.data, value);
+    }
+}
+
+pub struct StateReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    // Fails when data is left over, which means the payload does not fit the reader
+    pub fn finish(self) -> Result<(), Error> {
+        if !self.data.is_empty() {
+            return Err(Error::new("state contains unexpected data"));
+        }
+        Ok(())
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let (value, rest) = self
+            .data
+            .split_first_chunk::<N>()
+            .ok_or_else(|| Error::new("state is truncated"))?;
+        self.data = rest;
+        Ok(*value)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, Error> {
+        Ok(self.read_array::<1>()?[0])
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, Error> {
+        match self.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(Error::new("state contains an invalid flag")),
+        }
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes(self.read_array()?))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.read_array()?))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_le_bytes(self.read_array()?))
+    }
+
+    #[allow(clippy::unnecessary_fallible_conversions)]
+    pub fn read_instant(&mut self) -> Result<Instant, Error> {
+        let femtos = Femtos::try_from(u128::from_le_bytes(self.read_array()?))
+            .map_err(|_| Error::new("state contains a clock beyond the supported range"))?;
+        Ok(Instant::START + Duration::from_femtos(femtos))
+    }
+
+    pub fn read_bytes(&mut self) -> Result<&'a [u8], Error> {
+        let length = self.read_u32()? as usize;
+        if self.data.len() < length {
+            return Err(Error::new("state is truncated"));
+        }
+        let (value, rest) = self.data.split_at(length);
+        self.data = rest;
+        Ok(value)
+    }
+
+    pub fn read_string(&mut self) -> Result<String, Error> {
+        String::from_utf8(self.read_bytes()?.to_vec())
+            .map_err(|_| Error::new("state contains invalid text"))
+    }
+}
+
+fn write_bytes(buffer: &mut Vec<u8>, value: &[u8]) {
+    buffer.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    buffer.extend_from_slice(value);
+}
+
+fn write_string(buffer: &mut Vec<u8>, value: &str) {
+    write_bytes(buffer, value.as_bytes());
+}
+
+fn read_u32(data: &[u8]) -> Result<(u32, &[u8]), Error> {
+    let (value, rest) = data
+        .split_first_chunk::<4>()
+        .ok_or_else(|| Error::new("snapshot header is truncated"))?;
+    Ok((u32::from_le_bytes(*value), rest))
+}
+
+fn read_string(data: &[u8]) -> Result<(String, &[u8]), Error> {
+    let (length, data) = read_u32(data)?;
+    if data.len() < length as usize {
+        return Err(Error::new("snapshot header is truncated"));
+    }
+    let (value, rest) = data.split_at(length as usize);
+    let value = String::from_utf8(value.to_vec())
+        .map_err(|_| Error::new("snapshot header contains invalid text"))?;
+    Ok((value, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_round_trip() {
+        let clock = Instant::START + Duration::from_nanos(1234);
+        let mut writer = StateWriter::new();
+        writer.write_u8(7);
+        writer.write_bool(true);
+        writer.write_u16(0x1234);
+        writer.write_instant(clock);
+        writer.write_bytes(&[1, 2, 3]);
+        writer.write_string("cpu");
+        let data = writer.into_data();
+
+        let mut reader = StateReader::new(&data);
+        assert_eq!(reader.read_u8().unwrap(), 7);
+        assert!(reader.read_bool().unwrap());
+        assert_eq!(reader.read_u16().unwrap(), 0x1234);
+        assert_eq!(reader.read_instant().unwrap(), clock);
+        assert_eq!(reader.read_bytes().unwrap(), &[1, 2, 3]);
+        assert_eq!(reader.read_string().unwrap(), "cpu");
+        reader.finish().unwrap();
+    }
+
+    #[test]
+    fn truncated_state_fails() {
+        let mut writer = StateWriter::new();
+        writer.write_bytes(&[1, 2, 3]);
+        let data = writer.into_data();
+        assert!(StateReader::new(&data[..5]).read_bytes().is_err());
+        assert!(StateReader::new(&data).finish().is_err());
+    }
+
+    fn snapshot(version: u32, payload: &[u8]) -> Vec<u8> {
+        SnapshotHeader::new("Chip8", version, "abc").encode(payload)
+    }
+
+    // Appends the version it upgrades from, so the order of the steps shows in the payload
+    fn append_version(from_version: u32, mut payload: Vec<u8>) -> Result<Vec<u8>, Error> {
+        if from_version == 3 {
+            return Err(Error::new("version 3 cannot be upgraded"));
+        }
+        payload.push(from_version as u8);
+        Ok(payload)
+    }
+
+    #[test]
+    fn header_round_trip() {
+        let data = snapshot(2, &[1, 2, 3]);
+        let (header, payload) = SnapshotHeader::decode(&data).unwrap();
+        assert_eq!(header, SnapshotHeader::new("Chip8", 2, "abc"));
+        assert_eq!(payload, &[1, 2, 3]);
+    }
+
+    #[test]
+    fn header_with_bad_magic_fails() {
+        let mut data = snapshot(1, &[]);
+        data[0] = b'X';
+        let error = SnapshotHeader::decode(&data).unwrap_err();
+        assert!(error.to_string().contains("not a snapshot file"));
+    }
+
+    #[test]
+    fn truncated_header_fails() {
+        let data = snapshot(1, &[]);
+        for length in [2, 6, 12, data.len() - 1] {
+            assert!(SnapshotHeader::decode(&data[..length]).is_err());
+        }
+    }
+
+    #[test]
+    fn upgrade_rejects_newer_versions() {
+        let error = upgrade_snapshot(&snapshot(3, &[]), "Chip8", 2, "abc", Some(append_version))
+            .unwrap_err();
+        assert!(error.to_string().contains("only supports up to version 2"));
+    }
+
+    #[test]
+    fn upgrade_rejects_other_backends_and_roms() {
+        let data = snapshot(1, &[]);
+        assert!(upgrade_snapshot(&data, "SuperChip", 1, "abc", None).is_err());
+        assert!(upgrade_snapshot(&data, "Chip8", 1, "def", None).is_err());
+    }
+
+    #[test]
+    fn upgrade_chains_the_migrations() {
+        let data = snapshot(1, &[0xFF]);
+        assert_eq!(
+            upgrade_snapshot(&data, "Chip8", 3, "abc", Some(append_version)).unwrap(),
+            vec![0xFF, 1, 2]
+        );
+        // The current version needs no migration
+        assert_eq!(
+            upgrade_snapshot(&snapshot(3, &[0xFF]), "Chip8", 3, "abc", None).unwrap(),
+            vec![0xFF]
+        );
+    }
+
+    #[test]
+    fn upgrade_fails_without_or_with_a_failing_migration() {
+        let error = upgrade_snapshot(&snapshot(1, &[]), "Chip8", 2, "abc", None).unwrap_err();
+        assert!(error.to_string().contains("cannot upgrade it to version 2"));
+        assert!(
+            upgrade_snapshot(&snapshot(2, &[]), "Chip8", 4, "abc", Some(append_version)).is_err()
+        );
+    }
+
+    #[test]
+    fn migrates_the_state_of_one_component() {
+        let mut writer = StateWriter::new();
+        writer.write_instant(Instant::START);
+        for _ in 0..6 {
+            writer.write_u64(0);
+        }
+        writer.write_u32(1);
+        writer.write_string("cpu");
+        writer.write_instant(Instant::START);
+        writer.write_u32(2);
+        writer.write_string("cpu");
+        writer.write_bytes(&[1]);
+        writer.write_string("timer");
+        writer.write_bytes(&[2]);
+        let payload = writer.into_data();
+
+        let migrated = migrate_component_state(payload.clone(), false, "cpu", |state| {
+            Ok([state, &[3]].concat())
+        })
+        .unwrap();
+        assert_eq!(migrated.len(), payload.len() + 1);
+        assert!(migrated.ends_with(&[
+            2, 0, 0, 0, 1, 3, 5, 0, 0, 0, b't', b'i', b'm', b'e', b'r', 1, 0, 0, 0, 2
+        ]));
+        assert!(
+            migrate_component_state(payload, false, "audio", |state| Ok(state.to_vec())).is_err()
+        );
+    }
+}
@@ -0,0 +1,256 @@
+use std::{cell::RefCell, rc::Rc};
+
+use femtos::Instant;
+
+use super::{
+    component::{MemoryAddress, MemorySize},
+    memory::{BusAccess, BusHook},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerCondition {
+    // Fires once when the emulated clock reaches the instant
+    Clock(Instant),
+    // Fires on every write matching the watchpoint
+    Write(Watchpoint),
+    // Fires whenever the byte at the address changes to the value
+    Value(MemoryAddress, u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ValueCondition {
+    Equal(u64),
+    NotEqual(u64),
+    Less(u64),
+    Greater(u64),
+}
+
+impl ValueCondition {
+    pub fn matches(&self, value: u64) -> bool {
+        match *self {
+            ValueCondition::Equal(expected) => value == expected,
+            ValueCondition::NotEqual(expected) => value != expected,
+            ValueCondition::Less(expected) => value < expected,
+            ValueCondition::Greater(expected) => value > expected,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Watchpoint {
+    pub address: MemoryAddress,
+    // Width of the watched value in bytes, stored big endian
+    pub size: MemorySize,
+    pub value: Option<ValueCondition>,
+}
+
+impl Watchpoint {
+    pub fn new(address: MemoryAddress) -> Self {
+        Self {
+            address,
+            size: 1,
+            value: None,
+        }
+    }
+
+    pub fn with_size(mut self, size: MemorySize) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn with_value(mut self, value: ValueCondition) -> Self {
+        self.value = Some(value);
+        self
+    }
+
+    // Without a value condition any write touching the watched bytes matches. With one, the write
+    // has to cover all of them, as the value is taken from the written data only.
+    pub fn matches(&self, address: MemoryAddress, data: &[u8]) -> bool {
+        let watched = self.address..self.address + self.size;
+        let written = address..address + data.len();
+        let Some(condition) = self.value else {
+            return written.start < watched.end && watched.start < written.end;
+        };
+        if watched.start < written.start || watched.end > written.end {
+            return false;
+        }
+        let value = data[watched.start - address..watched.end - address]
+            .iter()
+            .fold(0u64, |value, byte| (value << 8) | *byte as u64);
+        condition.matches(value)
+    }
+}
+
+pub type TriggerId = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Trigger {
+    pub id: TriggerId,
+    pub condition: TriggerCondition,
+    matched: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TriggerEvent {
+    pub id: TriggerId,
+    pub condition: TriggerCondition,
+    pub clock: Instant,
+}
+
+#[derive(Default)]
+pub(crate) struct WriteWatch {
+    watchpoints: Vec<Watchpoint>,
+    hits: Vec<Watchpoint>,
+}
+
+impl BusHook for WriteWatch {
+    fn on_access(&mut self, access: BusAccess, address: MemoryAddress, data: &[u8]) {
+        if access != BusAccess::Write {
+            return;
+        }
+        for watchpoint in self.watchpoints.iter() {
+            if watchpoint.matches(address, data) {
+                self.hits.push(*watchpoint);
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Triggers {
+    next_id: TriggerId,
+    triggers: Vec<Trigger>,
+    events: Vec<TriggerEvent>,
+    write_watch: Rc<RefCell<WriteWatch>>,
+    write_watch_installed: bool,
+}
+
+impl Triggers {
+    pub(crate) fn add(&mut self, condition: TriggerCondition, matched: bool) -> TriggerId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.triggers.push(Trigger {
+            id,
+            condition,
+            matched,
+        });
+        self.update_write_watch();
+        id
+    }
+
+    // Returns the hook that has to be added to the bus, only once the first write trigger exists
+    pub(crate) fn install_write_watch(&mut self) -> Option<Rc<RefCell<WriteWatch>>> {
+        if self.write_watch_installed || self.write_watch.borrow().watchpoints.is_empty() {
+            return None;
+        }
+        self.write_watch_installed = true;
+        Some(self.write_watch.clone())
+    }
+
+    pub(crate) fn remove(&mut self, id: TriggerId) {
+        self.triggers.retain(|t| t.id != id);
+        self.update_write_watch();
+    }
+
+    pub(crate) fn list(&self) -> &[Trigger] {
+        &self.triggers
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.triggers.is_empty()
+    }
+
+    pub(crate) fn take_events(&mut self) -> Vec<TriggerEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    pub(crate) fn has_events(&self) -> bool {
+        !self.events.is_empty()
+    }
+
+    // Checks all conditions after a step, the read function gives access to the current memory
+    pub(crate) fn check<F>(&mut self, clock: Instant, read: F)
+    where
+        F: Fn(MemoryAddress) -> Option<u8>,
+    {
+        let hits = std::mem::take(&mut self.write_watch.borrow_mut().hits);
+        for trigger in self.triggers.iter_mut() {
+            let matches = match trigger.condition {
+                TriggerCondition::Clock(instant) => clock >= instant,
+                TriggerCondition::Write(watchpoint) => hits.contains(&watchpoint),
+                TriggerCondition::Value(address, value) => read(address) == Some(value),
+            };
+            let edge = match trigger.condition {
+                TriggerCondition::Write(_) => matches,
+                _ => matches && !trigger.matched,
+            };
+            trigger.matched = matches;
+            if edge {
+                self.events.push(TriggerEvent {
+                    id: trigger.id,
+                    condition: trigger.condition,
+                    clock,
+                });
+            }
+        }
+        self.triggers
+            .retain(|t| !(t.matched && matches!(t.condition, TriggerCondition::Clock(_))));
+    }
+
+    fn update_write_watch(&mut self) {
+        self.write_watch.borrow_mut().watchpoints = self
+            .triggers
+            .iter()
+            .filter_map(|t| match t.condition {
+                TriggerCondition::Write(watchpoint) => Some(watchpoint),
+                _ => None,
+            })
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watchpoint_without_value_matches_any_overlapping_write() {
+        let watchpoint = Watchpoint::new(0x1E0).with_size(2);
+        assert!(watchpoint.matches(0x1DF, &[0, 0]));
+        assert!(watchpoint.matches(0x1E1, &[0]));
+        assert!(!watchpoint.matches(0x1E2, &[0]));
+        assert!(!watchpoint.matches(0x1DE, &[0, 0]));
+    }
+
+    #[test]
+    fn watchpoint_compares_written_value() {
+        let watchpoint = Watchpoint::new(0x1E0).with_value(ValueCondition::Greater(0x80));
+        assert!(watchpoint.matches(0x1E0, &[0x81]));
+        assert!(!watchpoint.matches(0x1E0, &[0x80]));
+        assert!(watchpoint.matches(0x1DF, &[0x00, 0xFF]));
+    }
+
+    #[test]
+    fn watchpoint_value_is_big_endian_and_needs_full_coverage() {
+        let watchpoint = Watchpoint::new(0x10)
+            .with_size(2)
+            .with_value(ValueCondition::Equal(0x1234));
+        assert!(watchpoint.matches(0x10, &[0x12, 0x34]));
+        assert!(!watchpoint.matches(0x10, &[0x34, 0x12]));
+        assert!(!watchpoint.matches(0x10, &[0x12]));
+    }
+
+    #[test]
+    fn write_watch_ignores_reads() {
+        let mut watch = WriteWatch {
+            watchpoints: vec![Watchpoint::new(0x10)],
+            hits: vec![],
+        };
+        watch.on_access(BusAccess::Read, 0x10, &[1]);
+        assert!(watch.hits.is_empty());
+        watch.on_access(BusAccess::Write, 0x10, &[1]);
+        assert_eq!(watch.hits, vec![Watchpoint::new(0x10)]);
+    }
+}
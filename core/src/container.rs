@@ -0,0 +1,303 @@
+use std::io::Read;
+
+use flate2::read::{DeflateDecoder, GzDecoder};
+
+use crate::{error::Error, rom::Rom};
+
+const ZIP_LOCAL_HEADER: u32 = 0x0403_4B50;
+const ZIP_CENTRAL_HEADER: u32 = 0x0201_4B50;
+const ZIP_END_OF_DIRECTORY: u32 = 0x0605_4B50;
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+// Larger files are refused, so a small archive cannot expand into all of the memory
+pub const MAX_UNPACKED_SIZE: usize = 64 * 1024 * 1024;
+
+// Files which come along with roms in archives but are never roms themselves
+const IGNORED_EXTENSIONS: [&str; 10] = [
+    "txt", "nfo", "diz", "md", "htm", "html", "pdf", "jpg", "png", "url",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContainerKind {
+    Zip,
+    Gzip,
+}
+
+impl ContainerKind {
+    // Judged by content, as archives are often renamed to the extension of the rom
+    pub fn of(data: &[u8]) -> Option<Self> {
+        if data.starts_with(&ZIP_LOCAL_HEADER.to_le_bytes())
+            || data.starts_with(&ZIP_END_OF_DIRECTORY.to_le_bytes())
+        {
+            Some(Self::Zip)
+        } else if data.starts_with(&GZIP_MAGIC) {
+            Some(Self::Gzip)
+        } else {
+            None
+        }
+    }
+}
+
+// Returns the candidate roms inside of a container, or the rom itself if it is none. Zip archives
+// can contain several candidates, which the user has to choose from.
+pub fn unpack_rom(rom: Rom) -> Result<Vec<Rom>, Error> {
+    let roms = match ContainerKind::of(&rom.data) {
+        None => return Ok(vec![rom]),
+        Some(ContainerKind::Gzip) => match unpack_gzip(&rom)? {
+            Some(unpacked) => vec![unpacked],
+            // Raw roms can start with the magic too, e.g. a chip8 rom with 1F8B as its first opcode
+            None => return Ok(vec![rom]),
+        },
+        Some(ContainerKind::Zip) => unpack_zip(&rom.data)?
+            .into_iter()
+            .filter(|rom| {
+                !rom.extension()
+                    .is_some_and(|e| IGNORED_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+            })
+            .collect(),
+    };
+    if roms.is_empty() {
+        return Err(Error::new("the archive contains no rom"));
+    }
+    // A patch next to the archive belongs to the rom inside
+    Ok(roms
+        .into_iter()
+        .map(|unpacked| unpacked.with_patch(rom.patch.clone()))
+        .collect())
+}
+
+// None when the data does not decompress, while exceeding the size limit is an error
+fn read_limited(reader: impl Read, size_hint: usize) -> Result<Option<Vec<u8>>, Error> {
+    let mut data = Vec::with_capacity(size_hint.min(MAX_UNPACKED_SIZE));
+    if reader
+        .take(MAX_UNPACKED_SIZE as u64 + 1)
+        .read_to_end(&mut data)
+        .is_err()
+    {
+        return Ok(None);
+    }
+    if data.len() > MAX_UNPACKED_SIZE {
+        return Err(Error::new("the compressed rom is too large"));
+    }
+    Ok(Some(data))
+}
+
+// The name stored in the gzip header is preferred, it is the original name of the rom. None when
+// the data is no gzip file after all.
+fn unpack_gzip(rom: &Rom) -> Result<Option<Rom>, Error> {
+    let mut decoder = GzDecoder::new(rom.data.as_slice());
+    let Some(data) = read_limited(&mut decoder, rom.data.len())? else {
+        return Ok(None);
+    };
+    let stored_name = decoder
+        .header()
+        .and_then(|header| header.filename())
+        .map(|name| String::from_utf8_lossy(name).to_string());
+    let file_name = stored_name.or_else(|| {
+        let name = rom.file_name.as_deref()?;
+        Some(name.strip_suffix(".gz").unwrap_or(name).to_string())
+    });
+    Ok(Some(Rom::new(file_name, data)))
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, Error> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+        .ok_or_else(|| Error::new("the archive is truncated"))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, Error> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or_else(|| Error::new("the archive is truncated"))
+}
+
+// Reads the files listed in the central directory. Only stored and deflated files are supported,
+// which covers the archives of rom sets.
+fn unpack_zip(data: &[u8]) -> Result<Vec<Rom>, Error> {
+    // The end of directory record is followed by a comment of up to 64 KiB
+    let end = (0..data.len().saturating_sub(21))
+        .rev()
+        .take(0x10000 + 22)
+        .find(|offset| read_u32(data, *offset).ok() == Some(ZIP_END_OF_DIRECTORY))
+        .ok_or_else(|| Error::new("the archive has no directory"))?;
+    let entries = read_u16(data, end + 10)?;
+    let mut offset = read_u32(data, end + 16)? as usize;
+
+    let mut roms = vec![];
+    for _ in 0..entries {
+        if read_u32(data, offset)? != ZIP_CENTRAL_HEADER {
+            return Err(Error::new("the archive directory is corrupt"));
+        }
+        let method = read_u16(data, offset + 10)?;
+        let crc = read_u32(data, offset + 16)?;
+        let compressed_size = read_u32(data, offset + 20)? as usize;
+        let size = read_u32(data, offset + 24)? as usize;
+        let name_length = read_u16(data, offset + 28)? as usize;
+        let extra_length = read_u16(data, offset + 30)? as usize;
+        let comment_length = read_u16(data, offset + 32)? as usize;
+        let local_offset = read_u32(data, offset + 42)? as usize;
+        let name = data
+            .get(offset + 46..offset + 46 + name_length)
+            .map(|name| String::from_utf8_lossy(name).to_string())
+            .ok_or_else(|| Error::new("the archive is truncated"))?;
+        offset += 46 + name_length + extra_length + comment_length;
+
+        // Directories and the resource forks macOS adds to archives
+        if name.ends_with('/') || name.starts_with("__MACOSX/") {
+            continue;
+        }
+        if compressed_size == u32::MAX as usize || size == u32::MAX as usize {
+            return Err(Error::new("zip64 archives are not supported"));
+        }
+        if size > MAX_UNPACKED_SIZE {
+            return Err(Error::new(format!("{} in the archive is too large", name)));
+        }
+
+        if read_u32(data, local_offset)? != ZIP_LOCAL_HEADER {
+            return Err(Error::new("the archive is corrupt"));
+        }
+        let start = local_offset
+            + 30
+            + read_u16(data, local_offset + 26)? as usize
+            + read_u16(data, local_offset + 28)? as usize;
+        let compressed = data
+            .get(start..start + compressed_size)
+            .ok_or_else(|| Error::new("the archive is truncated"))?;
+        let content = match method {
+            0 => compressed.to_vec(),
+            8 => read_limited(DeflateDecoder::new(compressed), size)?
+                .ok_or_else(|| Error::new(format!("{} in the archive is corrupt", name)))?,
+            _ => {
+                return Err(Error::new(format!(
+                    "{} uses an unsupported compression method",
+                    name
+                )));
+            }
+        };
+        if content.len() != size || crc32fast::hash(&content) != crc {
+            return Err(Error::new(format!("{} in the archive is corrupt", name)));
+        }
+
+        // Folders inside the archive are only for organizing, the rom keeps its own name
+        let file_name = name.rsplit('/').next().unwrap_or(&name).to_string();
+        roms.push(Rom::new(Some(file_name), content));
+    }
+    Ok(roms)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{Compression, GzBuilder, write::DeflateEncoder};
+
+    use super::*;
+
+    // Writes a zip archive with the given files, stored or deflated
+    fn build_zip(files: &[(&str, &[u8], bool)]) -> Vec<u8> {
+        let mut data = vec![];
+        let mut directory = vec![];
+        for (name, content, deflate) in files {
+            let compressed = if *deflate {
+                let mut encoder = DeflateEncoder::new(vec![], Compression::default());
+                encoder.write_all(content).unwrap();
+                encoder.finish().unwrap()
+            } else {
+                content.to_vec()
+            };
+            let method: u16 = if *deflate { 8 } else { 0 };
+            let crc = crc32fast::hash(content);
+            let local_offset = data.len() as u32;
+
+            data.extend_from_slice(&ZIP_LOCAL_HEADER.to_le_bytes());
+            data.extend_from_slice(&[20, 0, 0, 0]);
+            data.extend_from_slice(&method.to_le_bytes());
+            data.extend_from_slice(&[0; 4]);
+            data.extend_from_slice(&crc.to_le_bytes());
+            data.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+            data.extend_from_slice(&(content.len() as u32).to_le_bytes());
+            data.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            data.extend_from_slice(&[0, 0]);
+            data.extend_from_slice(name.as_bytes());
+            data.extend_from_slice(&compressed);
+
+            directory.extend_from_slice(&ZIP_CENTRAL_HEADER.to_le_bytes());
+            directory.extend_from_slice(&[20, 0, 20, 0, 0, 0]);
+            directory.extend_from_slice(&method.to_le_bytes());
+            directory.extend_from_slice(&[0; 4]);
+            directory.extend_from_slice(&crc.to_le_bytes());
+            directory.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+            directory.extend_from_slice(&(content.len() as u32).to_le_bytes());
+            directory.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            directory.extend_from_slice(&[0; 12]);
+            directory.extend_from_slice(&local_offset.to_le_bytes());
+            directory.extend_from_slice(name.as_bytes());
+        }
+        let directory_offset = data.len() as u32;
+        data.extend_from_slice(&directory);
+        data.extend_from_slice(&ZIP_END_OF_DIRECTORY.to_le_bytes());
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&(files.len() as u16).to_le_bytes());
+        data.extend_from_slice(&(files.len() as u16).to_le_bytes());
+        data.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+        data.extend_from_slice(&directory_offset.to_le_bytes());
+        data.extend_from_slice(&[0, 0]);
+        data
+    }
+
+    #[test]
+    fn plain_rom_is_passed_through() {
+        let rom = Rom::new(Some("game.ch8".to_string()), vec![0x00, 0xE0]);
+        let roms = unpack_rom(rom).unwrap();
+        assert_eq!(roms.len(), 1);
+        assert_eq!(roms[0].data, vec![0x00, 0xE0]);
+    }
+
+    #[test]
+    fn lists_roms_of_zip() {
+        let data = build_zip(&[
+            ("readme.txt", b"hello", false),
+            ("roms/", b"", false),
+            ("roms/first.ch8", &[1, 2, 3], false),
+            ("roms/second.ch8", &[4; 100], true),
+        ]);
+        let roms = unpack_rom(Rom::new(Some("set.zip".to_string()), data)).unwrap();
+        let names: Vec<_> = roms
+            .iter()
+            .map(|rom| rom.file_name.clone().unwrap())
+            .collect();
+        assert_eq!(names, vec!["first.ch8", "second.ch8"]);
+        assert_eq!(roms[0].data, vec![1, 2, 3]);
+        assert_eq!(roms[1].data, vec![4; 100]);
+    }
+
+    #[test]
+    fn corrupt_zip_fails() {
+        let mut data = build_zip(&[("game.ch8", &[1, 2, 3], false)]);
+        data[30 + "game.ch8".len()] ^= 0xFF;
+        assert!(unpack_rom(Rom::new(None, data)).is_err());
+    }
+
+    #[test]
+    fn unpacks_gzip_with_its_name() {
+        let mut encoder = GzBuilder::new()
+            .filename("game.ch8")
+            .write(vec![], Compression::default());
+        encoder.write_all(&[0x12, 0x00]).unwrap();
+        let data = encoder.finish().unwrap();
+
+        let roms = unpack_rom(Rom::new(Some("download.gz".to_string()), data)).unwrap();
+        assert_eq!(roms[0].file_name.as_deref(), Some("game.ch8"));
+        assert_eq!(roms[0].data, vec![0x12, 0x00]);
+    }
+
+    #[test]
+    fn raw_rom_with_gzip_magic_is_passed_through() {
+        // JP 0xF8B; CLS
+        let data = vec![0x1F, 0x8B, 0x00, 0xE0];
+        let roms = unpack_rom(Rom::new(Some("game.ch8".to_string()), data.clone())).unwrap();
+        assert_eq!(roms.len(), 1);
+        assert_eq!(roms[0].file_name.as_deref(), Some("game.ch8"));
+        assert_eq!(roms[0].data, data);
+    }
+}
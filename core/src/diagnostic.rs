@@ -0,0 +1,111 @@
+use std::fmt::{self, Display};
+
+use femtos::{Duration, Instant};
+
+use crate::{
+    backend::Backend,
+    error::Error,
+    trace::{format_trace_line, replay_trace},
+};
+
+// Steps of the failed component kept in a report
+pub const FAILURE_TRACE_LINES: usize = 200;
+
+// The machine at the point a component failed, for bug reports
+#[derive(Debug, Clone)]
+pub struct FailureReport {
+    pub error: String,
+    pub component: String,
+    pub clock: Instant,
+    // The state of every inspectable component, by name
+    pub components: Vec<(String, Vec<String>)>,
+    // The last steps of the failed component, ending with the failing one. Empty when the backend
+    // has no rewind snapshots to replay them from.
+    pub trace: Vec<String>,
+    // Save state right before the failing step if it could be replayed, otherwise the state after
+    // the failure. Stepping the backend after loading it fails again.
+    pub state: Vec<u8>,
+}
+
+// Has to be called right after the error, before the backend is stepped again. The backend is
+// rewound to just before the failing step when it has rewind snapshots of the replayed duration.
+pub fn capture_failure(
+    backend: &mut Backend,
+    error: &Error,
+    replay_duration: Duration,
+) -> Result<FailureReport, Error> {
+    let clock = backend.get_current_clock();
+    let component = backend
+        .failed_component()
+        .ok_or_else(|| Error::new("no component of the backend failed"))?
+        .to_string();
+
+    let mut trace = vec![];
+    let traceable = backend
+        .get_component(&component)?
+        .borrow_mut()
+        .as_traceable()
+        .is_some();
+    // The failing step runs exactly at the clock, so the replay ends right before it
+    if let (true, Some(end)) = (traceable, clock.checked_sub(Duration::from_femtos(1))) {
+        let start = clock.checked_sub(replay_duration).unwrap_or(Instant::START);
+        if let Ok(lines) = replay_trace(backend, &component, start.min(end), end) {
+            trace = lines;
+            let registers = backend
+                .get_component(&component)?
+                .borrow_mut()
+                .as_traceable()
+                .unwrap()
+                .trace_registers();
+            trace.push(format_trace_line(&registers));
+            let skipped = trace.len().saturating_sub(FAILURE_TRACE_LINES);
+            trace.drain(..skipped);
+        }
+    }
+
+    let mut components: Vec<(String, Vec<String>)> = backend
+        .get_all_components()
+        .into_iter()
+        .filter_map(|(name, component)| {
+            let lines = component.borrow_mut().as_inspectable()?.inspect();
+            Some((name, lines))
+        })
+        .collect();
+    components.sort();
+
+    Ok(FailureReport {
+        error: error.to_string(),
+        component,
+        clock,
+        components,
+        trace,
+        state: backend.save_state()?,
+    })
+}
+
+impl Display for FailureReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Error: {}", self.error)?;
+        writeln!(f, "Component: {}", self.component)?;
+        writeln!(
+            f,
+            "Clock: {:.9}s",
+            self.clock.as_duration().as_nanos() as f64 / 1e9
+        )?;
+        for (name, lines) in &self.components {
+            writeln!(f)?;
+            writeln!(f, "[{}]", name)?;
+            for line in lines {
+                writeln!(f, "{}", line)?;
+            }
+        }
+        if !self.trace.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "[trace of {}]", self.component)?;
+            for line in &self.trace {
+                writeln!(f, "{}", line)?;
+            }
+        }
+        Ok(())
+    }
+}
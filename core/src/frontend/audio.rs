@@ -1,14 +1,26 @@
 use std::ops::RangeBounds;
 
 use femtos::Instant;
+use rubato::{
+    FastFixedIn, PolynomialDegree, SincFixedIn, SincInterpolationParameters, SincInterpolationType,
+    VecResampler, WindowFunction,
+};
 
-use crate::utils::ClockedRingbuffer;
+use crate::{
+    error::Error,
+    utils::{ClockedBroadcast, ClockedRingbuffer, Ringbuffer},
+};
 
+// Samples are generated inside the emulated machine and therefore are part of
+// its deterministic state. Backends must derive them from integer or
+// fixed-point state (e.g. a u32 phase accumulator) and only convert to f32 at
+// the very end, never via libm functions like sin/cos whose results differ
+// between native and wasm targets. This is enforced by backends/clippy.toml.
 pub type Sample = f32;
 
 pub struct AudioSender {
     sample_rate: f32,
-    queue: ClockedRingbuffer<Sample>,
+    queue: ClockedBroadcast<Sample>,
 }
 
 impl AudioSender {
@@ -31,6 +43,7 @@ impl AudioSender {
 
 pub struct AudioReceiver {
     sample_rate: f32,
+    broadcast: ClockedBroadcast<Sample>,
     queue: ClockedRingbuffer<Sample>,
 }
 
@@ -59,18 +72,193 @@ impl AudioReceiver {
     pub fn sample_rate(&self) -> f32 {
         self.sample_rate
     }
+    // Another receiver of the same samples with its own queue, which only receives samples sent
+    // after subscribing
+    pub fn subscribe(&self) -> AudioReceiver {
+        AudioReceiver {
+            sample_rate: self.sample_rate,
+            broadcast: self.broadcast.clone(),
+            queue: self.broadcast.subscribe(),
+        }
+    }
 }
 
 pub fn build_audio_channel(sample_rate: f32, buffer_size: usize) -> (AudioSender, AudioReceiver) {
     let sender = AudioSender {
         sample_rate,
-        queue: ClockedRingbuffer::new(buffer_size),
+        queue: ClockedBroadcast::new(buffer_size),
     };
 
     let receiver = AudioReceiver {
         sample_rate,
-        queue: sender.queue.clone(),
+        broadcast: sender.queue.clone(),
+        queue: sender.queue.subscribe(),
     };
 
     (sender, receiver)
 }
+
+const RESAMPLER_CHUNK_SIZE: usize = 1024;
+const RESAMPLER_TARGET_BUFFER_LEN: usize = 2 * RESAMPLER_CHUNK_SIZE;
+const RESAMPLER_MOVING_AVERAGE_RATIO: f64 = 0.05;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResamplerQuality {
+    Fast,
+    #[default]
+    Sinc,
+}
+
+// Converts the emulated sample rate to the host output rate. The ratio is continuously nudged so
+// the frontends output buffer hovers around a target level, compensating for drift between the
+// emulated clock and the audio device clock.
+pub struct Resampler {
+    input_sample_rate: f64,
+    output_sample_rate: f64,
+    resampler: Box<dyn VecResampler<Sample>>,
+    buffer_len_average: usize,
+    buffer_len_average_history: Ringbuffer<usize>,
+}
+
+impl Resampler {
+    pub fn new(
+        input_sample_rate: f32,
+        output_sample_rate: f64,
+        quality: ResamplerQuality,
+    ) -> Result<Self, Error> {
+        let input_sample_rate = input_sample_rate as f64;
+        let ratio = output_sample_rate / input_sample_rate;
+        let resampler: Box<dyn VecResampler<Sample>> = match quality {
+            ResamplerQuality::Fast => Box::new(
+                FastFixedIn::<Sample>::new(
+                    ratio,
+                    2.0,
+                    PolynomialDegree::Linear,
+                    RESAMPLER_CHUNK_SIZE,
+                    1,
+                )
+                .map_err(|err| Error::Other(err.to_string()))?,
+            ),
+            ResamplerQuality::Sinc => {
+                let params = SincInterpolationParameters {
+                    sinc_len: 64,
+                    f_cutoff: 0.91,
+                    oversampling_factor: 1024,
+                    interpolation: SincInterpolationType::Linear,
+                    window: WindowFunction::Hann2,
+                };
+                Box::new(
+                    SincFixedIn::<Sample>::new(ratio, 2.0, params, RESAMPLER_CHUNK_SIZE, 1)
+                        .map_err(|err| Error::Other(err.to_string()))?,
+                )
+            }
+        };
+
+        Ok(Self {
+            input_sample_rate,
+            output_sample_rate,
+            resampler,
+            buffer_len_average: 0,
+            buffer_len_average_history: Ringbuffer::new(60),
+        })
+    }
+
+    pub fn output_sample_rate(&self) -> f64 {
+        self.output_sample_rate
+    }
+
+    pub fn target_buffer_len(&self) -> usize {
+        RESAMPLER_TARGET_BUFFER_LEN
+    }
+
+    // Resamples all complete chunks currently waiting in the receiver
+    pub fn process(&mut self, receiver: &AudioReceiver) -> Result<Vec<Sample>, Error> {
+        self.process_with_tap(receiver, |_, _| {})
+    }
+
+    // Like process, but hands every original sample to the tap before it is resampled
+    pub fn process_with_tap<F>(
+        &mut self,
+        receiver: &AudioReceiver,
+        mut tap: F,
+    ) -> Result<Vec<Sample>, Error>
+    where
+        F: FnMut(Instant, Sample),
+    {
+        let mut result = vec![];
+        while receiver.len() > RESAMPLER_CHUNK_SIZE {
+            let samples = receiver
+                .pop_range(..RESAMPLER_CHUNK_SIZE)
+                .iter()
+                .map(|(clock, sample)| {
+                    tap(*clock, *sample);
+                    *sample
+                })
+                .collect::<Vec<Sample>>();
+
+            let resampled = self
+                .resampler
+                .process(&[samples], None)
+                .map_err(|err| Error::Other(err.to_string()))?;
+            if let Some(channel) = resampled.first() {
+                result.extend_from_slice(channel);
+            }
+        }
+        Ok(result)
+    }
+
+    pub fn adjust_to_buffer_len(&mut self, buffer_len: usize) -> Result<(), Error> {
+        self.buffer_len_average =
+            ((self.buffer_len_average as f64) * (1.0 - RESAMPLER_MOVING_AVERAGE_RATIO)
+                + buffer_len as f64 * RESAMPLER_MOVING_AVERAGE_RATIO) as usize;
+        self.buffer_len_average_history
+            .push_back(self.buffer_len_average);
+
+        // slope via regression
+        let (mut sx, mut sy, mut sxx, mut sxy) = (0, 0, 0, 0);
+        for (idx, avg) in self
+            .buffer_len_average_history
+            .peek_range(..)
+            .iter()
+            .enumerate()
+        {
+            sx += idx;
+            sy += avg;
+            sxx += idx * idx;
+            sxy += idx * avg;
+        }
+        let n = self.buffer_len_average_history.len();
+        let num = (n * sxy) as f64 - (sx * sy) as f64;
+        let den = (n * sxx) as f64 - (sx * sx) as f64;
+        let slope: f64 = if den == 0.0 { 0.0 } else { num / den };
+
+        let difference = self.buffer_len_average as f64 - RESAMPLER_TARGET_BUFFER_LEN as f64;
+        let direction = if difference == 0.0 {
+            0.0
+        } else {
+            difference / difference.abs()
+        };
+
+        let mut adjustment = 0.0;
+
+        if direction * slope < -1.0 {
+            adjustment = slope.abs() / 4.0;
+            if adjustment > 1.0 {
+                adjustment = 1.0;
+            }
+        } else if direction * slope > 0.0 || self.buffer_len_average == 0 {
+            let skew = (difference.abs() / 400.0) * 10.0;
+            adjustment = (slope.abs() + skew) / -2.0;
+            if adjustment < -2.0 {
+                adjustment = -2.0;
+            }
+        }
+
+        adjustment *= direction;
+        self.output_sample_rate += adjustment;
+
+        self.resampler
+            .set_resample_ratio(self.output_sample_rate / self.input_sample_rate, false)
+            .map_err(|err| Error::Other(err.to_string()))
+    }
+}
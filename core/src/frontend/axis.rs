@@ -0,0 +1,137 @@
+use crate::frontend::input::ButtonState;
+
+// Axis values are normalized to -1.0..=1.0
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Deadzone {
+    // Values below inner are treated as 0, values above outer as fully deflected
+    pub inner: f32,
+    pub outer: f32,
+}
+
+impl Default for Deadzone {
+    fn default() -> Self {
+        Self {
+            inner: 0.15,
+            outer: 0.95,
+        }
+    }
+}
+
+impl Deadzone {
+    fn rescale(&self, magnitude: f32) -> f32 {
+        if magnitude <= self.inner {
+            0.0
+        } else if magnitude >= self.outer {
+            1.0
+        } else {
+            (magnitude - self.inner) / (self.outer - self.inner)
+        }
+    }
+
+    pub fn apply(&self, value: f32) -> f32 {
+        self.rescale(value.abs()) * value.signum()
+    }
+
+    // Applies the deadzone to the length of the stick vector, so diagonals are not cut off
+    pub fn apply_radial(&self, x: f32, y: f32) -> (f32, f32) {
+        let magnitude = (x * x + y * y).sqrt();
+        if magnitude == 0.0 {
+            return (0.0, 0.0);
+        }
+        let scale = self.rescale(magnitude) / magnitude;
+        ((x * scale).clamp(-1.0, 1.0), (y * scale).clamp(-1.0, 1.0))
+    }
+}
+
+// Turns an axis into a pair of buttons. Uses separate press and release thresholds, so an axis
+// resting near the threshold does not toggle the buttons every frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AxisButtons {
+    pub press_threshold: f32,
+    pub release_threshold: f32,
+    negative: ButtonState,
+    positive: ButtonState,
+}
+
+impl Default for AxisButtons {
+    fn default() -> Self {
+        Self::new(0.5, 0.4)
+    }
+}
+
+impl AxisButtons {
+    pub fn new(press_threshold: f32, release_threshold: f32) -> Self {
+        Self {
+            press_threshold,
+            release_threshold,
+            negative: ButtonState::Released,
+            positive: ButtonState::Released,
+        }
+    }
+
+    fn next_state(&self, current: ButtonState, deflection: f32) -> ButtonState {
+        match current {
+            ButtonState::Released if deflection >= self.press_threshold => ButtonState::Pressed,
+            ButtonState::Pressed if deflection < self.release_threshold => ButtonState::Released,
+            state => state,
+        }
+    }
+
+    // Returns the states of the (negative, positive) buttons
+    pub fn update(&mut self, value: f32) -> (ButtonState, ButtonState) {
+        self.negative = self.next_state(self.negative, -value);
+        self.positive = self.next_state(self.positive, value);
+        (self.negative, self.positive)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DpadState {
+    pub up: ButtonState,
+    pub down: ButtonState,
+    pub left: ButtonState,
+    pub right: ButtonState,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AxisDpad {
+    pub deadzone: Deadzone,
+    horizontal: AxisButtons,
+    vertical: AxisButtons,
+}
+
+impl AxisDpad {
+    pub fn new(deadzone: Deadzone, press_threshold: f32, release_threshold: f32) -> Self {
+        Self {
+            deadzone,
+            horizontal: AxisButtons::new(press_threshold, release_threshold),
+            vertical: AxisButtons::new(press_threshold, release_threshold),
+        }
+    }
+
+    // y grows downwards, like on most gamepads
+    pub fn update(&mut self, x: f32, y: f32) -> DpadState {
+        let (x, y) = self.deadzone.apply_radial(x, y);
+        let (left, right) = self.horizontal.update(x);
+        let (up, down) = self.vertical.update(y);
+        DpadState {
+            up,
+            down,
+            left,
+            right,
+        }
+    }
+}
+
+// Emulates an axis with two digital buttons, pressing both cancels out
+pub fn buttons_to_axis(negative: ButtonState, positive: ButtonState, magnitude: f32) -> f32 {
+    let value = |state| {
+        if state == ButtonState::Pressed {
+            magnitude
+        } else {
+            0.0
+        }
+    };
+    value(positive) - value(negative)
+}
@@ -0,0 +1,73 @@
+use femtos::Instant;
+
+use crate::utils::ClockedRingbuffer;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugWidget {
+    // Shows the latest value as number
+    Value,
+    // Shows the history of the value as graph
+    Plot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DebugSample {
+    pub name: &'static str,
+    pub widget: DebugWidget,
+    pub value: f64,
+}
+
+// Values a backend exposes for debugging, usually pushed once per emulated frame. Frontends render
+// them generically, so backends need no bespoke debug panel.
+pub struct DebugSender {
+    queue: ClockedRingbuffer<DebugSample>,
+}
+
+impl DebugSender {
+    pub fn value(&self, clock: Instant, name: &'static str, value: f64) {
+        self.add(clock, name, DebugWidget::Value, value);
+    }
+
+    pub fn plot(&self, clock: Instant, name: &'static str, value: f64) {
+        self.add(clock, name, DebugWidget::Plot, value);
+    }
+
+    fn add(&self, clock: Instant, name: &'static str, widget: DebugWidget, value: f64) {
+        self.queue.push_back((
+            clock,
+            DebugSample {
+                name,
+                widget,
+                value,
+            },
+        ));
+    }
+}
+
+pub struct DebugReceiver {
+    queue: ClockedRingbuffer<DebugSample>,
+}
+
+impl DebugReceiver {
+    pub fn pop(&self) -> Option<(Instant, DebugSample)> {
+        self.queue.pop_front()
+    }
+    pub fn pop_all(&self) -> Vec<(Instant, DebugSample)> {
+        self.queue.drain_and_pop_range(..)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+pub fn build_debug_channel(buffer_size: usize) -> (DebugSender, DebugReceiver) {
+    let sender = DebugSender {
+        queue: ClockedRingbuffer::new(buffer_size),
+    };
+
+    let receiver = DebugReceiver {
+        queue: sender.queue.clone(),
+    };
+
+    (sender, receiver)
+}
@@ -0,0 +1,266 @@
+use super::graphics::{Frame, Pixel};
+
+// Post-processing step that turns a frame coming out of the backend into the displayed one
+pub trait VideoFilter {
+    fn name(&self) -> &'static str;
+    fn apply(&mut self, frame: &Frame) -> Frame;
+}
+
+#[derive(Default)]
+pub struct FilterChain {
+    filters: Vec<Box<dyn VideoFilter>>,
+}
+
+impl FilterChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Builds a chain from the names of built-in filters, unknown names are skipped
+    pub fn from_names<S: AsRef<str>>(names: &[S]) -> Self {
+        Self {
+            filters: names
+                .iter()
+                .filter_map(|name| create_builtin_filter(name.as_ref()))
+                .collect(),
+        }
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.filters.iter().map(|filter| filter.name()).collect()
+    }
+
+    pub fn push(&mut self, filter: Box<dyn VideoFilter>) {
+        self.filters.push(filter);
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        if index < self.filters.len() {
+            self.filters.remove(index);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.filters.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    pub fn apply(&mut self, frame: Frame) -> Frame {
+        self.filters
+            .iter_mut()
+            .fold(frame, |frame, filter| filter.apply(&frame))
+    }
+}
+
+pub const BUILTIN_FILTERS: [&str; 3] = [Scale2x::NAME, Grayscale::NAME, NtscComposite::NAME];
+
+pub fn create_builtin_filter(name: &str) -> Option<Box<dyn VideoFilter>> {
+    match name {
+        Scale2x::NAME => Some(Box::new(Scale2x)),
+        Grayscale::NAME => Some(Box::new(Grayscale)),
+        NtscComposite::NAME => Some(Box::new(NtscComposite)),
+        _ => None,
+    }
+}
+
+fn pixel_at(frame: &Frame, x: isize, y: isize) -> Pixel {
+    let x = x.clamp(0, frame.width as isize - 1) as usize;
+    let y = y.clamp(0, frame.height as isize - 1) as usize;
+    frame.data[y * frame.width + x]
+}
+
+// Doubles the resolution while keeping diagonal edges smooth (EPX)
+pub struct Scale2x;
+
+impl Scale2x {
+    pub const NAME: &str = "Scale2x";
+}
+
+impl VideoFilter for Scale2x {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn apply(&mut self, frame: &Frame) -> Frame {
+        let (width, height) = (frame.width * 2, frame.height * 2);
+        let mut data = vec![(0, 0, 0, 255); width * height];
+        for y in 0..frame.height as isize {
+            for x in 0..frame.width as isize {
+                let p = pixel_at(frame, x, y);
+                let (a, b, c, d) = (
+                    pixel_at(frame, x, y - 1),
+                    pixel_at(frame, x + 1, y),
+                    pixel_at(frame, x - 1, y),
+                    pixel_at(frame, x, y + 1),
+                );
+                let quadrants = if a != d && c != b {
+                    [
+                        if c == a { c } else { p },
+                        if a == b { b } else { p },
+                        if d == c { c } else { p },
+                        if b == d { b } else { p },
+                    ]
+                } else {
+                    [p; 4]
+                };
+                let (x, y) = (x as usize * 2, y as usize * 2);
+                data[y * width + x] = quadrants[0];
+                data[y * width + x + 1] = quadrants[1];
+                data[(y + 1) * width + x] = quadrants[2];
+                data[(y + 1) * width + x + 1] = quadrants[3];
+            }
+        }
+        Frame {
+            width,
+            height,
+            data,
+        }
+    }
+}
+
+pub struct Grayscale;
+
+impl Grayscale {
+    pub const NAME: &str = "Grayscale";
+}
+
+impl VideoFilter for Grayscale {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn apply(&mut self, frame: &Frame) -> Frame {
+        Frame {
+            width: frame.width,
+            height: frame.height,
+            data: frame
+                .data
+                .iter()
+                .map(|(r, g, b, a)| {
+                    // integer Rec. 601 luma
+                    let luma = ((*r as u32 * 299 + *g as u32 * 587 + *b as u32 * 114) / 1000) as u8;
+                    (luma, luma, luma, *a)
+                })
+                .collect(),
+        }
+    }
+}
+
+// Approximates the limited bandwidth of a composite signal, colors bleed into their neighbours
+// far more than the brightness does
+pub struct NtscComposite;
+
+impl NtscComposite {
+    pub const NAME: &str = "NTSC composite";
+    const LUMA_KERNEL: [f32; 3] = [1.0, 4.0, 1.0];
+    const CHROMA_KERNEL: [f32; 5] = [1.0, 2.0, 3.0, 2.0, 1.0];
+}
+
+fn blur_row(values: &[f32], kernel: &[f32]) -> Vec<f32> {
+    let radius = (kernel.len() / 2) as isize;
+    let total: f32 = kernel.iter().sum();
+    (0..values.len() as isize)
+        .map(|x| {
+            kernel
+                .iter()
+                .enumerate()
+                .map(|(i, weight)| {
+                    let index = (x + i as isize - radius).clamp(0, values.len() as isize - 1);
+                    values[index as usize] * weight
+                })
+                .sum::<f32>()
+                / total
+        })
+        .collect()
+}
+
+impl VideoFilter for NtscComposite {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn apply(&mut self, frame: &Frame) -> Frame {
+        let mut data = Vec::with_capacity(frame.data.len());
+        for row in frame.data.chunks(frame.width.max(1)) {
+            let (mut y, mut i, mut q) = (vec![], vec![], vec![]);
+            for (r, g, b, _) in row {
+                let (r, g, b) = (*r as f32, *g as f32, *b as f32);
+                y.push(0.299 * r + 0.587 * g + 0.114 * b);
+                i.push(0.596 * r - 0.274 * g - 0.322 * b);
+                q.push(0.211 * r - 0.523 * g + 0.312 * b);
+            }
+            let y = blur_row(&y, &Self::LUMA_KERNEL);
+            let i = blur_row(&i, &Self::CHROMA_KERNEL);
+            let q = blur_row(&q, &Self::CHROMA_KERNEL);
+            for (x, (_, _, _, a)) in row.iter().enumerate() {
+                let channel = |value: f32| value.round().clamp(0.0, 255.0) as u8;
+                data.push((
+                    channel(y[x] + 0.956 * i[x] + 0.621 * q[x]),
+                    channel(y[x] - 0.272 * i[x] - 0.647 * q[x]),
+                    channel(y[x] - 1.106 * i[x] + 1.703 * q[x]),
+                    *a,
+                ));
+            }
+        }
+        Frame {
+            width: frame.width,
+            height: frame.height,
+            data,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: Pixel = (0, 0, 0, 255);
+    const WHITE: Pixel = (255, 255, 255, 255);
+
+    fn frame(width: usize, height: usize, data: Vec<Pixel>) -> Frame {
+        Frame {
+            width,
+            height,
+            data,
+        }
+    }
+
+    #[test]
+    fn scale2x_smooths_diagonals() {
+        let input = frame(2, 2, vec![WHITE, BLACK, BLACK, WHITE]);
+        let output = Scale2x.apply(&input);
+        assert_eq!((output.width, output.height), (4, 4));
+        // the black pixel at the top right gets its lower left corner filled by the diagonal
+        assert_eq!(output.data[4 + 2], WHITE);
+        assert_eq!(output.data[3], BLACK);
+    }
+
+    #[test]
+    fn scale2x_keeps_flat_areas() {
+        let input = frame(1, 1, vec![WHITE]);
+        assert_eq!(Scale2x.apply(&input).data, vec![WHITE; 4]);
+    }
+
+    #[test]
+    fn grayscale_keeps_alpha() {
+        let input = frame(1, 1, vec![(255, 0, 0, 128)]);
+        assert_eq!(Grayscale.apply(&input).data, vec![(76, 76, 76, 128)]);
+    }
+
+    #[test]
+    fn ntsc_keeps_flat_colors() {
+        let input = frame(3, 1, vec![WHITE; 3]);
+        assert_eq!(NtscComposite.apply(&input).data, vec![WHITE; 3]);
+    }
+
+    #[test]
+    fn chain_applies_filters_in_order() {
+        let mut chain = FilterChain::from_names(&["Grayscale", "unknown", "Scale2x"]);
+        assert_eq!(chain.names(), vec!["Grayscale", "Scale2x"]);
+        let output = chain.apply(frame(1, 1, vec![(255, 0, 0, 255)]));
+        assert_eq!(output.data, vec![(76, 76, 76, 255); 4]);
+    }
+}
@@ -1,6 +1,11 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+};
+
 use femtos::Instant;
 
-use crate::utils::ClockedRingbuffer;
+use crate::utils::{ClockedBroadcast, ClockedRingbuffer};
 
 pub type Pixel = (u8, u8, u8, u8);
 
@@ -33,21 +38,68 @@ impl Frame {
 
         result
     }
+
+    // Scales the frame down to fit into the bounds keeping its aspect ratio, every pixel averages
+    // the pixels it covers. Frames already fitting are returned unchanged.
+    pub fn thumbnail(&self, max_width: usize, max_height: usize) -> Frame {
+        if self.width <= max_width && self.height <= max_height {
+            return self.clone();
+        }
+        let scale =
+            (max_width as f64 / self.width as f64).min(max_height as f64 / self.height as f64);
+        let width = ((self.width as f64 * scale).round() as usize).max(1);
+        let height = ((self.height as f64 * scale).round() as usize).max(1);
+
+        let mut thumbnail = Frame::new((width, height));
+        // Source rows or columns covered by the target pixel, at least one
+        let span = |index: usize, source: usize, target: usize| {
+            let start = index * source / target;
+            start..((index + 1) * source / target).max(start + 1)
+        };
+        for y in 0..height {
+            let rows = span(y, self.height, height);
+            for x in 0..width {
+                let columns = span(x, self.width, width);
+                let mut sum = [0u32; 4];
+                for source_y in rows.clone() {
+                    for source_x in columns.clone() {
+                        let pixel = self.data[source_y * self.width + source_x];
+                        sum[0] += pixel.0 as u32;
+                        sum[1] += pixel.1 as u32;
+                        sum[2] += pixel.2 as u32;
+                        sum[3] += pixel.3 as u32;
+                    }
+                }
+                let count = (rows.len() * columns.len()) as u32;
+                thumbnail.data[y * width + x] = (
+                    (sum[0] / count) as u8,
+                    (sum[1] / count) as u8,
+                    (sum[2] / count) as u8,
+                    (sum[3] / count) as u8,
+                );
+            }
+        }
+        thumbnail
+    }
 }
 
 pub struct FrameSender {
-    queue: ClockedRingbuffer<Frame>,
+    queue: ClockedBroadcast<Frame>,
+    emitted: Arc<AtomicU64>,
 }
 
 impl FrameSender {
     pub fn add(&self, clock: Instant, frame: Frame) {
         self.queue.push_back((clock, frame));
+        self.emitted.fetch_add(1, Ordering::Relaxed);
     }
 }
 
 pub struct FrameReceiver {
     max_size: (usize, usize),
+    broadcast: ClockedBroadcast<Frame>,
     queue: ClockedRingbuffer<Frame>,
+    emitted: Arc<AtomicU64>,
 }
 
 impl FrameReceiver {
@@ -55,20 +107,100 @@ impl FrameReceiver {
         self.max_size
     }
 
+    pub fn emitted(&self) -> u64 {
+        self.emitted.load(Ordering::Relaxed)
+    }
+
+    // Drops all frames but the newest, for consumers only interested in what is on screen now
     pub fn latest(&self) -> Option<(Instant, Frame)> {
         self.queue.drain_and_pop_latest()
     }
+
+    // All frames since the last call, oldest first
+    pub fn pop_all(&self) -> Vec<(Instant, Frame)> {
+        self.queue.drain_and_pop_range(..)
+    }
+
+    // Another receiver of the same frames with its own queue, e.g. for recording the output while
+    // it is shown. It only receives frames sent after subscribing.
+    pub fn subscribe(&self) -> FrameReceiver {
+        FrameReceiver {
+            max_size: self.max_size,
+            broadcast: self.broadcast.clone(),
+            queue: self.broadcast.subscribe(),
+            emitted: self.emitted.clone(),
+        }
+    }
 }
 
 pub fn build_frame_channel(width: usize, height: usize) -> (FrameSender, FrameReceiver) {
     let sender = FrameSender {
-        queue: ClockedRingbuffer::new(20),
+        queue: ClockedBroadcast::new(20),
+        emitted: Arc::new(AtomicU64::new(0)),
     };
 
     let receiver = FrameReceiver {
         max_size: (width, height),
-        queue: sender.queue.clone(),
+        broadcast: sender.queue.clone(),
+        queue: sender.queue.subscribe(),
+        emitted: sender.emitted.clone(),
     };
 
     (sender, receiver)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subscribers_receive_frames_independently() {
+        let (sender, screen) = build_frame_channel(2, 2);
+        let recorder = screen.subscribe();
+        for i in 0..3 {
+            sender.add(Instant::START, Frame::new((i + 1, 1)));
+        }
+        assert_eq!(screen.latest().unwrap().1.width, 3);
+        assert!(screen.latest().is_none());
+        assert_eq!(recorder.latest().unwrap().1.width, 3);
+    }
+
+    #[test]
+    fn pop_all_keeps_every_frame_with_its_clock() {
+        let (sender, receiver) = build_frame_channel(2, 2);
+        for i in 0..3 {
+            sender.add(
+                Instant::START + femtos::Duration::from_millis(i),
+                Frame::new((i as usize + 1, 1)),
+            );
+        }
+        let frames = receiver.pop_all();
+        let clocks: Vec<u64> = frames
+            .iter()
+            .map(|(clock, _)| clock.as_duration().as_millis())
+            .collect();
+        assert_eq!(clocks, vec![0, 1, 2]);
+        assert_eq!(frames[2].1.width, 3);
+        assert!(receiver.pop_all().is_empty());
+    }
+
+    #[test]
+    fn thumbnail_keeps_aspect_and_averages() {
+        let mut frame = Frame::new((4, 2));
+        frame.data[0] = (255, 255, 255, 255);
+        frame.data[1] = (255, 255, 255, 255);
+        let thumbnail = frame.thumbnail(2, 2);
+        assert_eq!((thumbnail.width, thumbnail.height), (2, 1));
+        assert_eq!(thumbnail.data[0], (127, 127, 127, 255));
+        assert_eq!(thumbnail.data[1], (0, 0, 0, 255));
+        assert_eq!(frame.thumbnail(8, 8).data, frame.data);
+    }
+
+    #[test]
+    fn dropped_subscribers_stop_receiving() {
+        let (sender, screen) = build_frame_channel(2, 2);
+        drop(screen.subscribe());
+        sender.add(Instant::START, Frame::new((1, 1)));
+        assert_eq!(sender.queue.subscribers(), 1);
+    }
+}
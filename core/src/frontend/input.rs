@@ -1,6 +1,13 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+};
+
+use femtos::Instant;
+
 use crate::utils::ClockedRingbuffer;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum KeyboardEventKey {
     A,
     B,
@@ -38,6 +45,104 @@ pub enum KeyboardEventKey {
     Number7,
     Number8,
     Number9,
+    Space,
+    Enter,
+}
+
+impl KeyboardEventKey {
+    pub const ALL: [KeyboardEventKey; 38] = [
+        Self::A,
+        Self::B,
+        Self::C,
+        Self::D,
+        Self::E,
+        Self::F,
+        Self::G,
+        Self::H,
+        Self::I,
+        Self::J,
+        Self::K,
+        Self::L,
+        Self::M,
+        Self::N,
+        Self::O,
+        Self::P,
+        Self::Q,
+        Self::R,
+        Self::S,
+        Self::T,
+        Self::U,
+        Self::V,
+        Self::W,
+        Self::X,
+        Self::Y,
+        Self::Z,
+        Self::Number0,
+        Self::Number1,
+        Self::Number2,
+        Self::Number3,
+        Self::Number4,
+        Self::Number5,
+        Self::Number6,
+        Self::Number7,
+        Self::Number8,
+        Self::Number9,
+        Self::Space,
+        Self::Enter,
+    ];
+
+    // Parses the names given by the Debug output, e.g. Number1
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|key| format!("{:?}", key) == name)
+    }
+
+    // Used to type text like pasted programs, characters without a key are skipped
+    pub fn from_char(c: char) -> Option<Self> {
+        let key = match c.to_ascii_uppercase() {
+            'A' => Self::A,
+            'B' => Self::B,
+            'C' => Self::C,
+            'D' => Self::D,
+            'E' => Self::E,
+            'F' => Self::F,
+            'G' => Self::G,
+            'H' => Self::H,
+            'I' => Self::I,
+            'J' => Self::J,
+            'K' => Self::K,
+            'L' => Self::L,
+            'M' => Self::M,
+            'N' => Self::N,
+            'O' => Self::O,
+            'P' => Self::P,
+            'Q' => Self::Q,
+            'R' => Self::R,
+            'S' => Self::S,
+            'T' => Self::T,
+            'U' => Self::U,
+            'V' => Self::V,
+            'W' => Self::W,
+            'X' => Self::X,
+            'Y' => Self::Y,
+            'Z' => Self::Z,
+            '0' => Self::Number0,
+            '1' => Self::Number1,
+            '2' => Self::Number2,
+            '3' => Self::Number3,
+            '4' => Self::Number4,
+            '5' => Self::Number5,
+            '6' => Self::Number6,
+            '7' => Self::Number7,
+            '8' => Self::Number8,
+            '9' => Self::Number9,
+            ' ' => Self::Space,
+            '\n' => Self::Enter,
+            _ => return None,
+        };
+        Some(key)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -46,7 +151,7 @@ pub enum ButtonState {
     Released,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum InputEvent {
     Keyboard(KeyboardEventKey, ButtonState),
     // controller
@@ -54,28 +159,82 @@ pub enum InputEvent {
     // ...
 }
 
+// Players are counted from 0, backends for a single player can ignore them
+pub type PlayerId = u8;
+pub const PLAYER_ONE: PlayerId = 0;
+
+#[derive(Clone, Copy)]
+struct QueuedInput {
+    sequence: u64,
+    player: PlayerId,
+    event: InputEvent,
+}
+
 pub struct InputSender {
-    queue: ClockedRingbuffer<InputEvent>,
+    queue: ClockedRingbuffer<QueuedInput>,
+    sent: AtomicU64,
+    consumed: Arc<AtomicU64>,
 }
 
 impl InputSender {
-    pub fn add(&self, input: InputEvent) {
-        self.queue.push_back((femtos::Instant::START, input));
+    // Returns the sequence number of the event, which can be compared against consumed()
+    pub fn add(&self, input: InputEvent) -> u64 {
+        self.add_at(Instant::START, input)
+    }
+
+    // The event is held back until the backend clock reaches the given time
+    pub fn add_at(&self, clock: Instant, input: InputEvent) -> u64 {
+        self.add_for_player(PLAYER_ONE, clock, input)
+    }
+
+    pub fn add_for_player(&self, player: PlayerId, clock: Instant, input: InputEvent) -> u64 {
+        let sequence = self.sent.fetch_add(1, Ordering::Relaxed) + 1;
+        let queued = QueuedInput {
+            sequence,
+            player,
+            event: input,
+        };
+        self.queue.push_back((clock, queued));
+        sequence
+    }
+
+    // Events beyond the capacity push out the oldest ones still queued
+    pub fn free_capacity(&self) -> usize {
+        self.queue.capacity().saturating_sub(self.queue.len())
+    }
+
+    // Sequence number of the last event taken by the backend. Events pushed out of a full queue
+    // count as consumed once a later one is taken.
+    pub fn consumed(&self) -> u64 {
+        self.consumed.load(Ordering::Relaxed)
     }
 }
 
 pub struct InputReceiver {
-    queue: ClockedRingbuffer<InputEvent>,
+    queue: ClockedRingbuffer<QueuedInput>,
+    consumed: Arc<AtomicU64>,
 }
 
 impl InputReceiver {
     pub fn pop(&self) -> Option<InputEvent> {
-        if let Some((_, ie)) = self.queue.pop_front() {
-            Some(ie)
-        } else {
-            None
-        }
+        let (_, queued) = self.queue.pop_front()?;
+        Some(self.consume(queued).1)
     }
+
+    pub fn pop_due(&self, clock: Instant) -> Option<InputEvent> {
+        self.pop_due_with_player(clock).map(|(_, ie)| ie)
+    }
+
+    pub fn pop_due_with_player(&self, clock: Instant) -> Option<(PlayerId, InputEvent)> {
+        let (_, queued) = self.queue.pop_front_if(|(at, _)| *at <= clock)?;
+        Some(self.consume(queued))
+    }
+
+    fn consume(&self, queued: QueuedInput) -> (PlayerId, InputEvent) {
+        self.consumed.fetch_max(queued.sequence, Ordering::Relaxed);
+        (queued.player, queued.event)
+    }
+
     pub fn is_empty(&self) -> bool {
         self.queue.is_empty()
     }
@@ -84,11 +243,37 @@ impl InputReceiver {
 pub fn build_input_channel() -> (InputSender, InputReceiver) {
     let sender = InputSender {
         queue: ClockedRingbuffer::new(20),
+        sent: AtomicU64::new(0),
+        consumed: Arc::new(AtomicU64::new(0)),
     };
 
     let receiver = InputReceiver {
         queue: sender.queue.clone(),
+        consumed: sender.consumed.clone(),
     };
 
     (sender, receiver)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_pushed_out_of_the_queue_count_as_consumed() {
+        let (sender, receiver) = build_input_channel();
+        let press = InputEvent::Keyboard(KeyboardEventKey::A, ButtonState::Pressed);
+        let first = sender.add(press);
+        while sender.free_capacity() > 0 {
+            sender.add(press);
+        }
+        let latest = sender.add(press);
+        assert_eq!(sender.consumed(), 0);
+
+        // The first event was pushed out, taking the next one resolves it
+        receiver.pop();
+        assert!(sender.consumed() > first);
+        while receiver.pop().is_some() {}
+        assert_eq!(sender.consumed(), latest);
+    }
+}
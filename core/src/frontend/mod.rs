@@ -1,16 +1,28 @@
 use std::error::Error;
 
 use audio::AudioReceiver;
+use debug::DebugReceiver;
 use error::FrontendError;
 use graphics::FrameReceiver;
 use input::InputSender;
 use text::TextReceiver;
+use trace::TraceReceiver;
 
 pub mod audio;
+pub mod axis;
+pub mod debug;
 pub mod error;
+pub mod filter;
 pub mod graphics;
 pub mod input;
+pub mod movie;
+pub mod players;
+#[cfg(feature = "image")]
+pub mod screenshot;
+pub mod storage;
 pub mod text;
+pub mod trace;
+pub mod wav;
 
 pub trait Frontend {
     type Error: Error;
@@ -29,6 +41,15 @@ pub trait Frontend {
         Err(FrontendError::GraphicsNotSupported)
     }
 
+    // Additional video outputs like second displays or debug views, identified by their name
+    fn register_secondary_graphics_receiver(
+        &mut self,
+        _name: &str,
+        _receiver: FrameReceiver,
+    ) -> Result<(), FrontendError<Self::Error>> {
+        Err(FrontendError::GraphicsNotSupported)
+    }
+
     fn register_audio_receiver(
         &mut self,
         _receiver: AudioReceiver,
@@ -36,6 +57,22 @@ pub trait Frontend {
         Err(FrontendError::AudioNotSupported)
     }
 
+    // Debug values are optional, frontends without support simply never drain them
+    fn register_debug_receiver(
+        &mut self,
+        _receiver: DebugReceiver,
+    ) -> Result<(), FrontendError<Self::Error>> {
+        Ok(())
+    }
+
+    // Execution traces are optional as well, cpus only record while the receiver enables them
+    fn register_trace_receiver(
+        &mut self,
+        _receiver: TraceReceiver,
+    ) -> Result<(), FrontendError<Self::Error>> {
+        Ok(())
+    }
+
     fn register_input_sender(
         &mut self,
         _sender: InputSender,
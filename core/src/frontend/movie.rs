@@ -0,0 +1,178 @@
+use femtos::{Duration, Instant};
+
+use crate::error::Error;
+
+use super::input::{ButtonState, InputEvent, InputSender, KeyboardEventKey, PlayerId};
+
+const MOVIE_HEADER: &str = "axwmovie 1";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MovieEvent {
+    pub clock: Instant,
+    pub player: PlayerId,
+    pub event: InputEvent,
+}
+
+// Input of a session from power on, replaying it needs the same rom, options and rng seed
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputMovie {
+    pub rng_seed: u64,
+    pub events: Vec<MovieEvent>,
+}
+
+impl InputMovie {
+    pub fn new(rng_seed: u64) -> Self {
+        Self {
+            rng_seed,
+            events: vec![],
+        }
+    }
+
+    pub fn record(&mut self, clock: Instant, player: PlayerId, event: InputEvent) {
+        self.events.push(MovieEvent {
+            clock,
+            player,
+            event,
+        });
+    }
+
+    // A line based text format, one event per line as "<clock in ns> <player> <key> <down|up>"
+    pub fn encode(&self) -> String {
+        let mut result = format!("{}\nseed {}\n", MOVIE_HEADER, self.rng_seed);
+        for event in self.events.iter() {
+            let InputEvent::Keyboard(key, state) = event.event;
+            let state = match state {
+                ButtonState::Pressed => "down",
+                ButtonState::Released => "up",
+            };
+            result.push_str(&format!(
+                "{} {} {:?} {}\n",
+                event.clock.as_duration().as_nanos(),
+                event.player,
+                key,
+                state
+            ));
+        }
+        result
+    }
+
+    pub fn decode(content: &str) -> Result<Self, Error> {
+        let mut lines = content.lines().map(str::trim).filter(|l| !l.is_empty());
+        if lines.next() != Some(MOVIE_HEADER) {
+            return Err(Error::new("not an input movie"));
+        }
+        let rng_seed = lines
+            .next()
+            .and_then(|line| line.strip_prefix("seed "))
+            .and_then(|seed| seed.parse().ok())
+            .ok_or_else(|| Error::new("input movie has no seed"))?;
+
+        let mut movie = Self::new(rng_seed);
+        for line in lines {
+            let invalid = || Error::new(format!("invalid input movie event {}", line));
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [clock, player, key, state] = fields[..] else {
+                return Err(invalid());
+            };
+            let clock = clock.parse().map_err(|_| invalid())?;
+            let player = player.parse().map_err(|_| invalid())?;
+            let key = KeyboardEventKey::from_name(key).ok_or_else(invalid)?;
+            let state = match state {
+                "down" => ButtonState::Pressed,
+                "up" => ButtonState::Released,
+                _ => return Err(invalid()),
+            };
+            movie.record(
+                Instant::START + Duration::from_nanos(clock),
+                player,
+                InputEvent::Keyboard(key, state),
+            );
+        }
+        Ok(movie)
+    }
+}
+
+// Feeds the events of a movie into the input channel of a backend. The events are timestamped,
+// so they are handed over as soon as there is room and applied by the backend on time.
+pub struct MoviePlayer {
+    movie: InputMovie,
+    position: usize,
+}
+
+impl MoviePlayer {
+    pub fn new(movie: InputMovie) -> Self {
+        Self { movie, position: 0 }
+    }
+
+    pub fn feed(&mut self, sender: &InputSender) {
+        while sender.free_capacity() > 0 {
+            let Some(event) = self.movie.events.get(self.position) else {
+                break;
+            };
+            sender.add_for_player(event.player, event.clock, event.event);
+            self.position += 1;
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.movie.events.len()
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.movie.events.len() - self.position
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::frontend::input::build_input_channel;
+
+    use super::*;
+
+    fn movie() -> InputMovie {
+        let mut movie = InputMovie::new(42);
+        for i in 0..30 {
+            let state = if i % 2 == 0 {
+                ButtonState::Pressed
+            } else {
+                ButtonState::Released
+            };
+            movie.record(
+                Instant::START + Duration::from_millis(i * 10),
+                (i % 2) as PlayerId,
+                InputEvent::Keyboard(KeyboardEventKey::Number1, state),
+            );
+        }
+        movie
+    }
+
+    #[test]
+    fn encoding_roundtrips() {
+        let movie = movie();
+        assert_eq!(InputMovie::decode(&movie.encode()).unwrap(), movie);
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        let content = format!("{}\nseed 1\n0 0 Escape down\n", MOVIE_HEADER);
+        assert!(InputMovie::decode(&content).is_err());
+        assert!(InputMovie::decode("seed 1\n").is_err());
+    }
+
+    #[test]
+    fn player_waits_for_room_in_the_input_queue() {
+        let (sender, receiver) = build_input_channel();
+        let mut player = MoviePlayer::new(movie());
+        player.feed(&sender);
+        assert_eq!(player.remaining(), 10);
+
+        let clock = Instant::START + Duration::from_millis(95);
+        let mut due = 0;
+        while receiver.pop_due(clock).is_some() {
+            due += 1;
+        }
+        assert_eq!(due, 10);
+        player.feed(&sender);
+        assert!(player.is_finished());
+    }
+}
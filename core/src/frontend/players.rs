@@ -0,0 +1,154 @@
+use std::collections::BTreeMap;
+
+use crate::frontend::input::{KeyboardEventKey, PlayerId};
+
+// Part of the keyboard acting as its own device, so two players can share one keyboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum KeyboardRegion {
+    Whole,
+    Left,
+    Right,
+}
+
+impl KeyboardRegion {
+    // Splits the keyboard down the middle, between the T/G/B and Y/H/N columns
+    pub fn of(key: KeyboardEventKey) -> Self {
+        use KeyboardEventKey::*;
+        match key {
+            Number1 | Number2 | Number3 | Number4 | Number5 | Q | W | E | R | T | A | S | D | F
+            | G | Z | X | C | V | B => KeyboardRegion::Left,
+            _ => KeyboardRegion::Right,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum InputDevice {
+    Keyboard(KeyboardRegion),
+    Gamepad(u32),
+}
+
+// Maps the connected devices to players. New devices take the lowest free player, devices which
+// found no free player take over the player of the next device that disconnects.
+#[derive(Debug, Clone)]
+pub struct PlayerAssignment {
+    players: u8,
+    devices: BTreeMap<InputDevice, Option<PlayerId>>,
+}
+
+impl PlayerAssignment {
+    pub fn new(players: u8) -> Self {
+        Self {
+            players: players.max(1),
+            devices: BTreeMap::new(),
+        }
+    }
+
+    pub fn players(&self) -> u8 {
+        self.players
+    }
+
+    pub fn connect(&mut self, device: InputDevice) -> Option<PlayerId> {
+        if let Some(player) = self.devices.get(&device) {
+            return *player;
+        }
+        let player = self.free_player();
+        self.devices.insert(device, player);
+        player
+    }
+
+    pub fn disconnect(&mut self, device: InputDevice) {
+        let Some(Some(player)) = self.devices.remove(&device) else {
+            return;
+        };
+        if self.devices.values().any(|p| *p == Some(player)) {
+            return;
+        }
+        if let Some(waiting) = self.devices.values_mut().find(|p| p.is_none()) {
+            *waiting = Some(player);
+        }
+    }
+
+    // Manual assignment, several devices may control the same player
+    pub fn assign(&mut self, device: InputDevice, player: Option<PlayerId>) {
+        if let Some(assigned) = self.devices.get_mut(&device) {
+            *assigned = player.filter(|p| *p < self.players);
+        }
+    }
+
+    pub fn player_for(&self, device: &InputDevice) -> Option<PlayerId> {
+        self.devices.get(device).copied().flatten()
+    }
+
+    pub fn devices(&self) -> impl Iterator<Item = (&InputDevice, &Option<PlayerId>)> {
+        self.devices.iter()
+    }
+
+    fn free_player(&self) -> Option<PlayerId> {
+        (0..self.players).find(|player| !self.devices.values().any(|p| *p == Some(*player)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEFT: InputDevice = InputDevice::Keyboard(KeyboardRegion::Left);
+    const RIGHT: InputDevice = InputDevice::Keyboard(KeyboardRegion::Right);
+
+    #[test]
+    fn devices_take_the_lowest_free_player() {
+        let mut assignment = PlayerAssignment::new(2);
+        assert_eq!(assignment.connect(LEFT), Some(0));
+        assert_eq!(assignment.connect(InputDevice::Gamepad(7)), Some(1));
+        assert_eq!(assignment.connect(RIGHT), None);
+        assert_eq!(assignment.connect(LEFT), Some(0));
+    }
+
+    #[test]
+    fn waiting_device_takes_over_disconnected_player() {
+        let mut assignment = PlayerAssignment::new(2);
+        assignment.connect(InputDevice::Gamepad(1));
+        assignment.connect(InputDevice::Gamepad(2));
+        assignment.connect(RIGHT);
+        assignment.disconnect(InputDevice::Gamepad(1));
+        assert_eq!(assignment.player_for(&RIGHT), Some(0));
+        assert_eq!(assignment.player_for(&InputDevice::Gamepad(1)), None);
+    }
+
+    #[test]
+    fn player_stays_taken_while_another_device_controls_it() {
+        let mut assignment = PlayerAssignment::new(2);
+        assignment.connect(LEFT);
+        assignment.connect(RIGHT);
+        assignment.assign(RIGHT, Some(0));
+        assignment.connect(InputDevice::Gamepad(3));
+        assert_eq!(assignment.player_for(&InputDevice::Gamepad(3)), Some(1));
+        assignment.disconnect(LEFT);
+        assert_eq!(assignment.player_for(&RIGHT), Some(0));
+    }
+
+    #[test]
+    fn manual_assignment_is_limited_to_existing_players() {
+        let mut assignment = PlayerAssignment::new(1);
+        assignment.connect(LEFT);
+        assignment.assign(LEFT, Some(3));
+        assert_eq!(assignment.player_for(&LEFT), None);
+    }
+
+    #[test]
+    fn splits_keyboard_in_the_middle() {
+        assert_eq!(
+            KeyboardRegion::of(KeyboardEventKey::G),
+            KeyboardRegion::Left
+        );
+        assert_eq!(
+            KeyboardRegion::of(KeyboardEventKey::H),
+            KeyboardRegion::Right
+        );
+        assert_eq!(
+            KeyboardRegion::of(KeyboardEventKey::Number6),
+            KeyboardRegion::Right
+        );
+    }
+}
@@ -0,0 +1,74 @@
+use crate::error::Error;
+
+use super::graphics::Frame;
+
+// Shared by every consumer of frames that writes them to disk, e.g. screenshots, golden images of
+// tests and the thumbnails of savestates
+pub fn encode_png(frame: &Frame) -> Result<Vec<u8>, Error> {
+    let map_error =
+        |error: png::EncodingError| Error::new(format!("unable to encode png: {}", error));
+    let mut data = vec![];
+    let mut encoder = png::Encoder::new(&mut data, frame.width as u32, frame.height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(map_error)?;
+    writer
+        .write_image_data(&frame.as_rgba_vec())
+        .map_err(map_error)?;
+    writer.finish().map_err(map_error)?;
+    Ok(data)
+}
+
+// Palette and 16 bit images are converted to 8 bit color, e.g. for golden images made elsewhere
+pub fn decode_png(data: &[u8]) -> Result<Frame, Error> {
+    let map_error =
+        |error: png::DecodingError| Error::new(format!("unable to decode png: {}", error));
+    let mut decoder = png::Decoder::new(data);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(map_error)?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(map_error)?;
+    let bytes = &buffer[..info.buffer_size()];
+
+    let pixels = match info.color_type {
+        png::ColorType::Rgba => bytes
+            .chunks_exact(4)
+            .map(|p| (p[0], p[1], p[2], p[3]))
+            .collect(),
+        png::ColorType::Rgb => bytes
+            .chunks_exact(3)
+            .map(|p| (p[0], p[1], p[2], 255))
+            .collect(),
+        png::ColorType::GrayscaleAlpha => bytes
+            .chunks_exact(2)
+            .map(|p| (p[0], p[0], p[0], p[1]))
+            .collect(),
+        png::ColorType::Grayscale => bytes.iter().map(|p| (*p, *p, *p, 255)).collect(),
+        png::ColorType::Indexed => return Err(Error::new("unsupported png color type")),
+    };
+    Ok(Frame {
+        width: info.width as usize,
+        height: info.height as usize,
+        data: pixels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn png_round_trip_keeps_pixels() {
+        let mut frame = Frame::new((3, 2));
+        frame.data[1] = (10, 20, 30, 255);
+        frame.data[5] = (200, 100, 50, 128);
+        let decoded = decode_png(&encode_png(&frame).unwrap()).unwrap();
+        assert_eq!((decoded.width, decoded.height), (3, 2));
+        assert_eq!(decoded.data, frame.data);
+    }
+
+    #[test]
+    fn rejects_other_data() {
+        assert!(decode_png(b"not a png").is_err());
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::BTreeMap;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+
+use crate::error::Error;
+
+// Where a frontend keeps its data, like settings, saves, states and the rom library. Keys are
+// relative paths separated by /, e.g. "screenshots/chip8-1000.png".
+pub trait StorageProvider {
+    fn read(&self, key: &str) -> Result<Option<Vec<u8>>, Error>;
+    fn write(&mut self, key: &str, data: &[u8]) -> Result<(), Error>;
+    fn remove(&mut self, key: &str) -> Result<(), Error>;
+    // All keys starting with the prefix, sorted
+    fn list(&self, prefix: &str) -> Result<Vec<String>, Error>;
+
+    // Where the data of the key ends up, shown to the user
+    fn location(&self, key: &str) -> String {
+        key.to_string()
+    }
+}
+
+// Keys must not leave the storage, so they cannot be absolute or contain . and .. segments
+pub fn validate_key(key: &str) -> Result<(), Error> {
+    let valid = !key.is_empty()
+        && !key.contains('\\')
+        && key
+            .split('/')
+            .all(|segment| !segment.is_empty() && segment != "." && segment != "..");
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::new(format!("invalid storage key {}", key)))
+    }
+}
+
+// Kept for the lifetime of the frontend only, e.g. for tests and the web
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStorage {
+    entries: BTreeMap<String, Vec<u8>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl StorageProvider for MemoryStorage {
+    fn read(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        validate_key(key)?;
+        Ok(self.entries.get(key).cloned())
+    }
+
+    fn write(&mut self, key: &str, data: &[u8]) -> Result<(), Error> {
+        validate_key(key)?;
+        self.entries.insert(key.to_string(), data.to_vec());
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &str) -> Result<(), Error> {
+        validate_key(key)?;
+        self.entries.remove(key);
+        Ok(())
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<String>, Error> {
+        Ok(self
+            .entries
+            .keys()
+            .filter(|key| key.starts_with(prefix))
+            .cloned()
+            .collect())
+    }
+}
+
+// Keys are files below the root directory, which is created on the first write
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone)]
+pub struct FileStorage {
+    root: PathBuf,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FileStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf, Error> {
+        validate_key(key)?;
+        Ok(key
+            .split('/')
+            .fold(self.root.clone(), |path, segment| path.join(segment)))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn collect_keys(
+    directory: PathBuf,
+    key_prefix: String,
+    keys: &mut Vec<String>,
+) -> Result<(), Error> {
+    let entries = match std::fs::read_dir(&directory) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(error) => return Err(storage_error(&directory, error)),
+    };
+    for entry in entries {
+        let entry = entry.map_err(|error| storage_error(&directory, error))?;
+        let key = format!("{}{}", key_prefix, entry.file_name().to_string_lossy());
+        if entry.path().is_dir() {
+            collect_keys(entry.path(), format!("{}/", key), keys)?;
+        } else {
+            keys.push(key);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn storage_error(path: &std::path::Path, error: std::io::Error) -> Error {
+    Error::new(format!("{}: {}", path.display(), error))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl StorageProvider for FileStorage {
+    fn read(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        let path = self.path(key)?;
+        match std::fs::read(&path) {
+            Ok(data) => Ok(Some(data)),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(storage_error(&path, error)),
+        }
+    }
+
+    fn write(&mut self, key: &str, data: &[u8]) -> Result<(), Error> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|error| storage_error(parent, error))?;
+        }
+        std::fs::write(&path, data).map_err(|error| storage_error(&path, error))
+    }
+
+    fn remove(&mut self, key: &str) -> Result<(), Error> {
+        let path = self.path(key)?;
+        match std::fs::remove_file(&path) {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => {
+                Err(storage_error(&path, error))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Only the directory of the prefix is searched, not the whole storage
+    fn list(&self, prefix: &str) -> Result<Vec<String>, Error> {
+        let directory = prefix.rfind('/').map_or("", |end| &prefix[..=end]);
+        let mut keys = vec![];
+        let path = if directory.is_empty() {
+            self.root.clone()
+        } else {
+            self.path(directory.trim_end_matches('/'))?
+        };
+        collect_keys(path, directory.to_string(), &mut keys)?;
+        keys.retain(|key| key.starts_with(prefix));
+        keys.sort();
+        Ok(keys)
+    }
+
+    fn location(&self, key: &str) -> String {
+        self.path(key)
+            .map_or(key.to_string(), |path| path.display().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_storage_lists_by_prefix() {
+        let mut storage = MemoryStorage::new();
+        storage.write("states/b.state", &[2]).unwrap();
+        storage.write("states/a.state", &[1]).unwrap();
+        storage.write("settings.json", &[0]).unwrap();
+        assert_eq!(
+            storage.list("states/").unwrap(),
+            vec!["states/a.state", "states/b.state"]
+        );
+        assert_eq!(storage.read("states/a.state").unwrap(), Some(vec![1]));
+        storage.remove("states/a.state").unwrap();
+        assert_eq!(storage.read("states/a.state").unwrap(), None);
+    }
+
+    #[test]
+    fn keys_cannot_leave_the_storage() {
+        for key in [
+            "",
+            "/etc/passwd",
+            "../settings",
+            "states/../../x",
+            "a//b",
+            "a\\b",
+        ] {
+            assert!(validate_key(key).is_err(), "{}", key);
+        }
+        assert!(validate_key("states/game.state").is_ok());
+        let storage = FileStorage::new("/nonexistent");
+        assert!(storage.read("../outside").is_err());
+    }
+}
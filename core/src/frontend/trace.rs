@@ -0,0 +1,137 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+use femtos::Instant;
+
+use crate::{backend::component::MemoryAddress, utils::ClockedRingbuffer};
+
+// One executed instruction of a cpu
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub pc: MemoryAddress,
+    pub opcode: Vec<u8>,
+    pub disassembly: String,
+    // Registers the instruction changed, with their new value
+    pub register_delta: Vec<(&'static str, u64)>,
+}
+
+impl std::fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04X} ", self.pc)?;
+        for byte in &self.opcode {
+            write!(f, "{:02X}", byte)?;
+        }
+        write!(f, " {:<20}", self.disassembly)?;
+        for (name, value) in &self.register_delta {
+            write!(f, " {}={:X}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+// The registers of after which differ from before, both as returned by Traceable
+pub fn register_delta(
+    before: &[(&'static str, u64)],
+    after: &[(&'static str, u64)],
+) -> Vec<(&'static str, u64)> {
+    after
+        .iter()
+        .filter(|register| !before.contains(register))
+        .copied()
+        .collect()
+}
+
+// Executed instructions of a cpu. Recording every instruction is expensive, so cpus only emit
+// entries while the frontend has the trace enabled, which it is not by default.
+pub struct TraceSender {
+    queue: ClockedRingbuffer<TraceEntry>,
+    enabled: Arc<AtomicBool>,
+}
+
+impl TraceSender {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn add(&self, clock: Instant, entry: TraceEntry) {
+        if self.is_enabled() {
+            self.queue.push_back((clock, entry));
+        }
+    }
+}
+
+pub struct TraceReceiver {
+    queue: ClockedRingbuffer<TraceEntry>,
+    enabled: Arc<AtomicBool>,
+}
+
+impl TraceReceiver {
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+    pub fn pop(&self) -> Option<(Instant, TraceEntry)> {
+        self.queue.pop_front()
+    }
+    pub fn pop_all(&self) -> Vec<(Instant, TraceEntry)> {
+        self.queue.drain_and_pop_range(..)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+// Entries beyond the buffer size drop the oldest ones, so the receiver has to drain it regularly
+pub fn build_trace_channel(buffer_size: usize) -> (TraceSender, TraceReceiver) {
+    let sender = TraceSender {
+        queue: ClockedRingbuffer::new(buffer_size),
+        enabled: Arc::new(AtomicBool::new(false)),
+    };
+
+    let receiver = TraceReceiver {
+        queue: sender.queue.clone(),
+        enabled: sender.enabled.clone(),
+    };
+
+    (sender, receiver)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(pc: MemoryAddress) -> TraceEntry {
+        TraceEntry {
+            pc,
+            opcode: vec![0x60, 0x05],
+            disassembly: "LVI[0x0,0x5]".to_string(),
+            register_delta: register_delta(
+                &[("PC", 0x200), ("V0", 0)],
+                &[("PC", 0x202), ("V0", 5)],
+            ),
+        }
+    }
+
+    #[test]
+    fn only_records_while_enabled() {
+        let (sender, receiver) = build_trace_channel(16);
+        sender.add(Instant::START, entry(0x200));
+        assert!(receiver.is_empty());
+        receiver.set_enabled(true);
+        assert!(sender.is_enabled());
+        sender.add(Instant::START, entry(0x202));
+        assert_eq!(receiver.pop_all(), vec![(Instant::START, entry(0x202))]);
+    }
+
+    #[test]
+    fn formats_changed_registers() {
+        assert_eq!(
+            entry(0x200).to_string(),
+            "0200 6005 LVI[0x0,0x5]         PC=202 V0=5"
+        );
+    }
+}
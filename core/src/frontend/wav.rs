@@ -0,0 +1,73 @@
+use femtos::{Duration, Instant};
+
+use super::audio::Sample;
+
+// Records samples as they come out of a backend into a 16 bit mono WAV file. The emulated clock
+// of each sample is used to fill gaps, like a paused backend, with silence.
+pub struct WavRecorder {
+    sample_rate: f32,
+    sample_period: Duration,
+    samples: Vec<i16>,
+    next_clock: Option<Instant>,
+}
+
+impl WavRecorder {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            sample_period: Duration::from_nanos((1_000_000_000.0 / sample_rate as f64) as u64),
+            samples: vec![],
+            next_clock: None,
+        }
+    }
+
+    pub fn push(&mut self, clock: Instant, sample: Sample) {
+        if let Some(next_clock) = self.next_clock {
+            if clock > next_clock + self.sample_period {
+                let missing = clock.duration_since(next_clock).as_nanos()
+                    / self.sample_period.as_nanos().max(1);
+                self.samples
+                    .extend(std::iter::repeat_n(0, missing as usize));
+            }
+        }
+        self.samples
+            .push((sample.clamp(-1.0, 1.0) * i16::MAX as Sample) as i16);
+        self.next_clock = Some(clock + self.sample_period);
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn duration_secs(&self) -> f64 {
+        self.samples.len() as f64 / self.sample_rate as f64
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let sample_rate = self.sample_rate as u32;
+        let data_size = (self.samples.len() * 2) as u32;
+        let mut result = Vec::with_capacity(44 + data_size as usize);
+        result.extend_from_slice(b"RIFF");
+        result.extend_from_slice(&(36 + data_size).to_le_bytes());
+        result.extend_from_slice(b"WAVE");
+        result.extend_from_slice(b"fmt ");
+        result.extend_from_slice(&16u32.to_le_bytes());
+        // PCM, mono, sample rate, byte rate, block align, bits per sample
+        result.extend_from_slice(&1u16.to_le_bytes());
+        result.extend_from_slice(&1u16.to_le_bytes());
+        result.extend_from_slice(&sample_rate.to_le_bytes());
+        result.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+        result.extend_from_slice(&2u16.to_le_bytes());
+        result.extend_from_slice(&16u16.to_le_bytes());
+        result.extend_from_slice(b"data");
+        result.extend_from_slice(&data_size.to_le_bytes());
+        for sample in self.samples.iter() {
+            result.extend_from_slice(&sample.to_le_bytes());
+        }
+        result
+    }
+}
@@ -1,4 +1,15 @@
+pub mod audio;
 pub mod backend;
+#[cfg(feature = "archive")]
+pub mod container;
+pub mod diagnostic;
 pub mod error;
 pub mod frontend;
+pub mod patch;
+pub mod rom;
+pub mod statediff;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod trace;
 pub mod utils;
+pub mod video;
@@ -0,0 +1,356 @@
+use crate::error::Error;
+
+pub const PATCH_EXTENSIONS: [&str; 2] = ["ips", "bps"];
+
+const IPS_MAGIC: &[u8] = b"PATCH";
+const IPS_END: &[u8] = b"EOF";
+const BPS_MAGIC: &[u8] = b"BPS1";
+// Source, target and patch checksum
+const BPS_FOOTER_SIZE: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchFormat {
+    Ips,
+    Bps,
+}
+
+impl PatchFormat {
+    pub fn of(data: &[u8]) -> Option<Self> {
+        if data.starts_with(IPS_MAGIC) {
+            Some(Self::Ips)
+        } else if data.starts_with(BPS_MAGIC) {
+            Some(Self::Bps)
+        } else {
+            None
+        }
+    }
+}
+
+// A patch applied to the rom while loading it, e.g. a translation or a rom hack
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomPatch {
+    pub file_name: String,
+    pub data: Vec<u8>,
+}
+
+impl RomPatch {
+    pub fn new(file_name: String, data: Vec<u8>) -> Self {
+        Self { file_name, data }
+    }
+
+    // BPS patches verify the rom they are applied to and the result. IPS patches have no
+    // checksums, so they are applied to any rom they fit.
+    pub fn apply(&self, rom: &[u8]) -> Result<Vec<u8>, Error> {
+        let result = match PatchFormat::of(&self.data) {
+            Some(PatchFormat::Ips) => apply_ips(rom, &self.data),
+            Some(PatchFormat::Bps) => apply_bps(rom, &self.data),
+            None => Err(Error::new("unknown patch format")),
+        };
+        result.map_err(|error| Error::new(format!("patch {}: {}", self.file_name, error)))
+    }
+}
+
+fn truncated() -> Error {
+    Error::new("the patch is truncated")
+}
+
+fn invalid_patch() -> Error {
+    Error::new("invalid patch")
+}
+
+fn invalid_number() -> Error {
+    Error::new("the patch contains an invalid number")
+}
+
+fn apply_ips(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut data = rom.to_vec();
+    let mut position = IPS_MAGIC.len();
+    let mut read = |length: usize| -> Result<&[u8], Error> {
+        let end = position.checked_add(length).ok_or_else(invalid_patch)?;
+        let bytes = patch.get(position..end).ok_or_else(truncated)?;
+        position = end;
+        Ok(bytes)
+    };
+    loop {
+        let offset = read(3)?;
+        if offset == IPS_END {
+            break;
+        }
+        let offset = u32::from_be_bytes([0, offset[0], offset[1], offset[2]]) as usize;
+        let size = read(2)?;
+        let size = u16::from_be_bytes([size[0], size[1]]) as usize;
+        let (length, record) = if size == 0 {
+            // Run length encoded record
+            let run = read(3)?;
+            let length = u16::from_be_bytes([run[0], run[1]]) as usize;
+            (length, vec![run[2]; length])
+        } else {
+            (size, read(size)?.to_vec())
+        };
+        let end = offset.checked_add(length).ok_or_else(invalid_patch)?;
+        if data.len() < end {
+            data.resize(end, 0);
+        }
+        data[offset..end].copy_from_slice(&record);
+    }
+    // Optional extension, which cuts the rom to the given size
+    if let Ok(size) = read(3) {
+        data.truncate(u32::from_be_bytes([0, size[0], size[1], size[2]]) as usize);
+    }
+    Ok(data)
+}
+
+fn read_number(patch: &[u8], position: &mut usize) -> Result<usize, Error> {
+    let mut number: usize = 0;
+    let mut shift: usize = 1;
+    loop {
+        let byte = *patch.get(*position).ok_or_else(truncated)?;
+        *position += 1;
+        number = (byte as usize & 0x7F)
+            .checked_mul(shift)
+            .and_then(|value| number.checked_add(value))
+            .ok_or_else(invalid_number)?;
+        if byte & 0x80 != 0 {
+            return Ok(number);
+        }
+        shift = shift.checked_mul(0x80).ok_or_else(invalid_number)?;
+        number = number.checked_add(shift).ok_or_else(invalid_number)?;
+    }
+}
+
+fn read_crc(patch: &[u8], position: usize) -> u32 {
+    u32::from_le_bytes([
+        patch[position],
+        patch[position + 1],
+        patch[position + 2],
+        patch[position + 3],
+    ])
+}
+
+fn apply_bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, Error> {
+    if patch.len() < BPS_MAGIC.len() + BPS_FOOTER_SIZE {
+        return Err(truncated());
+    }
+    let footer = patch.len() - BPS_FOOTER_SIZE;
+    if crc32fast::hash(&patch[..patch.len() - 4]) != read_crc(patch, footer + 8) {
+        return Err(Error::new("the patch file is corrupt"));
+    }
+    if crc32fast::hash(rom) != read_crc(patch, footer) {
+        return Err(Error::new("the patch was made for a different rom"));
+    }
+
+    let mut position = BPS_MAGIC.len();
+    let source_size = read_number(patch, &mut position)?;
+    let target_size = read_number(patch, &mut position)?;
+    let metadata_size = read_number(patch, &mut position)?;
+    position = position
+        .checked_add(metadata_size)
+        .ok_or_else(invalid_patch)?;
+    if source_size != rom.len() {
+        return Err(Error::new("the patch was made for a different rom"));
+    }
+
+    let invalid = || Error::new("the patch reads outside of the rom");
+    // The sizes come from the patch, which must not make us reserve more than it can produce
+    let mut target = Vec::with_capacity(target_size.min(rom.len().saturating_add(patch.len())));
+    let (mut source_offset, mut target_offset) = (0isize, 0isize);
+    while position < footer {
+        let action = read_number(patch, &mut position)?;
+        let length = (action >> 2) + 1;
+        if target
+            .len()
+            .checked_add(length)
+            .is_none_or(|end| end > target_size)
+        {
+            return Err(Error::new("the patch writes past the size of the target"));
+        }
+        match action & 3 {
+            // Source read, the bytes stay as in the rom
+            0 => {
+                let start = target.len();
+                let end = start.checked_add(length).ok_or_else(invalid_patch)?;
+                target.extend_from_slice(rom.get(start..end).ok_or_else(invalid)?);
+            }
+            // Target read, the bytes follow in the patch
+            1 => {
+                let end = position.checked_add(length).ok_or_else(invalid_patch)?;
+                let bytes = patch
+                    .get(position..end)
+                    .filter(|_| end <= footer)
+                    .ok_or_else(truncated)?;
+                target.extend_from_slice(bytes);
+                position = end;
+            }
+            // Source copy and target copy, both from a position relative to the previous copy
+            command => {
+                let data = read_number(patch, &mut position)?;
+                let delta = isize::try_from(data >> 1).map_err(|_| invalid_patch())?;
+                let offset = if command == 2 {
+                    &mut source_offset
+                } else {
+                    &mut target_offset
+                };
+                *offset = if data & 1 != 0 {
+                    offset.checked_sub(delta)
+                } else {
+                    offset.checked_add(delta)
+                }
+                .ok_or_else(invalid_patch)?;
+                for _ in 0..length {
+                    let index = usize::try_from(*offset).map_err(|_| invalid())?;
+                    let byte = if command == 2 {
+                        rom.get(index)
+                    } else {
+                        target.get(index)
+                    };
+                    target.push(*byte.ok_or_else(invalid)?);
+                    *offset += 1;
+                }
+            }
+        }
+    }
+
+    if target.len() != target_size || crc32fast::hash(&target) != read_crc(patch, footer + 4) {
+        return Err(Error::new(
+            "the patched rom does not match the expected checksum",
+        ));
+    }
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_number(buffer: &mut Vec<u8>, mut number: usize) {
+        loop {
+            let byte = (number & 0x7F) as u8;
+            number >>= 7;
+            if number == 0 {
+                buffer.push(byte | 0x80);
+                return;
+            }
+            buffer.push(byte);
+            number -= 1;
+        }
+    }
+
+    fn finish_bps(mut patch: Vec<u8>, source: &[u8], target: &[u8]) -> Vec<u8> {
+        patch.extend_from_slice(&crc32fast::hash(source).to_le_bytes());
+        patch.extend_from_slice(&crc32fast::hash(target).to_le_bytes());
+        let checksum = crc32fast::hash(&patch);
+        patch.extend_from_slice(&checksum.to_le_bytes());
+        patch
+    }
+
+    #[test]
+    fn applies_ips_records() {
+        let mut patch = IPS_MAGIC.to_vec();
+        // Two bytes at 0x0001
+        patch.extend_from_slice(&[0, 0, 1, 0, 2, 0xAA, 0xBB]);
+        // A run of three bytes at 0x0005, past the end of the rom
+        patch.extend_from_slice(&[0, 0, 5, 0, 0, 0, 3, 0xCC]);
+        patch.extend_from_slice(IPS_END);
+        let patch = RomPatch::new("hack.ips".to_string(), patch);
+        assert_eq!(
+            patch.apply(&[0, 1, 2, 3]).unwrap(),
+            vec![0, 0xAA, 0xBB, 3, 0, 0xCC, 0xCC, 0xCC]
+        );
+    }
+
+    #[test]
+    fn ips_without_end_fails() {
+        let mut patch = IPS_MAGIC.to_vec();
+        patch.extend_from_slice(&[0, 0, 1, 0, 2, 0xAA]);
+        let patch = RomPatch::new("hack.ips".to_string(), patch);
+        assert!(patch.apply(&[0, 1, 2, 3]).is_err());
+    }
+
+    #[test]
+    fn applies_bps_actions() {
+        let source = [1, 2, 3, 4, 5, 6];
+        let target = [1, 2, 9, 9, 5, 6, 1, 2];
+        let mut patch = BPS_MAGIC.to_vec();
+        write_number(&mut patch, source.len());
+        write_number(&mut patch, target.len());
+        write_number(&mut patch, 0);
+        // Source read of 2, target read of 1, target copy of the 9, source read of 2
+        write_number(&mut patch, 1 << 2);
+        write_number(&mut patch, 1);
+        patch.push(9);
+        write_number(&mut patch, 3);
+        write_number(&mut patch, 2 << 1);
+        write_number(&mut patch, 1 << 2);
+        // Source copy of 2 from the start of the rom
+        write_number(&mut patch, (1 << 2) | 2);
+        write_number(&mut patch, 0);
+        let patch = RomPatch::new("hack.bps".to_string(), finish_bps(patch, &source, &target));
+        assert_eq!(patch.apply(&source).unwrap(), target.to_vec());
+    }
+
+    #[test]
+    fn bps_rejects_other_rom() {
+        let source = [1, 2, 3];
+        let mut patch = BPS_MAGIC.to_vec();
+        write_number(&mut patch, 3);
+        write_number(&mut patch, 3);
+        write_number(&mut patch, 0);
+        write_number(&mut patch, 2 << 2);
+        let patch = RomPatch::new("hack.bps".to_string(), finish_bps(patch, &source, &source));
+        assert_eq!(patch.apply(&source).unwrap(), source.to_vec());
+        let error = patch.apply(&[1, 2, 4]).unwrap_err();
+        assert!(error.to_string().contains("different rom"));
+    }
+
+    #[test]
+    fn bps_with_a_huge_target_size_fails() {
+        let source = [1, 2, 3];
+        let mut patch = BPS_MAGIC.to_vec();
+        write_number(&mut patch, 3);
+        write_number(&mut patch, 1 << 60);
+        write_number(&mut patch, 0);
+        write_number(&mut patch, 2 << 2);
+        let patch = RomPatch::new("hack.bps".to_string(), finish_bps(patch, &source, &source));
+        assert!(patch.apply(&source).is_err());
+    }
+
+    #[test]
+    fn bps_writing_past_the_target_size_fails() {
+        let source = [1, 2, 3];
+        let mut patch = BPS_MAGIC.to_vec();
+        write_number(&mut patch, 3);
+        write_number(&mut patch, 3);
+        write_number(&mut patch, 0);
+        // A target read of one byte, then a target copy repeating it a million times
+        write_number(&mut patch, 1);
+        patch.push(7);
+        write_number(&mut patch, ((1_000_000 - 1) << 2) | 3);
+        write_number(&mut patch, 0);
+        let patch = RomPatch::new("hack.bps".to_string(), finish_bps(patch, &source, &source));
+        let error = patch.apply(&source).unwrap_err();
+        assert!(error.to_string().contains("past the size of the target"));
+    }
+
+    #[test]
+    fn bps_with_an_overflowing_metadata_size_fails() {
+        let source = [1, 2, 3];
+        let mut patch = BPS_MAGIC.to_vec();
+        write_number(&mut patch, 3);
+        write_number(&mut patch, 3);
+        write_number(&mut patch, usize::MAX - 2);
+        let patch = RomPatch::new("hack.bps".to_string(), finish_bps(patch, &source, &source));
+        let error = patch.apply(&source).unwrap_err();
+        assert!(error.to_string().contains("invalid patch"));
+    }
+
+    #[test]
+    fn bps_with_an_over_long_number_fails() {
+        let source = [1, 2, 3];
+        let mut patch = BPS_MAGIC.to_vec();
+        patch.extend_from_slice(&[0x7F; 12]);
+        patch.push(0x80);
+        let patch = RomPatch::new("hack.bps".to_string(), finish_bps(patch, &source, &source));
+        let error = patch.apply(&source).unwrap_err();
+        assert!(error.to_string().contains("invalid number"));
+    }
+}
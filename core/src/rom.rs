@@ -0,0 +1,199 @@
+use std::fmt::{self, Display};
+
+use crate::{error::Error, patch::RomPatch, utils::rom_hash};
+
+#[derive(Debug, Clone, Default)]
+pub struct Rom {
+    pub file_name: Option<String>,
+    pub data: Vec<u8>,
+    // Applied before the rom is handed to the backend
+    pub patch: Option<RomPatch>,
+}
+
+impl Rom {
+    pub fn new(file_name: Option<String>, data: Vec<u8>) -> Self {
+        Self {
+            file_name,
+            data,
+            patch: None,
+        }
+    }
+
+    pub fn with_patch(mut self, patch: Option<RomPatch>) -> Self {
+        self.patch = patch;
+        self
+    }
+
+    pub fn apply_patch(mut self) -> Result<Self, Error> {
+        if let Some(patch) = self.patch.take() {
+            self.data = patch.apply(&self.data)?;
+        }
+        Ok(self)
+    }
+
+    pub fn extension(&self) -> Option<&str> {
+        self.file_name
+            .as_deref()
+            .and_then(|name| name.rsplit_once('.'))
+            .map(|(_, extension)| extension)
+    }
+
+    pub fn hash(&self) -> String {
+        rom_hash(&self.data)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DetectionConfidence {
+    None,
+    Low,
+    Medium,
+    High,
+}
+
+// Inspects header magic, size or known hashes of a rom to judge whether a backend can run it
+pub type RomDetector = fn(&Rom) -> DetectionConfidence;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Detection {
+    pub backend: &'static str,
+    pub confidence: DetectionConfidence,
+}
+
+// Returns the best detection if it is certain enough to skip asking the user
+pub fn unambiguous_detection(detections: &[Detection]) -> Option<Detection> {
+    let best = *detections.first()?;
+    let runner_up = detections
+        .get(1)
+        .map_or(DetectionConfidence::None, |d| d.confidence);
+    match best.confidence {
+        DetectionConfidence::High if runner_up < DetectionConfidence::High => Some(best),
+        DetectionConfidence::Medium if runner_up < DetectionConfidence::Medium => Some(best),
+        _ => None,
+    }
+}
+
+// A dump in the database of a backend, identified by its SHA-1 hash
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KnownDump {
+    pub title: &'static str,
+    pub hash: &'static str,
+    // Bad dumps are listed too, so they can be told apart from unknown roms
+    pub good: bool,
+}
+
+// Signs of a corrupt rom file, which make the rom behave differently than on the real machine
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RomIssue {
+    // Listed as bad dump of the title
+    BadDump(String),
+    // Larger than the header says, usually padding or garbage at the end
+    Overdump { expected: usize, actual: usize },
+    // Smaller than the header says, parts of the rom are missing
+    Truncated { expected: usize, actual: usize },
+    // The checksum in the header does not match the data
+    HeaderChecksum,
+}
+
+impl Display for RomIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadDump(title) => write!(f, "known bad dump of {}", title),
+            Self::Overdump { expected, actual } => {
+                write!(f, "overdump, {} bytes instead of {}", actual, expected)
+            }
+            Self::Truncated { expected, actual } => {
+                write!(f, "truncated, {} bytes instead of {}", actual, expected)
+            }
+            Self::HeaderChecksum => write!(f, "header checksum does not match"),
+        }
+    }
+}
+
+// Compares a rom against what its header says, for backends whose roms have one
+pub type RomHeaderCheck = fn(&Rom) -> Vec<RomIssue>;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RomIntegrity {
+    // Title of the good dump the rom matches
+    pub verified: Option<String>,
+    pub issues: Vec<RomIssue>,
+}
+
+impl RomIntegrity {
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+// Roms missing from the database are neither verified nor bad, only their header is checked
+pub fn check_integrity(
+    rom: &Rom,
+    known_dumps: &[KnownDump],
+    header_check: Option<RomHeaderCheck>,
+) -> RomIntegrity {
+    let mut integrity = RomIntegrity::default();
+    if !known_dumps.is_empty() {
+        let hash = rom.hash();
+        match known_dumps.iter().find(|dump| dump.hash == hash) {
+            Some(dump) if dump.good => integrity.verified = Some(dump.title.to_string()),
+            Some(dump) => integrity
+                .issues
+                .push(RomIssue::BadDump(dump.title.to_string())),
+            None => {}
+        }
+    }
+    // The database knows better than the header
+    if integrity.verified.is_none() {
+        if let Some(header_check) = header_check {
+            integrity.issues.extend(header_check(rom));
+        }
+    }
+    integrity
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integrity_checks_the_database_before_the_header() {
+        let good = Rom::new(None, vec![1, 2, 3]);
+        let bad = Rom::new(None, vec![1, 2]);
+        let known_dumps = [
+            KnownDump {
+                title: "Game",
+                hash: Box::leak(good.hash().into_boxed_str()),
+                good: true,
+            },
+            KnownDump {
+                title: "Game",
+                hash: Box::leak(bad.hash().into_boxed_str()),
+                good: false,
+            },
+        ];
+        let header_check: RomHeaderCheck = |rom| {
+            vec![RomIssue::Truncated {
+                expected: 3,
+                actual: rom.data.len(),
+            }]
+        };
+
+        let integrity = check_integrity(&good, &known_dumps, Some(header_check));
+        assert_eq!(integrity.verified.as_deref(), Some("Game"));
+        assert!(integrity.is_ok());
+
+        let integrity = check_integrity(&bad, &known_dumps, Some(header_check));
+        assert_eq!(
+            integrity.issues,
+            vec![
+                RomIssue::BadDump("Game".to_string()),
+                RomIssue::Truncated {
+                    expected: 3,
+                    actual: 2
+                }
+            ]
+        );
+        assert!(check_integrity(&bad, &[], None).is_ok());
+    }
+}
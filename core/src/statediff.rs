@@ -0,0 +1,301 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Display},
+};
+
+use femtos::Instant;
+
+use crate::{
+    backend::{Backend, snapshot::StatePayload},
+    error::Error,
+};
+
+// Longest run of differing bytes reported as one difference
+pub const MAX_DIFF_BYTES: usize = 16;
+
+// Something that differs between two save states of the same machine
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateDifference {
+    // A value of the machine or of a register, formatted for display
+    Field {
+        name: String,
+        left: String,
+        right: String,
+    },
+    // Bytes which differ, in the memory of a component or in its saved state
+    Bytes {
+        name: String,
+        offset: usize,
+        left: Vec<u8>,
+        right: Vec<u8>,
+    },
+}
+
+impl Display for StateDifference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Field { name, left, right } => write!(f, "{}: {} | {}", name, left, right),
+            Self::Bytes {
+                name,
+                offset,
+                left,
+                right,
+            } => {
+                write!(f, "{}[{:04X}]:", name, offset)?;
+                for byte in left {
+                    write!(f, " {:02X}", byte)?;
+                }
+                write!(f, " |")?;
+                for byte in right {
+                    write!(f, " {:02X}", byte)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// The seed followed by the four words of the generator
+fn format_rng(payload: &StatePayload) -> String {
+    let mut words = vec![payload.rng_seed];
+    words.extend(payload.rng_state);
+    format!("{:X?}", words)
+}
+
+// What a component shows of itself while a state is loaded
+#[derive(Default)]
+struct ComponentView {
+    registers: Vec<(&'static str, u64)>,
+    memory: Vec<u8>,
+}
+
+fn capture_components(
+    backend: &mut Backend,
+    data: &[u8],
+) -> Result<BTreeMap<String, ComponentView>, Error> {
+    backend.load_state(data)?;
+    let mut views = BTreeMap::new();
+    for (name, component) in backend.get_all_components() {
+        let mut component = component.borrow_mut();
+        let mut view = ComponentView::default();
+        if let Some(traceable) = component.as_traceable() {
+            view.registers = traceable.trace_registers();
+        }
+        if let Some(addressable) = component.as_addressable() {
+            let mut memory = vec![0; addressable.size()];
+            // Memory that cannot be peeked is left out
+            if addressable.peek(0, &mut memory).is_ok() {
+                view.memory = memory;
+            }
+        }
+        views.insert(name, view);
+    }
+    Ok(views)
+}
+
+fn format_instant(instant: Option<&Instant>) -> String {
+    match instant {
+        Some(instant) => format!("{:.9}s", instant.as_duration().as_nanos() as f64 / 1e9),
+        None => "-".to_string(),
+    }
+}
+
+fn diff_field(differences: &mut Vec<StateDifference>, name: String, left: String, right: String) {
+    if left != right {
+        differences.push(StateDifference::Field { name, left, right });
+    }
+}
+
+fn diff_bytes(differences: &mut Vec<StateDifference>, name: &str, left: &[u8], right: &[u8]) {
+    if left.len() != right.len() {
+        diff_field(
+            differences,
+            format!("{} size", name),
+            left.len().to_string(),
+            right.len().to_string(),
+        );
+    }
+    let mut offset = 0;
+    let length = left.len().min(right.len());
+    while offset < length {
+        if left[offset] == right[offset] {
+            offset += 1;
+            continue;
+        }
+        let start = offset;
+        while offset < length && offset - start < MAX_DIFF_BYTES && left[offset] != right[offset] {
+            offset += 1;
+        }
+        differences.push(StateDifference::Bytes {
+            name: name.to_string(),
+            offset: start,
+            left: left[start..offset].to_vec(),
+            right: right[start..offset].to_vec(),
+        });
+    }
+}
+
+// Components queued in only one of the states show up with "-" on the other side
+fn diff_wakeups(
+    differences: &mut Vec<StateDifference>,
+    left: &BTreeMap<String, Instant>,
+    right: &BTreeMap<String, Instant>,
+) {
+    let names: BTreeSet<&String> = left.keys().chain(right.keys()).collect();
+    for name in names {
+        diff_field(
+            differences,
+            format!("{} wakeup", name),
+            format_instant(left.get(name)),
+            format_instant(right.get(name)),
+        );
+    }
+}
+
+// Compares two payloads of save_state field by field: the machine clock, the rng, the rtc, the
+// scheduler queue, the registers and memory of every component and what else the components
+// saved. The states are loaded into the backend to read the registers and memory, its own state is
+// restored afterwards.
+pub fn diff_states(
+    backend: &mut Backend,
+    left: &[u8],
+    right: &[u8],
+) -> Result<Vec<StateDifference>, Error> {
+    let (left_payload, right_payload) = (StatePayload::read(left)?, StatePayload::read(right)?);
+    let current = backend.save_state()?;
+    let views = capture_components(backend, left)
+        .and_then(|left| Ok((left, capture_components(backend, right)?)));
+    backend.load_state(&current)?;
+    let (left_views, right_views) = views?;
+
+    let mut differences = vec![];
+    diff_field(
+        &mut differences,
+        "clock".to_string(),
+        format_instant(Some(&left_payload.clock)),
+        format_instant(Some(&right_payload.clock)),
+    );
+    diff_field(
+        &mut differences,
+        "frame count".to_string(),
+        left_payload.frame_count.to_string(),
+        right_payload.frame_count.to_string(),
+    );
+    diff_field(
+        &mut differences,
+        "rng".to_string(),
+        format_rng(&left_payload),
+        format_rng(&right_payload),
+    );
+    diff_field(
+        &mut differences,
+        "rtc".to_string(),
+        format!("{:?}", left_payload.rtc),
+        format!("{:?}", right_payload.rtc),
+    );
+    diff_wakeups(
+        &mut differences,
+        &left_payload.wakeups,
+        &right_payload.wakeups,
+    );
+
+    for (name, left_view) in left_views.iter() {
+        let Some(right_view) = right_views.get(name) else {
+            continue;
+        };
+        let count = differences.len();
+        for (register, value) in left_view.registers.iter() {
+            let other = right_view
+                .registers
+                .iter()
+                .find(|(other, _)| other == register)
+                .map(|(_, value)| format!("{:X}", value));
+            diff_field(
+                &mut differences,
+                format!("{} {}", name, register),
+                format!("{:X}", value),
+                other.unwrap_or_else(|| "-".to_string()),
+            );
+        }
+        diff_bytes(
+            &mut differences,
+            name,
+            &left_view.memory,
+            &right_view.memory,
+        );
+
+        // The rest of the saved state only matters when the registers and memory look the same
+        if let (true, Some(left_state), Some(right_state)) = (
+            differences.len() == count,
+            left_payload.state(name),
+            right_payload.state(name),
+        ) {
+            diff_bytes(
+                &mut differences,
+                &format!("{} state", name),
+                left_state,
+                right_state,
+            );
+        }
+    }
+    Ok(differences)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_differing_bytes_into_runs() {
+        let left = vec![0; 40];
+        let mut right = left.clone();
+        right[2] = 1;
+        right[3] = 2;
+        for byte in right[10..30].iter_mut() {
+            *byte = 0xFF;
+        }
+        let mut differences = vec![];
+        diff_bytes(&mut differences, "ram", &left, &right);
+        assert_eq!(differences.len(), 3);
+        assert_eq!(differences[0].to_string(), "ram[0002]: 00 00 | 01 02");
+        assert!(
+            matches!(&differences[1], StateDifference::Bytes { offset: 10, left, .. } if left.len() == MAX_DIFF_BYTES)
+        );
+        assert!(
+            matches!(&differences[2], StateDifference::Bytes { offset: 26, left, .. } if left.len() == 4)
+        );
+    }
+
+    #[test]
+    fn reports_size_changes() {
+        let mut differences = vec![];
+        diff_bytes(&mut differences, "cpu state", &[1, 2], &[1, 2, 3]);
+        assert_eq!(
+            differences,
+            vec![StateDifference::Field {
+                name: "cpu state size".to_string(),
+                left: "2".to_string(),
+                right: "3".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn reports_wakeups_of_either_state() {
+        let left = BTreeMap::from([("cpu".to_string(), Instant::START)]);
+        let right = BTreeMap::from([
+            ("cpu".to_string(), Instant::START),
+            ("timer".to_string(), Instant::START),
+        ]);
+        let mut differences = vec![];
+        diff_wakeups(&mut differences, &left, &right);
+        assert_eq!(
+            differences,
+            vec![StateDifference::Field {
+                name: "timer wakeup".to_string(),
+                left: "-".to_string(),
+                right: "0.000000000s".to_string(),
+            }]
+        );
+    }
+}
@@ -0,0 +1,321 @@
+use std::collections::HashMap;
+
+use femtos::{Duration, Instant};
+
+use crate::{
+    backend::{
+        Backend,
+        component::{Addressable, Component, MemoryAddress, MemorySize, Steppable},
+        memory::MemoryBlock,
+    },
+    error::Error,
+    frontend::{
+        Frontend,
+        audio::{AudioReceiver, Sample},
+        debug::{DebugReceiver, DebugSample},
+        error::FrontendError,
+        graphics::{Frame, FrameReceiver, Pixel},
+        input::{ButtonState, InputEvent, InputSender, KeyboardEventKey},
+        movie::MoviePlayer,
+        text::TextReceiver,
+        trace::{TraceEntry, TraceReceiver},
+    },
+};
+
+// A backend with nothing but a bus, in which components are stepped directly instead of through
+// the scheduler. Panics on any error, as it is only meant to be used in tests.
+#[derive(Default)]
+pub struct MockBackend {
+    backend: Backend,
+}
+
+impl MockBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_memory(self, address: MemoryAddress, size: MemorySize) -> Self {
+        self.with_data(address, vec![0; size])
+    }
+
+    pub fn with_data(mut self, address: MemoryAddress, data: Vec<u8>) -> Self {
+        let name = format!("mem_{:#x}", address);
+        self.backend
+            .add_addressable_component(&name, address, Component::new(MemoryBlock::from(data)))
+            .expect("memory overlaps another mount");
+        self
+    }
+
+    pub fn with_component(mut self, name: &str, component: Component) -> Self {
+        self.backend.add_component(name, component);
+        self
+    }
+
+    pub fn with_rng_seed(mut self, seed: u64) -> Self {
+        self.backend.set_rng_seed(seed);
+        self
+    }
+
+    pub fn backend(&self) -> &Backend {
+        &self.backend
+    }
+
+    pub fn backend_mut(&mut self) -> &mut Backend {
+        &mut self.backend
+    }
+
+    pub fn clock(&self) -> Instant {
+        self.backend.get_current_clock()
+    }
+
+    // Steps the component once and advances the clock by the duration it asked for
+    pub fn step(&mut self, component: &mut dyn Steppable) -> Duration {
+        let duration = component.step(&self.backend).expect("step failed");
+        self.backend.set_clock(self.clock() + duration);
+        duration
+    }
+
+    pub fn step_n(&mut self, component: &mut dyn Steppable, count: usize) -> Duration {
+        (0..count).fold(Duration::from_nanos(0), |total, _| {
+            total + self.step(component)
+        })
+    }
+
+    // Steps the component until the clock reached the instant
+    pub fn step_until(&mut self, component: &mut dyn Steppable, clock: Instant) {
+        while self.clock() < clock {
+            self.step(component);
+        }
+    }
+
+    pub fn read(&self, address: MemoryAddress, size: MemorySize) -> Vec<u8> {
+        let mut buffer = vec![0; size];
+        self.backend
+            .get_bus()
+            .peek(address, &mut buffer)
+            .expect("read from unmapped memory");
+        buffer
+    }
+
+    pub fn read_u8(&self, address: MemoryAddress) -> u8 {
+        self.read(address, 1)[0]
+    }
+
+    pub fn write(&self, address: MemoryAddress, data: &[u8]) {
+        self.backend
+            .get_bus()
+            .write(address, data)
+            .expect("write to unmapped memory");
+    }
+
+    pub fn write_u8(&self, address: MemoryAddress, value: u8) {
+        self.write(address, &[value]);
+    }
+
+    pub fn assert_memory(&self, address: MemoryAddress, expected: &[u8]) {
+        assert_eq!(
+            self.read(address, expected.len()),
+            expected,
+            "memory at {:#06x}",
+            address
+        );
+    }
+}
+
+// Collects all channels a backend registers, so tests can feed input and inspect the output
+#[derive(Default)]
+pub struct MockFrontend {
+    text: Option<TextReceiver>,
+    graphics: Option<FrameReceiver>,
+    secondary_graphics: HashMap<String, FrameReceiver>,
+    audio: Option<AudioReceiver>,
+    debug: Option<DebugReceiver>,
+    trace: Option<TraceReceiver>,
+    input: Option<InputSender>,
+}
+
+impl MockFrontend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn frames_emitted(&self) -> u64 {
+        self.graphics.as_ref().map_or(0, FrameReceiver::emitted)
+    }
+
+    pub fn latest_frame(&self) -> Frame {
+        self.graphics
+            .as_ref()
+            .expect("no graphics registered")
+            .latest()
+            .expect("no frame emitted")
+            .1
+    }
+
+    // Every frame emitted since the last call, with the clock it was emitted at
+    pub fn frames(&self) -> Vec<(Instant, Frame)> {
+        self.graphics
+            .as_ref()
+            .expect("no graphics registered")
+            .pop_all()
+    }
+
+    pub fn latest_secondary_frame(&self, name: &str) -> Frame {
+        self.secondary_graphics
+            .get(name)
+            .unwrap_or_else(|| panic!("no screen named {} registered", name))
+            .latest()
+            .expect("no frame emitted")
+            .1
+    }
+
+    pub fn audio_samples(&self) -> Vec<Sample> {
+        self.audio
+            .as_ref()
+            .expect("no audio registered")
+            .pop_range(..)
+            .into_iter()
+            .map(|(_, sample)| sample)
+            .collect()
+    }
+
+    pub fn text_lines(&self) -> Vec<String> {
+        let text = self.text.as_ref().expect("no text registered");
+        std::iter::from_fn(|| text.pop())
+            .map(|(_, line)| line)
+            .collect()
+    }
+
+    pub fn debug_samples(&self, name: &str) -> Vec<f64> {
+        self.debug
+            .as_ref()
+            .expect("no debug values registered")
+            .pop_all()
+            .into_iter()
+            .filter(|(_, sample): &(Instant, DebugSample)| sample.name == name)
+            .map(|(_, sample)| sample.value)
+            .collect()
+    }
+
+    // Enables the trace of the cpu, only instructions executed afterwards are recorded
+    pub fn enable_trace(&self) {
+        self.trace
+            .as_ref()
+            .expect("no trace registered")
+            .set_enabled(true);
+    }
+
+    pub fn trace_entries(&self) -> Vec<TraceEntry> {
+        self.trace
+            .as_ref()
+            .expect("no trace registered")
+            .pop_all()
+            .into_iter()
+            .map(|(_, entry)| entry)
+            .collect()
+    }
+
+    pub fn press(&self, key: KeyboardEventKey) {
+        self.send_key(key, ButtonState::Pressed);
+    }
+
+    pub fn release(&self, key: KeyboardEventKey) {
+        self.send_key(key, ButtonState::Released);
+    }
+
+    // Hands over as many events of the movie as the input queue takes, call again while running
+    pub fn feed_movie(&self, player: &mut MoviePlayer) {
+        player.feed(self.input.as_ref().expect("no input registered"));
+    }
+
+    fn send_key(&self, key: KeyboardEventKey, state: ButtonState) {
+        self.input
+            .as_ref()
+            .expect("no input registered")
+            .add(InputEvent::Keyboard(key, state));
+    }
+
+    pub fn assert_frame_count(&self, expected: u64) {
+        assert_eq!(self.frames_emitted(), expected, "frames emitted");
+    }
+
+    pub fn assert_pixel(frame: &Frame, x: usize, y: usize, expected: Pixel) {
+        assert_eq!(
+            frame.data[y * frame.width + x],
+            expected,
+            "pixel at {},{}",
+            x,
+            y
+        );
+    }
+}
+
+impl Frontend for MockFrontend {
+    type Error = Error;
+
+    fn register_text_receiver(
+        &mut self,
+        receiver: TextReceiver,
+    ) -> Result<(), FrontendError<Self::Error>> {
+        self.text = Some(receiver);
+        Ok(())
+    }
+
+    fn register_graphics_receiver(
+        &mut self,
+        receiver: FrameReceiver,
+    ) -> Result<(), FrontendError<Self::Error>> {
+        self.graphics = Some(receiver);
+        Ok(())
+    }
+
+    fn register_secondary_graphics_receiver(
+        &mut self,
+        name: &str,
+        receiver: FrameReceiver,
+    ) -> Result<(), FrontendError<Self::Error>> {
+        self.secondary_graphics.insert(name.to_string(), receiver);
+        Ok(())
+    }
+
+    fn register_audio_receiver(
+        &mut self,
+        receiver: AudioReceiver,
+    ) -> Result<(), FrontendError<Self::Error>> {
+        self.audio = Some(receiver);
+        Ok(())
+    }
+
+    fn register_debug_receiver(
+        &mut self,
+        receiver: DebugReceiver,
+    ) -> Result<(), FrontendError<Self::Error>> {
+        self.debug = Some(receiver);
+        Ok(())
+    }
+
+    fn register_trace_receiver(
+        &mut self,
+        receiver: TraceReceiver,
+    ) -> Result<(), FrontendError<Self::Error>> {
+        self.trace = Some(receiver);
+        Ok(())
+    }
+
+    fn register_input_sender(
+        &mut self,
+        sender: InputSender,
+    ) -> Result<(), FrontendError<Self::Error>> {
+        self.input = Some(sender);
+        Ok(())
+    }
+}
+
+// Instants after the start of the machine, as most tests only deal with short runs
+pub fn at_ms(ms: u64) -> Instant {
+    Instant::START + Duration::from_millis(ms)
+}
+
+pub fn at_ns(ns: u64) -> Instant {
+    Instant::START + Duration::from_nanos(ns)
+}
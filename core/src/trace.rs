@@ -0,0 +1,329 @@
+use std::{
+    collections::VecDeque,
+    fmt::{self, Display},
+};
+
+use femtos::Instant;
+
+use crate::{
+    backend::{
+        Backend,
+        component::{Addressable, MemoryAddress},
+    },
+    error::Error,
+};
+
+// Key of the bytes at the program counter, as used by gameboy-doctor style logs
+const PC_MEMORY_KEY: &str = "PCMEM";
+// Number of instructions stepped for other components before the traced one is considered stuck
+const MAX_FOREIGN_STEPS: usize = 1_000_000;
+// Replayed traces keep the latest steps only, fast cpus run millions of them per second
+pub const MAX_REPLAY_LINES: usize = 100_000;
+
+// One line of a reference log holds the state before executing an instruction, given as
+// whitespace separated NAME:VALUE pairs with hexadecimal values, e.g. "PC:0200 I:0000 V0:00".
+// PCMEM lists the comma separated bytes at the program counter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceValue {
+    Register(String, u64),
+    PcMemory(Vec<u8>),
+}
+
+pub fn parse_trace_line(line: &str) -> Result<Vec<TraceValue>, Error> {
+    line.split_whitespace()
+        .map(|pair| {
+            let (name, value) = pair
+                .split_once(':')
+                .ok_or_else(|| Error::new(format!("invalid trace entry {}", pair)))?;
+            let invalid = || Error::new(format!("invalid trace value {}", pair));
+            if name.eq_ignore_ascii_case(PC_MEMORY_KEY) {
+                let bytes = value
+                    .split(',')
+                    .map(|byte| u8::from_str_radix(byte, 16).map_err(|_| invalid()))
+                    .collect::<Result<_, _>>()?;
+                return Ok(TraceValue::PcMemory(bytes));
+            }
+            let value =
+                u64::from_str_radix(value.trim_start_matches("0x"), 16).map_err(|_| invalid())?;
+            Ok(TraceValue::Register(name.to_uppercase(), value))
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceMismatch {
+    pub name: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct TraceDivergence {
+    // Line numbers start at 1, like in editors
+    pub line_number: usize,
+    pub clock: Instant,
+    pub reference_line: String,
+    pub previous_line: Option<String>,
+    pub registers: Vec<(&'static str, u64)>,
+    pub mismatches: Vec<TraceMismatch>,
+    pub pc_memory: Option<(MemoryAddress, Vec<u8>)>,
+}
+
+impl Display for TraceDivergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let nanos = self.clock.as_duration().as_nanos();
+        writeln!(
+            f,
+            "trace diverged at line {} ({}.{:09}s)",
+            self.line_number,
+            nanos / 1_000_000_000,
+            nanos % 1_000_000_000
+        )?;
+        if let Some(previous_line) = &self.previous_line {
+            writeln!(f, "last matching: {}", previous_line)?;
+        }
+        writeln!(f, "reference:     {}", self.reference_line)?;
+        writeln!(f, "{:>8}  {:>18}  {:>18}", "", "expected", "actual")?;
+        for mismatch in self.mismatches.iter() {
+            writeln!(
+                f,
+                "{:>8}  {:>18}  {:>18}",
+                mismatch.name, mismatch.expected, mismatch.actual
+            )?;
+        }
+        writeln!(f, "registers:")?;
+        for (name, value) in self.registers.iter() {
+            let marker = if self.mismatches.iter().any(|m| m.name == *name) {
+                '>'
+            } else {
+                ' '
+            };
+            writeln!(f, "{} {:>6}: {:#x}", marker, name, value)?;
+        }
+        if let Some((address, bytes)) = &self.pc_memory {
+            write!(f, "memory at {:#06x}:", address)?;
+            for byte in bytes {
+                write!(f, " {:02x}", byte)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum TraceComparison {
+    Matched { lines: usize },
+    Diverged(Box<TraceDivergence>),
+}
+
+// Runs the backend while following the reference log, comparing the state of the traced component
+// before each of its steps. Empty lines and lines starting with # are skipped. Stops at the first
+// divergence, or once the log is exhausted.
+pub fn compare_trace<'a, I>(
+    backend: &mut Backend,
+    component_name: &str,
+    reference: I,
+) -> Result<TraceComparison, Error>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let component = backend.get_component(component_name)?;
+    if component.borrow_mut().as_traceable().is_none() {
+        return Err(Error::new(format!(
+            "component {} does not support tracing",
+            component_name
+        )));
+    }
+
+    let mut lines = 0;
+    let mut previous_line = None;
+    for (index, line) in reference.into_iter().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let expected = parse_trace_line(line)
+            .map_err(|error| Error::new(format!("line {}: {}", index + 1, error)))?;
+
+        let mut foreign_steps = 0;
+        while backend.next_component().as_ref() != Some(&component) {
+            if foreign_steps == MAX_FOREIGN_STEPS {
+                return Err(Error::new(format!(
+                    "component {} is not stepped anymore",
+                    component_name
+                )));
+            }
+            backend.step()?;
+            foreign_steps += 1;
+        }
+
+        let registers = component
+            .borrow_mut()
+            .as_traceable()
+            .unwrap()
+            .trace_registers();
+        let pc = registers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("PC"))
+            .map(|(_, value)| *value as MemoryAddress);
+        let mut mismatches = vec![];
+        let mut pc_memory = None;
+        for value in expected.iter() {
+            match value {
+                TraceValue::Register(name, expected) => {
+                    let actual = registers
+                        .iter()
+                        .find(|(register, _)| register.eq_ignore_ascii_case(name))
+                        .map(|(_, value)| *value);
+                    if actual != Some(*expected) {
+                        mismatches.push(TraceMismatch {
+                            name: name.clone(),
+                            expected: format!("{:#x}", expected),
+                            actual: actual.map_or("missing".to_string(), |v| format!("{:#x}", v)),
+                        });
+                    }
+                }
+                TraceValue::PcMemory(expected) => {
+                    let Some(pc) = pc else {
+                        return Err(Error::new("PCMEM given, but the component has no PC"));
+                    };
+                    let mut actual = vec![0; expected.len()];
+                    backend.get_bus().peek(pc, &mut actual)?;
+                    if actual != *expected {
+                        mismatches.push(TraceMismatch {
+                            name: PC_MEMORY_KEY.to_string(),
+                            expected: format_bytes(expected),
+                            actual: format_bytes(&actual),
+                        });
+                    }
+                    pc_memory = Some((pc, actual));
+                }
+            }
+        }
+
+        if !mismatches.is_empty() {
+            if pc_memory.is_none() {
+                pc_memory = pc.and_then(|pc| {
+                    let mut bytes = vec![0; 8];
+                    backend.get_bus().peek(pc, &mut bytes).ok()?;
+                    Some((pc, bytes))
+                });
+            }
+            return Ok(TraceComparison::Diverged(Box::new(TraceDivergence {
+                line_number: index + 1,
+                clock: backend.get_current_clock(),
+                reference_line: line.to_string(),
+                previous_line,
+                registers,
+                mismatches,
+                pc_memory,
+            })));
+        }
+
+        backend.step()?;
+        lines += 1;
+        previous_line = Some(line.to_string());
+    }
+    Ok(TraceComparison::Matched { lines })
+}
+
+// A line in the format of the reference logs, so recorded traces can be compared later on
+pub fn format_trace_line(registers: &[(&'static str, u64)]) -> String {
+    registers
+        .iter()
+        .map(|(name, value)| format!("{}:{:04X}", name, value))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Goes back to the start with the rewind snapshots and runs up to the end again, recording the
+// state of the traced component before each of its steps. Shows the steps leading up to an event at
+// the end, like a breakpoint hit, which is where the backend stops again.
+pub fn replay_trace(
+    backend: &mut Backend,
+    component_name: &str,
+    start: Instant,
+    end: Instant,
+) -> Result<Vec<String>, Error> {
+    let component = backend.get_component(component_name)?;
+    if component.borrow_mut().as_traceable().is_none() {
+        return Err(Error::new(format!(
+            "component {} does not support tracing",
+            component_name
+        )));
+    }
+    let clock = backend.get_current_clock();
+    if start > end || end > clock {
+        return Err(Error::new("the replay has to end before the current clock"));
+    }
+    if !backend.rewind(clock.duration_since(start))? {
+        return Err(Error::new("rewinding is disabled or has no snapshots yet"));
+    }
+
+    // Only a hit at the end stops the replay, like it stopped the run before
+    let mut lines = VecDeque::new();
+    backend.discard_breakpoint_events_before(end);
+    while backend.next_clock().is_some_and(|next| next <= end) && !backend.has_breakpoint_events() {
+        if backend.next_component().as_ref() == Some(&component) {
+            if lines.len() == MAX_REPLAY_LINES {
+                lines.pop_front();
+            }
+            let registers = component
+                .borrow_mut()
+                .as_traceable()
+                .unwrap()
+                .trace_registers();
+            lines.push_back(format_trace_line(&registers));
+        }
+        backend.step()?;
+        backend.discard_breakpoint_events_before(end);
+    }
+    Ok(lines.into())
+}
+
+fn format_bytes(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_registers_and_pc_memory() {
+        let values = parse_trace_line("PC:0200 v0:0x1f PCMEM:60,05").unwrap();
+        assert_eq!(
+            values,
+            vec![
+                TraceValue::Register("PC".to_string(), 0x200),
+                TraceValue::Register("V0".to_string(), 0x1F),
+                TraceValue::PcMemory(vec![0x60, 0x05]),
+            ]
+        );
+    }
+
+    #[test]
+    fn formatted_lines_parse_again() {
+        let line = format_trace_line(&[("PC", 0x202), ("V0", 0x6)]);
+        assert_eq!(line, "PC:0202 V0:0006");
+        assert_eq!(
+            parse_trace_line(&line).unwrap(),
+            vec![
+                TraceValue::Register("PC".to_string(), 0x202),
+                TraceValue::Register("V0".to_string(), 0x6),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_entries() {
+        assert!(parse_trace_line("PC0200").is_err());
+        assert!(parse_trace_line("PC:zz").is_err());
+        assert!(parse_trace_line("PCMEM:60,").is_err());
+    }
+}
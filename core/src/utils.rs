@@ -5,6 +5,7 @@ use std::{
 };
 
 use femtos::Instant;
+use sha1::{Digest, Sha1};
 
 #[derive(Clone, Default)]
 pub struct Ringbuffer<T>(Arc<Mutex<VecDeque<T>>>, usize);
@@ -29,6 +30,15 @@ impl<T: Clone> Ringbuffer<T> {
         self.0.lock().unwrap().pop_front()
     }
 
+    pub fn pop_front_if(&self, predicate: impl FnOnce(&T) -> bool) -> Option<T> {
+        let mut queue = self.0.lock().unwrap();
+        if queue.front().is_some_and(predicate) {
+            queue.pop_front()
+        } else {
+            None
+        }
+    }
+
     pub fn drain_and_pop_latest(&self) -> Option<T> {
         self.0.lock().unwrap().drain(..).last()
     }
@@ -66,3 +76,60 @@ impl<T: Clone> Ringbuffer<T> {
 }
 
 pub type ClockedRingbuffer<T> = Ringbuffer<(Instant, T)>;
+
+// Hands every value to the queues of all subscribers, so each of them drains at its own pace.
+// Queues of dropped subscribers are removed on the next push.
+#[derive(Clone)]
+pub struct Broadcast<T>(Arc<Mutex<Vec<Ringbuffer<T>>>>, usize);
+
+impl<T: Clone> Broadcast<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self(Arc::new(Mutex::new(vec![])), capacity)
+    }
+
+    pub fn subscribe(&self) -> Ringbuffer<T> {
+        let queue = Ringbuffer::new(self.1);
+        self.0.lock().unwrap().push(queue.clone());
+        queue
+    }
+
+    pub fn push_back(&self, value: T) {
+        let mut queues = self.0.lock().unwrap();
+        queues.retain(|queue| Arc::strong_count(&queue.0) > 1);
+        for queue in queues.iter() {
+            queue.push_back(value.clone());
+        }
+    }
+
+    pub fn subscribers(&self) -> usize {
+        self.0.lock().unwrap().len()
+    }
+
+    // Length of the fullest queue
+    pub fn len(&self) -> usize {
+        let queues = self.0.lock().unwrap();
+        queues.iter().map(Ringbuffer::len).max().unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.1
+    }
+}
+
+pub type ClockedBroadcast<T> = Broadcast<(Instant, T)>;
+
+pub fn rom_hash(data: &[u8]) -> String {
+    sha1_hex(data)
+}
+
+pub fn sha1_hex(data: &[u8]) -> String {
+    let mut result = String::new();
+    for b in Sha1::digest(data) {
+        result.push_str(&format!("{:02x}", b));
+    }
+    result
+}
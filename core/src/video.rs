@@ -0,0 +1,306 @@
+use crate::frontend::graphics::Pixel;
+
+pub const TILE_SIZE: usize = 8;
+
+// Bit-plane layouts of 8x8 tiles, the leftmost pixel is always stored in the highest bit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TileFormat {
+    // 2 bits per pixel, both planes of a row are stored next to each other (Gameboy)
+    Interleaved2bpp,
+    // 2 bits per pixel, all rows of the first plane are followed by the second plane (NES)
+    Planar2bpp,
+    // 4 bits per pixel, all four planes of a row are stored next to each other (Master System)
+    Interleaved4bpp,
+}
+
+impl TileFormat {
+    pub fn bits_per_pixel(&self) -> usize {
+        match self {
+            TileFormat::Interleaved2bpp | TileFormat::Planar2bpp => 2,
+            TileFormat::Interleaved4bpp => 4,
+        }
+    }
+
+    pub fn bytes_per_tile(&self) -> usize {
+        TILE_SIZE * self.bits_per_pixel()
+    }
+
+    // Decodes a row of a tile into color indices, tiles stored after each other continue with
+    // their rows, so tall sprites can be decoded in one go
+    pub fn decode_row(&self, data: &[u8], row: usize) -> [u8; TILE_SIZE] {
+        let tile = &data[(row / TILE_SIZE) * self.bytes_per_tile()..];
+        let row = row % TILE_SIZE;
+        let mut planes = [0u8; 4];
+        match self {
+            TileFormat::Interleaved2bpp => planes[..2].copy_from_slice(&tile[row * 2..row * 2 + 2]),
+            TileFormat::Planar2bpp => {
+                planes[0] = tile[row];
+                planes[1] = tile[row + TILE_SIZE];
+            }
+            TileFormat::Interleaved4bpp => planes.copy_from_slice(&tile[row * 4..row * 4 + 4]),
+        }
+
+        let mut result = [0; TILE_SIZE];
+        for (x, index) in result.iter_mut().enumerate() {
+            for (bit, plane) in planes[..self.bits_per_pixel()].iter().enumerate() {
+                *index |= ((plane >> (7 - x)) & 1) << bit;
+            }
+        }
+        result
+    }
+
+    pub fn decode_tile(&self, data: &[u8]) -> [[u8; TILE_SIZE]; TILE_SIZE] {
+        std::array::from_fn(|row| self.decode_row(data, row))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    colors: Vec<Pixel>,
+}
+
+impl Palette {
+    pub fn new(colors: Vec<Pixel>) -> Self {
+        Self { colors }
+    }
+
+    pub fn color(&self, index: u8) -> Pixel {
+        self.colors
+            .get(index as usize)
+            .copied()
+            .unwrap_or((0, 0, 0, 255))
+    }
+
+    pub fn set_color(&mut self, index: u8, color: Pixel) {
+        if let Some(entry) = self.colors.get_mut(index as usize) {
+            *entry = color;
+        }
+    }
+}
+
+// A color index together with the palette it has to be looked up in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IndexedPixel {
+    pub index: u8,
+    pub palette: usize,
+}
+
+pub fn apply_palettes(pixels: &[IndexedPixel], palettes: &[Palette]) -> Vec<Pixel> {
+    pixels
+        .iter()
+        .map(|pixel| {
+            palettes
+                .get(pixel.palette)
+                .map_or((0, 0, 0, 255), |palette| palette.color(pixel.index))
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpritePriority {
+    AboveBackground,
+    // Only visible where the background uses color index 0
+    BehindBackground,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Sprite<'a> {
+    pub x: isize,
+    pub y: isize,
+    pub height: usize,
+    pub data: &'a [u8],
+    pub format: TileFormat,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    pub palette: usize,
+    pub priority: SpritePriority,
+}
+
+impl Sprite<'_> {
+    // Color index of the sprite at a screen position, None when outside or transparent
+    fn pixel_at(&self, x: isize, y: isize) -> Option<u8> {
+        let (sprite_x, sprite_y) = (x - self.x, y - self.y);
+        if !(0..TILE_SIZE as isize).contains(&sprite_x)
+            || !(0..self.height as isize).contains(&sprite_y)
+        {
+            return None;
+        }
+        let row = if self.flip_y {
+            self.height - 1 - sprite_y as usize
+        } else {
+            sprite_y as usize
+        };
+        let column = if self.flip_x {
+            TILE_SIZE - 1 - sprite_x as usize
+        } else {
+            sprite_x as usize
+        };
+        Some(self.format.decode_row(self.data, row)[column]).filter(|index| *index != 0)
+    }
+}
+
+// Draws the sprites over a background scanline. Sprites earlier in the list win over later ones,
+// like the OAM ordering of the consoles, even if the winner ends up hidden behind the background.
+pub fn compose_scanline(line: usize, background: &mut [IndexedPixel], sprites: &[Sprite]) {
+    let y = line as isize;
+    for (x, pixel) in background.iter_mut().enumerate() {
+        let Some((sprite, index)) = sprites
+            .iter()
+            .find_map(|sprite| sprite.pixel_at(x as isize, y).map(|index| (sprite, index)))
+        else {
+            continue;
+        };
+        if sprite.priority == SpritePriority::AboveBackground || pixel.index == 0 {
+            *pixel = IndexedPixel {
+                index,
+                palette: sprite.palette,
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BACKGROUND: IndexedPixel = IndexedPixel {
+        index: 1,
+        palette: 0,
+    };
+
+    fn sprite(data: &[u8], x: isize, palette: usize, priority: SpritePriority) -> Sprite<'_> {
+        Sprite {
+            x,
+            y: 0,
+            height: 8,
+            data,
+            format: TileFormat::Interleaved2bpp,
+            flip_x: false,
+            flip_y: false,
+            palette,
+            priority,
+        }
+    }
+
+    #[test]
+    fn decode_interleaved_2bpp() {
+        let data = [0b1010_0000, 0b1100_0000];
+        assert_eq!(
+            TileFormat::Interleaved2bpp.decode_row(&data, 0),
+            [3, 2, 1, 0, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn decode_planar_2bpp() {
+        let mut data = [0; 16];
+        data[1] = 0b1010_0000;
+        data[9] = 0b1100_0000;
+        assert_eq!(
+            TileFormat::Planar2bpp.decode_row(&data, 1),
+            [3, 2, 1, 0, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn decode_interleaved_4bpp() {
+        let data = [0b1000_0001, 0b0100_0001, 0b0010_0001, 0b0001_0001];
+        assert_eq!(
+            TileFormat::Interleaved4bpp.decode_row(&data, 0),
+            [1, 2, 4, 8, 0, 0, 0, 15]
+        );
+    }
+
+    #[test]
+    fn decode_row_continues_in_next_tile() {
+        let mut data = [0; 32];
+        data[16] = 0xFF;
+        assert_eq!(TileFormat::Interleaved2bpp.decode_row(&data, 8), [1; 8]);
+    }
+
+    #[test]
+    fn palette_lookup() {
+        let palettes = [
+            Palette::new(vec![(0, 0, 0, 255), (255, 255, 255, 255)]),
+            Palette::new(vec![(0, 0, 0, 255), (255, 0, 0, 255)]),
+        ];
+        let pixels = [
+            IndexedPixel {
+                index: 1,
+                palette: 1,
+            },
+            IndexedPixel {
+                index: 1,
+                palette: 0,
+            },
+        ];
+        assert_eq!(
+            apply_palettes(&pixels, &palettes),
+            vec![(255, 0, 0, 255), (255, 255, 255, 255)]
+        );
+    }
+
+    #[test]
+    fn sprite_color_zero_is_transparent() {
+        let data = [0b0111_1111, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let mut line = [BACKGROUND; 2];
+        compose_scanline(
+            0,
+            &mut line,
+            &[sprite(&data, 0, 1, SpritePriority::AboveBackground)],
+        );
+        assert_eq!(line[0], BACKGROUND);
+        assert_eq!(
+            line[1],
+            IndexedPixel {
+                index: 1,
+                palette: 1
+            }
+        );
+    }
+
+    #[test]
+    fn sprite_behind_background_only_shows_on_color_zero() {
+        let data = [0xFF; 16];
+        let mut line = [BACKGROUND, IndexedPixel::default()];
+        compose_scanline(
+            0,
+            &mut line,
+            &[sprite(&data, 0, 1, SpritePriority::BehindBackground)],
+        );
+        assert_eq!(line[0], BACKGROUND);
+        assert_eq!(
+            line[1],
+            IndexedPixel {
+                index: 3,
+                palette: 1
+            }
+        );
+    }
+
+    #[test]
+    fn earlier_sprite_wins_even_when_hidden() {
+        let data = [0xFF; 16];
+        let mut line = [BACKGROUND];
+        compose_scanline(
+            0,
+            &mut line,
+            &[
+                sprite(&data, 0, 1, SpritePriority::BehindBackground),
+                sprite(&data, 0, 2, SpritePriority::AboveBackground),
+            ],
+        );
+        assert_eq!(line[0], BACKGROUND);
+    }
+
+    #[test]
+    fn sprite_flip() {
+        let mut data = [0; 16];
+        data[0] = 0b1000_0000;
+        let mut flipped = sprite(&data, 0, 1, SpritePriority::AboveBackground);
+        flipped.flip_x = true;
+        flipped.flip_y = true;
+        assert_eq!(flipped.pixel_at(7, 7), Some(1));
+        assert_eq!(flipped.pixel_at(0, 0), None);
+    }
+}
@@ -0,0 +1,37 @@
+pub use axwemulator_core as core;
+
+#[cfg(feature = "chip8")]
+pub use axwemulator_backends_chip8 as chip8;
+#[cfg(feature = "gameboy")]
+pub use axwemulator_backends_gameboy as gameboy;
+#[cfg(feature = "simple")]
+pub use axwemulator_backends_simple as simple;
+
+use axwemulator_core::{
+    backend::{
+        Backend,
+        registry::{BackendOptions, BackendRegistry},
+    },
+    error::Error,
+    frontend::Frontend,
+};
+
+pub fn registry() -> BackendRegistry {
+    // the registry is intentionally mutable here, it stays empty without any backend feature
+    #[allow(unused_mut)]
+    let mut registry = BackendRegistry::default();
+    #[cfg(feature = "chip8")]
+    chip8::register(&mut registry);
+    // the gameboy crate has no backend yet, it registers once it can create one
+    #[cfg(feature = "simple")]
+    simple::register(&mut registry);
+    registry
+}
+
+pub fn create_backend<F: Frontend<Error = Error>>(
+    name: &str,
+    options: BackendOptions,
+    frontend: &mut F,
+) -> Result<Backend, Error> {
+    registry().get(name)?.create(frontend, options)
+}
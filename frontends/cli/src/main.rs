@@ -0,0 +1,288 @@
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use axwemulator_core::{
+    backend::{
+        options::OptionValues, registry::BackendOptions, rtc::RtcPolicy, snapshot::SnapshotHeader,
+    },
+    container::unpack_rom,
+    error::Error,
+    frontend::{
+        Frontend, audio::AudioReceiver, error::FrontendError, graphics::FrameReceiver,
+        input::InputSender, text::TextReceiver,
+    },
+    rom::{Rom, unambiguous_detection},
+    statediff::diff_states,
+};
+
+const USAGE: &str = "usage: axwemulator-cli bisect --rom FILE [--backend NAME] [--seed N] [--frames N] [--expect-hash HASH]
+       axwemulator-cli diff-states --rom FILE [--backend NAME] LEFT RIGHT
+
+bisect runs the rom without input for the given number of frames and prints the hash of the final
+state. With --expect-hash the exit code tells git bisect run whether the state matches:
+  0    the hash matches
+  1    the hash differs or the emulation failed
+  128  the arguments or the rom are invalid, which aborts the bisection
+
+diff-states prints every field in which two save states of the rom differ, with the value of LEFT
+before the value of RIGHT. The exit code is 0 if they are the same and 1 otherwise.";
+
+const EXIT_ABORT: u8 = 128;
+
+// Accepts every channel and drops it, nothing of the output is needed for the hash
+struct HeadlessFrontend;
+
+impl Frontend for HeadlessFrontend {
+    type Error = Error;
+
+    fn register_graphics_receiver(
+        &mut self,
+        _receiver: FrameReceiver,
+    ) -> Result<(), FrontendError<Self::Error>> {
+        Ok(())
+    }
+
+    fn register_audio_receiver(
+        &mut self,
+        _receiver: AudioReceiver,
+    ) -> Result<(), FrontendError<Self::Error>> {
+        Ok(())
+    }
+
+    fn register_input_sender(
+        &mut self,
+        _sender: InputSender,
+    ) -> Result<(), FrontendError<Self::Error>> {
+        Ok(())
+    }
+
+    fn register_text_receiver(
+        &mut self,
+        _receiver: TextReceiver,
+    ) -> Result<(), FrontendError<Self::Error>> {
+        Ok(())
+    }
+}
+
+struct BisectArgs {
+    rom: PathBuf,
+    backend: Option<String>,
+    seed: u64,
+    frames: u64,
+    expect_hash: Option<String>,
+}
+
+fn parse_bisect_args(mut args: impl Iterator<Item = String>) -> Result<BisectArgs, String> {
+    let (mut rom, mut backend, mut seed, mut frames, mut expect_hash) = (None, None, 0, 600, None);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--rom" => rom = Some(PathBuf::from(value()?)),
+            "--backend" => backend = Some(value()?),
+            "--seed" => seed = value()?.parse().map_err(|_| "--seed needs a number")?,
+            "--frames" => frames = value()?.parse().map_err(|_| "--frames needs a number")?,
+            "--expect-hash" => expect_hash = Some(value()?.to_lowercase()),
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+    Ok(BisectArgs {
+        rom: rom.ok_or("--rom is required")?,
+        backend,
+        seed,
+        frames,
+        expect_hash,
+    })
+}
+
+struct DiffArgs {
+    rom: PathBuf,
+    backend: Option<String>,
+    left: PathBuf,
+    right: PathBuf,
+}
+
+fn parse_diff_args(mut args: impl Iterator<Item = String>) -> Result<DiffArgs, String> {
+    let (mut rom, mut backend, mut states) = (None, None, vec![]);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--rom" => rom = Some(PathBuf::from(value()?)),
+            "--backend" => backend = Some(value()?),
+            _ if arg.starts_with("--") => return Err(format!("unknown argument {}", arg)),
+            _ => states.push(PathBuf::from(arg)),
+        }
+    }
+    let [left, right]: [PathBuf; 2] = states
+        .try_into()
+        .map_err(|_| "two save states are required")?;
+    Ok(DiffArgs {
+        rom: rom.ok_or("--rom is required")?,
+        backend,
+        left,
+        right,
+    })
+}
+
+// Archives are unpacked, but have to contain a single rom as there is nobody to choose one
+fn read_rom(path: &Path) -> Result<Rom, Error> {
+    let data = std::fs::read(path)
+        .map_err(|error| Error::new(format!("unable to read rom: {}", error)))?;
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string());
+    let mut roms = unpack_rom(Rom::new(name, data))?;
+    if roms.len() > 1 {
+        return Err(Error::new("the archive contains several roms"));
+    }
+    Ok(roms.remove(0))
+}
+
+// Signs of a corrupt rom are only warned about, the run might be meant to reproduce them
+fn resolve_backend(backend: Option<&str>, rom: &Rom) -> Result<&'static str, Error> {
+    let registry = axwemulator::registry();
+    let name = match backend {
+        Some(name) => registry.get(name)?.name,
+        None => {
+            let detections = registry.detect(rom);
+            unambiguous_detection(&detections)
+                .map(|detection| detection.backend)
+                .ok_or_else(|| Error::new("unable to detect the backend, pass --backend"))?
+        }
+    };
+    for issue in registry.get(name)?.check_integrity(rom).issues {
+        eprintln!("warning: possibly corrupt rom, {}", issue);
+    }
+    Ok(name)
+}
+
+fn run_bisect(args: &BisectArgs, backend_name: &str, data: Vec<u8>) -> Result<String, Error> {
+    let mut backend = axwemulator::create_backend(
+        backend_name,
+        BackendOptions {
+            rom_data: data,
+            options: OptionValues::default(),
+            firmware: Default::default(),
+            rng_seed: Some(args.seed),
+            // Runs compared by their state hash must not see the time of the host
+            rtc_policy: Some(RtcPolicy::Fixed(0)),
+        },
+        &mut HeadlessFrontend,
+    )?;
+    let frame_duration = backend.get_info().frame_duration();
+    for _ in 0..args.frames {
+        backend.run_for(frame_duration)?;
+    }
+    backend.state_hash()
+}
+
+// Snapshot files are checked against the rom and upgraded, anything else is taken as a payload
+// of Backend::save_state like the one of a bug report
+fn read_state(path: &Path, backend_name: &str, rom: &Rom) -> Result<Vec<u8>, Error> {
+    let data = std::fs::read(path)
+        .map_err(|error| Error::new(format!("unable to read {}: {}", path.display(), error)))?;
+    if SnapshotHeader::decode(&data).is_err() {
+        return Ok(data);
+    }
+    axwemulator::registry()
+        .get(backend_name)?
+        .upgrade_snapshot(&data, &rom.hash())
+}
+
+fn run_diff(args: &DiffArgs, backend_name: &str, rom: Rom) -> Result<Vec<String>, Error> {
+    let left = read_state(&args.left, backend_name, &rom)?;
+    let right = read_state(&args.right, backend_name, &rom)?;
+    let mut backend = axwemulator::create_backend(
+        backend_name,
+        BackendOptions {
+            rom_data: rom.data,
+            options: OptionValues::default(),
+            firmware: Default::default(),
+            rng_seed: None,
+            rtc_policy: None,
+        },
+        &mut HeadlessFrontend,
+    )?;
+    let differences = diff_states(&mut backend, &left, &right)?;
+    Ok(differences
+        .iter()
+        .map(|difference| difference.to_string())
+        .collect())
+}
+
+fn main_diff(args: impl Iterator<Item = String>) -> ExitCode {
+    let args = match parse_diff_args(args) {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("{}\n\n{}", error, USAGE);
+            return ExitCode::from(EXIT_ABORT);
+        }
+    };
+    let differences = read_rom(&args.rom)
+        .and_then(|rom| Ok((resolve_backend(args.backend.as_deref(), &rom)?, rom)))
+        .and_then(|(backend_name, rom)| run_diff(&args, backend_name, rom));
+    match differences {
+        Ok(differences) if differences.is_empty() => {
+            println!("the states are the same");
+            ExitCode::SUCCESS
+        }
+        Ok(differences) => {
+            for difference in differences {
+                println!("{}", difference);
+            }
+            ExitCode::FAILURE
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            ExitCode::from(EXIT_ABORT)
+        }
+    }
+}
+
+fn main_bisect(args: impl Iterator<Item = String>) -> ExitCode {
+    let args = match parse_bisect_args(args) {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("{}\n\n{}", error, USAGE);
+            return ExitCode::from(EXIT_ABORT);
+        }
+    };
+    let setup = read_rom(&args.rom)
+        .and_then(|rom| Ok((resolve_backend(args.backend.as_deref(), &rom)?, rom.data)));
+    let (backend_name, data) = match setup {
+        Ok(setup) => setup,
+        Err(error) => {
+            eprintln!("{}", error);
+            return ExitCode::from(EXIT_ABORT);
+        }
+    };
+
+    let hash = match run_bisect(&args, backend_name, data) {
+        Ok(hash) => hash,
+        Err(error) => {
+            eprintln!("emulation failed: {}", error);
+            return ExitCode::FAILURE;
+        }
+    };
+    println!("{}", hash);
+    match &args.expect_hash {
+        Some(expected) if *expected != hash => {
+            eprintln!("state diverged, expected {}", expected);
+            ExitCode::FAILURE
+        }
+        _ => ExitCode::SUCCESS,
+    }
+}
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    match args.next().as_deref() {
+        Some("bisect") => main_bisect(args),
+        Some("diff-states") => main_diff(args),
+        _ => {
+            eprintln!("{}", USAGE);
+            ExitCode::from(EXIT_ABORT)
+        }
+    }
+}
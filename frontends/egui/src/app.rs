@@ -1,107 +1,959 @@
-use std::sync::mpsc;
-
-use axwemulator_core::{error::Error, frontend::Frontend};
-
-use crate::components::{
-    Component,
-    audio::AudioComponent,
-    emulator::{AvailableBackends, EmulatorComponent},
-    input::InputComponent,
-    inspector::InspectorComponent,
-    memory::MemoryComponent,
-    metrics::{MeasurementType, MetricsComponent},
-    screen::ScreenComponent,
-    selection::SelectionComponent,
+use std::collections::HashMap;
+
+use axwemulator_core::{
+    backend::{
+        DisplayConfig, ExecutionEvent, SPEED_RANGE,
+        component::MemoryAddress,
+        firmware::FirmwareFiles,
+        options::{OptionValue, OptionValues},
+        registry::BackendRegistry,
+        trigger::{TriggerCondition, TriggerId},
+    },
+    error::Error,
+    frontend::{
+        Frontend,
+        movie::InputMovie,
+        storage::{MemoryStorage, StorageProvider},
+    },
+    patch::RomPatch,
+    rom::Rom,
+    utils::rom_hash,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    appearance::Appearance,
+    command::{CommandReceiver, CommandReply, CommandSender, build_command_bus},
+    components::{
+        Component,
+        audio::AudioComponent,
+        debug::DebugComponent,
+        diagnostic::{BugReportContext, DiagnosticComponent, export_bug_report},
+        emulator::EmulatorComponent,
+        help::HelpComponent,
+        input::{InputComponent, REWIND_KEY},
+        inspector::InspectorComponent,
+        memory::MemoryComponent,
+        metrics::{MeasurementType, MetricsComponent},
+        savestates::{SaveSlot, SavestateSettings, SavestatesComponent, UNDO_LOAD_KEY},
+        scheduler::{SchedulerComponent, describe_scheduler_warning},
+        screen::ScreenComponent,
+        selection::SelectionComponent,
+        stack::StackComponent,
+        switches::SwitchesComponent,
+        textlog::TextLogComponent,
+        tracelog::TraceLogComponent,
+        triggers::{TriggerAction, TriggersComponent},
+        vram::VramComponent,
+    },
+    layout::DebuggerLayout,
+    overrides::Overrides,
+    session::{LastSession, SESSION_ROM_KEY, SESSION_STATE_KEY},
 };
 
+pub const APP_ID: &str = "eframe template";
+
+const ROM_OPTIONS_STORAGE_KEY: &str = "rom_options";
+#[cfg(target_arch = "wasm32")]
+const OVERRIDES_STORAGE_KEY: &str = "overrides";
+const FIRMWARE_DIRECTORY_STORAGE_KEY: &str = "firmware_directory";
+const ROM_DIRECTORY_STORAGE_KEY: &str = "rom_directory";
+const DISPLAY_CONFIGS_STORAGE_KEY: &str = "display_configs";
+const VIDEO_FILTERS_STORAGE_KEY: &str = "video_filters";
+const DEMOS_STORAGE_KEY: &str = "demos";
+const DEBUGGER_LAYOUTS_STORAGE_KEY: &str = "debugger_layouts";
+const APPEARANCE_STORAGE_KEY: &str = "appearance";
+const LAST_SESSION_STORAGE_KEY: &str = "last_session";
+const SAVESTATE_SETTINGS_STORAGE_KEY: &str = "savestate_settings";
+// Keeps things like the audio fade out going while the emulation is paused
+const IDLE_REPAINT_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
 #[derive(Debug)]
 pub enum AppCommand {
-    InitBackendWithRom(AvailableBackends, Vec<u8>),
+    ConfigureBackend(String, Vec<u8>),
+    // The last flag replays the demo recorded for the rom instead of taking live input
+    InitBackendWithRom(String, Vec<u8>, OptionValues, FirmwareFiles, bool),
+    SetFirmwareDirectory(String),
+    SetRomDirectory(String),
+    LoadFirmware(String, Vec<u8>),
+    RunSelfTest(Vec<u8>),
+    ImportOverrides(String),
+    OpenRom(Rom),
+    OpenRomWithBackend(String, Rom),
+    SetRomPatch(Option<RomPatch>),
+    AddTrigger(TriggerCondition, TriggerAction),
+    RemoveTrigger(TriggerId),
+    AddBreakpoint(String, MemoryAddress),
+    RemoveBreakpoint(String, MemoryAddress),
+    SetComponentPaused(String, bool),
+    StepInstruction(String),
+    FreezeMemory(MemoryAddress, u8),
+    UnfreezeMemory(MemoryAddress),
+    TogglePause,
+    Reset,
+    SetRewinding(bool),
+    RunTo(ExecutionEvent),
+    SetStepLimit(String, Option<usize>),
+    SetSpeed(f64),
+    OpenPanel(SidepanelContent),
+    ShowMemory(MemoryAddress),
+    // Stored for the running rom, the backend reads it the next time the rom starts
+    SetRomOption(String, OptionValue),
+    TraceBefore(Option<String>, femtos::Instant),
+    ExportBugReport,
+    // Shown over the screen for a moment
+    ShowMessage(String),
+    // Launches the rom of the last session again, see LastSession
+    ContinueSession,
+    SetSessionResume(bool),
+    SaveState(SaveSlot),
+    LoadState(SaveSlot),
+    // Goes back to the state replaced by the last load
+    UndoLoadState,
     QuitBackend,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SidepanelContent {
+    #[default]
     Metrics,
     Inspector,
     Memory,
+    Stack,
+    Switches,
+    Scheduler,
+    Screens,
+    Triggers,
+    Players,
+    Debug,
+    TraceLog,
+    TextLog,
+    Vram,
+    Savestates,
+    Diagnostic,
+    Appearance,
+}
+
+struct SecondaryScreen {
+    screen: ScreenComponent,
+    open: bool,
 }
 
 pub struct EmulatorApp {
-    app_command_receiver: mpsc::Receiver<AppCommand>,
-    app_command_sender: mpsc::Sender<AppCommand>,
+    storage: Box<dyn StorageProvider>,
+    registry: BackendRegistry,
+    rom_options: HashMap<String, OptionValues>,
+    firmware_directory: String,
+    // Directory shown in the rom browser of the selection screen
+    rom_directory: String,
+    // User overrides of the display configuration of the backends, keyed by backend name
+    display_configs: HashMap<String, DisplayConfig>,
+    // Names of the built-in filters applied to all screens, in order
+    video_filters: Vec<String>,
+    // Input movies in their text format, keyed like the rom options
+    demos: HashMap<String, String>,
+    // Keyed like the rom options
+    debugger_layouts: HashMap<String, DebuggerLayout>,
+    appearance: Appearance,
+    savestate_settings: SavestateSettings,
+    last_session: Option<LastSession>,
+    current_rom_key: Option<String>,
+    overrides: Overrides,
+    // Overrides imported on the web are kept as the original JSON in the app storage
+    #[cfg(target_arch = "wasm32")]
+    overrides_json: String,
+    command_receiver: CommandReceiver,
+    command_sender: CommandSender,
     sidepanel_selection: SidepanelContent,
     selection: SelectionComponent,
     emulator: Option<EmulatorComponent>,
     screen: Option<ScreenComponent>,
+    secondary_screens: Vec<SecondaryScreen>,
     input: Option<InputComponent>,
+    help: Option<HelpComponent>,
     audio: Option<AudioComponent>,
     metrics: Option<MetricsComponent>,
     inspector: Option<InspectorComponent>,
     memory: Option<MemoryComponent>,
+    stack: Option<StackComponent>,
+    switches: Option<SwitchesComponent>,
+    scheduler: Option<SchedulerComponent>,
+    triggers: Option<TriggersComponent>,
+    debug: Option<DebugComponent>,
+    trace_log: Option<TraceLogComponent>,
+    text_log: Option<TextLogComponent>,
+    diagnostic: Option<DiagnosticComponent>,
+    vram: Option<VramComponent>,
+    savestates: Option<SavestatesComponent>,
 }
 
 impl eframe::App for EmulatorApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         if let Some(metrics) = self.metrics.as_mut() {
             metrics.stop(MeasurementType::FullFrametime);
+            metrics.sample_host();
             metrics.start(MeasurementType::FullFrametime);
             metrics.start(MeasurementType::Frametime);
         }
         self._handle_commands();
         self._update(ctx);
         self._draw(ctx);
-        ctx.request_repaint();
+        self._schedule_repaint(ctx);
         if let Some(metrics) = self.metrics.as_mut() {
             metrics.stop(MeasurementType::Frametime);
         }
     }
+
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        self.capture_debugger_layout();
+        eframe::set_value(
+            storage,
+            DEBUGGER_LAYOUTS_STORAGE_KEY,
+            &self.debugger_layouts,
+        );
+        eframe::set_value(storage, ROM_OPTIONS_STORAGE_KEY, &self.rom_options);
+        eframe::set_value(
+            storage,
+            FIRMWARE_DIRECTORY_STORAGE_KEY,
+            &self.firmware_directory,
+        );
+        eframe::set_value(storage, ROM_DIRECTORY_STORAGE_KEY, &self.rom_directory);
+        eframe::set_value(storage, DISPLAY_CONFIGS_STORAGE_KEY, &self.display_configs);
+        eframe::set_value(storage, VIDEO_FILTERS_STORAGE_KEY, &self.video_filters);
+        eframe::set_value(storage, DEMOS_STORAGE_KEY, &self.demos);
+        eframe::set_value(storage, APPEARANCE_STORAGE_KEY, &self.appearance);
+        eframe::set_value(
+            storage,
+            SAVESTATE_SETTINGS_STORAGE_KEY,
+            &self.savestate_settings,
+        );
+        self.store_session_state();
+        eframe::set_value(storage, LAST_SESSION_STORAGE_KEY, &self.last_session);
+        #[cfg(target_arch = "wasm32")]
+        eframe::set_value(storage, OVERRIDES_STORAGE_KEY, &self.overrides_json);
+    }
+}
+
+// Files of the app are kept next to the settings of eframe
+fn default_storage() -> Box<dyn StorageProvider> {
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(directory) = eframe::storage_dir(APP_ID) {
+        return Box::new(axwemulator_core::frontend::storage::FileStorage::new(
+            directory,
+        ));
+    }
+    Box::new(MemoryStorage::new())
 }
 
 impl EmulatorApp {
-    pub fn new(_cc: &eframe::CreationContext<'_>) -> Self {
-        let (app_command_sender, app_command_receiver) = mpsc::channel();
+    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
+        Self::with_storage(cc, default_storage())
+    }
+
+    pub fn with_storage(
+        cc: &eframe::CreationContext<'_>,
+        storage: Box<dyn StorageProvider>,
+    ) -> Self {
+        let (command_sender, command_receiver) = build_command_bus(cc.egui_ctx.clone());
+        let registry = axwemulator::registry();
+        let rom_options = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, ROM_OPTIONS_STORAGE_KEY))
+            .unwrap_or_default();
+        let firmware_directory: String = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, FIRMWARE_DIRECTORY_STORAGE_KEY))
+            .unwrap_or_default();
+        let rom_directory: String = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, ROM_DIRECTORY_STORAGE_KEY))
+            .unwrap_or_default();
+        let display_configs = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, DISPLAY_CONFIGS_STORAGE_KEY))
+            .unwrap_or_default();
+        let video_filters = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, VIDEO_FILTERS_STORAGE_KEY))
+            .unwrap_or_default();
+        let demos = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, DEMOS_STORAGE_KEY))
+            .unwrap_or_default();
+        let debugger_layouts = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, DEBUGGER_LAYOUTS_STORAGE_KEY))
+            .unwrap_or_default();
+        let appearance: Appearance = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, APPEARANCE_STORAGE_KEY))
+            .unwrap_or_default();
+        let savestate_settings = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, SAVESTATE_SETTINGS_STORAGE_KEY))
+            .unwrap_or_default();
+        appearance.apply(&cc.egui_ctx);
+        if appearance != Appearance::default() {
+            appearance.apply_fonts(&cc.egui_ctx);
+        }
+        // Nothing to continue with once the rom is gone from the storage, e.g. on the web
+        let last_session: Option<LastSession> = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, LAST_SESSION_STORAGE_KEY))
+            .flatten()
+            .filter(|_| matches!(storage.read(SESSION_ROM_KEY), Ok(Some(_))));
+        #[cfg(target_arch = "wasm32")]
+        let overrides_json: String = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, OVERRIDES_STORAGE_KEY))
+            .unwrap_or_default();
+        #[cfg(target_arch = "wasm32")]
+        let overrides = Overrides::from_json(&overrides_json).unwrap_or_default();
+        #[cfg(not(target_arch = "wasm32"))]
+        let overrides = Overrides::load(storage.as_ref());
         Self {
-            app_command_receiver,
-            app_command_sender,
+            storage,
+            selection: SelectionComponent::new(
+                registry.clone(),
+                firmware_directory.clone(),
+                &rom_directory,
+            )
+            .with_last_session(last_session.clone()),
+            registry,
+            rom_options,
+            firmware_directory,
+            rom_directory,
+            display_configs,
+            video_filters,
+            demos,
+            debugger_layouts,
+            appearance,
+            savestate_settings,
+            last_session,
+            current_rom_key: None,
+            overrides,
+            #[cfg(target_arch = "wasm32")]
+            overrides_json,
+            command_receiver,
+            command_sender,
             sidepanel_selection: SidepanelContent::Metrics,
-            selection: SelectionComponent::new(),
             emulator: None,
             screen: None,
+            secondary_screens: Vec::new(),
             input: None,
+            help: None,
             audio: None,
             metrics: None,
             inspector: None,
             memory: None,
+            stack: None,
+            switches: None,
+            scheduler: None,
+            triggers: None,
+            debug: None,
+            trace_log: None,
+            text_log: None,
+            diagnostic: None,
+            vram: None,
+            savestates: None,
         }
     }
 
+    pub fn command_sender(&self) -> CommandSender {
+        self.command_sender.clone()
+    }
+
+    fn rom_options_key(backend_name: &str, rom_data: &[u8]) -> String {
+        format!("{}/{}", backend_name, rom_hash(rom_data))
+    }
+
     fn _handle_commands(&mut self) {
-        if let Ok(cmd) = self.app_command_receiver.try_recv() {
-            match cmd {
-                AppCommand::InitBackendWithRom(backend_selection, rom_data) => {
-                    self.emulator = Some(EmulatorComponent::from_selection(
-                        backend_selection,
-                        self,
-                        &rom_data,
+        while let Some(envelope) = self.command_receiver.try_recv() {
+            envelope.handle(|command| {
+                let reply = self._handle_command(command);
+                if let Err(error) = &reply {
+                    log::error!("{}", error);
+                }
+                reply
+            });
+        }
+    }
+
+    fn _handle_command(&mut self, command: AppCommand) -> CommandReply {
+        match command {
+            AppCommand::ConfigureBackend(backend_name, rom_data) => {
+                let descriptor = self
+                    .registry
+                    .get(&backend_name)
+                    .map_err(|error| error.to_string())?;
+                let (schema, firmware) = ((descriptor.options)(), descriptor.firmware);
+                let key = Self::rom_options_key(&backend_name, &rom_data);
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    self.overrides = Overrides::load(self.storage.as_ref());
+                }
+                if schema.is_empty() && firmware.is_empty() {
+                    return self._handle_command(AppCommand::InitBackendWithRom(
+                        backend_name,
+                        rom_data,
+                        OptionValues::default(),
+                        FirmwareFiles::default(),
+                        false,
                     ));
-                    self.metrics = Some(MetricsComponent::new());
-                    self.inspector = Some(InspectorComponent::new());
-                    self.memory = Some(MemoryComponent::new());
                 }
-                AppCommand::QuitBackend => {
-                    self.selection = SelectionComponent::new();
-                    self.emulator = None;
-                    self.screen = None;
-                    self.input = None;
-                    self.audio = None;
-                    self.metrics = None;
-                    self.inspector = None;
+                let mut values =
+                    schema.resolve(&self.rom_options.get(&key).cloned().unwrap_or_default());
+                self.overrides.apply_options(
+                    &backend_name,
+                    &rom_hash(&rom_data),
+                    &schema,
+                    &mut values,
+                );
+                let has_demo = self.demos.contains_key(&key);
+                self.selection.configure(
+                    backend_name,
+                    rom_data,
+                    schema,
+                    values,
+                    firmware,
+                    has_demo,
+                );
+            }
+            AppCommand::InitBackendWithRom(
+                backend_name,
+                rom_data,
+                options,
+                firmware,
+                play_demo,
+            ) => {
+                let descriptor = self
+                    .registry
+                    .get(&backend_name)
+                    .map_err(|error| error.to_string())?
+                    .clone();
+                let key = Self::rom_options_key(&backend_name, &rom_data);
+                self.rom_options.insert(key.clone(), options.clone());
+                let demo = if play_demo {
+                    let content = self
+                        .demos
+                        .get(&key)
+                        .ok_or("no demo recorded for this rom")?;
+                    Some(
+                        InputMovie::decode(content)
+                            .map_err(|error| format!("unable to load demo: {}", error))?,
+                    )
+                } else {
+                    None
+                };
+                let emulator = match EmulatorComponent::from_descriptor(
+                    &descriptor,
+                    self,
+                    &rom_data,
+                    options,
+                    firmware,
+                    demo.as_ref().map(|demo| demo.rng_seed),
+                ) {
+                    Ok(emulator) => emulator,
+                    Err(error) => {
+                        // the backend might have registered some of its channels before failing
+                        self.close_backend();
+                        return Err(format!("could not create backend: {}", error));
+                    }
+                };
+                for secondary_screen in self.secondary_screens.iter_mut() {
+                    secondary_screen.screen.set_filters(&self.video_filters);
+                }
+                if let Some(screen) = self.screen.as_mut() {
+                    screen.set_filters(&self.video_filters);
+                    screen.set_display_config(
+                        self.display_configs
+                            .get(&backend_name)
+                            .copied()
+                            .unwrap_or(emulator.get_backend().get_info().display),
+                    );
+                }
+                if let Some(input) = self.input.as_mut() {
+                    input.start_session(emulator.get_backend().get_rng_seed(), demo);
+                    input.set_players(emulator.get_backend().get_info().players);
+                    input.set_keybindings(
+                        self.overrides
+                            .keybindings(&backend_name, &rom_hash(&rom_data)),
+                    );
+                }
+                self.help = Some(HelpComponent::new(
+                    emulator.get_backend().get_info().controls,
+                    self.input.as_ref(),
+                ));
+                self.emulator = Some(emulator);
+                self.metrics = Some(MetricsComponent::new());
+                self.inspector = Some(InspectorComponent::new());
+                self.memory = Some(MemoryComponent::new());
+                self.stack = Some(StackComponent::new());
+                self.switches = SwitchesComponent::new(
+                    (descriptor.options)().options(),
+                    self.rom_options.get(&key).cloned().unwrap_or_default(),
+                );
+                self.scheduler = Some(SchedulerComponent::new());
+                self.triggers = Some(TriggersComponent::new());
+                self.vram = Some(VramComponent::new());
+                self.savestates = Some(SavestatesComponent::new(
+                    self.storage.as_ref(),
+                    &backend_name,
+                    &rom_hash(&rom_data),
+                    self.savestate_settings.clone(),
+                ));
+                if let Some(layout) = self.debugger_layouts.get(&key).cloned() {
+                    self.restore_debugger_layout(layout);
                 }
+                self.current_rom_key = Some(key);
+                self.remember_session(&backend_name, &rom_data);
+            }
+            AppCommand::SetRomDirectory(directory) => {
+                self.rom_directory = directory;
+            }
+            AppCommand::SetFirmwareDirectory(directory) => {
+                self.firmware_directory = directory;
+            }
+            AppCommand::LoadFirmware(key, data) => {
+                self.selection.load_firmware(&key, data);
+            }
+            AppCommand::RunSelfTest(data) => {
+                self.selection.run_self_test(&data);
+            }
+            AppCommand::ImportOverrides(content) => {
+                self.overrides = Overrides::from_json(&content)
+                    .map_err(|error| format!("unable to import overrides: {}", error))?;
+                #[cfg(target_arch = "wasm32")]
+                {
+                    self.overrides_json = content;
+                }
+            }
+            AppCommand::OpenRom(rom) => {
+                // Roms can also arrive from outside while a backend runs
+                if self.emulator.is_some() {
+                    self.close_backend();
+                    self.selection = self.new_selection();
+                }
+                self.selection.open_rom(rom, &self.command_sender);
+            }
+            AppCommand::SetRomPatch(patch) => self.selection.set_patch(patch),
+            AppCommand::OpenRomWithBackend(backend_name, rom) => {
+                self.selection
+                    .open_rom_with(Some(&backend_name), rom, &self.command_sender);
+            }
+            AppCommand::AddTrigger(condition, action) => {
+                let id = self
+                    .emulator
+                    .as_mut()
+                    .ok_or("no backend is running")?
+                    .add_trigger(condition);
+                if let Some(triggers) = self.triggers.as_mut() {
+                    triggers.set_action(id, action);
+                }
+            }
+            AppCommand::FreezeMemory(address, value) => {
+                self.emulator
+                    .as_mut()
+                    .ok_or("no backend is running")?
+                    .freeze_memory(address, value)
+                    .map_err(|error| error.to_string())?;
+            }
+            AppCommand::UnfreezeMemory(address) => {
+                self.emulator
+                    .as_mut()
+                    .ok_or("no backend is running")?
+                    .unfreeze_memory(address);
+            }
+            AppCommand::RemoveTrigger(id) => {
+                self.emulator
+                    .as_mut()
+                    .ok_or("no backend is running")?
+                    .remove_trigger(id);
+            }
+            AppCommand::AddBreakpoint(component, address) => {
+                self.emulator
+                    .as_mut()
+                    .ok_or("no backend is running")?
+                    .add_breakpoint(&component, address)
+                    .map_err(|error| error.to_string())?;
+            }
+            AppCommand::RemoveBreakpoint(component, address) => {
+                self.emulator
+                    .as_mut()
+                    .ok_or("no backend is running")?
+                    .remove_breakpoint(&component, address);
+            }
+            AppCommand::SetComponentPaused(component, paused) => {
+                self.emulator
+                    .as_mut()
+                    .ok_or("no backend is running")?
+                    .set_component_paused(&component, paused)
+                    .map_err(|error| error.to_string())?;
+            }
+            AppCommand::StepInstruction(component) => {
+                self.emulator
+                    .as_mut()
+                    .ok_or("no backend is running")?
+                    .step_instruction(&component)
+                    .map_err(|error| error.to_string())?;
+            }
+            AppCommand::TogglePause => {
+                let emulator = self.emulator.as_mut().ok_or("no backend is running")?;
+                emulator.toggle_pause();
+                let text = if emulator.is_paused() {
+                    "Paused"
+                } else {
+                    "Running"
+                };
+                self.show_message("pause", text);
+            }
+            AppCommand::Reset => {
+                let emulator = self.emulator.as_mut().ok_or("no backend is running")?;
+                emulator.reset().map_err(|error| error.to_string())?;
+                // The recorded session starts over with the machine, a running demo stops
+                if let Some(input) = self.input.as_mut() {
+                    input.start_session(emulator.get_backend().get_rng_seed(), None);
+                }
+                self.diagnostic = None;
+                if self.sidepanel_selection == SidepanelContent::Diagnostic {
+                    self.sidepanel_selection = SidepanelContent::default();
+                }
+                self.show_message("reset", "Reset");
+            }
+            AppCommand::SetRewinding(rewinding) => {
+                self.emulator
+                    .as_mut()
+                    .ok_or("no backend is running")?
+                    .set_rewinding(rewinding);
+            }
+            AppCommand::RunTo(event) => {
+                self.emulator
+                    .as_mut()
+                    .ok_or("no backend is running")?
+                    .run_to(event);
+            }
+            AppCommand::SetStepLimit(name, limit) => {
+                self.emulator
+                    .as_mut()
+                    .ok_or("no backend is running")?
+                    .set_step_limit(&name, limit);
+            }
+            AppCommand::SetSpeed(speed) => {
+                self.emulator
+                    .as_mut()
+                    .ok_or("no backend is running")?
+                    .set_speed(speed)
+                    .map_err(|error| error.to_string())?;
+                self.show_message("speed", format!("Speed {}x", speed));
+            }
+            AppCommand::OpenPanel(panel) => {
+                self.sidepanel_selection = panel;
+            }
+            AppCommand::SetRomOption(option, value) => {
+                let key = self
+                    .current_rom_key
+                    .clone()
+                    .ok_or("no backend is running")?;
+                self.rom_options.entry(key).or_default().set(&option, value);
+                self.show_message("rom option", "Stored, applies the next time the rom starts");
+            }
+            AppCommand::ExportBugReport => {
+                let emulator = self.emulator.as_ref().ok_or("no backend is running")?;
+                let diagnostic = self.diagnostic.as_mut().ok_or("no failure to report")?;
+                let rom_key = self.current_rom_key.clone().unwrap_or_default();
+                let context = BugReportContext {
+                    backend_name: emulator.name().to_string(),
+                    options: serde_json::to_string(&self.rom_options.get(&rom_key))
+                        .unwrap_or_default(),
+                    rom_key,
+                };
+                let location =
+                    export_bug_report(self.storage.as_mut(), diagnostic.report(), &context)
+                        .map_err(|error| format!("unable to export bug report: {}", error))?;
+                log::info!("bug report written to {}", location);
+                diagnostic.set_exported_to(location);
+                self.show_message("bug report", "Bug report exported");
             }
+            AppCommand::ShowMessage(text) => self.show_message(&text, text.clone()),
+            AppCommand::TraceBefore(component, clock) => {
+                let (component, lines) = self
+                    .emulator
+                    .as_mut()
+                    .ok_or("no backend is running")?
+                    .trace_before(component.as_deref(), clock)
+                    .map_err(|error| format!("unable to trace: {}", error))?;
+                if let Some(inspector) = self.inspector.as_mut() {
+                    inspector.set_trace(component, clock, lines);
+                }
+                self.sidepanel_selection = SidepanelContent::Inspector;
+            }
+            AppCommand::ShowMemory(address) => {
+                self.memory
+                    .as_mut()
+                    .ok_or("no backend is running")?
+                    .show_address(address);
+                self.sidepanel_selection = SidepanelContent::Memory;
+            }
+            AppCommand::ContinueSession => {
+                let session = self.last_session.clone().ok_or("no session to continue")?;
+                let rom_data = self
+                    .storage
+                    .read(SESSION_ROM_KEY)
+                    .map_err(|error| error.to_string())?
+                    .ok_or("the rom of the last session is gone")?;
+                self.selection.set_rom_name(session.rom_name.clone());
+                self._handle_command(AppCommand::ConfigureBackend(
+                    session.backend_name.clone(),
+                    rom_data,
+                ))?;
+                // The options screen stays open when firmware is missing
+                if let Some(command) = self.selection.take_ready_launch() {
+                    self._handle_command(command)?;
+                }
+                if session.resume && self.emulator.is_some() {
+                    self.resume_session_state(&session)?;
+                }
+            }
+            AppCommand::SetSessionResume(resume) => {
+                if let Some(session) = self.last_session.as_mut() {
+                    session.resume = resume;
+                }
+                // A state stored earlier would be loaded once resuming is turned on again
+                if !resume {
+                    self.storage
+                        .remove(SESSION_STATE_KEY)
+                        .map_err(|error| error.to_string())?;
+                }
+            }
+            AppCommand::SaveState(slot) => {
+                let emulator = self.emulator.as_ref().ok_or("no backend is running")?;
+                let descriptor = self
+                    .registry
+                    .get(emulator.name())
+                    .map_err(|error| error.to_string())?;
+                self.savestates
+                    .as_mut()
+                    .ok_or("no backend is running")?
+                    .save(self.storage.as_mut(), descriptor, emulator, slot)
+                    .map_err(|error| format!("unable to save the state: {}", error))?;
+                // Auto saves happen all the time, they would cover the screen
+                if slot == SaveSlot::Quick {
+                    self.show_message("savestate", format!("Saved to the {}", slot.label()));
+                }
+            }
+            AppCommand::LoadState(slot) => {
+                let emulator = self.emulator.as_mut().ok_or("no backend is running")?;
+                let descriptor = self
+                    .registry
+                    .get(emulator.name())
+                    .map_err(|error| error.to_string())?;
+                self.savestates
+                    .as_mut()
+                    .ok_or("no backend is running")?
+                    .load(self.storage.as_ref(), descriptor, emulator, slot)
+                    .map_err(|error| format!("unable to load the state: {}", error))?;
+                self.show_message(
+                    "savestate",
+                    format!(
+                        "Loaded the {}, {} undoes",
+                        slot.label(),
+                        UNDO_LOAD_KEY.name()
+                    ),
+                );
+            }
+            AppCommand::UndoLoadState => {
+                let emulator = self.emulator.as_mut().ok_or("no backend is running")?;
+                self.savestates
+                    .as_mut()
+                    .ok_or("no backend is running")?
+                    .undo_load(emulator)
+                    .map_err(|error| format!("unable to undo the load: {}", error))?;
+                self.show_message("savestate", "Load undone");
+            }
+            AppCommand::QuitBackend => {
+                self.close_backend();
+                self.selection = self.new_selection();
+            }
+        }
+        Ok(())
+    }
+
+    // Repaints follow the frames of the backend. While paused the UI only ticks slowly, without a
+    // backend it waits for input or commands entirely.
+    fn _schedule_repaint(&self, ctx: &egui::Context) {
+        if let Some(emulator) = self.emulator.as_ref() {
+            match emulator.next_frame_in() {
+                Some(delay) => ctx.request_repaint_after(delay),
+                None => ctx.request_repaint_after(IDLE_REPAINT_INTERVAL),
+            }
+        }
+    }
+
+    fn capture_debugger_layout(&mut self) {
+        let (Some(key), Some(emulator)) = (self.current_rom_key.as_ref(), self.emulator.as_ref())
+        else {
+            return;
+        };
+        let layout = self.debugger_layouts.entry(key.clone()).or_default();
+        layout.sidepanel = self.sidepanel_selection;
+        if let Some(inspector) = self.inspector.as_ref() {
+            layout.inspector_component = inspector.selected_component().to_string();
+        }
+        if let Some(memory) = self.memory.as_ref() {
+            layout.memory_component = memory.selected_component().cloned();
+            layout.memory_scroll_offset = memory.scroll_offset();
+        }
+        let backend = emulator.get_backend();
+        layout.set_triggers(backend.get_triggers().iter().map(|t| &t.condition));
+        layout.frozen_memory = backend.get_frozen_memory();
+    }
+
+    fn restore_debugger_layout(&mut self, layout: DebuggerLayout) {
+        // Some panels only exist for backends with the matching channel or options
+        let available = match layout.sidepanel {
+            SidepanelContent::Debug => self.debug.is_some(),
+            SidepanelContent::TraceLog => self.trace_log.is_some(),
+            SidepanelContent::TextLog => self.text_log.is_some(),
+            SidepanelContent::Switches => self.switches.is_some(),
+            SidepanelContent::Diagnostic => self.diagnostic.is_some(),
+            _ => true,
+        };
+        if available {
+            self.sidepanel_selection = layout.sidepanel;
+        }
+        if let Some(inspector) = self.inspector.as_mut() {
+            inspector.select(layout.inspector_component.clone());
+        }
+        if let Some(memory) = self.memory.as_mut() {
+            memory.restore(layout.memory_component.clone(), layout.memory_scroll_offset);
+        }
+        let Some(emulator) = self.emulator.as_mut() else {
+            return;
+        };
+        for condition in layout.triggers() {
+            emulator.add_trigger(condition);
+        }
+        for (address, value) in layout.frozen_memory {
+            if let Err(error) = emulator.freeze_memory(address, value) {
+                log::warn!("unable to freeze memory at {:#06x}: {}", address, error);
+            }
+        }
+    }
+
+    fn show_message(&mut self, kind: &str, text: impl Into<String>) {
+        if let Some(screen) = self.screen.as_mut() {
+            screen.show_message(kind, text);
+        }
+    }
+
+    fn new_selection(&self) -> SelectionComponent {
+        SelectionComponent::new(
+            self.registry.clone(),
+            self.firmware_directory.clone(),
+            &self.rom_directory,
+        )
+        .with_last_session(self.last_session.clone())
+    }
+
+    // The rom is copied into the storage, a state of a different rom is dropped
+    fn remember_session(&mut self, backend_name: &str, rom_data: &[u8]) {
+        let session = LastSession {
+            backend_name: backend_name.to_string(),
+            rom_name: self.selection.rom_name().map(str::to_string),
+            rom_hash: rom_hash(rom_data),
+            resume: self
+                .last_session
+                .as_ref()
+                .is_some_and(|session| session.resume),
+        };
+        let same_rom = self.last_session.as_ref().is_some_and(|last| {
+            last.backend_name == session.backend_name && last.rom_hash == session.rom_hash
+        });
+        if !same_rom {
+            let result = self
+                .storage
+                .remove(SESSION_STATE_KEY)
+                .and_then(|_| self.storage.write(SESSION_ROM_KEY, rom_data));
+            if let Err(error) = result {
+                log::warn!("unable to store the rom of the session: {}", error);
+                return;
+            }
+        }
+        self.last_session = Some(session);
+    }
+
+    // Failed machines are not kept, continuing starts them over instead
+    fn store_session_state(&mut self) {
+        let (Some(emulator), Some(session)) = (self.emulator.as_ref(), self.last_session.as_ref())
+        else {
+            return;
+        };
+        if !session.resume || self.diagnostic.is_some() {
+            return;
+        }
+        let result = self
+            .registry
+            .get(&session.backend_name)
+            .and_then(|descriptor| {
+                let state = emulator.get_backend().save_state()?;
+                Ok(descriptor.snapshot_header(&session.rom_hash).encode(&state))
+            })
+            .and_then(|data| self.storage.write(SESSION_STATE_KEY, &data));
+        if let Err(error) = result {
+            log::warn!("unable to store the state of the session: {}", error);
         }
     }
 
+    fn resume_session_state(&mut self, session: &LastSession) -> Result<(), String> {
+        let Some(data) = self
+            .storage
+            .read(SESSION_STATE_KEY)
+            .map_err(|error| error.to_string())?
+        else {
+            return Ok(());
+        };
+        let descriptor = self
+            .registry
+            .get(&session.backend_name)
+            .map_err(|error| error.to_string())?;
+        descriptor
+            .upgrade_snapshot(&data, &session.rom_hash)
+            .and_then(|state| {
+                self.emulator
+                    .as_mut()
+                    .ok_or_else(|| Error::new("no backend is running"))?
+                    .load_state(&state)
+            })
+            .map_err(|error| format!("unable to resume the last session: {}", error))?;
+        self.show_message("session", "Resumed where you left off");
+        Ok(())
+    }
+
+    fn close_backend(&mut self) {
+        self.store_session_state();
+        self.capture_debugger_layout();
+        self.emulator = None;
+        self.current_rom_key = None;
+        self.screen = None;
+        self.secondary_screens.clear();
+        self.input = None;
+        self.help = None;
+        self.audio = None;
+        self.metrics = None;
+        self.inspector = None;
+        self.memory = None;
+        self.stack = None;
+        self.switches = None;
+        self.scheduler = None;
+        self.triggers = None;
+        self.vram = None;
+        self.savestates = None;
+        self.debug = None;
+        self.trace_log = None;
+        self.text_log = None;
+        self.diagnostic = None;
+    }
+
     fn _update(&mut self, ctx: &egui::Context) {
         if let Some(emulator) = self.emulator.as_mut() {
             if let Some(metrics) = self.metrics.as_mut() {
@@ -111,32 +963,150 @@ impl EmulatorApp {
             if let Some(metrics) = self.metrics.as_mut() {
                 metrics.stop(MeasurementType::EmulatorFrametime);
             }
+            if let Some(report) = emulator.take_failure() {
+                self.diagnostic = Some(DiagnosticComponent::new(report));
+                self.sidepanel_selection = SidepanelContent::Diagnostic;
+            }
 
             if let Some(screen) = self.screen.as_mut() {
-                screen.update(emulator, &self.app_command_sender, ctx);
+                screen.update(emulator, &self.command_sender, ctx);
+                if let Some(metrics) = self.metrics.as_mut() {
+                    metrics.observe_frames(screen.frames_received(), screen.frames_unshown());
+                }
+            }
+
+            if let Some(debug) = self.debug.as_mut() {
+                debug.update(emulator, &self.command_sender, ctx);
+            }
+
+            if let Some(trace_log) = self.trace_log.as_mut() {
+                trace_log.update(emulator, &self.command_sender, ctx);
+            }
+
+            if let Some(text_log) = self.text_log.as_mut() {
+                text_log.update(emulator, &self.command_sender, ctx);
+            }
+
+            if let Some(diagnostic) = self.diagnostic.as_mut() {
+                diagnostic.update(emulator, &self.command_sender, ctx);
+            }
+
+            for warning in emulator.take_scheduler_warnings() {
+                log::warn!("{}", describe_scheduler_warning(&warning));
+                if let Some(scheduler) = self.scheduler.as_mut() {
+                    scheduler.record_warning(warning);
+                }
+            }
+
+            for event in emulator.take_trigger_events() {
+                let Some(triggers) = self.triggers.as_mut() else {
+                    continue;
+                };
+                // Saved right away, the run stopped at the step which fired the trigger
+                let state_saved = triggers.action(event.id).saves_state()
+                    && match (self.savestates.as_mut(), self.registry.get(emulator.name())) {
+                        (Some(savestates), Ok(descriptor)) => savestates
+                            .save(
+                                self.storage.as_mut(),
+                                descriptor,
+                                emulator,
+                                SaveSlot::Trigger,
+                            )
+                            .inspect_err(|error| log::error!("unable to save the state: {}", error))
+                            .is_ok(),
+                        _ => false,
+                    };
+                let frame = self.screen.as_ref().and_then(ScreenComponent::last_frame);
+                triggers.capture(
+                    self.storage.as_mut(),
+                    emulator.name(),
+                    event,
+                    frame,
+                    state_saved,
+                    &self.command_sender,
+                );
+            }
+
+            for event in emulator.take_breakpoint_events() {
+                log::info!(
+                    "{} stopped at breakpoint {:#06X}",
+                    event.component,
+                    event.address
+                );
+                if let Some(inspector) = self.inspector.as_mut() {
+                    inspector.record_breakpoint(event);
+                }
+            }
+
+            for secondary_screen in self.secondary_screens.iter_mut() {
+                secondary_screen
+                    .screen
+                    .update(emulator, &self.command_sender, ctx);
             }
 
             if let Some(input) = self.input.as_mut() {
-                input.update(emulator, &self.app_command_sender, ctx);
+                input.update(emulator, &self.command_sender, ctx);
+
+                if let Some(metrics) = self.metrics.as_mut() {
+                    for probe in input.take_latency_probes() {
+                        metrics.track_input(probe);
+                    }
+                    let frames_emitted = self
+                        .screen
+                        .as_ref()
+                        .map_or(0, ScreenComponent::frames_emitted);
+                    metrics.observe_input(input.consumed(), frames_emitted);
+                }
+            }
+
+            if let Some(help) = self.help.as_mut() {
+                help.update(emulator, &self.command_sender, ctx);
             }
 
             if let Some(audio) = self.audio.as_mut() {
-                audio.update(emulator, &self.app_command_sender, ctx);
+                audio.update(emulator, &self.command_sender, ctx);
             }
 
             if let Some(metrics) = self.metrics.as_mut() {
-                metrics.update(emulator, &self.app_command_sender, ctx);
+                metrics.update(emulator, &self.command_sender, ctx);
             }
 
             if let Some(inspector) = self.inspector.as_mut() {
-                inspector.update(emulator, &self.app_command_sender, ctx);
+                inspector.update(emulator, &self.command_sender, ctx);
             }
 
             if let Some(memory) = self.memory.as_mut() {
-                memory.update(emulator, &self.app_command_sender, ctx);
+                memory.update(emulator, &self.command_sender, ctx);
+            }
+
+            if let Some(stack) = self.stack.as_mut() {
+                stack.update(emulator, &self.command_sender, ctx);
+            }
+
+            if let Some(switches) = self.switches.as_mut() {
+                switches.update(emulator, &self.command_sender, ctx);
+            }
+
+            if let Some(scheduler) = self.scheduler.as_mut() {
+                scheduler.update(emulator, &self.command_sender, ctx);
+            }
+
+            if let Some(triggers) = self.triggers.as_mut() {
+                triggers.update(emulator, &self.command_sender, ctx);
+            }
+
+            if let Some(savestates) = self.savestates.as_mut() {
+                savestates.update(emulator, &self.command_sender, ctx);
+            }
+
+            // Decoding the bitmap is only worth it while it is visible
+            if self.sidepanel_selection == SidepanelContent::Vram {
+                if let Some(vram) = self.vram.as_mut() {
+                    vram.update(emulator, &self.command_sender, ctx);
+                }
             }
         } else {
-            self.selection.update(&self.app_command_sender, ctx);
+            self.selection.update(&self.command_sender, ctx);
         }
     }
 
@@ -163,54 +1133,346 @@ impl EmulatorApp {
                                 SidepanelContent::Memory,
                                 "Memory",
                             );
+                            ui.selectable_value(
+                                &mut self.sidepanel_selection,
+                                SidepanelContent::Stack,
+                                "Stack",
+                            );
+                            if self.switches.is_some() {
+                                ui.selectable_value(
+                                    &mut self.sidepanel_selection,
+                                    SidepanelContent::Switches,
+                                    "DIP switches",
+                                );
+                            }
+                            ui.selectable_value(
+                                &mut self.sidepanel_selection,
+                                SidepanelContent::Scheduler,
+                                "Scheduler",
+                            );
+                            ui.selectable_value(
+                                &mut self.sidepanel_selection,
+                                SidepanelContent::Screens,
+                                "Screens",
+                            );
+                            ui.selectable_value(
+                                &mut self.sidepanel_selection,
+                                SidepanelContent::Triggers,
+                                "Triggers",
+                            );
+                            ui.selectable_value(
+                                &mut self.sidepanel_selection,
+                                SidepanelContent::Players,
+                                "Players",
+                            );
+                            ui.selectable_value(
+                                &mut self.sidepanel_selection,
+                                SidepanelContent::Vram,
+                                "Memory bitmap",
+                            );
+                            ui.selectable_value(
+                                &mut self.sidepanel_selection,
+                                SidepanelContent::Savestates,
+                                "Savestates",
+                            );
+                            if self.debug.is_some() {
+                                ui.selectable_value(
+                                    &mut self.sidepanel_selection,
+                                    SidepanelContent::Debug,
+                                    "Debug values",
+                                );
+                            }
+                            if self.trace_log.is_some() {
+                                ui.selectable_value(
+                                    &mut self.sidepanel_selection,
+                                    SidepanelContent::TraceLog,
+                                    "Trace log",
+                                );
+                            }
+                            if self.text_log.is_some() {
+                                ui.selectable_value(
+                                    &mut self.sidepanel_selection,
+                                    SidepanelContent::TextLog,
+                                    "Text log",
+                                );
+                            }
+                            if self.diagnostic.is_some() {
+                                ui.selectable_value(
+                                    &mut self.sidepanel_selection,
+                                    SidepanelContent::Diagnostic,
+                                    "Failure",
+                                );
+                            }
+                            ui.selectable_value(
+                                &mut self.sidepanel_selection,
+                                SidepanelContent::Appearance,
+                                "Appearance",
+                            );
                         });
+                    ui.horizontal(|ui| {
+                        let label = if emulator.is_paused() {
+                            "Resume"
+                        } else {
+                            "Pause"
+                        };
+                        if ui.button(label).clicked() {
+                            self.command_sender.send(AppCommand::TogglePause);
+                        }
+                        if ui
+                            .button("Reset")
+                            .on_hover_text("Starts the rom over with the same options")
+                            .clicked()
+                        {
+                            self.command_sender.send(AppCommand::Reset);
+                        }
+                        for (event, label) in [
+                            (ExecutionEvent::Frame, "Next frame"),
+                            (ExecutionEvent::TimerExpiry, "Next timer"),
+                            (ExecutionEvent::Signal, "Next signal"),
+                        ] {
+                            let response = ui
+                                .selectable_label(emulator.run_target() == Some(event), label)
+                                .on_hover_text("Runs until the event occurs, then pauses");
+                            if response.clicked() {
+                                self.command_sender.send(AppCommand::RunTo(event));
+                            }
+                        }
+                        let mut speed = emulator.speed();
+                        if ui
+                            .add(
+                                egui::DragValue::new(&mut speed)
+                                    .range(SPEED_RANGE)
+                                    .speed(0.05)
+                                    .suffix("x"),
+                            )
+                            .changed()
+                        {
+                            self.command_sender.send(AppCommand::SetSpeed(speed));
+                        }
+                        let rewind = format!(
+                            "{:.1}s to rewind",
+                            emulator.rewind_available().as_secs_f64()
+                        );
+                        if emulator.is_rewinding() {
+                            ui.colored_label(ui.visuals().warn_fg_color, "Rewinding");
+                        } else {
+                            let usage = emulator.rewind_usage();
+                            ui.label(rewind).on_hover_text(format!(
+                                "Hold {} to rewind\n{} of {} snapshots in {} KiB, {} KiB uncompressed",
+                                REWIND_KEY.name(),
+                                usage.snapshots,
+                                usage.capacity,
+                                usage.stored_bytes / 1024,
+                                usage.full_bytes / 1024
+                            ));
+                        }
+                        if let Some(recording) = self
+                            .audio
+                            .as_mut()
+                            .and_then(|audio| audio.draw_recording_controls(ui))
+                        {
+                            save_recording(emulator.name(), recording.encode());
+                        }
+                    });
+                    if let Some(input) = self.input.as_mut() {
+                        ui.horizontal(|ui| {
+                            if input.draw_demo_controls(ui) {
+                                if let Some(key) = self.current_rom_key.clone() {
+                                    self.demos.insert(key, input.session_movie().encode());
+                                }
+                            }
+                        });
+                        ui.horizontal(|ui| input.draw_paste_controls(ui));
+                        ui.horizontal(|ui| input.draw_capture_indicator(ui));
+                    }
                     ui.separator();
 
                     match self.sidepanel_selection {
                         SidepanelContent::Metrics => {
                             if let Some(metrics) = self.metrics.as_mut() {
-                                metrics.draw(emulator, ctx, ui);
+                                metrics.draw(emulator, &self.command_sender, ctx, ui);
                             }
                         }
                         SidepanelContent::Inspector => {
                             if let Some(inspector) = self.inspector.as_mut() {
-                                inspector.draw(emulator, ctx, ui);
+                                inspector.draw(emulator, &self.command_sender, ctx, ui);
                             }
                         }
                         SidepanelContent::Memory => {
                             if let Some(memory) = self.memory.as_mut() {
-                                memory.draw(emulator, ctx, ui);
+                                memory.draw(emulator, &self.command_sender, ctx, ui);
+                            }
+                        }
+                        SidepanelContent::Stack => {
+                            if let Some(stack) = self.stack.as_mut() {
+                                stack.draw(emulator, &self.command_sender, ctx, ui);
+                            }
+                        }
+                        SidepanelContent::Switches => {
+                            if let Some(switches) = self.switches.as_mut() {
+                                switches.draw(emulator, &self.command_sender, ctx, ui);
+                            }
+                        }
+                        SidepanelContent::Scheduler => {
+                            if let Some(scheduler) = self.scheduler.as_mut() {
+                                scheduler.draw(emulator, &self.command_sender, ctx, ui);
+                            }
+                        }
+                        SidepanelContent::Triggers => {
+                            if let Some(triggers) = self.triggers.as_mut() {
+                                triggers.draw(emulator, &self.command_sender, ctx, ui);
+                            }
+                        }
+                        SidepanelContent::Players => {
+                            if let Some(input) = self.input.as_mut() {
+                                input.draw_player_settings(ui);
+                            }
+                        }
+                        SidepanelContent::Debug => {
+                            if let Some(debug) = self.debug.as_mut() {
+                                debug.draw(emulator, &self.command_sender, ctx, ui);
+                            }
+                        }
+                        SidepanelContent::Diagnostic => {
+                            if let Some(diagnostic) = self.diagnostic.as_mut() {
+                                diagnostic.draw(emulator, &self.command_sender, ctx, ui);
+                            }
+                        }
+                        SidepanelContent::Appearance => {
+                            self.appearance.draw_settings(ctx, ui);
+                        }
+                        SidepanelContent::TraceLog => {
+                            if let Some(trace_log) = self.trace_log.as_mut() {
+                                trace_log.draw(emulator, &self.command_sender, ctx, ui);
+                            }
+                        }
+                        SidepanelContent::TextLog => {
+                            if let Some(text_log) = self.text_log.as_mut() {
+                                text_log.draw(emulator, &self.command_sender, ctx, ui);
+                            }
+                        }
+                        SidepanelContent::Vram => {
+                            if let Some(vram) = self.vram.as_mut() {
+                                vram.draw(emulator, &self.command_sender, ctx, ui);
+                            }
+                        }
+                        SidepanelContent::Savestates => {
+                            if let Some(savestates) = self.savestates.as_mut() {
+                                savestates.draw(emulator, &self.command_sender, ctx, ui);
+                                self.savestate_settings = savestates.settings().clone();
+                            }
+                        }
+                        SidepanelContent::Screens => {
+                            if let Some(screen) = self.screen.as_mut() {
+                                if screen.draw_display_settings(ui) {
+                                    self.display_configs.insert(
+                                        emulator.name().to_string(),
+                                        screen.display_config(),
+                                    );
+                                }
+                                screen.draw_overlay_settings(ui);
+                                if ui.button("Reset to backend default").clicked() {
+                                    self.display_configs.remove(emulator.name());
+                                    screen.set_display_config(
+                                        emulator.get_backend().get_info().display,
+                                    );
+                                }
+                                ui.separator();
+                                ui.label("Filters");
+                                if screen.draw_filter_settings(ui) {
+                                    self.video_filters = screen.filter_names();
+                                    for secondary_screen in self.secondary_screens.iter_mut() {
+                                        secondary_screen.screen.set_filters(&self.video_filters);
+                                    }
+                                }
+                                ui.separator();
+                            }
+                            if self.secondary_screens.is_empty() {
+                                ui.label("This backend has no additional screens");
+                            }
+                            for secondary_screen in self.secondary_screens.iter_mut() {
+                                ui.checkbox(
+                                    &mut secondary_screen.open,
+                                    secondary_screen.screen.name(),
+                                );
                             }
                         }
                     }
                 });
+
+            for secondary_screen in self.secondary_screens.iter_mut() {
+                egui::Window::new(secondary_screen.screen.name().to_string())
+                    .open(&mut secondary_screen.open)
+                    .default_size([256.0, 160.0])
+                    .resizable(true)
+                    .show(ctx, |ui| {
+                        secondary_screen
+                            .screen
+                            .draw(emulator, &self.command_sender, ctx, ui);
+                    });
+            }
         }
         egui::CentralPanel::default().show(ctx, |ui| {
             if let Some(emulator) = self.emulator.as_mut() {
                 if let Some(screen) = self.screen.as_mut() {
-                    screen.draw(emulator, ctx, ui);
+                    screen.draw(emulator, &self.command_sender, ctx, ui);
                 }
 
                 if let Some(input) = self.input.as_mut() {
-                    input.draw(emulator, ctx, ui);
+                    input.draw(emulator, &self.command_sender, ctx, ui);
+                }
+                if let Some(help) = self.help.as_mut() {
+                    help.draw(emulator, &self.command_sender, ctx, ui);
                 }
                 if let Some(audio) = self.audio.as_mut() {
-                    audio.draw(emulator, ctx, ui);
+                    audio.draw(emulator, &self.command_sender, ctx, ui);
                 }
             } else {
-                self.selection.draw(&self.app_command_sender, ctx, ui);
+                self.selection.draw(&self.command_sender, ctx, ui);
+                ui.separator();
+                egui::CollapsingHeader::new("Appearance").show(ui, |ui| {
+                    self.appearance.draw_settings(ctx, ui);
+                });
             }
         });
     }
 }
 
+fn save_recording(backend_name: &str, data: Vec<u8>) {
+    let file_name = format!("{}.wav", backend_name.to_lowercase());
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_futures::spawn_local(async move {
+        if let Some(handle) = rfd::AsyncFileDialog::new()
+            .add_filter("WAV", &["wav"])
+            .set_file_name(file_name)
+            .save_file()
+            .await
+        {
+            if let Err(error) = handle.write(&data).await {
+                log::error!("unable to save recording: {}", error);
+            }
+        }
+    });
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(path) = rfd::FileDialog::new()
+        .add_filter("WAV", &["wav"])
+        .set_file_name(file_name)
+        .save_file()
+    {
+        if let Err(error) = std::fs::write(path, data) {
+            log::error!("unable to save recording: {}", error);
+        }
+    }
+}
+
 impl Frontend for EmulatorApp {
     type Error = Error;
 
     fn register_text_receiver(
         &mut self,
-        _receiver: axwemulator_core::frontend::text::TextReceiver,
+        text_receiver: axwemulator_core::frontend::text::TextReceiver,
     ) -> Result<(), axwemulator_core::frontend::error::FrontendError<Self::Error>> {
+        self.text_log = Some(TextLogComponent::new(text_receiver));
         Ok(())
     }
 
@@ -218,7 +1480,35 @@ impl Frontend for EmulatorApp {
         &mut self,
         frame_receiver: axwemulator_core::frontend::graphics::FrameReceiver,
     ) -> Result<(), axwemulator_core::frontend::error::FrontendError<Self::Error>> {
-        self.screen = Some(ScreenComponent::new(frame_receiver));
+        self.screen = Some(ScreenComponent::new("screen", frame_receiver));
+        Ok(())
+    }
+
+    fn register_secondary_graphics_receiver(
+        &mut self,
+        name: &str,
+        frame_receiver: axwemulator_core::frontend::graphics::FrameReceiver,
+    ) -> Result<(), axwemulator_core::frontend::error::FrontendError<Self::Error>> {
+        self.secondary_screens.push(SecondaryScreen {
+            screen: ScreenComponent::new(name, frame_receiver),
+            open: true,
+        });
+        Ok(())
+    }
+
+    fn register_debug_receiver(
+        &mut self,
+        debug_receiver: axwemulator_core::frontend::debug::DebugReceiver,
+    ) -> Result<(), axwemulator_core::frontend::error::FrontendError<Self::Error>> {
+        self.debug = Some(DebugComponent::new(debug_receiver));
+        Ok(())
+    }
+
+    fn register_trace_receiver(
+        &mut self,
+        trace_receiver: axwemulator_core::frontend::trace::TraceReceiver,
+    ) -> Result<(), axwemulator_core::frontend::error::FrontendError<Self::Error>> {
+        self.trace_log = Some(TraceLogComponent::new(trace_receiver));
         Ok(())
     }
 
@@ -0,0 +1,176 @@
+use egui::{Color32, FontId, TextStyle, ThemePreference};
+use serde::{Deserialize, Serialize};
+
+pub const UI_SCALE_RANGE: std::ops::RangeInclusive<f32> = 0.5..=3.0;
+pub const MONOSPACE_SIZE_RANGE: std::ops::RangeInclusive<f32> = 8.0..=24.0;
+#[cfg(not(target_arch = "wasm32"))]
+const CUSTOM_MONOSPACE_FONT: &str = "custom_monospace";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Theme {
+    #[default]
+    System,
+    Dark,
+    Light,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 3] = [Theme::System, Theme::Dark, Theme::Light];
+
+    fn preference(self) -> ThemePreference {
+        match self {
+            Theme::System => ThemePreference::System,
+            Theme::Dark => ThemePreference::Dark,
+            Theme::Light => ThemePreference::Light,
+        }
+    }
+}
+
+// How the app itself looks, independent of the running rom
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Appearance {
+    // Scales the whole UI on top of the scale of the display
+    pub ui_scale: f32,
+    pub theme: Theme,
+    // Color of selections and links, None keeps the one of the theme
+    pub accent: Option<(u8, u8, u8)>,
+    // Size of the monospace text of e.g. the memory view and the disassembly
+    pub monospace_size: f32,
+    // Font file used for monospace text instead of the built-in one
+    #[cfg(not(target_arch = "wasm32"))]
+    pub monospace_font: Option<String>,
+}
+
+impl Default for Appearance {
+    fn default() -> Self {
+        Self {
+            ui_scale: 1.0,
+            theme: Theme::default(),
+            accent: None,
+            monospace_size: 12.0,
+            #[cfg(not(target_arch = "wasm32"))]
+            monospace_font: None,
+        }
+    }
+}
+
+impl Appearance {
+    pub fn apply(&self, ctx: &egui::Context) {
+        ctx.set_zoom_factor(
+            self.ui_scale
+                .clamp(*UI_SCALE_RANGE.start(), *UI_SCALE_RANGE.end()),
+        );
+        ctx.set_theme(self.theme.preference());
+        let accent = self.accent.map(|(r, g, b)| Color32::from_rgb(r, g, b));
+        let monospace_size = self
+            .monospace_size
+            .clamp(*MONOSPACE_SIZE_RANGE.start(), *MONOSPACE_SIZE_RANGE.end());
+        ctx.all_styles_mut(|style| {
+            // Each theme has its own defaults to fall back to
+            let defaults = if style.visuals.dark_mode {
+                egui::Visuals::dark()
+            } else {
+                egui::Visuals::light()
+            };
+            style.visuals.selection.bg_fill = accent.unwrap_or(defaults.selection.bg_fill);
+            style.visuals.hyperlink_color = accent.unwrap_or(defaults.hyperlink_color);
+            style
+                .text_styles
+                .insert(TextStyle::Monospace, FontId::monospace(monospace_size));
+        });
+    }
+
+    // Loading fonts rebuilds the font atlas, so this only runs when the font changed
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn apply_fonts(&self, ctx: &egui::Context) {
+        let mut fonts = egui::FontDefinitions::default();
+        if let Some(path) = self.monospace_font.as_ref() {
+            match std::fs::read(path) {
+                Ok(data) => {
+                    fonts.font_data.insert(
+                        CUSTOM_MONOSPACE_FONT.to_string(),
+                        std::sync::Arc::new(egui::FontData::from_owned(data)),
+                    );
+                    fonts
+                        .families
+                        .entry(egui::FontFamily::Monospace)
+                        .or_default()
+                        .insert(0, CUSTOM_MONOSPACE_FONT.to_string());
+                }
+                Err(error) => log::warn!("unable to load font {}: {}", path, error),
+            }
+        }
+        ctx.set_fonts(fonts);
+    }
+
+    // The built-in fonts are all there is on the web
+    #[cfg(target_arch = "wasm32")]
+    pub fn apply_fonts(&self, _ctx: &egui::Context) {}
+
+    // Applies the changes right away, they are stored with the rest of the app settings
+    pub fn draw_settings(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) {
+        let before = self.clone();
+        ui.add(
+            egui::Slider::new(&mut self.ui_scale, UI_SCALE_RANGE)
+                .step_by(0.05)
+                .text("UI scale"),
+        );
+        ui.horizontal(|ui| {
+            for theme in Theme::ALL {
+                ui.selectable_value(&mut self.theme, theme, format!("{:?}", theme));
+            }
+            ui.label("Theme");
+        });
+        ui.horizontal(|ui| {
+            let mut custom_accent = self.accent.is_some();
+            ui.checkbox(&mut custom_accent, "Accent color");
+            match (custom_accent, self.accent.as_mut()) {
+                (true, Some((r, g, b))) => {
+                    let mut color = [*r, *g, *b];
+                    ui.color_edit_button_srgb(&mut color);
+                    (*r, *g, *b) = (color[0], color[1], color[2]);
+                }
+                (true, None) => {
+                    let color = ui.visuals().selection.bg_fill;
+                    self.accent = Some((color.r(), color.g(), color.b()));
+                }
+                (false, _) => self.accent = None,
+            }
+        });
+        ui.add(
+            egui::Slider::new(&mut self.monospace_size, MONOSPACE_SIZE_RANGE)
+                .step_by(0.5)
+                .text("Monospace size"),
+        );
+        #[cfg(not(target_arch = "wasm32"))]
+        ui.horizontal(|ui| {
+            ui.label("Monospace font");
+            let name = self.monospace_font.as_deref().unwrap_or("built-in");
+            ui.label(name)
+                .on_hover_text("Used by the memory view and the disassembly");
+            if ui.button("Choose").clicked() {
+                if let Some(path) = rfd::FileDialog::new()
+                    .add_filter("Fonts", &["ttf", "otf"])
+                    .pick_file()
+                {
+                    self.monospace_font = Some(path.to_string_lossy().to_string());
+                }
+            }
+            if self.monospace_font.is_some() && ui.small_button("Built-in").clicked() {
+                self.monospace_font = None;
+            }
+        });
+        if ui.button("Reset appearance").clicked() {
+            *self = Self::default();
+        }
+        if *self == before {
+            return;
+        }
+        self.apply(ctx);
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.monospace_font != before.monospace_font {
+            self.apply_fonts(ctx);
+        }
+    }
+}
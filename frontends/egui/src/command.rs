@@ -0,0 +1,76 @@
+use std::sync::mpsc::{self, TryRecvError};
+
+use crate::app::AppCommand;
+
+pub type CommandReply = Result<(), String>;
+
+pub struct CommandEnvelope {
+    command: AppCommand,
+    reply_sender: Option<mpsc::Sender<CommandReply>>,
+}
+
+impl CommandEnvelope {
+    pub fn handle<F>(self, handler: F)
+    where
+        F: FnOnce(AppCommand) -> CommandReply,
+    {
+        let reply = handler(self.command);
+        if let Some(reply_sender) = self.reply_sender {
+            // the requester may have lost interest in the reply already
+            let _ = reply_sender.send(reply);
+        }
+    }
+}
+
+pub struct PendingReply(mpsc::Receiver<CommandReply>);
+
+impl PendingReply {
+    pub fn poll(&self) -> Option<CommandReply> {
+        match self.0.try_recv() {
+            Ok(reply) => Some(reply),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err("command was dropped".to_string())),
+        }
+    }
+}
+
+// The app only repaints on demand, so sending a command wakes it up to handle the command
+#[derive(Clone)]
+pub struct CommandSender(mpsc::Sender<CommandEnvelope>, egui::Context);
+
+impl CommandSender {
+    pub fn send(&self, command: AppCommand) {
+        self.0
+            .send(CommandEnvelope {
+                command,
+                reply_sender: None,
+            })
+            .unwrap();
+        self.1.request_repaint();
+    }
+
+    pub fn request(&self, command: AppCommand) -> PendingReply {
+        let (reply_sender, reply_receiver) = mpsc::channel();
+        self.0
+            .send(CommandEnvelope {
+                command,
+                reply_sender: Some(reply_sender),
+            })
+            .unwrap();
+        self.1.request_repaint();
+        PendingReply(reply_receiver)
+    }
+}
+
+pub struct CommandReceiver(mpsc::Receiver<CommandEnvelope>);
+
+impl CommandReceiver {
+    pub fn try_recv(&self) -> Option<CommandEnvelope> {
+        self.0.try_recv().ok()
+    }
+}
+
+pub fn build_command_bus(ctx: egui::Context) -> (CommandSender, CommandReceiver) {
+    let (sender, receiver) = mpsc::channel();
+    (CommandSender(sender, ctx), CommandReceiver(receiver))
+}
@@ -1,69 +1,97 @@
-use std::{fmt::Debug, sync::mpsc};
+use std::{
+    fmt::Debug,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
 
-use axwemulator_core::{frontend::audio::AudioReceiver, utils::Ringbuffer};
+use axwemulator_core::{
+    frontend::{
+        audio::{AudioReceiver, Resampler, ResamplerQuality},
+        wav::WavRecorder,
+    },
+    utils::Ringbuffer,
+};
 use cpal::{
     FromSample, Sample, Stream,
     traits::{DeviceTrait, HostTrait, StreamTrait},
 };
-use rubato::{
-    Resampler, SincFixedIn, SincInterpolationParameters, SincInterpolationType, WindowFunction,
-};
 
-use crate::app::AppCommand;
+use crate::command::CommandSender;
 
 use super::Component;
 
-const CHUNK_SIZE: usize = 1024;
-const TARGET: usize = 2 * CHUNK_SIZE;
-const MOVING_AVERAGE_RATIO: f64 = 0.05;
+const RESAMPLER_QUALITY: ResamplerQuality = ResamplerQuality::Sinc;
+const FADE_DURATION_SECS: f32 = 0.02;
+
+// Shared with the output stream. While closed the stream fades to silence and then stops pulling
+// samples, so pausing neither pops nor loops stale buffer contents.
+#[derive(Default)]
+struct OutputGate {
+    open: AtomicBool,
+    silent: AtomicBool,
+}
 
 pub struct AudioComponent {
     audio_receiver: AudioReceiver,
-    input_sample_rate: f64,
-    resampler: SincFixedIn<f32>,
+    resampler: Resampler,
     output_buffer: Ringbuffer<f32>,
-    output_sample_rate: f64,
-    output_stream: Option<Stream>,
-    output_buffer_len_average: usize,
-    output_buffer_len_average_history: Ringbuffer<usize>,
+    output_gate: Arc<OutputGate>,
+    flushed: bool,
+    recorder: Option<WavRecorder>,
+    // only held to keep the device stream playing
+    _output_stream: Option<Stream>,
 }
 
 impl AudioComponent {
     pub fn new(audio_receiver: AudioReceiver) -> Self {
-        let params = SincInterpolationParameters {
-            sinc_len: 64,
-            f_cutoff: 0.91,
-            oversampling_factor: 1024,
-            interpolation: SincInterpolationType::Linear,
-            window: WindowFunction::Hann2,
-        };
-
-        let resampler = SincFixedIn::<f32>::new(
-            48000.0 / (audio_receiver.sample_rate() as f64),
-            2.0,
-            params,
-            CHUNK_SIZE,
-            1,
+        let output_buffer = Ringbuffer::new(5000);
+        let output_gate = Arc::new(OutputGate::default());
+        let (output_stream, output_sample_rate) = Self::init(&output_buffer, &output_gate);
+
+        let resampler = Resampler::new(
+            audio_receiver.sample_rate(),
+            output_sample_rate,
+            RESAMPLER_QUALITY,
         )
         .unwrap();
 
-        let mut result = Self {
-            input_sample_rate: audio_receiver.sample_rate() as f64,
+        Self {
             audio_receiver,
             resampler,
-            output_buffer: Ringbuffer::new(5000),
-            output_buffer_len_average: 0,
-            output_buffer_len_average_history: Ringbuffer::new(60),
-            output_sample_rate: 48000.0,
-            output_stream: None,
-        };
-
-        result.init();
+            output_buffer,
+            output_gate,
+            flushed: false,
+            recorder: None,
+            _output_stream: output_stream,
+        }
+    }
 
-        result
+    // Draws the record button and returns the finished recording once it is stopped
+    pub fn draw_recording_controls(&mut self, ui: &mut egui::Ui) -> Option<WavRecorder> {
+        match self.recorder.as_ref() {
+            None => {
+                if ui.button("Record audio").clicked() {
+                    self.recorder = Some(WavRecorder::new(self.audio_receiver.sample_rate()));
+                }
+                None
+            }
+            Some(recorder) => {
+                let label = format!("Stop recording ({:.1}s)", recorder.duration_secs());
+                if ui.button(label).clicked() {
+                    self.recorder.take()
+                } else {
+                    None
+                }
+            }
+        }
     }
 
-    pub fn init(&mut self) {
+    fn init(
+        output_buffer: &Ringbuffer<f32>,
+        output_gate: &Arc<OutputGate>,
+    ) -> (Option<Stream>, f64) {
         let host = cpal::default_host();
         let device = host
             .default_output_device()
@@ -75,122 +103,120 @@ impl AudioComponent {
         let err_fn = move |err| {
             eprintln!("an error occurred on stream: {}", err);
         };
-        let output_buffer = self.output_buffer.clone();
+        let output_buffer = output_buffer.clone();
+        let output_gate = output_gate.clone();
+        let fade_step = 1.0 / (FADE_DURATION_SECS * config.sample_rate().0 as f32);
+        let mut gain = 0.0;
 
-        self.output_sample_rate = config.sample_rate().0 as f64 * 1.02;
-        self.output_stream = match config.sample_format() {
+        let output_sample_rate = config.sample_rate().0 as f64 * 1.02;
+        let output_stream = match config.sample_format() {
             cpal::SampleFormat::F32 => device
                 .build_output_stream(
                     &config.into(),
-                    move |data, _: &_| write_data::<f32>(data, &output_buffer, channels as usize),
+                    move |data, _: &_| {
+                        write_data::<f32>(
+                            data,
+                            &output_buffer,
+                            &output_gate,
+                            &mut gain,
+                            fade_step,
+                            channels as usize,
+                        )
+                    },
                     err_fn,
                     None,
                 )
                 .ok(),
             _ => unimplemented!("unimplemented sample format"),
         };
-        self.output_stream.as_ref().unwrap().play().unwrap();
-    }
-
-    pub fn recalculate_resampler_ratio(&mut self) {
-        // slope via regression
-        let (mut sx, mut sy, mut sxx, mut sxy) = (0, 0, 0, 0);
-        for (idx, avg) in self
-            .output_buffer_len_average_history
-            .peek_range(..)
-            .iter()
-            .enumerate()
-        {
-            sx += idx;
-            sy += avg;
-            sxx += idx * idx;
-            sxy += idx * avg;
-        }
-        let n = self.output_buffer_len_average_history.len();
-        let num = (n * sxy) as f64 - (sx * sy) as f64;
-        let den = (n * sxx) as f64 - (sx * sx) as f64;
-        let slope: f64 = if den == 0.0 { 0.0 } else { num / den };
-
-        let difference = self.output_buffer_len_average as f64 - TARGET as f64;
-        let direction = if difference == 0.0 {
-            0.0
-        } else {
-            difference / difference.abs()
-        };
+        output_stream.as_ref().unwrap().play().unwrap();
 
-        let mut adjustment = 0.0;
-
-        if direction * slope < -1.0 {
-            adjustment = slope.abs() / 4.0;
-            if adjustment > 1.0 {
-                adjustment = 1.0;
-            }
-        } else if direction * slope > 0.0 || self.output_buffer_len_average == 0 {
-            let skew = (difference.abs() / 400.0) * 10.0;
-            adjustment = (slope.abs() + skew) / -2.0;
-            if adjustment < -2.0 {
-                adjustment = -2.0;
-            }
-        }
-
-        adjustment *= direction;
-        self.output_sample_rate += adjustment;
-
-        self.resampler
-            .set_resample_ratio(self.output_sample_rate / self.input_sample_rate, false)
-            .unwrap();
+        (output_stream, output_sample_rate)
     }
 }
 
-fn write_data<T>(output: &mut [T], input: &Ringbuffer<f32>, channels: usize)
-where
+fn write_data<T>(
+    output: &mut [T],
+    input: &Ringbuffer<f32>,
+    gate: &OutputGate,
+    gain: &mut f32,
+    fade_step: f32,
+    channels: usize,
+) where
     T: Sample + FromSample<f32> + Debug,
 {
+    let open = gate.open.load(Ordering::Acquire);
     for frame in output.chunks_mut(channels) {
-        let received = input.pop_front().unwrap_or(0.0);
+        *gain = if open {
+            (*gain + fade_step).min(1.0)
+        } else {
+            (*gain - fade_step).max(0.0)
+        };
+        let received = if *gain > 0.0 {
+            input.pop_front().unwrap_or(0.0) * *gain
+        } else {
+            0.0
+        };
         let value: T = T::from_sample(received);
         for sample in frame.iter_mut() {
             *sample = value;
         }
     }
+    gate.silent.store(*gain == 0.0, Ordering::Release);
 }
 
 impl Component for AudioComponent {
     fn update(
         &mut self,
-        _emulator: &super::emulator::EmulatorComponent,
-        _command_sender: &mpsc::Sender<AppCommand>,
+        emulator: &super::emulator::EmulatorComponent,
+        _command_sender: &CommandSender,
         _ctx: &egui::Context,
     ) {
-        // pull samples
-        while self.audio_receiver.len() > CHUNK_SIZE {
-            let samples = self
-                .audio_receiver
-                .pop_range(..CHUNK_SIZE)
-                .iter()
-                .map(|s| s.1)
-                .collect::<Vec<f32>>();
-
-            // convert to target sample rate
-            let resampled = self.resampler.process(&[samples], None).unwrap();
-
-            for s in resampled.first().unwrap() {
-                self.output_buffer.push_back(*s);
+        if emulator.is_paused() {
+            self.output_gate.open.store(false, Ordering::Release);
+            if !self.flushed && self.output_gate.silent.load(Ordering::Acquire) {
+                self.output_buffer.drain_and_pop_range(..);
+                for (clock, sample) in self.audio_receiver.pop_range(..) {
+                    if let Some(recorder) = self.recorder.as_mut() {
+                        recorder.push(clock, sample);
+                    }
+                }
+                self.flushed = true;
             }
+            return;
+        }
+        self.flushed = false;
+
+        let recorder = &mut self.recorder;
+        let resampled = self
+            .resampler
+            .process_with_tap(&self.audio_receiver, |clock, sample| {
+                if let Some(recorder) = recorder.as_mut() {
+                    recorder.push(clock, sample);
+                }
+            })
+            .unwrap();
+        for s in resampled {
+            self.output_buffer.push_back(s);
         }
 
-        self.output_buffer_len_average =
-            ((self.output_buffer_len_average as f64) * (1.0 - MOVING_AVERAGE_RATIO)
-                + self.output_buffer.len() as f64 * MOVING_AVERAGE_RATIO) as usize;
-        self.output_buffer_len_average_history
-            .push_back(self.output_buffer_len_average);
+        // after starting or resuming, only unmute once the buffer is filled to the target level
+        if !self.output_gate.open.load(Ordering::Acquire) {
+            if self.output_buffer.len() >= self.resampler.target_buffer_len() {
+                self.output_gate.open.store(true, Ordering::Release);
+            }
+            return;
+        }
 
-        self.recalculate_resampler_ratio();
+        self.resampler
+            .adjust_to_buffer_len(self.output_buffer.len())
+            .unwrap();
     }
 
     fn draw(
         &mut self,
         _emulator: &super::emulator::EmulatorComponent,
+        _command_sender: &CommandSender,
         _ctx: &egui::Context,
         _ui: &mut egui::Ui,
     ) {
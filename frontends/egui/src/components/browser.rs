@@ -0,0 +1,208 @@
+use std::path::{Path, PathBuf};
+
+use axwemulator_core::{
+    backend::registry::BackendRegistry,
+    rom::{Rom, unambiguous_detection},
+};
+use egui::{Key, RichText};
+
+use super::selection::read_rom_file;
+
+// Larger files are listed without detection, reading them would stall the listing
+const MAX_DETECTION_SIZE: u64 = 16 * 1024 * 1024;
+
+enum EntryKind {
+    Parent,
+    Directory,
+    Rom { platform: Option<&'static str> },
+}
+
+struct BrowserEntry {
+    path: PathBuf,
+    name: String,
+    kind: EntryKind,
+}
+
+pub enum BrowserAction {
+    Launch(Rom),
+    // The browser moved to another directory, which should be remembered
+    Navigated(PathBuf),
+}
+
+// Lists a rom folder with the detected platform of each file. Can be operated with the arrow keys
+// and enter alone, for setups without a mouse.
+pub struct RomBrowser {
+    directory: PathBuf,
+    entries: Vec<BrowserEntry>,
+    selected: usize,
+    error: Option<String>,
+}
+
+impl RomBrowser {
+    pub fn new(directory: PathBuf, registry: &BackendRegistry) -> Self {
+        let mut browser = Self {
+            directory,
+            entries: vec![],
+            selected: 0,
+            error: None,
+        };
+        browser.refresh(registry);
+        browser
+    }
+
+    fn refresh(&mut self, registry: &BackendRegistry) {
+        self.entries.clear();
+        self.selected = 0;
+        self.error = None;
+        if let Some(parent) = self.directory.parent() {
+            self.entries.push(BrowserEntry {
+                path: parent.to_path_buf(),
+                name: "..".to_string(),
+                kind: EntryKind::Parent,
+            });
+        }
+        let read_dir = match std::fs::read_dir(&self.directory) {
+            Ok(read_dir) => read_dir,
+            Err(error) => {
+                self.error = Some(format!("unable to read directory: {}", error));
+                return;
+            }
+        };
+
+        let mut entries: Vec<BrowserEntry> = read_dir
+            .flatten()
+            .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok()?;
+                let name = entry.file_name().to_string_lossy().to_string();
+                let kind = if metadata.is_dir() {
+                    EntryKind::Directory
+                } else {
+                    EntryKind::Rom {
+                        platform: detect_platform(&entry.path(), metadata.len(), registry),
+                    }
+                };
+                Some(BrowserEntry {
+                    path: entry.path(),
+                    name,
+                    kind,
+                })
+            })
+            .collect();
+        entries.sort_by(|a, b| {
+            let is_file = |entry: &BrowserEntry| matches!(entry.kind, EntryKind::Rom { .. });
+            (is_file(a), a.name.to_lowercase()).cmp(&(is_file(b), b.name.to_lowercase()))
+        });
+        self.entries.extend(entries);
+    }
+
+    fn activate(&mut self, index: usize, registry: &BackendRegistry) -> Option<BrowserAction> {
+        let entry = self.entries.get(index)?;
+        match entry.kind {
+            EntryKind::Parent | EntryKind::Directory => {
+                let previous = std::mem::replace(&mut self.directory, entry.path.clone());
+                self.refresh(registry);
+                // Going up keeps the directory we came from selected
+                if let Some(index) = self.entries.iter().position(|e| e.path == previous) {
+                    self.selected = index;
+                }
+                Some(BrowserAction::Navigated(self.directory.clone()))
+            }
+            EntryKind::Rom { .. } => match read_rom_file(&entry.path) {
+                Ok(rom) => Some(BrowserAction::Launch(rom)),
+                Err(error) => {
+                    self.error = Some(format!("unable to read rom: {}", error));
+                    None
+                }
+            },
+        }
+    }
+
+    fn go_up(&mut self, registry: &BackendRegistry) -> Option<BrowserAction> {
+        match self.entries.first() {
+            Some(BrowserEntry {
+                kind: EntryKind::Parent,
+                ..
+            }) => self.activate(0, registry),
+            _ => None,
+        }
+    }
+
+    pub fn draw(&mut self, registry: &BackendRegistry, ui: &mut egui::Ui) -> Option<BrowserAction> {
+        ui.label(RichText::new(self.directory.to_string_lossy()).strong());
+        if let Some(error) = &self.error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+
+        let mut action = None;
+        let previous_selection = self.selected;
+        // Text fields keep their keys, e.g. while editing the firmware directory
+        if ui.memory(|memory| memory.focused().is_none()) {
+            let last = self.entries.len().saturating_sub(1);
+            ui.input(|input| {
+                if input.key_pressed(Key::ArrowDown) {
+                    self.selected = (self.selected + 1).min(last);
+                }
+                if input.key_pressed(Key::ArrowUp) {
+                    self.selected = self.selected.saturating_sub(1);
+                }
+                if input.key_pressed(Key::PageDown) {
+                    self.selected = (self.selected + 10).min(last);
+                }
+                if input.key_pressed(Key::PageUp) {
+                    self.selected = self.selected.saturating_sub(10);
+                }
+            });
+            if ui.input(|input| input.key_pressed(Key::Enter)) {
+                action = self.activate(self.selected, registry);
+            } else if ui.input(|input| input.key_pressed(Key::Backspace)) {
+                action = self.go_up(registry);
+            }
+        }
+
+        let scroll_to_selected = self.selected != previous_selection;
+        let mut clicked = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("rom_browser")
+                .num_columns(2)
+                .striped(true)
+                .show(ui, |ui| {
+                    for (index, entry) in self.entries.iter().enumerate() {
+                        let name = match entry.kind {
+                            EntryKind::Rom { .. } => entry.name.clone(),
+                            _ => format!("{}/", entry.name),
+                        };
+                        let response = ui.selectable_label(index == self.selected, name);
+                        if index == self.selected && scroll_to_selected {
+                            response.scroll_to_me(None);
+                        }
+                        if response.double_clicked() {
+                            clicked = Some(index);
+                        } else if response.clicked() {
+                            self.selected = index;
+                        }
+                        if let EntryKind::Rom { platform } = entry.kind {
+                            ui.label(platform.unwrap_or("unknown"));
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
+        if let Some(index) = clicked {
+            action = self.activate(index, registry);
+        }
+        action
+    }
+}
+
+fn detect_platform(path: &Path, size: u64, registry: &BackendRegistry) -> Option<&'static str> {
+    if size == 0 || size > MAX_DETECTION_SIZE {
+        return None;
+    }
+    let data = std::fs::read(path).ok()?;
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string());
+    let detections = registry.detect(&Rom::new(name, data));
+    unambiguous_detection(&detections).map(|detection| detection.backend)
+}
@@ -0,0 +1,110 @@
+use std::collections::VecDeque;
+
+use axwemulator_core::frontend::debug::{DebugReceiver, DebugWidget};
+use egui::{Color32, RichText, Sense, Stroke, pos2, vec2};
+
+use crate::command::CommandSender;
+
+use super::Component;
+
+// About ten seconds of history at 60 values per second
+const PLOT_HISTORY: usize = 600;
+const PLOT_HEIGHT: f32 = 60.0;
+
+struct DebugSeries {
+    name: &'static str,
+    widget: DebugWidget,
+    history: VecDeque<f64>,
+}
+
+// Renders the values a backend pushes through its debug channel, in the order they first appeared
+pub struct DebugComponent {
+    receiver: DebugReceiver,
+    series: Vec<DebugSeries>,
+}
+
+impl DebugComponent {
+    pub fn new(receiver: DebugReceiver) -> Self {
+        Self {
+            receiver,
+            series: vec![],
+        }
+    }
+
+    fn draw_plot(ui: &mut egui::Ui, history: &VecDeque<f64>) {
+        let (response, painter) =
+            ui.allocate_painter(vec2(ui.available_width(), PLOT_HEIGHT), Sense::hover());
+        let rect = response.rect;
+        painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+
+        let min = history.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = history.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let range = if max > min { max - min } else { 1.0 };
+        let points = history
+            .iter()
+            .enumerate()
+            .map(|(index, value)| {
+                let x = rect.left() + rect.width() * index as f32 / (PLOT_HISTORY - 1) as f32;
+                let y = rect.bottom() - rect.height() * ((value - min) / range) as f32;
+                pos2(x, y)
+            })
+            .collect();
+        painter.line(points, Stroke::new(1.0, Color32::LIGHT_GREEN));
+
+        if let Some(hover) = response.hover_pos() {
+            let index = ((hover.x - rect.left()) / rect.width() * (PLOT_HISTORY - 1) as f32).round()
+                as usize;
+            if let Some(value) = history.get(index) {
+                response.on_hover_text(format!("{}", value));
+            }
+        }
+    }
+}
+
+impl Component for DebugComponent {
+    fn update(
+        &mut self,
+        _emulator: &super::emulator::EmulatorComponent,
+        _command_sender: &CommandSender,
+        _ctx: &egui::Context,
+    ) {
+        for (_, sample) in self.receiver.pop_all() {
+            let index = match self.series.iter().position(|s| s.name == sample.name) {
+                Some(index) => index,
+                None => {
+                    self.series.push(DebugSeries {
+                        name: sample.name,
+                        widget: sample.widget,
+                        history: VecDeque::with_capacity(PLOT_HISTORY),
+                    });
+                    self.series.len() - 1
+                }
+            };
+            let history = &mut self.series[index].history;
+            if history.len() == PLOT_HISTORY {
+                history.pop_front();
+            }
+            history.push_back(sample.value);
+        }
+    }
+
+    fn draw(
+        &mut self,
+        _emulator: &super::emulator::EmulatorComponent,
+        _command_sender: &CommandSender,
+        _ctx: &egui::Context,
+        ui: &mut egui::Ui,
+    ) {
+        if self.series.is_empty() {
+            ui.label("The backend reports no debug values");
+            return;
+        }
+        for series in self.series.iter() {
+            let latest = series.history.back().copied().unwrap_or_default();
+            ui.label(RichText::new(format!("{:>16}: {}", series.name, latest)).monospace());
+            if series.widget == DebugWidget::Plot {
+                Self::draw_plot(ui, &series.history);
+            }
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use axwemulator_core::{
+    diagnostic::FailureReport, error::Error, frontend::storage::StorageProvider,
+};
+use egui::{RichText, ScrollArea, TextStyle};
+
+use crate::{app::AppCommand, command::CommandSender};
+
+use super::Component;
+
+// Details of the rom the failure happened with, written on top of the report
+pub struct BugReportContext {
+    pub backend_name: String,
+    pub rom_key: String,
+    pub options: String,
+}
+
+// The report and the state next to it in a directory of its own, returns where it ended up
+pub fn export_bug_report(
+    storage: &mut dyn StorageProvider,
+    report: &FailureReport,
+    context: &BugReportContext,
+) -> Result<String, Error> {
+    let directory = format!(
+        "bug-reports/{}-{}",
+        context.backend_name.to_lowercase(),
+        report.clock.as_duration().as_nanos()
+    );
+    let text = format!(
+        "Backend: {}\nRom: {}\nOptions: {}\n{}",
+        context.backend_name, context.rom_key, context.options, report
+    );
+    storage.write(&format!("{}/report.txt", directory), text.as_bytes())?;
+    storage.write(&format!("{}/failure.state", directory), &report.state)?;
+    Ok(storage.location(&directory))
+}
+
+// Shows why and where the emulation stopped after a component failed
+pub struct DiagnosticComponent {
+    report: FailureReport,
+    exported_to: Option<String>,
+}
+
+impl DiagnosticComponent {
+    pub fn new(report: FailureReport) -> Self {
+        Self {
+            report,
+            exported_to: None,
+        }
+    }
+
+    pub fn report(&self) -> &FailureReport {
+        &self.report
+    }
+
+    pub fn set_exported_to(&mut self, location: String) {
+        self.exported_to = Some(location);
+    }
+}
+
+impl Component for DiagnosticComponent {
+    fn update(
+        &mut self,
+        _emulator: &super::emulator::EmulatorComponent,
+        _command_sender: &CommandSender,
+        _ctx: &egui::Context,
+    ) {
+    }
+
+    fn draw(
+        &mut self,
+        _emulator: &super::emulator::EmulatorComponent,
+        command_sender: &CommandSender,
+        _ctx: &egui::Context,
+        ui: &mut egui::Ui,
+    ) {
+        ui.colored_label(ui.visuals().error_fg_color, &self.report.error);
+        ui.label(format!(
+            "{} failed after {:.6}s",
+            self.report.component,
+            self.report.clock.as_duration().as_nanos() as f64 / 1e9
+        ));
+        ui.horizontal(|ui| {
+            if ui
+                .button("Export bug report")
+                .on_hover_text("Writes the report and a save state from right before the failure")
+                .clicked()
+            {
+                command_sender.send(AppCommand::ExportBugReport);
+            }
+            if ui.small_button("Copy").clicked() {
+                ui.ctx().copy_text(self.report.to_string());
+            }
+        });
+        if let Some(location) = self.exported_to.as_ref() {
+            ui.label(format!("Written to {}", location));
+        }
+
+        for (name, lines) in self.report.components.iter() {
+            egui::CollapsingHeader::new(name)
+                .default_open(*name == self.report.component)
+                .show(ui, |ui| {
+                    for line in lines {
+                        ui.label(RichText::new(line).monospace());
+                    }
+                });
+        }
+
+        if self.report.trace.is_empty() {
+            ui.label("No steps could be replayed before the failure");
+            return;
+        }
+        ui.separator();
+        ui.label(format!("Last {} steps", self.report.trace.len()));
+        let row_height = ui.text_style_height(&TextStyle::Monospace);
+        ScrollArea::both()
+            .id_salt("failure_trace")
+            .stick_to_bottom(true)
+            .show_rows(ui, row_height, self.report.trace.len(), |ui, rows| {
+                for line in &self.report.trace[rows] {
+                    ui.label(RichText::new(line).monospace());
+                }
+            });
+    }
+}
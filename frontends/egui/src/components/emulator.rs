@@ -1,62 +1,389 @@
-use web_time::Instant;
+use web_time::{Instant, SystemTime, UNIX_EPOCH};
 
-use axwemulator_backends_chip8::{Chip8Options, Platform, create_chip8_backend};
-use axwemulator_core::{backend::Backend, frontend::Frontend};
+use axwemulator_core::{
+    backend::{
+        Backend, CatchUpPolicy, ExecutionEvent,
+        breakpoint::BreakpointEvent,
+        component::MemoryAddress,
+        fairness::SchedulerWarning,
+        firmware::FirmwareFiles,
+        options::OptionValues,
+        registry::{BackendDescriptor, BackendOptions},
+        rewind::{RewindBuffer, RewindUsage},
+        rtc::RtcPolicy,
+        trigger::{TriggerCondition, TriggerEvent, TriggerId},
+    },
+    diagnostic::{FailureReport, capture_failure},
+    error::Error,
+    frontend::Frontend,
+    trace::replay_trace,
+};
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
-pub enum AvailableBackends {
-    #[default]
-    Chip8,
-    SuperChip,
-}
+const MAX_SKIPPED_FRAMES: usize = 4;
+// One minute of emulated time can be rewound
+const REWIND_INTERVAL_MS: u64 = 100;
+const REWIND_SNAPSHOTS: usize = 600;
+// Longest emulated time of one update, the rest of a host stall is dropped instead of caught up
+const MAX_CATCH_UP_MS: u64 = 250;
+// Frames replayed before a breakpoint or trigger to trace how the machine got there
+pub const REPLAY_FRAMES: u32 = 60;
 
 pub struct EmulatorComponent {
+    name: String,
     backend: Backend,
     backend_last_update: Instant,
+    frames_to_skip: usize,
+    skipped_frames: usize,
+    frames_behind: f64,
+    // Runs at normal pace until the backend signals the event, then pauses
+    run_target: Option<ExecutionEvent>,
+    // Emulated time the backend should have reached according to the wall clock
+    target_time: std::time::Duration,
+    rewinding: bool,
+    // Taken by the app to open the diagnostic panel
+    failure: Option<FailureReport>,
 }
 
 impl EmulatorComponent {
-    pub fn from_selection(
-        backend_selection: AvailableBackends,
-        frontend: &mut impl Frontend,
+    pub fn from_descriptor(
+        descriptor: &BackendDescriptor,
+        frontend: &mut dyn Frontend<Error = Error>,
         rom_data: &[u8],
-    ) -> Self {
-        match backend_selection {
-            AvailableBackends::Chip8 => Self::new_chip8(frontend, rom_data, false),
-            AvailableBackends::SuperChip => Self::new_chip8(frontend, rom_data, true),
-        }
-    }
-
-    fn new_chip8(frontend: &mut impl Frontend, rom_data: &[u8], super8: bool) -> Self {
-        let platform = match super8 {
-            false => Platform::Chip8,
-            true => Platform::SuperChip,
-        };
-
-        let backend = create_chip8_backend(
+        options: OptionValues,
+        firmware: FirmwareFiles,
+        // Seeds from the current time when not given
+        rng_seed: Option<u64>,
+    ) -> Result<Self, Error> {
+        let mut backend = descriptor.create(
             frontend,
-            Chip8Options {
-                platform,
+            BackendOptions {
                 rom_data: rom_data.to_vec(),
+                options,
+                firmware,
+                rng_seed: Some(rng_seed.unwrap_or_else(|| {
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_nanos() as u64
+                })),
+                // Save states keep the time of day they were taken at
+                rtc_policy: Some(RtcPolicy::Anchored),
             },
-        )
-        .expect("could not create backend");
+        )?;
+        backend.set_rewind(Some(
+            RewindBuffer::new(
+                femtos::Duration::from_millis(REWIND_INTERVAL_MS),
+                REWIND_SNAPSHOTS,
+            )
+            .with_compression(),
+        ));
+        backend.set_max_catch_up(Some((
+            femtos::Duration::from_millis(MAX_CATCH_UP_MS),
+            CatchUpPolicy::Drop,
+        )));
 
-        Self {
+        Ok(Self {
+            name: descriptor.name.to_string(),
             backend,
             backend_last_update: Instant::now(),
-        }
+            frames_to_skip: 0,
+            skipped_frames: 0,
+            frames_behind: 0.0,
+            run_target: None,
+            target_time: std::time::Duration::ZERO,
+            rewinding: false,
+            failure: None,
+        })
     }
 
     pub fn update(&mut self) {
-        // TODO: speed boost
         let last_update_delta = self.backend_last_update.elapsed();
         self.backend_last_update = Instant::now();
+        if self.rewinding {
+            self.step_back();
+            return;
+        }
+        // Running a paused backend does nothing, so neither does catching up with the wall clock
+        if self.backend.is_paused() {
+            return;
+        }
+
+        self.backend.set_render_enabled(self.frames_to_skip == 0);
+        let run_start = Instant::now();
+        let run_duration = self.backend.catch_up_duration(last_update_delta.into());
+        self.target_time += std::time::Duration::from_nanos(run_duration.as_nanos());
+        let clock = self.backend.get_current_clock() + run_duration;
+        let result = match self.run_target {
+            Some(event) => self.backend.run_to(event, clock),
+            None => self.backend.run_until(clock).map(|_| false),
+        };
+        let reached_target = match result {
+            Ok(reached_target) => reached_target,
+            Err(error) => {
+                self.fail(error);
+                return;
+            }
+        };
+        // breakpoints pause until the user continues
+        let hit_breakpoint = self.backend.has_breakpoint_events();
+        if reached_target || hit_breakpoint {
+            self.run_target = None;
+            self.backend.pause();
+        }
+        if reached_target || hit_breakpoint || self.backend.has_trigger_events() {
+            // the run stopped early, the remaining time is dropped instead of caught up
+            self.target_time = std::time::Duration::from_nanos(
+                self.backend.get_current_clock().as_duration().as_nanos(),
+            );
+        }
+
+        // when emulating the last interval took longer than the interval itself we are falling
+        // behind real time, so skip rendering for a few frames while keeping the logic running
+        let frame_duration = 1.0 / self.backend.get_info().refresh_rate;
+        self.frames_behind = run_start
+            .elapsed()
+            .saturating_sub(last_update_delta)
+            .as_secs_f64()
+            / frame_duration;
+        if self.frames_to_skip > 0 {
+            self.frames_to_skip -= 1;
+            self.skipped_frames += 1;
+        } else if self.frames_behind > 0.0 {
+            self.frames_to_skip = (self.frames_behind.ceil() as usize).min(MAX_SKIPPED_FRAMES);
+        }
+    }
 
-        let result = self.backend.run_for(last_update_delta.into());
+    // Goes back one snapshot per update, which rewinds several times faster than real time. The
+    // backend then runs up to its next frame, so the screen shows the rewound state.
+    fn step_back(&mut self) {
+        let Some(interval) = self.backend.get_rewind().map(RewindBuffer::interval) else {
+            return;
+        };
+        self.backend.set_render_enabled(true);
+        let frame_duration = self.backend.get_info().frame_duration();
+        let result = self.backend.rewind(interval).and_then(|_| {
+            let clock = self.backend.get_current_clock() + frame_duration;
+            self.backend.run_to(ExecutionEvent::Frame, clock)
+        });
         if let Err(error) = result {
-            panic!("{}", error);
+            log::error!("unable to rewind: {}", error);
+            self.rewinding = false;
+        }
+        self.target_time = std::time::Duration::from_nanos(
+            self.backend.get_current_clock().as_duration().as_nanos(),
+        );
+    }
+
+    pub fn set_rewinding(&mut self, rewinding: bool) {
+        self.rewinding = rewinding;
+    }
+
+    pub fn is_rewinding(&self) -> bool {
+        self.rewinding
+    }
+
+    pub fn rewind_usage(&self) -> RewindUsage {
+        self.backend
+            .get_rewind()
+            .map(RewindBuffer::usage)
+            .unwrap_or_default()
+    }
+
+    // Emulated time which can be rewound at most
+    pub fn rewind_available(&self) -> std::time::Duration {
+        self.backend
+            .get_rewind()
+            .map_or(std::time::Duration::ZERO, |rewind| {
+                let available = rewind.available(self.backend.get_current_clock());
+                std::time::Duration::from_nanos(available.as_nanos())
+            })
+    }
+
+    // Wall time until the backend is due for its next frame, None while paused. Above normal speed
+    // several frames are emulated per repaint instead of repainting faster than the display.
+    pub fn next_frame_in(&self) -> Option<std::time::Duration> {
+        if self.backend.is_paused() && !self.rewinding {
+            return None;
         }
+        let frame_duration = std::time::Duration::from_secs_f64(
+            1.0 / self.backend.get_info().refresh_rate / self.backend.get_speed().min(1.0),
+        );
+        Some(frame_duration.saturating_sub(self.backend_last_update.elapsed()))
+    }
+
+    pub fn add_trigger(&mut self, condition: TriggerCondition) -> TriggerId {
+        self.backend.add_trigger(condition)
+    }
+
+    pub fn remove_trigger(&mut self, id: TriggerId) {
+        self.backend.remove_trigger(id);
+    }
+
+    pub fn freeze_memory(&mut self, address: MemoryAddress, value: u8) -> Result<(), Error> {
+        self.backend.freeze_memory(address, value)
+    }
+
+    pub fn unfreeze_memory(&mut self, address: MemoryAddress) {
+        self.backend.unfreeze_memory(address);
+    }
+
+    pub fn take_trigger_events(&mut self) -> Vec<TriggerEvent> {
+        self.backend.take_trigger_events()
+    }
+
+    pub fn add_breakpoint(&mut self, component: &str, address: MemoryAddress) -> Result<(), Error> {
+        self.backend.add_breakpoint(component, address)
+    }
+
+    pub fn remove_breakpoint(&mut self, component: &str, address: MemoryAddress) {
+        self.backend.remove_breakpoint(component, address);
+    }
+
+    pub fn take_breakpoint_events(&mut self) -> Vec<BreakpointEvent> {
+        self.backend.take_breakpoint_events()
+    }
+
+    pub fn set_component_paused(&mut self, component: &str, paused: bool) -> Result<(), Error> {
+        if paused {
+            self.backend.pause_component(component)
+        } else {
+            self.backend.resume_component(component)
+        }
+    }
+
+    pub fn step_instruction(&mut self, component: &str) -> Result<(), Error> {
+        self.backend.set_render_enabled(true);
+        self.backend.step_instruction(component)?;
+        self.target_time = std::time::Duration::from_nanos(
+            self.backend.get_current_clock().as_duration().as_nanos(),
+        );
+        Ok(())
+    }
+
+    // Rewinds to shortly before the clock and runs up to it again while tracing the component, or
+    // the first traceable one. Pauses at the clock afterwards and returns the traced component.
+    pub fn trace_before(
+        &mut self,
+        component: Option<&str>,
+        clock: femtos::Instant,
+    ) -> Result<(String, Vec<String>), Error> {
+        let component = match component {
+            Some(component) => component.to_string(),
+            None => self
+                .backend
+                .get_all_components()
+                .iter()
+                .filter(|(_, component)| component.borrow_mut().as_traceable().is_some())
+                .map(|(name, _)| name.clone())
+                .min()
+                .ok_or_else(|| Error::new("no component of the backend supports tracing"))?,
+        };
+        let duration = self.backend.get_info().frame_duration() * REPLAY_FRAMES;
+        let start = clock
+            .checked_sub(duration)
+            .unwrap_or(femtos::Instant::START);
+        let lines = replay_trace(&mut self.backend, &component, start, clock)?;
+        self.backend.pause();
+        self.run_target = None;
+        self.target_time = std::time::Duration::from_nanos(
+            self.backend.get_current_clock().as_duration().as_nanos(),
+        );
+        Ok((component, lines))
+    }
+
+    // Pauses at the failure, the backend is rewound to right before the failing step if possible
+    fn fail(&mut self, error: Error) {
+        log::error!("{} failed: {}", self.name, error);
+        let replay_duration = self.backend.get_info().frame_duration() * REPLAY_FRAMES;
+        match capture_failure(&mut self.backend, &error, replay_duration) {
+            Ok(report) => self.failure = Some(report),
+            Err(capture_error) => log::error!("unable to capture the failure: {}", capture_error),
+        }
+        self.backend.pause();
+        self.run_target = None;
+        self.target_time = std::time::Duration::from_nanos(
+            self.backend.get_current_clock().as_duration().as_nanos(),
+        );
+    }
+
+    pub fn take_failure(&mut self) -> Option<FailureReport> {
+        self.failure.take()
+    }
+
+    pub fn take_scheduler_warnings(&mut self) -> Vec<SchedulerWarning> {
+        self.backend.take_scheduler_warnings()
+    }
+
+    pub fn set_step_limit(&mut self, name: &str, limit: Option<usize>) {
+        self.backend.set_step_limit(name, limit);
+    }
+
+    // Keeps the pause state, so a paused machine can be stepped from power-on
+    pub fn reset(&mut self) -> Result<(), Error> {
+        self.backend.reset()?;
+        self.run_target = None;
+        self.rewinding = false;
+        self.target_time = std::time::Duration::ZERO;
+        Ok(())
+    }
+
+    // The wall clock picks up from the clock of the state
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.backend.load_state(data)?;
+        self.run_target = None;
+        self.rewinding = false;
+        self.target_time = std::time::Duration::from_nanos(
+            self.backend.get_current_clock().as_duration().as_nanos(),
+        );
+        Ok(())
+    }
+
+    pub fn toggle_pause(&mut self) {
+        if self.backend.is_paused() {
+            self.backend.resume();
+        } else {
+            self.backend.pause();
+        }
+        self.run_target = None;
+    }
+
+    pub fn run_to(&mut self, event: ExecutionEvent) {
+        self.run_target = Some(event);
+        self.backend.resume();
+    }
+
+    pub fn run_target(&self) -> Option<ExecutionEvent> {
+        self.run_target
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.backend.is_paused()
+    }
+
+    pub fn set_speed(&mut self, speed: f64) -> Result<(), Error> {
+        self.backend.set_speed(speed)
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.backend.get_speed()
+    }
+
+    pub fn skipped_frames(&self) -> usize {
+        self.skipped_frames
+    }
+
+    pub fn frames_behind(&self) -> f64 {
+        self.frames_behind
+    }
+
+    // Seconds the emulated clock is ahead of where real time says it should be
+    pub fn drift(&self) -> f64 {
+        let clock = self.backend.get_current_clock().as_duration();
+        clock.as_nanos() as f64 / 1e9 - self.target_time.as_secs_f64()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
     }
 
     pub fn get_backend(&self) -> &Backend {
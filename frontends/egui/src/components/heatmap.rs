@@ -0,0 +1,140 @@
+use std::{cell::RefCell, rc::Rc};
+
+use axwemulator_core::backend::{
+    component::{Addressable, MemoryAddress, MemorySize},
+    memory::{BusAccess, BusHook, BusHookHandle},
+};
+use egui::{Color32, ColorImage, Sense, TextureHandle, TextureOptions, Vec2};
+use web_time::Instant;
+
+use super::emulator::EmulatorComponent;
+
+const HEATMAP_COLUMNS: usize = 64;
+const HEATMAP_MAX_CELLS: usize = HEATMAP_COLUMNS * HEATMAP_COLUMNS;
+const HEATMAP_HALF_LIFE_SECS: f32 = 1.0;
+
+#[derive(Default)]
+struct AccessCounter {
+    bytes_per_cell: usize,
+    reads: Vec<u32>,
+    writes: Vec<u32>,
+}
+
+impl AccessCounter {
+    fn new(bus_size: MemorySize) -> Self {
+        let bytes_per_cell = bus_size.div_ceil(HEATMAP_MAX_CELLS).max(1);
+        let cells = bus_size.div_ceil(bytes_per_cell);
+        Self {
+            bytes_per_cell,
+            reads: vec![0; cells],
+            writes: vec![0; cells],
+        }
+    }
+}
+
+impl BusHook for AccessCounter {
+    fn on_access(&mut self, access: BusAccess, address: MemoryAddress, data: &[u8]) {
+        let counts = match access {
+            BusAccess::Read => &mut self.reads,
+            BusAccess::Write => &mut self.writes,
+        };
+        for cell in address / self.bytes_per_cell..=(address + data.len() - 1) / self.bytes_per_cell
+        {
+            if let Some(count) = counts.get_mut(cell) {
+                *count += 1;
+            }
+        }
+    }
+}
+
+pub struct MemoryHeatmap {
+    counter: Rc<RefCell<AccessCounter>>,
+    hook: Option<BusHookHandle>,
+    reads: Vec<f32>,
+    writes: Vec<f32>,
+    last_update: Instant,
+    texture: Option<TextureHandle>,
+}
+
+impl Default for MemoryHeatmap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryHeatmap {
+    pub fn new() -> Self {
+        Self {
+            counter: Rc::new(RefCell::new(AccessCounter::default())),
+            hook: None,
+            reads: Vec::new(),
+            writes: Vec::new(),
+            last_update: Instant::now(),
+            texture: None,
+        }
+    }
+
+    pub fn update(&mut self, emulator: &EmulatorComponent, ctx: &egui::Context) {
+        if self.hook.is_none() {
+            let bus_size = emulator.get_backend().get_bus().size();
+            self.counter = Rc::new(RefCell::new(AccessCounter::new(bus_size)));
+            let hook: BusHookHandle = self.counter.clone();
+            emulator.get_backend().get_bus().add_hook(hook.clone());
+            self.hook = Some(hook);
+        }
+
+        let decay = 0.5f32.powf(self.last_update.elapsed().as_secs_f32() / HEATMAP_HALF_LIFE_SECS);
+        self.last_update = Instant::now();
+
+        let mut counter = self.counter.borrow_mut();
+        self.reads.resize(counter.reads.len(), 0.0);
+        self.writes.resize(counter.writes.len(), 0.0);
+        for (heat, count) in self.reads.iter_mut().zip(counter.reads.iter_mut()) {
+            *heat = *heat * decay + *count as f32;
+            *count = 0;
+        }
+        for (heat, count) in self.writes.iter_mut().zip(counter.writes.iter_mut()) {
+            *heat = *heat * decay + *count as f32;
+            *count = 0;
+        }
+        drop(counter);
+
+        let rows = self.reads.len().div_ceil(HEATMAP_COLUMNS).max(1);
+        let max_heat = self
+            .reads
+            .iter()
+            .chain(self.writes.iter())
+            .fold(1.0f32, |max, heat| max.max(*heat));
+        let mut image = ColorImage::new([HEATMAP_COLUMNS, rows], Color32::BLACK);
+        for (cell, pixel) in image.pixels.iter_mut().enumerate().take(self.reads.len()) {
+            let read = (self.reads[cell] / max_heat).sqrt();
+            let write = (self.writes[cell] / max_heat).sqrt();
+            *pixel = Color32::from_rgb((write * 255.0) as u8, 0, (read * 255.0) as u8);
+        }
+        self.texture = Some(ctx.load_texture("memory_heatmap", image, TextureOptions::NEAREST));
+    }
+
+    // Returns the bus address of the clicked cell
+    pub fn draw(&self, ui: &mut egui::Ui) -> Option<MemoryAddress> {
+        let texture = self.texture.as_ref()?;
+        let width = ui.available_width();
+        let size = texture.size_vec2();
+        let response = ui.add(
+            egui::Image::new(texture)
+                .fit_to_exact_size(Vec2::new(width, width * size.y / size.x))
+                .sense(Sense::click()),
+        );
+        response.widget_info(|| {
+            egui::WidgetInfo::labeled(egui::WidgetType::Image, true, "Memory access heatmap")
+        });
+        ui.label("Blue: reads, red: writes");
+
+        let position = response
+            .interact_pointer_pos()
+            .filter(|_| response.clicked())?;
+        let relative = (position - response.rect.min) / response.rect.size();
+        let column = ((relative.x * size.x) as usize).min(HEATMAP_COLUMNS - 1);
+        let row = (relative.y * size.y) as usize;
+        Some((row * HEATMAP_COLUMNS + column) * self.counter.borrow().bytes_per_cell)
+    }
+}
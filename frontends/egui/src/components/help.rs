@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+
+use axwemulator_core::{backend::controls::ControlScheme, frontend::input::KeyboardEventKey};
+use egui::{Key, RichText};
+
+use crate::command::CommandSender;
+
+use super::{Component, input::InputComponent};
+
+// Quick help overlay toggled with F1, showing the controls of the backend with the host keys
+// they are currently bound to
+pub struct HelpComponent {
+    controls: ControlScheme,
+    host_keys: HashMap<KeyboardEventKey, Vec<Key>>,
+    open: bool,
+}
+
+impl HelpComponent {
+    pub fn new(controls: ControlScheme, input: Option<&InputComponent>) -> Self {
+        let host_keys = controls
+            .controls()
+            .map(|control| {
+                let keys = input.map_or(vec![], |input| input.host_keys(control.key));
+                (control.key, keys)
+            })
+            .collect();
+        Self {
+            controls,
+            host_keys,
+            open: false,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+    }
+
+    fn format_host_keys(&self, key: KeyboardEventKey) -> String {
+        match self.host_keys.get(&key) {
+            Some(keys) if !keys.is_empty() => keys
+                .iter()
+                .map(|key| key.symbol_or_name())
+                .collect::<Vec<_>>()
+                .join(" / "),
+            _ => "unbound".to_string(),
+        }
+    }
+}
+
+impl Component for HelpComponent {
+    fn update(
+        &mut self,
+        _emulator: &super::emulator::EmulatorComponent,
+        _command_sender: &CommandSender,
+        ctx: &egui::Context,
+    ) {
+        if ctx.input(|i| i.key_pressed(Key::F1)) {
+            self.toggle();
+        }
+    }
+
+    fn draw(
+        &mut self,
+        emulator: &super::emulator::EmulatorComponent,
+        _command_sender: &CommandSender,
+        ctx: &egui::Context,
+        _ui: &mut egui::Ui,
+    ) {
+        let mut open = self.open;
+        egui::Window::new(format!("{} controls", emulator.name()))
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                if self.controls.is_empty() {
+                    ui.label("This backend does not describe its controls");
+                    return;
+                }
+                egui::Grid::new("help_controls")
+                    .spacing([16.0, 8.0])
+                    .show(ui, |ui| {
+                        for row in self.controls.rows {
+                            for control in row.iter() {
+                                ui.vertical_centered(|ui| {
+                                    ui.label(RichText::new(control.label).heading());
+                                    ui.label(self.format_host_keys(control.key));
+                                });
+                            }
+                            ui.end_row();
+                        }
+                    });
+                if !self.controls.help.is_empty() {
+                    ui.separator();
+                    ui.label(self.controls.help);
+                }
+                ui.separator();
+                ui.label("Press F1 to close this help");
+            });
+        self.open = open;
+    }
+}
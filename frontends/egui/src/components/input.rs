@@ -1,40 +1,385 @@
-use std::sync::mpsc;
+use std::{
+    collections::{HashMap, VecDeque},
+    ops::RangeInclusive,
+};
 
-use axwemulator_core::frontend::input::{ButtonState, InputEvent, InputSender};
+use axwemulator_core::frontend::{
+    input::{ButtonState, InputEvent, InputSender, KeyboardEventKey, PLAYER_ONE, PlayerId},
+    movie::{InputMovie, MoviePlayer},
+    players::{InputDevice, KeyboardRegion, PlayerAssignment},
+};
 use egui::{Event, Key};
+use web_time::Instant;
 
-use crate::{app::AppCommand, utils};
+use crate::{app::AppCommand, command::CommandSender, utils};
 
 use super::Component;
 
+pub struct InputLatencyProbe {
+    pub sequence: u64,
+    pub captured: Instant,
+    pub enqueued: Instant,
+}
+
+pub const PASTE_RATE_RANGE: RangeInclusive<f64> = 1.0..=100.0;
+// Switches the keyboard between the emulator and the interface, it is never forwarded itself
+pub const CAPTURE_TOGGLE_KEY: Key = Key::F8;
+// Rewinds the emulation while held, it is never forwarded either
+pub const REWIND_KEY: Key = Key::Backspace;
+
+// Pasted text is typed one key at a time, each key is held for half of its slot. The events are
+// timestamped in emulated time, so the rate stays the same regardless of the emulation speed.
+struct PasteQueue {
+    keys: VecDeque<KeyboardEventKey>,
+    next_press: femtos::Instant,
+}
+
 pub struct InputComponent {
     input_sender: InputSender,
+    latency_probes: Vec<InputLatencyProbe>,
+    keybindings: HashMap<Key, Key>,
+    paste: Option<PasteQueue>,
+    // keys per second of emulated time
+    paste_rate: f64,
+    players: PlayerAssignment,
+    split_keyboard: bool,
+    // All input of the running session, so it can be kept as demo of the rom
+    session: InputMovie,
+    demo: Option<MoviePlayer>,
+    // Turned off by the user to type into the interface without a widget holding the focus
+    capture_enabled: bool,
+    // Keys pressed in the backend, released again when the keyboard leaves the emulator
+    held_keys: Vec<(PlayerId, KeyboardEventKey)>,
 }
 
 impl InputComponent {
     pub fn new(input_sender: InputSender) -> Self {
-        Self { input_sender }
+        Self {
+            input_sender,
+            latency_probes: Vec::new(),
+            keybindings: HashMap::new(),
+            paste: None,
+            paste_rate: 20.0,
+            players: Self::keyboard_players(1, false),
+            split_keyboard: false,
+            session: InputMovie::new(0),
+            demo: None,
+            capture_enabled: true,
+            held_keys: Vec::new(),
+        }
+    }
+
+    // Live input is ignored while a demo plays, the session then records the demo itself
+    pub fn start_session(&mut self, rng_seed: u64, demo: Option<InputMovie>) {
+        self.session = demo.clone().unwrap_or_else(|| InputMovie::new(rng_seed));
+        self.demo = demo.map(MoviePlayer::new);
+    }
+
+    pub fn session_movie(&self) -> &InputMovie {
+        &self.session
+    }
+
+    fn send(&mut self, player: PlayerId, clock: femtos::Instant, event: InputEvent) -> u64 {
+        if self.demo.is_none() {
+            self.session.record(clock, player, event);
+        }
+        self.input_sender.add_for_player(player, clock, event)
+    }
+
+    // Returns true when the session should be saved as demo
+    pub fn draw_demo_controls(&mut self, ui: &mut egui::Ui) -> bool {
+        if let Some(demo) = self.demo.as_ref() {
+            if demo.is_finished() {
+                ui.label("Demo finished");
+            } else {
+                ui.label(format!("Playing demo ({} events left)", demo.remaining()));
+            }
+            if ui.button("Stop demo").clicked() {
+                self.demo = None;
+                self.session = InputMovie::new(self.session.rng_seed);
+            }
+            return false;
+        }
+        ui.button("Save as demo")
+            .on_hover_text("Keeps the input since launch to replay it from the rom selection")
+            .clicked()
+    }
+
+    fn keyboard_players(players: u8, split_keyboard: bool) -> PlayerAssignment {
+        let mut assignment = PlayerAssignment::new(players);
+        if split_keyboard {
+            assignment.connect(InputDevice::Keyboard(KeyboardRegion::Left));
+            assignment.connect(InputDevice::Keyboard(KeyboardRegion::Right));
+        } else {
+            assignment.connect(InputDevice::Keyboard(KeyboardRegion::Whole));
+        }
+        assignment
+    }
+
+    pub fn set_players(&mut self, players: u8) {
+        self.players = Self::keyboard_players(players, self.split_keyboard);
+    }
+
+    fn set_split_keyboard(&mut self, split_keyboard: bool) {
+        self.split_keyboard = split_keyboard;
+        let regions = [
+            KeyboardRegion::Whole,
+            KeyboardRegion::Left,
+            KeyboardRegion::Right,
+        ];
+        for region in regions {
+            let device = InputDevice::Keyboard(region);
+            let enabled = (region == KeyboardRegion::Whole) != split_keyboard;
+            if enabled {
+                self.players.connect(device);
+            } else {
+                self.players.disconnect(device);
+            }
+        }
+    }
+
+    fn device_for_key(&self, key: Key) -> InputDevice {
+        if !self.split_keyboard {
+            return InputDevice::Keyboard(KeyboardRegion::Whole);
+        }
+        let region = utils::translate_egui_key_to_frontend_key(key)
+            .map_or(KeyboardRegion::Right, KeyboardRegion::of);
+        InputDevice::Keyboard(region)
+    }
+
+    pub fn draw_player_settings(&mut self, ui: &mut egui::Ui) {
+        let mut split_keyboard = self.split_keyboard;
+        if ui
+            .checkbox(
+                &mut split_keyboard,
+                "Split keyboard into left and right half",
+            )
+            .changed()
+        {
+            self.set_split_keyboard(split_keyboard);
+        }
+        ui.separator();
+
+        let players = self.players.players();
+        let devices: Vec<(InputDevice, Option<u8>)> = self
+            .players
+            .devices()
+            .map(|(device, player)| (*device, *player))
+            .collect();
+        egui::Grid::new("player_assignment")
+            .num_columns(2)
+            .show(ui, |ui| {
+                for (device, player) in devices {
+                    ui.label(match device {
+                        InputDevice::Keyboard(KeyboardRegion::Whole) => "Keyboard".to_string(),
+                        InputDevice::Keyboard(region) => format!("Keyboard ({:?})", region),
+                        InputDevice::Gamepad(id) => format!("Gamepad {}", id),
+                    });
+                    let mut selected = player;
+                    let label = |player: Option<u8>| {
+                        player.map_or("Unassigned".to_string(), |p| format!("Player {}", p + 1))
+                    };
+                    egui::ComboBox::from_id_salt(("player", device))
+                        .selected_text(label(selected))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut selected, None, label(None));
+                            for p in 0..players {
+                                ui.selectable_value(&mut selected, Some(p), label(Some(p)));
+                            }
+                        });
+                    if selected != player {
+                        self.players.assign(device, selected);
+                    }
+                    ui.end_row();
+                }
+            });
+    }
+
+    pub fn paste_text(&mut self, text: &str, clock: femtos::Instant) {
+        let keys = text.chars().filter_map(KeyboardEventKey::from_char);
+        match self.paste.as_mut() {
+            Some(paste) => paste.keys.extend(keys),
+            None => {
+                self.paste = Some(PasteQueue {
+                    keys: keys.collect(),
+                    next_press: clock,
+                })
+            }
+        }
+    }
+
+    fn feed_paste(&mut self, clock: femtos::Instant) {
+        let Some(paste) = self.paste.as_mut() else {
+            return;
+        };
+        let interval = femtos::Duration::from_nanos((1e9 / self.paste_rate) as u64);
+        let mut events = vec![];
+        let mut capacity = self.input_sender.free_capacity();
+        // Never run ahead of the backend far enough to overflow the input queue
+        while capacity >= 2 {
+            let Some(key) = paste.keys.pop_front() else {
+                break;
+            };
+            let press = paste.next_press.max(clock);
+            events.push((press, InputEvent::Keyboard(key, ButtonState::Pressed)));
+            events.push((
+                press + interval / 2u32,
+                InputEvent::Keyboard(key, ButtonState::Released),
+            ));
+            paste.next_press = press + interval;
+            capacity -= 2;
+        }
+        if paste.keys.is_empty() {
+            self.paste = None;
+        }
+        for (at, event) in events {
+            self.send(PLAYER_ONE, at, event);
+        }
+    }
+
+    pub fn draw_paste_controls(&mut self, ui: &mut egui::Ui) {
+        ui.label("Paste rate");
+        ui.add(
+            egui::DragValue::new(&mut self.paste_rate)
+                .range(PASTE_RATE_RANGE)
+                .suffix(" keys/s"),
+        );
+        if let Some(paste) = self.paste.as_ref() {
+            ui.label(format!("{} keys left", paste.keys.len()));
+            if ui.button("Cancel paste").clicked() {
+                self.paste = None;
+            }
+        }
+    }
+
+    // A focused widget, e.g. a text field of a debugger panel, always keeps its keys
+    pub fn is_captured(&self, ctx: &egui::Context) -> bool {
+        self.capture_enabled && ctx.memory(|m| m.focused().is_none())
+    }
+
+    pub fn draw_capture_indicator(&mut self, ui: &mut egui::Ui) {
+        let (text, color) = if self.is_captured(ui.ctx()) {
+            ("Input captured by emulator", ui.visuals().warn_fg_color)
+        } else {
+            (
+                "Input goes to the interface",
+                ui.visuals().weak_text_color(),
+            )
+        };
+        ui.colored_label(color, text);
+        let label = if self.capture_enabled {
+            "Release"
+        } else {
+            "Capture"
+        };
+        if ui
+            .button(label)
+            .on_hover_text(format!("Toggle with {}", CAPTURE_TOGGLE_KEY.name()))
+            .clicked()
+        {
+            self.capture_enabled = !self.capture_enabled;
+        }
+    }
+
+    fn release_held_keys(&mut self, clock: femtos::Instant) {
+        for (player, key) in std::mem::take(&mut self.held_keys) {
+            self.send(
+                player,
+                clock,
+                InputEvent::Keyboard(key, ButtonState::Released),
+            );
+        }
+    }
+
+    pub fn set_keybindings(&mut self, keybindings: HashMap<Key, Key>) {
+        self.keybindings = keybindings;
+    }
+
+    // All host keys which currently produce the given emulated key
+    pub fn host_keys(&self, key: KeyboardEventKey) -> Vec<Key> {
+        Key::ALL
+            .iter()
+            .filter(|host_key| {
+                let emulated_key = self.keybindings.get(host_key).unwrap_or(host_key);
+                utils::translate_egui_key_to_frontend_key(*emulated_key) == Some(key)
+            })
+            .copied()
+            .collect()
+    }
+
+    pub fn consumed(&self) -> u64 {
+        self.input_sender.consumed()
+    }
+
+    pub fn take_latency_probes(&mut self) -> Vec<InputLatencyProbe> {
+        std::mem::take(&mut self.latency_probes)
     }
 }
 
 impl Component for InputComponent {
     fn update(
         &mut self,
-        _emulator: &super::emulator::EmulatorComponent,
-        command_sender: &mpsc::Sender<AppCommand>,
+        emulator: &super::emulator::EmulatorComponent,
+        command_sender: &CommandSender,
         ctx: &egui::Context,
     ) {
+        let clock = emulator.get_backend().get_current_clock();
+        if let Some(demo) = self.demo.as_mut() {
+            demo.feed(&self.input_sender);
+        }
+        let live = self.demo.is_none();
+        let captured = self.is_captured(ctx);
         ctx.input(|i| {
             for event in i.raw.events.iter() {
+                let Event::Key {
+                    key,
+                    pressed,
+                    repeat: false,
+                    ..
+                } = event
+                else {
+                    continue;
+                };
+                match *key {
+                    CAPTURE_TOGGLE_KEY if *pressed => self.capture_enabled = !self.capture_enabled,
+                    // Releasing always stops, even when the keyboard went to the interface meanwhile
+                    REWIND_KEY if captured || !*pressed => {
+                        command_sender.send(AppCommand::SetRewinding(*pressed));
+                    }
+                    _ => {}
+                }
+            }
+        });
+        if !self.is_captured(ctx) {
+            self.release_held_keys(clock);
+            self.feed_paste(clock);
+            return;
+        }
+        ctx.input(|i| {
+            for event in i.raw.events.iter() {
+                if let (Event::Paste(text), true) = (event, live) {
+                    self.paste_text(text, clock);
+                }
                 if let Event::Key {
                     key,
                     physical_key: _,
                     pressed,
                     repeat,
-                    modifiers: _,
+                    modifiers,
                 } = event
                 {
-                    if *repeat {
+                    // Shortcuts like the paste shortcut are not forwarded to the backend
+                    if *repeat
+                        || modifiers.command
+                        || *key == CAPTURE_TOGGLE_KEY
+                        || *key == REWIND_KEY
+                    {
+                        continue;
+                    }
+                    if *key == Key::Escape {
+                        command_sender.send(AppCommand::QuitBackend);
+                    }
+                    if !live {
                         continue;
                     }
                     let state = if *pressed {
@@ -42,20 +387,40 @@ impl Component for InputComponent {
                     } else {
                         ButtonState::Released
                     };
-                    if let Some(key) = utils::translate_egui_key_to_frontend_key(*key) {
-                        self.input_sender.add(InputEvent::Keyboard(key, state));
-                    }
-                    if *key == Key::Escape {
-                        command_sender.send(AppCommand::QuitBackend).unwrap();
+                    let emulated_key = self.keybindings.get(key).unwrap_or(key);
+                    let player = self.players.player_for(&self.device_for_key(*key));
+                    if let (Some(emulated_key), Some(player)) = (
+                        utils::translate_egui_key_to_frontend_key(*emulated_key),
+                        player,
+                    ) {
+                        let held = (player, emulated_key);
+                        if *pressed {
+                            self.held_keys.push(held);
+                        } else if self.held_keys.contains(&held) {
+                            self.held_keys.retain(|k| *k != held);
+                        } else {
+                            // Pressed while the interface had the keyboard, already released
+                            continue;
+                        }
+                        let captured = Instant::now();
+                        let sequence =
+                            self.send(player, clock, InputEvent::Keyboard(emulated_key, state));
+                        self.latency_probes.push(InputLatencyProbe {
+                            sequence,
+                            captured,
+                            enqueued: Instant::now(),
+                        });
                     }
                 }
             }
         });
+        self.feed_paste(clock);
     }
 
     fn draw(
         &mut self,
         _emulator: &super::emulator::EmulatorComponent,
+        _command_sender: &CommandSender,
         _ctx: &egui::Context,
         _ui: &mut egui::Ui,
     ) {
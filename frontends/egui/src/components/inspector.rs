@@ -1,20 +1,226 @@
-use std::sync::mpsc;
+use axwemulator_core::backend::breakpoint::BreakpointEvent;
+use egui::{RichText, ScrollArea, TextStyle};
+use femtos::Instant;
 
-use egui::RichText;
+use crate::{app::AppCommand, command::CommandSender};
 
-use crate::app::AppCommand;
+use super::{Component, emulator::REPLAY_FRAMES};
 
-use super::Component;
+// Memory disassembled from the program counter on
+const DISASSEMBLY_BYTES: usize = 16;
+
+struct ReplayedTrace {
+    component: String,
+    end: Instant,
+    lines: Vec<String>,
+}
 
 #[derive(Default)]
 pub struct InspectorComponent {
     selected_component: String,
+    breakpoint_address: String,
+    last_breakpoint: Option<BreakpointEvent>,
+    trace: Option<ReplayedTrace>,
 }
 
 impl InspectorComponent {
     pub fn new() -> Self {
         Self {
             selected_component: "".to_string(),
+            breakpoint_address: "".to_string(),
+            last_breakpoint: None,
+            trace: None,
+        }
+    }
+
+    pub fn selected_component(&self) -> &str {
+        &self.selected_component
+    }
+
+    pub fn select(&mut self, component_name: String) {
+        self.selected_component = component_name;
+    }
+
+    // The inspector switches to the component which hit the breakpoint
+    pub fn record_breakpoint(&mut self, event: BreakpointEvent) {
+        self.selected_component = event.component.clone();
+        self.last_breakpoint = Some(event);
+    }
+
+    pub fn set_trace(&mut self, component: String, end: Instant, lines: Vec<String>) {
+        self.selected_component = component.clone();
+        self.trace = Some(ReplayedTrace {
+            component,
+            end,
+            lines,
+        });
+    }
+
+    fn draw_trace(&mut self, ui: &mut egui::Ui) {
+        let Some(trace) = self
+            .trace
+            .as_ref()
+            .filter(|trace| trace.component == self.selected_component)
+        else {
+            return;
+        };
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label(format!(
+                "{} steps up to {:.6}s",
+                trace.lines.len(),
+                trace.end.as_duration().as_nanos() as f64 / 1e9
+            ));
+            if ui.small_button("Copy").clicked() {
+                ui.ctx().copy_text(trace.lines.join("\n"));
+            }
+        });
+        let row_height = ui.text_style_height(&TextStyle::Monospace);
+        ScrollArea::both()
+            .id_salt("replayed_trace")
+            .max_height(300.0)
+            .stick_to_bottom(true)
+            .show_rows(ui, row_height, trace.lines.len(), |ui, rows| {
+                for line in &trace.lines[rows] {
+                    ui.label(RichText::new(line).monospace());
+                }
+            });
+    }
+
+    fn draw_controls(
+        &mut self,
+        emulator: &super::emulator::EmulatorComponent,
+        command_sender: &CommandSender,
+        ui: &mut egui::Ui,
+    ) {
+        let backend = emulator.get_backend();
+        let (Ok(pc), Ok(paused)) = (
+            backend.get_pc(&self.selected_component),
+            backend.is_component_paused(&self.selected_component),
+        ) else {
+            return;
+        };
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label(RichText::new(format!("PC {:#06X}", pc)).monospace());
+            if ui
+                .button(if paused { "Resume" } else { "Pause" })
+                .on_hover_text("Only this component stops, the rest of the machine keeps running")
+                .clicked()
+            {
+                command_sender.send(AppCommand::SetComponentPaused(
+                    self.selected_component.clone(),
+                    !paused,
+                ));
+            }
+            if ui.button("Step instruction").clicked() {
+                command_sender.send(AppCommand::StepInstruction(self.selected_component.clone()));
+            }
+        });
+    }
+
+    fn draw_disassembly(
+        &mut self,
+        emulator: &super::emulator::EmulatorComponent,
+        command_sender: &CommandSender,
+        ui: &mut egui::Ui,
+    ) {
+        let backend = emulator.get_backend();
+        let Ok(pc) = backend.get_pc(&self.selected_component) else {
+            return;
+        };
+        let Ok(instructions) =
+            backend.disassemble(&self.selected_component, pc, pc + DISASSEMBLY_BYTES)
+        else {
+            return;
+        };
+        let breakpoints = backend.get_breakpoints(&self.selected_component);
+        ui.separator();
+        for instruction in instructions {
+            let marker = if breakpoints.contains(&instruction.address) {
+                "●"
+            } else {
+                " "
+            };
+            let line = format!(
+                "{}{:#06X}  {} {}",
+                marker,
+                instruction.address,
+                instruction.mnemonic,
+                instruction.operands.join(", ")
+            );
+            if ui
+                .link(RichText::new(line).monospace())
+                .on_hover_text("Add a breakpoint here")
+                .clicked()
+            {
+                command_sender.send(AppCommand::AddBreakpoint(
+                    self.selected_component.clone(),
+                    instruction.address,
+                ));
+            }
+        }
+    }
+
+    fn draw_breakpoints(
+        &mut self,
+        emulator: &super::emulator::EmulatorComponent,
+        command_sender: &CommandSender,
+        ui: &mut egui::Ui,
+    ) {
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label("Breakpoint");
+            ui.add(egui::TextEdit::singleline(&mut self.breakpoint_address).desired_width(60.0));
+            let address =
+                usize::from_str_radix(self.breakpoint_address.trim().trim_start_matches("0x"), 16);
+            if ui
+                .add_enabled(address.is_ok(), egui::Button::new("Add"))
+                .clicked()
+            {
+                command_sender.send(AppCommand::AddBreakpoint(
+                    self.selected_component.clone(),
+                    address.unwrap(),
+                ));
+            }
+        });
+        for address in emulator
+            .get_backend()
+            .get_breakpoints(&self.selected_component)
+        {
+            ui.horizontal(|ui| {
+                ui.label(RichText::new(format!("{:#06X}", address)).monospace());
+                if ui.small_button("Remove").clicked() {
+                    command_sender.send(AppCommand::RemoveBreakpoint(
+                        self.selected_component.clone(),
+                        address,
+                    ));
+                }
+            });
+        }
+        if let Some(event) = self
+            .last_breakpoint
+            .as_ref()
+            .filter(|event| event.component == self.selected_component && emulator.is_paused())
+        {
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                format!(
+                    "Stopped at {:#06X} after {:.6}s",
+                    event.address,
+                    event.clock.as_duration().as_nanos() as f64 / 1e9
+                ),
+            );
+            if ui
+                .button(format!("Rewind {} frames and trace", REPLAY_FRAMES))
+                .on_hover_text("Runs up to the breakpoint again, recording every step")
+                .clicked()
+            {
+                command_sender.send(AppCommand::TraceBefore(
+                    Some(event.component.clone()),
+                    event.clock,
+                ));
+            }
         }
     }
 }
@@ -23,7 +229,7 @@ impl Component for InspectorComponent {
     fn update(
         &mut self,
         _emulator: &super::emulator::EmulatorComponent,
-        _command_sender: &mpsc::Sender<AppCommand>,
+        _command_sender: &CommandSender,
         _ctx: &egui::Context,
     ) {
     }
@@ -31,6 +237,7 @@ impl Component for InspectorComponent {
     fn draw(
         &mut self,
         emulator: &super::emulator::EmulatorComponent,
+        command_sender: &CommandSender,
         _ctx: &egui::Context,
         ui: &mut egui::Ui,
     ) {
@@ -44,16 +251,24 @@ impl Component for InspectorComponent {
                 }
             });
 
-        if let Ok(component) = emulator
+        let Ok(component) = emulator
             .get_backend()
             .get_component(&self.selected_component)
-        {
-            if let Some(inspectable) = component.borrow_mut().as_inspectable() {
-                let lines = inspectable.inspect();
-                for line in lines {
-                    ui.label(RichText::new(line).monospace());
-                }
+        else {
+            return;
+        };
+        if let Some(inspectable) = component.borrow_mut().as_inspectable() {
+            let lines = inspectable.inspect();
+            for line in lines {
+                ui.label(RichText::new(line).monospace());
             }
         }
+        // Only components running code have a program counter to break on
+        if component.borrow_mut().as_steppable().is_some() {
+            self.draw_controls(emulator, command_sender, ui);
+            self.draw_disassembly(emulator, command_sender, ui);
+            self.draw_breakpoints(emulator, command_sender, ui);
+        }
+        self.draw_trace(ui);
     }
 }
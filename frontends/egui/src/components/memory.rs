@@ -1,69 +1,190 @@
-use std::{ops::Deref, sync::mpsc};
+use std::ops::Deref;
 
-use axwemulator_core::backend::component::Addressable;
+use axwemulator_core::backend::component::{Addressable, MemoryAddress};
 use egui::{RichText, ScrollArea, TextStyle};
 
-use crate::app::AppCommand;
+use crate::{app::AppCommand, command::CommandSender};
 
-use super::Component;
+use super::{Component, heatmap::MemoryHeatmap};
 
 const BYTES_PER_ROW: usize = 8;
 
 #[derive(Default)]
 pub struct MemoryComponent {
     selected_component: Option<String>,
+    heatmap: MemoryHeatmap,
+    scroll_to_address: Option<MemoryAddress>,
+    scroll_offset: f32,
+    restore_scroll_offset: Option<f32>,
 }
 
 impl MemoryComponent {
     pub fn new() -> Self {
         Self {
             selected_component: None,
+            heatmap: MemoryHeatmap::new(),
+            scroll_to_address: None,
+            scroll_offset: 0.0,
+            restore_scroll_offset: None,
         }
     }
 
-    pub fn draw_for_component<T>(&self, ui: &mut egui::Ui, addressable: &T)
-    where
+    pub fn selected_component(&self) -> Option<&String> {
+        self.selected_component.as_ref()
+    }
+
+    pub fn scroll_offset(&self) -> f32 {
+        self.scroll_offset
+    }
+
+    pub fn restore(&mut self, selected_component: Option<String>, scroll_offset: f32) {
+        self.selected_component = selected_component;
+        self.scroll_offset = scroll_offset;
+        self.restore_scroll_offset = Some(scroll_offset);
+    }
+
+    // Scrolls the bus view to the address
+    pub fn show_address(&mut self, address: MemoryAddress) {
+        self.selected_component = None;
+        self.scroll_to_address = Some(address);
+    }
+
+    fn draw_frozen(
+        &mut self,
+        command_sender: &CommandSender,
+        ui: &mut egui::Ui,
+        frozen: &[(MemoryAddress, u8)],
+    ) {
+        if frozen.is_empty() {
+            ui.label("Click a byte of the bus to freeze it");
+            return;
+        }
+        egui::Grid::new("frozen_memory")
+            .num_columns(3)
+            .show(ui, |ui| {
+                for (address, value) in frozen {
+                    ui.label(RichText::new(format!("{:#06X}", address)).monospace());
+                    let mut edited = *value;
+                    if ui
+                        .add(egui::DragValue::new(&mut edited).hexadecimal(2, false, true))
+                        .changed()
+                    {
+                        command_sender.send(AppCommand::FreezeMemory(*address, edited));
+                    }
+                    if ui.small_button("Unfreeze").clicked() {
+                        command_sender.send(AppCommand::UnfreezeMemory(*address));
+                    }
+                    ui.end_row();
+                }
+            });
+    }
+
+    // With frozen values given the bytes can be clicked to toggle freezing them
+    pub fn draw_for_component<T>(
+        &mut self,
+        command_sender: &CommandSender,
+        ui: &mut egui::Ui,
+        addressable: &T,
+        frozen: Option<&[(MemoryAddress, u8)]>,
+    ) where
         T: Addressable + ?Sized,
     {
         let text_style = TextStyle::Body;
         let row_height = ui.text_style_height(&text_style);
         let row_amount = addressable.size() / BYTES_PER_ROW;
 
-        ScrollArea::vertical().show_rows(ui, row_height, row_amount, |ui, row_range| {
+        let mut scroll_area = ScrollArea::vertical();
+        if let Some(address) = self.scroll_to_address.take() {
+            let row_height_with_spacing = row_height + ui.spacing().item_spacing.y;
+            scroll_area = scroll_area
+                .vertical_scroll_offset((address / BYTES_PER_ROW) as f32 * row_height_with_spacing);
+        } else if let Some(offset) = self.restore_scroll_offset.take() {
+            scroll_area = scroll_area.vertical_scroll_offset(offset);
+        }
+
+        let output = scroll_area.show_rows(ui, row_height, row_amount, |ui, row_range| {
             let mut data = [0u8; BYTES_PER_ROW];
 
             for row in row_range {
                 let address = row * BYTES_PER_ROW;
 
-                addressable.read(address, &mut data).unwrap();
+                addressable.peek(address, &mut data).unwrap();
 
-                let mut line = format!("{:#010X} | ", address);
+                let Some(frozen) = frozen else {
+                    let mut line = format!("{:#010X} | ", address);
 
-                for b in data {
-                    line = format!("{}{:02X} ", line, b);
-                }
+                    for b in data {
+                        line = format!("{}{:02X} ", line, b);
+                    }
 
-                ui.label(RichText::new(line).monospace());
+                    ui.label(RichText::new(line).monospace());
+                    continue;
+                };
+
+                ui.horizontal(|ui| {
+                    ui.spacing_mut().item_spacing.x = 4.0;
+                    ui.label(RichText::new(format!("{:#010X} |", address)).monospace());
+                    for (offset, b) in data.iter().enumerate() {
+                        let byte_address = address + offset;
+                        let is_frozen = frozen.iter().any(|(a, _)| *a == byte_address);
+                        let mut text = RichText::new(format!("{:02X}", b)).monospace();
+                        if is_frozen {
+                            text = text.color(ui.visuals().warn_fg_color).underline();
+                        }
+                        let response = ui
+                            .add(egui::Label::new(text).sense(egui::Sense::click()))
+                            .on_hover_text(format!("{:#06X}", byte_address));
+                        // Bytes are focusable with the keyboard, screen readers announce them
+                        // as the toggle they are
+                        response.widget_info(|| {
+                            egui::WidgetInfo::selected(
+                                egui::WidgetType::Checkbox,
+                                true,
+                                is_frozen,
+                                format!("Freeze {:#06X}, value {:02X}", byte_address, b),
+                            )
+                        });
+                        if response.clicked() {
+                            command_sender.send(if is_frozen {
+                                AppCommand::UnfreezeMemory(byte_address)
+                            } else {
+                                AppCommand::FreezeMemory(byte_address, *b)
+                            });
+                        }
+                    }
+                });
             }
         });
+        self.scroll_offset = output.state.offset.y;
     }
 }
 
 impl Component for MemoryComponent {
     fn update(
         &mut self,
-        _emulator: &super::emulator::EmulatorComponent,
-        _command_sender: &mpsc::Sender<AppCommand>,
-        _ctx: &egui::Context,
+        emulator: &super::emulator::EmulatorComponent,
+        _command_sender: &CommandSender,
+        ctx: &egui::Context,
     ) {
+        self.heatmap.update(emulator, ctx);
     }
 
     fn draw(
         &mut self,
         emulator: &super::emulator::EmulatorComponent,
+        command_sender: &CommandSender,
         _ctx: &egui::Context,
         ui: &mut egui::Ui,
     ) {
+        egui::CollapsingHeader::new("Heatmap")
+            .default_open(true)
+            .show(ui, |ui| {
+                if let Some(address) = self.heatmap.draw(ui) {
+                    self.selected_component = None;
+                    self.scroll_to_address = Some(address);
+                }
+            });
+
         egui::ComboBox::from_label("Memory")
             .selected_text(
                 self.selected_component
@@ -83,11 +204,20 @@ impl Component for MemoryComponent {
         if let Some(component_name) = &self.selected_component {
             if let Ok(component) = emulator.get_backend().get_component(component_name) {
                 if let Some(addressable) = component.borrow_mut().as_addressable() {
-                    self.draw_for_component(ui, addressable);
+                    self.draw_for_component(command_sender, ui, addressable, None);
                 }
             }
         } else if self.selected_component.is_none() {
-            self.draw_for_component(ui, emulator.get_backend().get_bus().deref());
+            let frozen = emulator.get_backend().get_frozen_memory();
+            egui::CollapsingHeader::new(format!("Frozen ({})", frozen.len()))
+                .default_open(false)
+                .show(ui, |ui| self.draw_frozen(command_sender, ui, &frozen));
+            self.draw_for_component(
+                command_sender,
+                ui,
+                emulator.get_backend().get_bus().deref(),
+                Some(&frozen),
+            );
         }
     }
 }
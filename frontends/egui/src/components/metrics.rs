@@ -1,12 +1,18 @@
-use std::{collections::BTreeMap, fmt::Display, sync::mpsc};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt::Display,
+};
 
 use axwemulator_core::utils::Ringbuffer;
 use egui::RichText;
 use web_time::{Duration, Instant};
 
-use crate::app::AppCommand;
+use crate::command::CommandSender;
 
-use super::Component;
+use super::{Component, input::InputLatencyProbe};
+
+const MAX_PENDING_INPUT_PROBES: usize = 64;
+const HOST_HISTORY: usize = 200;
 
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub enum MeasurementType {
@@ -25,6 +31,64 @@ impl Display for MeasurementType {
     }
 }
 
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum InputLatencyStage {
+    Enqueue,
+    Consume,
+    Frame,
+    Total,
+}
+
+impl Display for InputLatencyStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InputLatencyStage::Enqueue => write!(f, "{:>13}", "Enqueue"),
+            InputLatencyStage::Consume => write!(f, "{:>13}", "Consume"),
+            InputLatencyStage::Frame => write!(f, "{:>13}", "Frame"),
+            InputLatencyStage::Total => write!(f, "{:>13}", "Total"),
+        }
+    }
+}
+
+// The cpu time the process used so far, None where the platform does not tell
+fn process_cpu_time() -> Option<Duration> {
+    #[cfg(not(target_arch = "wasm32"))]
+    return cpu_time::ProcessTime::try_now()
+        .ok()
+        .map(|time| time.as_duration());
+    #[cfg(target_arch = "wasm32")]
+    None
+}
+
+// Allocations and allocated bytes so far, only counted with the alloc-metrics feature
+fn allocation_totals() -> Option<(u64, u64)> {
+    #[cfg(feature = "alloc-metrics")]
+    return Some(crate::allocations::totals());
+    #[cfg(not(feature = "alloc-metrics"))]
+    None
+}
+
+struct HostSample {
+    at: Instant,
+    cpu_time: Option<Duration>,
+    allocations: Option<(u64, u64)>,
+}
+
+impl HostSample {
+    fn now() -> Self {
+        Self {
+            at: Instant::now(),
+            cpu_time: process_cpu_time(),
+            allocations: allocation_totals(),
+        }
+    }
+}
+
+struct PendingInput {
+    probe: InputLatencyProbe,
+    consumed: Option<(Instant, u64)>,
+}
+
 pub struct Measurement {
     current_start: Instant,
     history: Ringbuffer<Duration>,
@@ -46,6 +110,10 @@ impl Measurement {
         self.history.push_back(self.current_start.elapsed());
     }
 
+    pub fn record(&mut self, duration: Duration) {
+        self.history.push_back(duration);
+    }
+
     pub fn average(&self) -> Duration {
         self.history.peek_range(..).iter().sum::<Duration>() / self.history.len() as u32
     }
@@ -65,6 +133,16 @@ impl Measurement {
             .max()
             .unwrap_or_default()
     }
+
+    pub fn percentile(&self, percentile: f32) -> Duration {
+        let mut history = self.history.peek_range(..);
+        if history.is_empty() {
+            return Duration::default();
+        }
+        history.sort();
+        let index = ((history.len() - 1) as f32 * percentile / 100.0).round() as usize;
+        history[index]
+    }
 }
 
 impl Default for Measurement {
@@ -76,13 +154,57 @@ impl Default for Measurement {
 #[derive(Default)]
 pub struct MetricsComponent {
     measurements: BTreeMap<MeasurementType, Measurement>,
+    input_latency: BTreeMap<InputLatencyStage, Measurement>,
+    pending_inputs: VecDeque<PendingInput>,
+    last_frames_emitted: u64,
+    frames_received: u64,
+    frames_unshown: u64,
+    last_host_sample: Option<HostSample>,
+    // Percent of a core the whole process used, per UI frame
+    cpu_usage: Ringbuffer<f32>,
+    // Allocations and allocated bytes per UI frame
+    allocations: Ringbuffer<(u64, u64)>,
 }
 
 impl MetricsComponent {
     pub fn new() -> Self {
         Self {
             measurements: BTreeMap::new(),
+            input_latency: BTreeMap::new(),
+            pending_inputs: VecDeque::new(),
+            last_frames_emitted: 0,
+            frames_received: 0,
+            frames_unshown: 0,
+            last_host_sample: None,
+            cpu_usage: Ringbuffer::new(HOST_HISTORY),
+            allocations: Ringbuffer::new(HOST_HISTORY),
+        }
+    }
+
+    // Called once per UI frame, the usage is taken over the time since the last call
+    pub fn sample_host(&mut self) {
+        let sample = HostSample::now();
+        if let Some(last) = self.last_host_sample.as_ref() {
+            let wall_time = sample.at.duration_since(last.at).as_secs_f32();
+            if let (Some(cpu_time), Some(last_cpu_time), true) =
+                (sample.cpu_time, last.cpu_time, wall_time > 0.0)
+            {
+                let used = cpu_time.saturating_sub(last_cpu_time).as_secs_f32();
+                self.cpu_usage.push_back(used / wall_time * 100.0);
+            }
+            if let (Some((count, bytes)), Some((last_count, last_bytes))) =
+                (sample.allocations, last.allocations)
+            {
+                self.allocations
+                    .push_back((count - last_count, bytes - last_bytes));
+            }
         }
+        self.last_host_sample = Some(sample);
+    }
+
+    pub fn observe_frames(&mut self, received: u64, unshown: u64) {
+        self.frames_received = received;
+        self.frames_unshown = unshown;
     }
 
     pub fn get_measurement(&self, measurement_type: MeasurementType) -> &Measurement {
@@ -102,23 +224,91 @@ impl MetricsComponent {
             .or_default()
             .stop_measurement();
     }
+
+    pub fn track_input(&mut self, probe: InputLatencyProbe) {
+        if self.pending_inputs.len() >= MAX_PENDING_INPUT_PROBES {
+            self.pending_inputs.pop_front();
+        }
+        self.pending_inputs.push_back(PendingInput {
+            probe,
+            consumed: None,
+        });
+    }
+
+    // Consumption and frame emission can only be observed between two runs of the emulator, so
+    // these stages have the resolution of the UI frame rate.
+    pub fn observe_input(&mut self, consumed: u64, frames_emitted: u64) {
+        let now = Instant::now();
+        for pending in self.pending_inputs.iter_mut() {
+            if pending.consumed.is_none() && pending.probe.sequence <= consumed {
+                pending.consumed = Some((now, self.last_frames_emitted));
+            }
+        }
+
+        while let Some(PendingInput {
+            probe,
+            consumed: Some((consumed_at, frames_before)),
+        }) = self.pending_inputs.front()
+        {
+            if frames_emitted <= *frames_before {
+                break;
+            }
+            let stages = [
+                (InputLatencyStage::Enqueue, probe.enqueued - probe.captured),
+                (InputLatencyStage::Consume, *consumed_at - probe.enqueued),
+                (InputLatencyStage::Frame, now - *consumed_at),
+                (InputLatencyStage::Total, now - probe.captured),
+            ];
+            for (stage, duration) in stages {
+                self.input_latency
+                    .entry(stage)
+                    .or_default()
+                    .record(duration);
+            }
+            self.pending_inputs.pop_front();
+        }
+
+        self.last_frames_emitted = frames_emitted;
+    }
 }
 
 impl Component for MetricsComponent {
     fn update(
         &mut self,
         _emulator: &super::emulator::EmulatorComponent,
-        _command_sender: &mpsc::Sender<AppCommand>,
+        _command_sender: &CommandSender,
         _ctx: &egui::Context,
     ) {
     }
 
     fn draw(
         &mut self,
-        _emulator: &super::emulator::EmulatorComponent,
+        emulator: &super::emulator::EmulatorComponent,
+        _command_sender: &CommandSender,
         _ctx: &egui::Context,
         ui: &mut egui::Ui,
     ) {
+        ui.label(
+            RichText::new(format!(
+                "{:>13}: {:.2}Hz",
+                "Refresh",
+                emulator.get_backend().get_info().refresh_rate
+            ))
+            .monospace(),
+        );
+        ui.label(
+            RichText::new(format!("{:>13}: {:.2}", "Behind", emulator.frames_behind())).monospace(),
+        );
+        ui.label(
+            RichText::new(format!("{:>13}: {}", "Skipped", emulator.skipped_frames())).monospace(),
+        );
+        ui.label(
+            RichText::new(format!(
+                "{:>13}: {} ({} unshown)",
+                "Frames", self.frames_received, self.frames_unshown
+            ))
+            .monospace(),
+        );
         for (measurement_type, measurement) in &self.measurements {
             ui.label(
                 RichText::new(format!(
@@ -131,5 +321,59 @@ impl Component for MetricsComponent {
                 .monospace(),
             );
         }
+
+        let cpu_usage = self.cpu_usage.peek_range(..);
+        if !cpu_usage.is_empty() {
+            ui.label(
+                RichText::new(format!(
+                    "{:>13}: {:.1}% | {:.1}%",
+                    "CPU",
+                    cpu_usage.iter().sum::<f32>() / cpu_usage.len() as f32,
+                    cpu_usage.iter().copied().fold(0.0, f32::max)
+                ))
+                .monospace(),
+            )
+            .on_hover_text("Average and highest usage of the process per frame, 100% is one core");
+        }
+        let allocations = self.allocations.peek_range(..);
+        if !allocations.is_empty() {
+            let frames = allocations.len() as u64;
+            ui.label(
+                RichText::new(format!(
+                    "{:>13}: {} | {} ({} KiB)",
+                    "Allocations",
+                    allocations.iter().map(|(count, _)| count).sum::<u64>() / frames,
+                    allocations
+                        .iter()
+                        .map(|(count, _)| *count)
+                        .max()
+                        .unwrap_or_default(),
+                    allocations.iter().map(|(_, bytes)| bytes).sum::<u64>() / frames / 1024
+                ))
+                .monospace(),
+            )
+            .on_hover_text("Average and highest allocations per frame, average size per frame");
+        }
+
+        if !self.input_latency.is_empty() {
+            ui.separator();
+            ui.label(
+                RichText::new(format!("{:>13}: min | p50 | p95 | max", "Input latency"))
+                    .monospace(),
+            );
+            for (stage, measurement) in &self.input_latency {
+                ui.label(
+                    RichText::new(format!(
+                        "{}: {:04.2}ms | {:04.2}ms | {:04.2}ms | {:04.2}ms",
+                        stage,
+                        measurement.min().as_secs_f32() * 1000.0,
+                        measurement.percentile(50.0).as_secs_f32() * 1000.0,
+                        measurement.percentile(95.0).as_secs_f32() * 1000.0,
+                        measurement.max().as_secs_f32() * 1000.0
+                    ))
+                    .monospace(),
+                );
+            }
+        }
     }
 }
@@ -1,24 +1,43 @@
-use std::sync::mpsc;
-
 use emulator::EmulatorComponent;
 
-use crate::app::AppCommand;
+use crate::command::CommandSender;
 
 pub mod audio;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod browser;
+pub mod debug;
+pub mod diagnostic;
 pub mod emulator;
+pub mod heatmap;
+pub mod help;
 pub mod input;
 pub mod inspector;
 pub mod memory;
 pub mod metrics;
+pub mod osd;
+pub mod savestates;
+pub mod scheduler;
 pub mod screen;
 pub mod selection;
+pub mod stack;
+pub mod switches;
+pub mod textlog;
+pub mod tracelog;
+pub mod triggers;
+pub mod vram;
 
 pub trait Component {
     fn update(
         &mut self,
         emulator: &EmulatorComponent,
-        command_sender: &mpsc::Sender<AppCommand>,
+        command_sender: &CommandSender,
+        ctx: &egui::Context,
+    );
+    fn draw(
+        &mut self,
+        emulator: &EmulatorComponent,
+        command_sender: &CommandSender,
         ctx: &egui::Context,
+        ui: &mut egui::Ui,
     );
-    fn draw(&mut self, emulator: &EmulatorComponent, ctx: &egui::Context, ui: &mut egui::Ui);
 }
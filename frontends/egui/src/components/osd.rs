@@ -0,0 +1,96 @@
+use std::collections::VecDeque;
+
+use egui::{Align2, Color32, FontId, Rect, vec2};
+use web_time::Instant;
+
+use super::emulator::EmulatorComponent;
+
+// A message is shown at full opacity this long, then fades out
+const MESSAGE_SECONDS: f32 = 1.5;
+const FADE_SECONDS: f32 = 0.5;
+const MAX_MESSAGES: usize = 4;
+
+struct Message {
+    // Messages of the same kind replace each other, e.g. while dragging the speed slider
+    kind: String,
+    text: String,
+    shown_at: Instant,
+}
+
+// On screen display of what the app just did, drawn over the top right corner of the screen.
+// States which last, like rewinding, are shown for as long as they last.
+#[derive(Default)]
+pub struct Osd {
+    messages: VecDeque<Message>,
+}
+
+impl Osd {
+    pub fn show(&mut self, kind: &str, text: impl Into<String>) {
+        self.messages.retain(|message| message.kind != kind);
+        self.messages.push_back(Message {
+            kind: kind.to_string(),
+            text: text.into(),
+            shown_at: Instant::now(),
+        });
+        while self.messages.len() > MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+    }
+
+    pub fn draw(&mut self, emulator: &EmulatorComponent, ui: &egui::Ui, screen: Rect) {
+        self.messages.retain(|message| {
+            message.shown_at.elapsed().as_secs_f32() < MESSAGE_SECONDS + FADE_SECONDS
+        });
+        let mut lines: Vec<(String, f32)> = self
+            .messages
+            .iter()
+            .map(|message| {
+                let faded = message.shown_at.elapsed().as_secs_f32() - MESSAGE_SECONDS;
+                (
+                    message.text.clone(),
+                    1.0 - (faded / FADE_SECONDS).clamp(0.0, 1.0),
+                )
+            })
+            .collect();
+        if emulator.is_rewinding() {
+            lines.insert(
+                0,
+                (
+                    format!(
+                        "Rewinding, {:.1}s left",
+                        emulator.rewind_available().as_secs_f32()
+                    ),
+                    1.0,
+                ),
+            );
+        }
+        if lines.is_empty() {
+            return;
+        }
+        // Keeps the fade going while the emulation is paused
+        ui.ctx().request_repaint();
+
+        let painter = ui.painter_at(screen);
+        let font = FontId::proportional(14.0);
+        let mut top = screen.top() + 4.0;
+        for (text, opacity) in lines {
+            let galley =
+                painter.layout_no_wrap(text, font.clone(), Color32::WHITE.gamma_multiply(opacity));
+            let size = galley.size() + vec2(12.0, 6.0);
+            let rect = Align2::RIGHT_TOP.align_size_within_rect(
+                size,
+                Rect::from_min_max(
+                    egui::pos2(screen.left(), top),
+                    egui::pos2(screen.right() - 4.0, screen.bottom()),
+                ),
+            );
+            painter.rect_filled(
+                rect,
+                3.0,
+                Color32::from_black_alpha(160).gamma_multiply(opacity),
+            );
+            painter.galley(rect.min + vec2(6.0, 3.0), galley, Color32::WHITE);
+            top = rect.bottom() + 4.0;
+        }
+    }
+}
@@ -0,0 +1,262 @@
+use std::{collections::BTreeSet, ops::RangeInclusive};
+
+use axwemulator_core::{
+    backend::registry::BackendDescriptor, error::Error, frontend::storage::StorageProvider,
+};
+use egui::Key;
+use serde::{Deserialize, Serialize};
+
+use crate::{app::AppCommand, command::CommandSender};
+
+use super::{Component, emulator::EmulatorComponent};
+
+pub const QUICK_SAVE_KEY: Key = Key::F5;
+pub const QUICK_LOAD_KEY: Key = Key::F9;
+pub const UNDO_LOAD_KEY: Key = Key::F7;
+pub const AUTO_SAVE_SECONDS_RANGE: RangeInclusive<u32> = 0..=600;
+pub const AUTO_SLOTS_RANGE: RangeInclusive<usize> = 1..=10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SaveSlot {
+    Quick,
+    // Written in turn every few seconds of emulated time
+    Auto(usize),
+    // Written by the triggers which save the state when they fire
+    Trigger,
+}
+
+impl SaveSlot {
+    pub fn label(&self) -> String {
+        match self {
+            SaveSlot::Quick => "quick slot".to_string(),
+            SaveSlot::Auto(slot) => format!("auto slot {}", slot + 1),
+            SaveSlot::Trigger => "trigger slot".to_string(),
+        }
+    }
+
+    fn file_name(&self) -> String {
+        match self {
+            SaveSlot::Quick => "quick.state".to_string(),
+            SaveSlot::Auto(slot) => format!("auto-{}.state", slot + 1),
+            SaveSlot::Trigger => "trigger.state".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SavestateSettings {
+    // Emulated seconds between two auto saves, 0 turns them off
+    pub auto_save_seconds: u32,
+    pub auto_slots: usize,
+}
+
+impl Default for SavestateSettings {
+    fn default() -> Self {
+        Self {
+            auto_save_seconds: 60,
+            auto_slots: 3,
+        }
+    }
+}
+
+// The states of the running rom, kept in the storage next to each other. Loading a state keeps
+// the one it replaced in memory, so a mistimed quick load can be undone.
+pub struct SavestatesComponent {
+    settings: SavestateSettings,
+    rom_hash: String,
+    // Storage keys of the slots start with it, e.g. "savestates/chip8/<hash>/"
+    prefix: String,
+    occupied: BTreeSet<SaveSlot>,
+    next_auto_slot: usize,
+    last_auto_save: femtos::Instant,
+    undo: Option<Vec<u8>>,
+}
+
+impl SavestatesComponent {
+    pub fn new(
+        storage: &dyn StorageProvider,
+        backend_name: &str,
+        rom_hash: &str,
+        settings: SavestateSettings,
+    ) -> Self {
+        let prefix = format!("savestates/{}/{}/", backend_name.to_lowercase(), rom_hash);
+        let keys = storage.list(&prefix).unwrap_or_else(|error| {
+            log::warn!("unable to list the savestates: {}", error);
+            vec![]
+        });
+        let occupied: BTreeSet<SaveSlot> = [SaveSlot::Quick, SaveSlot::Trigger]
+            .into_iter()
+            .chain((0..*AUTO_SLOTS_RANGE.end()).map(SaveSlot::Auto))
+            .filter(|slot| keys.contains(&format!("{}{}", prefix, slot.file_name())))
+            .collect();
+        // Continues with a free slot, the order of the earlier ones is not known
+        let next_auto_slot = (0..settings.auto_slots)
+            .find(|slot| !occupied.contains(&SaveSlot::Auto(*slot)))
+            .unwrap_or_default();
+        Self {
+            settings,
+            rom_hash: rom_hash.to_string(),
+            prefix,
+            occupied,
+            next_auto_slot,
+            last_auto_save: femtos::Instant::START,
+            undo: None,
+        }
+    }
+
+    pub fn settings(&self) -> &SavestateSettings {
+        &self.settings
+    }
+
+    fn storage_key(&self, slot: SaveSlot) -> String {
+        format!("{}{}", self.prefix, slot.file_name())
+    }
+
+    pub fn save(
+        &mut self,
+        storage: &mut dyn StorageProvider,
+        descriptor: &BackendDescriptor,
+        emulator: &EmulatorComponent,
+        slot: SaveSlot,
+    ) -> Result<(), Error> {
+        let state = emulator.get_backend().save_state()?;
+        let data = descriptor.snapshot_header(&self.rom_hash).encode(&state);
+        storage.write(&self.storage_key(slot), &data)?;
+        self.occupied.insert(slot);
+        Ok(())
+    }
+
+    pub fn load(
+        &mut self,
+        storage: &dyn StorageProvider,
+        descriptor: &BackendDescriptor,
+        emulator: &mut EmulatorComponent,
+        slot: SaveSlot,
+    ) -> Result<(), Error> {
+        let data = storage
+            .read(&self.storage_key(slot))?
+            .ok_or_else(|| Error::new(format!("the {} is empty", slot.label())))?;
+        let state = descriptor.upgrade_snapshot(&data, &self.rom_hash)?;
+        self.replace_state(emulator, &state)
+    }
+
+    // Undoing again brings back the state the undo replaced
+    pub fn undo_load(&mut self, emulator: &mut EmulatorComponent) -> Result<(), Error> {
+        let state = self
+            .undo
+            .take()
+            .ok_or_else(|| Error::new("no load to undo"))?;
+        self.replace_state(emulator, &state)
+    }
+
+    fn replace_state(
+        &mut self,
+        emulator: &mut EmulatorComponent,
+        state: &[u8],
+    ) -> Result<(), Error> {
+        let current = emulator.get_backend().save_state()?;
+        emulator.load_state(state)?;
+        self.undo = Some(current);
+        // Loaded states have their own clock, the interval starts over from there
+        self.last_auto_save = emulator.get_backend().get_current_clock();
+        Ok(())
+    }
+
+    fn draw_slot(&mut self, command_sender: &CommandSender, ui: &mut egui::Ui, slot: SaveSlot) {
+        ui.horizontal(|ui| {
+            let occupied = self.occupied.contains(&slot);
+            ui.label(slot.label());
+            if slot == SaveSlot::Quick && ui.small_button("Save").clicked() {
+                command_sender.send(AppCommand::SaveState(slot));
+            }
+            if ui
+                .add_enabled(occupied, egui::Button::new("Load").small())
+                .clicked()
+            {
+                command_sender.send(AppCommand::LoadState(slot));
+            }
+            if !occupied {
+                ui.weak("empty");
+            }
+        });
+    }
+}
+
+impl Component for SavestatesComponent {
+    fn update(
+        &mut self,
+        emulator: &EmulatorComponent,
+        command_sender: &CommandSender,
+        ctx: &egui::Context,
+    ) {
+        ctx.input(|i| {
+            if i.key_pressed(QUICK_SAVE_KEY) {
+                command_sender.send(AppCommand::SaveState(SaveSlot::Quick));
+            }
+            if i.key_pressed(QUICK_LOAD_KEY) {
+                command_sender.send(AppCommand::LoadState(SaveSlot::Quick));
+            }
+            if i.key_pressed(UNDO_LOAD_KEY) {
+                command_sender.send(AppCommand::UndoLoadState);
+            }
+        });
+
+        let clock = emulator.get_backend().get_current_clock();
+        // Rewinding goes back behind the last auto save
+        if clock < self.last_auto_save {
+            self.last_auto_save = clock;
+        }
+        if self.settings.auto_save_seconds == 0 || emulator.is_rewinding() {
+            return;
+        }
+        let elapsed = clock.duration_since(self.last_auto_save);
+        if elapsed.as_secs() >= self.settings.auto_save_seconds as u64 {
+            let slot = self.next_auto_slot % self.settings.auto_slots.max(1);
+            command_sender.send(AppCommand::SaveState(SaveSlot::Auto(slot)));
+            self.next_auto_slot = slot + 1;
+            self.last_auto_save = clock;
+        }
+    }
+
+    fn draw(
+        &mut self,
+        _emulator: &EmulatorComponent,
+        command_sender: &CommandSender,
+        _ctx: &egui::Context,
+        ui: &mut egui::Ui,
+    ) {
+        ui.label(format!(
+            "{} saves, {} loads the quick slot, {} undoes the last load",
+            QUICK_SAVE_KEY.name(),
+            QUICK_LOAD_KEY.name(),
+            UNDO_LOAD_KEY.name()
+        ));
+        self.draw_slot(command_sender, ui, SaveSlot::Quick);
+        self.draw_slot(command_sender, ui, SaveSlot::Trigger);
+        if ui
+            .add_enabled(self.undo.is_some(), egui::Button::new("Undo load"))
+            .on_hover_text("Goes back to the state before the last load")
+            .clicked()
+        {
+            command_sender.send(AppCommand::UndoLoadState);
+        }
+
+        ui.separator();
+        ui.add(
+            egui::Slider::new(
+                &mut self.settings.auto_save_seconds,
+                AUTO_SAVE_SECONDS_RANGE,
+            )
+            .suffix("s")
+            .text("Auto save interval"),
+        )
+        .on_hover_text("Seconds of emulated time, 0 turns auto saves off");
+        ui.add(
+            egui::Slider::new(&mut self.settings.auto_slots, AUTO_SLOTS_RANGE).text("Auto slots"),
+        );
+        for slot in 0..self.settings.auto_slots {
+            self.draw_slot(command_sender, ui, SaveSlot::Auto(slot));
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use axwemulator_core::backend::{SchedulerSnapshot, fairness::SchedulerWarning};
+use egui::RichText;
+use femtos::Instant;
+
+use crate::{app::AppCommand, command::CommandSender};
+
+use super::Component;
+
+const MAX_WARNINGS: usize = 20;
+
+#[derive(Default)]
+pub struct SchedulerComponent {
+    snapshot: Option<SchedulerSnapshot>,
+    step_limits: Vec<Option<usize>>,
+    drift: f64,
+    warnings: Vec<SchedulerWarning>,
+}
+
+impl SchedulerComponent {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_warning(&mut self, warning: SchedulerWarning) {
+        self.warnings.push(warning);
+        if self.warnings.len() > MAX_WARNINGS {
+            self.warnings.remove(0);
+        }
+    }
+}
+
+pub fn describe_scheduler_warning(warning: &SchedulerWarning) -> String {
+    match warning {
+        SchedulerWarning::NoAdvance { component, clock } => format!(
+            "{} did not advance the clock at {}",
+            component,
+            format_instant(*clock)
+        ),
+        SchedulerWarning::StepLimit {
+            component,
+            clock,
+            limit,
+        } => format!(
+            "{} reached its limit of {} steps at {}",
+            component,
+            limit,
+            format_instant(*clock)
+        ),
+    }
+}
+
+fn format_instant(instant: Instant) -> String {
+    let nanos = instant.as_duration().as_nanos();
+    format!("{}.{:09}s", nanos / 1_000_000_000, nanos % 1_000_000_000)
+}
+
+impl Component for SchedulerComponent {
+    fn update(
+        &mut self,
+        emulator: &super::emulator::EmulatorComponent,
+        _command_sender: &CommandSender,
+        _ctx: &egui::Context,
+    ) {
+        let snapshot = emulator.get_backend().scheduler_snapshot();
+        self.step_limits = snapshot
+            .wakeups
+            .iter()
+            .map(|wakeup| emulator.get_backend().get_step_limit(&wakeup.name))
+            .collect();
+        self.snapshot = Some(snapshot);
+        self.drift = emulator.drift();
+    }
+
+    fn draw(
+        &mut self,
+        _emulator: &super::emulator::EmulatorComponent,
+        command_sender: &CommandSender,
+        _ctx: &egui::Context,
+        ui: &mut egui::Ui,
+    ) {
+        let Some(snapshot) = self.snapshot.as_ref() else {
+            return;
+        };
+
+        egui::Grid::new("scheduler_clock")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Clock");
+                ui.label(RichText::new(format_instant(snapshot.clock)).monospace());
+                ui.end_row();
+                ui.label("Drift");
+                ui.label(RichText::new(format!("{:+.3}ms", self.drift * 1000.0)).monospace());
+                ui.end_row();
+            });
+
+        ui.separator();
+        egui::Grid::new("scheduler_wakeups")
+            .num_columns(4)
+            .striped(true)
+            .show(ui, |ui| {
+                ui.strong("Component");
+                ui.strong("Next wakeup");
+                ui.strong("In");
+                ui.strong("Step limit").on_hover_text(
+                    "Steps per run before the component has to wait, 0 is unlimited",
+                );
+                ui.end_row();
+                for (wakeup, limit) in snapshot.wakeups.iter().zip(self.step_limits.iter()) {
+                    let due = if wakeup.clock > snapshot.clock {
+                        format!(
+                            "{}us",
+                            wakeup.clock.duration_since(snapshot.clock).as_micros()
+                        )
+                    } else {
+                        "now".to_string()
+                    };
+                    ui.label(&wakeup.name);
+                    ui.label(RichText::new(format_instant(wakeup.clock)).monospace());
+                    ui.label(RichText::new(due).monospace());
+                    let mut edited = limit.unwrap_or(0);
+                    if ui
+                        .add(egui::DragValue::new(&mut edited).speed(10))
+                        .changed()
+                    {
+                        command_sender.send(AppCommand::SetStepLimit(
+                            wakeup.name.clone(),
+                            (edited > 0).then_some(edited),
+                        ));
+                    }
+                    ui.end_row();
+                }
+            });
+
+        if !self.warnings.is_empty() {
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.strong("Warnings");
+                if ui.small_button("Clear").clicked() {
+                    self.warnings.clear();
+                }
+            });
+            for warning in self.warnings.iter().rev() {
+                ui.colored_label(
+                    ui.visuals().warn_fg_color,
+                    describe_scheduler_warning(warning),
+                );
+            }
+        }
+    }
+}
@@ -1,53 +1,354 @@
-use std::sync::mpsc;
+use axwemulator_core::{
+    backend::DisplayConfig,
+    frontend::{
+        filter::{BUILTIN_FILTERS, FilterChain},
+        graphics::{Frame, FrameReceiver},
+    },
+};
+use egui::{Align2, Color32, ColorImage, FontId, Rect, TextureHandle, TextureOptions, Vec2, vec2};
+use femtos::Duration;
+use web_time::Instant;
 
-use axwemulator_core::frontend::graphics::FrameReceiver;
-use egui::{ColorImage, TextureHandle, TextureOptions};
+use crate::command::CommandSender;
 
-use crate::app::AppCommand;
+use super::{Component, osd::Osd};
 
-use super::Component;
+const ASPECT_RATIOS: [(&str, Option<f32>); 5] = [
+    ("Native", None),
+    ("1:1", Some(1.0)),
+    ("2:1", Some(2.0)),
+    ("4:3", Some(4.0 / 3.0)),
+    ("16:9", Some(16.0 / 9.0)),
+];
+// Rates of the HUD are averaged over this much wall time
+const HUD_SAMPLE_SECONDS: f64 = 0.5;
+
+// Frame count and emulated time of the backend at a point in wall time
+#[derive(Clone, Copy)]
+struct HudSample {
+    at: Instant,
+    frames: u64,
+    elapsed: Duration,
+}
+
+struct HudStats {
+    fps: f64,
+    // Emulated time per wall time, 1.0 is full speed
+    speed: f64,
+    frames: u64,
+    elapsed: Duration,
+}
 
 pub struct ScreenComponent {
+    name: String,
     frame_receiver: FrameReceiver,
     framebuffer_texture: Option<TextureHandle>,
+    last_frame: Option<Frame>,
+    display: DisplayConfig,
+    filters: FilterChain,
+    frames_received: u64,
+    // Frames which arrived together with a newer one and were never shown
+    frames_unshown: u64,
+    show_input_overlay: bool,
+    show_hud: bool,
+    hud_sample: Option<HudSample>,
+    hud: HudStats,
+    show_osd: bool,
+    osd: Osd,
 }
 
 impl ScreenComponent {
-    pub fn new(frame_receiver: FrameReceiver) -> Self {
+    pub fn new(name: &str, frame_receiver: FrameReceiver) -> Self {
         Self {
+            name: name.to_string(),
             frame_receiver,
             framebuffer_texture: None,
+            last_frame: None,
+            display: DisplayConfig::default(),
+            filters: FilterChain::new(),
+            frames_received: 0,
+            frames_unshown: 0,
+            show_input_overlay: false,
+            show_hud: false,
+            hud_sample: None,
+            hud: HudStats {
+                fps: 0.0,
+                speed: 0.0,
+                frames: 0,
+                elapsed: Duration::ZERO,
+            },
+            show_osd: true,
+            osd: Osd::default(),
         }
     }
+
+    // Messages of the same kind replace each other
+    pub fn show_message(&mut self, kind: &str, text: impl Into<String>) {
+        self.osd.show(kind, text);
+    }
+
+    pub fn draw_overlay_settings(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.show_input_overlay, "Show pressed buttons")
+            .on_hover_text("Shows the buttons as the emulated machine sees them");
+        ui.checkbox(&mut self.show_hud, "Show frame rate and speed");
+        ui.checkbox(&mut self.show_osd, "Show messages")
+            .on_hover_text("Confirms actions like pausing over the screen");
+    }
+
+    fn update_hud(&mut self, emulator: &super::emulator::EmulatorComponent) {
+        let backend = emulator.get_backend();
+        let now = HudSample {
+            at: Instant::now(),
+            frames: backend.get_frame_count(),
+            elapsed: backend.get_elapsed_time(),
+        };
+        self.hud.frames = now.frames;
+        self.hud.elapsed = now.elapsed;
+        let Some(sample) = self.hud_sample else {
+            self.hud_sample = Some(now);
+            return;
+        };
+        let wall = now.at.duration_since(sample.at).as_secs_f64();
+        if wall < HUD_SAMPLE_SECONDS {
+            return;
+        }
+        self.hud.fps = now.frames.saturating_sub(sample.frames) as f64 / wall;
+        let emulated = now
+            .elapsed
+            .as_nanos()
+            .saturating_sub(sample.elapsed.as_nanos());
+        self.hud.speed = emulated as f64 / 1e9 / wall;
+        self.hud_sample = Some(now);
+    }
+
+    fn draw_hud(&self, ui: &egui::Ui, screen: Rect) {
+        let text = format!(
+            "{:.1} fps  {:.0}%  frame {}  {:.2}s",
+            self.hud.fps,
+            self.hud.speed * 100.0,
+            self.hud.frames,
+            self.hud.elapsed.as_millis() as f64 / 1000.0,
+        );
+        let painter = ui.painter_at(screen);
+        let font = FontId::monospace(12.0);
+        let galley = painter.layout_no_wrap(text, font, Color32::from_white_alpha(220));
+        let rect = Rect::from_min_size(screen.left_top(), galley.size() + vec2(8.0, 4.0));
+        painter.rect_filled(rect, 3.0, Color32::from_black_alpha(160));
+        painter.galley(rect.min + vec2(4.0, 2.0), galley, Color32::WHITE);
+    }
+
+    // Draws the control scheme of the backend into the corner of the screen, lighting up the
+    // buttons the machine currently sees pressed
+    fn draw_input_overlay(
+        &self,
+        emulator: &super::emulator::EmulatorComponent,
+        ui: &egui::Ui,
+        screen: Rect,
+    ) {
+        let backend = emulator.get_backend();
+        let controls = backend.get_info().controls;
+        if controls.rows.is_empty() {
+            return;
+        }
+        let pressed = backend.pressed_keys();
+        let cell = 18.0;
+        let spacing = 2.0;
+        let painter = ui.painter_at(screen);
+        let origin = screen.left_bottom()
+            + vec2(
+                spacing,
+                -(cell + spacing) * controls.rows.len() as f32 - spacing,
+            );
+        for (y, row) in controls.rows.iter().enumerate() {
+            for (x, control) in row.iter().enumerate() {
+                let rect = Rect::from_min_size(
+                    origin + vec2(x as f32, y as f32) * (cell + spacing),
+                    Vec2::splat(cell),
+                );
+                let (fill, text) = if pressed.contains(&control.key) {
+                    (Color32::from_rgb(230, 180, 40), Color32::BLACK)
+                } else {
+                    (
+                        Color32::from_black_alpha(160),
+                        Color32::from_white_alpha(200),
+                    )
+                };
+                painter.rect_filled(rect, 3.0, fill);
+                painter.text(
+                    rect.center(),
+                    Align2::CENTER_CENTER,
+                    control.label,
+                    FontId::monospace(cell * 0.6),
+                    text,
+                );
+            }
+        }
+    }
+
+    pub fn display_config(&self) -> DisplayConfig {
+        self.display
+    }
+
+    pub fn set_display_config(&mut self, display: DisplayConfig) {
+        self.display = display;
+    }
+
+    // Returns whether the user changed the configuration
+    pub fn draw_display_settings(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = ui
+            .checkbox(&mut self.display.integer_scaling, "Integer scaling")
+            .changed();
+        let selected = ASPECT_RATIOS
+            .iter()
+            .find(|(_, aspect_ratio)| *aspect_ratio == self.display.aspect_ratio)
+            .map_or("Custom", |(name, _)| name);
+        egui::ComboBox::from_label("Aspect ratio")
+            .selected_text(selected)
+            .show_ui(ui, |ui| {
+                for (name, aspect_ratio) in ASPECT_RATIOS {
+                    changed |= ui
+                        .selectable_value(&mut self.display.aspect_ratio, aspect_ratio, name)
+                        .changed();
+                }
+            });
+        changed
+    }
+
+    pub fn filter_names(&self) -> Vec<String> {
+        self.filters
+            .names()
+            .into_iter()
+            .map(str::to_string)
+            .collect()
+    }
+
+    pub fn set_filters(&mut self, names: &[String]) {
+        self.filters = FilterChain::from_names(names);
+    }
+
+    // Returns whether the user changed the filter chain
+    pub fn draw_filter_settings(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+        let mut removed = None;
+        for (index, name) in self.filters.names().into_iter().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(format!("{}. {}", index + 1, name));
+                if ui.small_button("Remove").clicked() {
+                    removed = Some(index);
+                }
+            });
+        }
+        if let Some(index) = removed {
+            self.filters.remove(index);
+            changed = true;
+        }
+        egui::ComboBox::from_label("Add filter")
+            .selected_text("")
+            .show_ui(ui, |ui| {
+                for name in BUILTIN_FILTERS {
+                    if ui.selectable_label(false, name).clicked() {
+                        let mut names = self.filter_names();
+                        names.push(name.to_string());
+                        self.set_filters(&names);
+                        changed = true;
+                    }
+                }
+            });
+        changed
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // The frame currently on screen
+    pub fn last_frame(&self) -> Option<&Frame> {
+        self.last_frame.as_ref()
+    }
+
+    pub fn frames_emitted(&self) -> u64 {
+        self.frame_receiver.emitted()
+    }
+
+    pub fn frames_received(&self) -> u64 {
+        self.frames_received
+    }
+
+    pub fn frames_unshown(&self) -> u64 {
+        self.frames_unshown
+    }
 }
 
 impl Component for ScreenComponent {
     fn update(
         &mut self,
-        _emulator: &super::emulator::EmulatorComponent,
-        _command_sender: &mpsc::Sender<AppCommand>,
+        emulator: &super::emulator::EmulatorComponent,
+        _command_sender: &CommandSender,
         ctx: &egui::Context,
     ) {
-        if let Some((_clock, frame)) = self.frame_receiver.latest() {
+        if self.show_hud {
+            self.update_hud(emulator);
+        } else {
+            self.hud_sample = None;
+        }
+        let frames = self.frame_receiver.pop_all();
+        self.frames_received += frames.len() as u64;
+        self.frames_unshown += frames.len().saturating_sub(1) as u64;
+        if let Some((_clock, frame)) = frames.into_iter().last() {
+            let filtered = (!self.filters.is_empty()).then(|| self.filters.apply(frame.clone()));
+            let displayed = filtered.as_ref().unwrap_or(&frame);
             self.framebuffer_texture = Some(ctx.load_texture(
-                "screen",
+                self.name.as_str(),
                 ColorImage::from_rgba_unmultiplied(
-                    [frame.width as _, frame.height as _],
-                    &frame.as_rgba_vec(),
+                    [displayed.width as _, displayed.height as _],
+                    &displayed.as_rgba_vec(),
                 ),
                 TextureOptions::NEAREST,
             ));
+            self.last_frame = Some(frame);
         }
     }
 
     fn draw(
         &mut self,
-        _emulator: &super::emulator::EmulatorComponent,
+        emulator: &super::emulator::EmulatorComponent,
+        _command_sender: &CommandSender,
         _ctx: &egui::Context,
         ui: &mut egui::Ui,
     ) {
-        if let Some(framebuffer_texture) = self.framebuffer_texture.as_ref() {
-            ui.add(egui::Image::new(framebuffer_texture).shrink_to_fit());
+        let Some(framebuffer_texture) = self.framebuffer_texture.as_ref() else {
+            return;
+        };
+
+        let mut size = framebuffer_texture.size_vec2();
+        if let Some(aspect_ratio) = self.display.aspect_ratio {
+            size.x = size.y * aspect_ratio;
+        }
+        let available = ui.available_size();
+        let mut scale = (available.x / size.x).min(available.y / size.y);
+        if self.display.integer_scaling && scale >= 1.0 {
+            scale = scale.floor();
+        }
+        let size: Vec2 = size * scale;
+
+        let response = ui.vertical_centered(|ui| {
+            ui.add(egui::Image::new(framebuffer_texture).fit_to_exact_size(size))
+        });
+        response.inner.widget_info(|| {
+            egui::WidgetInfo::labeled(
+                egui::WidgetType::Image,
+                true,
+                format!("{} screen", emulator.name()),
+            )
+        });
+        if self.show_input_overlay {
+            self.draw_input_overlay(emulator, ui, response.inner.rect);
+        }
+        if self.show_hud {
+            self.draw_hud(ui, response.inner.rect);
+        }
+        if self.show_osd {
+            self.osd.draw(emulator, ui, response.inner.rect);
         }
     }
 }
@@ -1,70 +1,1043 @@
-use std::sync::mpsc;
+use axwemulator_core::{
+    backend::{
+        firmware::{FirmwareDescriptor, FirmwareFiles},
+        options::{OptionKind, OptionValue, OptionValues, OptionsSchema},
+        registry::BackendRegistry,
+        selftest::{SelfTestReport, SelfTestVerdict},
+    },
+    container::unpack_rom,
+    patch::{PATCH_EXTENSIONS, RomPatch},
+    rom::{Detection, Rom, RomIssue, unambiguous_detection},
+};
 
-use crate::app::AppCommand;
+use crate::{
+    app::AppCommand,
+    command::{CommandSender, PendingReply},
+    session::LastSession,
+};
 
-use super::emulator::AvailableBackends;
+#[cfg(not(target_arch = "wasm32"))]
+use super::browser::{BrowserAction, RomBrowser};
+use super::switches::draw_dip_switches;
+
+const ARCHIVE_EXTENSIONS: &[&str] = &["zip", "gz"];
+
+pub struct PendingLaunch {
+    backend_name: String,
+    rom_data: Vec<u8>,
+    schema: OptionsSchema,
+    values: OptionValues,
+    firmware: &'static [FirmwareDescriptor],
+    firmware_files: FirmwareFiles,
+    rejected_firmware: Vec<&'static str>,
+    has_demo: bool,
+    self_test_report: Option<Result<SelfTestReport, String>>,
+    // Notes of the last analysis, its values are applied right away
+    analysis_notes: Option<Result<Vec<String>, String>>,
+}
+
+impl PendingLaunch {
+    fn is_ready(&self) -> bool {
+        self.firmware
+            .iter()
+            .all(|f| !f.required || self.firmware_files.contains(f.key))
+    }
+}
+
+// A rom opened without choosing a backend, which could not be detected with enough confidence
+pub struct PendingDetection {
+    rom: Rom,
+    detections: Vec<Detection>,
+}
+
+// A rom which looks corrupt, the user decides whether to launch it anyway
+pub struct PendingIntegrity {
+    backend_name: String,
+    rom_data: Vec<u8>,
+    issues: Vec<RomIssue>,
+}
+
+// An archive containing several roms, the user picks the one to launch
+pub struct PendingArchive {
+    roms: Vec<Rom>,
+    backend_name: Option<String>,
+}
 
 #[derive(Default)]
 pub struct SelectionComponent {
-    emulator_backend_selection: AvailableBackends,
+    registry: BackendRegistry,
+    emulator_backend_selection: String,
+    firmware_directory: String,
+    pending_launch: Option<PendingLaunch>,
+    pending_detection: Option<PendingDetection>,
+    pending_integrity: Option<PendingIntegrity>,
+    pending_archive: Option<PendingArchive>,
+    // Chosen by the user, takes precedence over a patch found next to the rom
+    patch: Option<RomPatch>,
+    launch_reply: Option<PendingReply>,
+    launch_error: Option<String>,
+    // File name of the rom being launched, or its name in the library
+    rom_name: Option<String>,
+    last_session: Option<LastSession>,
+    #[cfg(not(target_arch = "wasm32"))]
+    browser: Option<RomBrowser>,
 }
 
 impl SelectionComponent {
-    pub fn new() -> Self {
+    // Only native builds can browse a rom directory
+    pub fn new(registry: BackendRegistry, firmware_directory: String, rom_directory: &str) -> Self {
+        let emulator_backend_selection = registry
+            .descriptors()
+            .first()
+            .map(|d| d.name.to_string())
+            .unwrap_or_default();
+        #[cfg(not(target_arch = "wasm32"))]
+        let browser =
+            (!rom_directory.is_empty()).then(|| RomBrowser::new(rom_directory.into(), &registry));
+        #[cfg(target_arch = "wasm32")]
+        let _ = rom_directory;
         Self {
-            emulator_backend_selection: Default::default(),
+            registry,
+            emulator_backend_selection,
+            firmware_directory,
+            pending_launch: None,
+            pending_detection: None,
+            pending_integrity: None,
+            pending_archive: None,
+            patch: None,
+            launch_reply: None,
+            launch_error: None,
+            rom_name: None,
+            last_session: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            browser,
+        }
+    }
+
+    pub fn with_last_session(mut self, last_session: Option<LastSession>) -> Self {
+        self.last_session = last_session;
+        self
+    }
+
+    pub fn rom_name(&self) -> Option<&str> {
+        self.rom_name.as_deref()
+    }
+
+    pub fn set_rom_name(&mut self, rom_name: Option<String>) {
+        self.rom_name = rom_name;
+    }
+
+    pub fn configure(
+        &mut self,
+        backend_name: String,
+        rom_data: Vec<u8>,
+        schema: OptionsSchema,
+        values: OptionValues,
+        firmware: &'static [FirmwareDescriptor],
+        has_demo: bool,
+    ) {
+        let mut firmware_files = FirmwareFiles::default();
+        for descriptor in firmware {
+            if let Some(data) = locate_firmware(&self.firmware_directory, descriptor) {
+                firmware_files.set(descriptor.key, data);
+            }
+        }
+        self.pending_launch = Some(PendingLaunch {
+            backend_name,
+            rom_data,
+            schema,
+            values,
+            firmware,
+            firmware_files,
+            rejected_firmware: vec![],
+            has_demo,
+            self_test_report: None,
+            analysis_notes: None,
+        });
+    }
+
+    pub fn load_firmware(&mut self, key: &str, data: Vec<u8>) {
+        let Some(pending) = self.pending_launch.as_mut() else {
+            return;
+        };
+        let Some(descriptor) = pending.firmware.iter().find(|f| f.key == key) else {
+            return;
+        };
+        pending.rejected_firmware.retain(|k| *k != descriptor.key);
+        if descriptor.verify(&data) {
+            pending.firmware_files.set(descriptor.key, data);
+        } else {
+            log::warn!(
+                "firmware {} does not match any known dump",
+                descriptor.label
+            );
+            pending.firmware_files.remove(descriptor.key);
+            pending.rejected_firmware.push(descriptor.key);
         }
     }
 
-    pub fn update(&mut self, _command_sender: &mpsc::Sender<AppCommand>, _ctx: &egui::Context) {}
+    // Runs the self-test rom of the backend with the options currently configured
+    pub fn run_self_test(&mut self, rom_data: &[u8]) {
+        let Some(pending) = self.pending_launch.as_mut() else {
+            return;
+        };
+        let Some(self_test) = self
+            .registry
+            .get(&pending.backend_name)
+            .ok()
+            .and_then(|descriptor| descriptor.self_test)
+        else {
+            return;
+        };
+        pending.self_test_report =
+            Some((self_test.run)(rom_data, pending.values.clone()).map_err(|e| e.to_string()));
+    }
+
+    // Lets the backend try the rom under different options and takes over the ones it suggests
+    pub fn analyze_options(&mut self) {
+        let Some(pending) = self.pending_launch.as_mut() else {
+            return;
+        };
+        let Some(analysis) = self
+            .registry
+            .get(&pending.backend_name)
+            .ok()
+            .and_then(|descriptor| descriptor.options_analysis)
+        else {
+            return;
+        };
+        let result = (analysis.run)(&pending.rom_data, pending.values.clone()).map(|suggestion| {
+            pending.values.apply(&suggestion.values);
+            suggestion.notes
+        });
+        pending.analysis_notes = Some(result.map_err(|e| e.to_string()));
+    }
+
+    pub fn open_rom(&mut self, rom: Rom, command_sender: &CommandSender) {
+        self.open_rom_with(None, rom, command_sender);
+    }
+
+    // Unpacks archives first, the backend is detected unless one was chosen already
+    pub fn open_rom_with(
+        &mut self,
+        backend_name: Option<&str>,
+        rom: Rom,
+        command_sender: &CommandSender,
+    ) {
+        let mut roms = match unpack_rom(rom) {
+            Ok(roms) => roms,
+            Err(error) => {
+                self.launch_error = Some(error.to_string());
+                return;
+            }
+        };
+        if roms.len() > 1 {
+            self.pending_archive = Some(PendingArchive {
+                roms,
+                backend_name: backend_name.map(str::to_string),
+            });
+            return;
+        }
+        self.start(backend_name, roms.remove(0), command_sender);
+    }
+
+    pub fn set_patch(&mut self, patch: Option<RomPatch>) {
+        self.patch = patch;
+    }
+
+    // Launches the configured rom right away unless firmware still has to be chosen
+    pub fn take_ready_launch(&mut self) -> Option<AppCommand> {
+        if !self.pending_launch.as_ref()?.is_ready() {
+            return None;
+        }
+        let pending = self.pending_launch.take()?;
+        Some(AppCommand::InitBackendWithRom(
+            pending.backend_name,
+            pending.rom_data,
+            pending.values,
+            pending.firmware_files,
+            false,
+        ))
+    }
+
+    fn start(&mut self, backend_name: Option<&str>, mut rom: Rom, command_sender: &CommandSender) {
+        let patch = self.patch.clone().or(rom.patch.take());
+        if let Some(patch) = patch.as_ref() {
+            log::info!("applying patch {}", patch.file_name);
+        }
+        let rom = match rom.with_patch(patch).apply_patch() {
+            Ok(rom) => rom,
+            Err(error) => {
+                self.launch_error = Some(error.to_string());
+                return;
+            }
+        };
+        self.rom_name = rom.file_name.clone();
+        match backend_name {
+            Some(backend_name) => self.launch(backend_name, rom.data, command_sender),
+            None => self.detect_backend(rom, command_sender),
+        }
+    }
+
+    fn detect_backend(&mut self, rom: Rom, command_sender: &CommandSender) {
+        let detections = self.registry.detect(&rom);
+        if let Some(detection) = unambiguous_detection(&detections) {
+            self.launch(detection.backend, rom.data, command_sender);
+        } else {
+            self.pending_detection = Some(PendingDetection { rom, detections });
+        }
+    }
+
+    // Corrupt roms are only launched once the user confirms it, the emulator would be blamed for
+    // their bugs otherwise
+    fn launch(&mut self, backend_name: &str, rom_data: Vec<u8>, command_sender: &CommandSender) {
+        let rom = Rom::new(self.rom_name.clone(), rom_data);
+        if let Ok(descriptor) = self.registry.get(backend_name) {
+            let integrity = descriptor.check_integrity(&rom);
+            if let Some(title) = integrity.verified.as_ref() {
+                log::info!("rom verified as good dump of {}", title);
+            }
+            if !integrity.is_ok() {
+                self.pending_integrity = Some(PendingIntegrity {
+                    backend_name: backend_name.to_string(),
+                    rom_data: rom.data,
+                    issues: integrity.issues,
+                });
+                return;
+            }
+        }
+        self.launch_unchecked(backend_name, rom.data, command_sender);
+    }
+
+    fn launch_unchecked(
+        &mut self,
+        backend_name: &str,
+        rom_data: Vec<u8>,
+        command_sender: &CommandSender,
+    ) {
+        self.emulator_backend_selection = backend_name.to_string();
+        self.launch_reply = Some(command_sender.request(AppCommand::ConfigureBackend(
+            backend_name.to_string(),
+            rom_data,
+        )));
+    }
+
+    fn pick_rom(&mut self, command_sender: &CommandSender) {
+        #[cfg(target_arch = "wasm32")]
+        {
+            let sender = command_sender.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                if let Some(handle) = rfd::AsyncFileDialog::new().pick_file().await {
+                    let data = handle.read().await;
+                    sender.send(AppCommand::OpenRom(Rom::new(
+                        Some(handle.file_name()),
+                        data,
+                    )));
+                }
+            });
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = rfd::FileDialog::new().pick_file() {
+            match read_rom_file(&path) {
+                Ok(rom) => self.open_rom(rom, command_sender),
+                Err(error) => self.launch_error = Some(format!("unable to read rom: {}", error)),
+            }
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn draw_browser(&mut self, command_sender: &CommandSender, ui: &mut egui::Ui) {
+        let mut action = None;
+        ui.horizontal(|ui| {
+            ui.label("Rom folder");
+            if ui.button("Choose").clicked() {
+                if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                    self.browser = Some(RomBrowser::new(path.clone(), &self.registry));
+                    action = Some(BrowserAction::Navigated(path));
+                }
+            }
+            if self.browser.is_some() && ui.button("Close").clicked() {
+                self.browser = None;
+                command_sender.send(AppCommand::SetRomDirectory(String::new()));
+            }
+        });
+        if let Some(browser) = self.browser.as_mut() {
+            ui.separator();
+            action = browser.draw(&self.registry, ui).or(action);
+            ui.separator();
+        }
+
+        match action {
+            Some(BrowserAction::Launch(rom)) => self.open_rom(rom, command_sender),
+            Some(BrowserAction::Navigated(path)) => command_sender.send(
+                AppCommand::SetRomDirectory(path.to_string_lossy().to_string()),
+            ),
+            None => {}
+        }
+    }
+
+    fn draw_detection(&mut self, command_sender: &CommandSender, ui: &mut egui::Ui) {
+        let Some(pending) = self.pending_detection.as_ref() else {
+            return;
+        };
+
+        ui.heading("Choose backend");
+        if pending.detections.is_empty() {
+            ui.label("No backend recognized this rom, it might not run correctly");
+        } else {
+            ui.label("The backend could not be detected reliably");
+        }
+
+        let mut chosen = None;
+        egui::Grid::new("backend_detection")
+            .num_columns(2)
+            .show(ui, |ui| {
+                for descriptor in self.registry.descriptors() {
+                    let confidence = pending
+                        .detections
+                        .iter()
+                        .find(|d| d.backend == descriptor.name)
+                        .map_or("unlikely".to_string(), |d| {
+                            format!("{:?} confidence", d.confidence).to_lowercase()
+                        });
+                    if ui.button(descriptor.name).clicked() {
+                        chosen = Some(descriptor.name);
+                    }
+                    ui.label(confidence);
+                    ui.end_row();
+                }
+            });
+        let back = ui.button("Back").clicked();
+
+        if let Some(backend_name) = chosen {
+            let pending = self.pending_detection.take().unwrap();
+            self.launch(backend_name, pending.rom.data, command_sender);
+        } else if back {
+            self.pending_detection = None;
+        }
+    }
+
+    fn draw_integrity(&mut self, command_sender: &CommandSender, ui: &mut egui::Ui) {
+        let Some(pending) = self.pending_integrity.as_ref() else {
+            return;
+        };
+
+        ui.heading("Possibly corrupt rom");
+        for issue in pending.issues.iter() {
+            ui.colored_label(ui.visuals().warn_fg_color, issue.to_string());
+        }
+        ui.label("The rom might not run correctly, try a different dump when it misbehaves");
+        let mut launch = false;
+        let mut back = false;
+        ui.horizontal(|ui| {
+            launch = ui.button("Continue anyway").clicked();
+            back = ui.button("Back").clicked();
+        });
+
+        if launch {
+            let pending = self.pending_integrity.take().unwrap();
+            self.launch_unchecked(&pending.backend_name, pending.rom_data, command_sender);
+        } else if back {
+            self.pending_integrity = None;
+        }
+    }
+
+    fn pick_patch(&mut self, command_sender: &CommandSender) {
+        #[cfg(target_arch = "wasm32")]
+        {
+            let sender = command_sender.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                if let Some(handle) = rfd::AsyncFileDialog::new()
+                    .add_filter("Patches", &PATCH_EXTENSIONS)
+                    .pick_file()
+                    .await
+                {
+                    let data = handle.read().await;
+                    sender.send(AppCommand::SetRomPatch(Some(RomPatch::new(
+                        handle.file_name(),
+                        data,
+                    ))));
+                }
+            });
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let _ = command_sender;
+            if let Some(path) = rfd::FileDialog::new()
+                .add_filter("Patches", &PATCH_EXTENSIONS)
+                .pick_file()
+            {
+                match std::fs::read(&path) {
+                    Ok(data) => {
+                        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+                        self.patch = Some(RomPatch::new(file_name.to_string(), data));
+                    }
+                    Err(error) => {
+                        self.launch_error = Some(format!("unable to read patch: {}", error))
+                    }
+                }
+            }
+        }
+    }
+
+    fn draw_patch(&mut self, command_sender: &CommandSender, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Patch");
+            match self.patch.as_ref() {
+                Some(patch) => {
+                    ui.label(&patch.file_name);
+                    if ui.button("Clear").clicked() {
+                        self.patch = None;
+                    }
+                }
+                None => {
+                    ui.weak("none")
+                        .on_hover_text("Patches named like the rom are applied automatically");
+                }
+            }
+            if ui.button("Choose").clicked() {
+                self.pick_patch(command_sender);
+            }
+        });
+    }
+
+    fn draw_archive(&mut self, command_sender: &CommandSender, ui: &mut egui::Ui) {
+        let Some(pending) = self.pending_archive.as_ref() else {
+            return;
+        };
+
+        ui.heading("Choose rom");
+        ui.label("The archive contains several roms");
+        let mut chosen = None;
+        egui::Grid::new("archive_roms")
+            .num_columns(2)
+            .show(ui, |ui| {
+                for (index, rom) in pending.roms.iter().enumerate() {
+                    let name = rom.file_name.as_deref().unwrap_or("unnamed");
+                    if ui.button(name).clicked() {
+                        chosen = Some(index);
+                    }
+                    ui.label(format!("{} bytes", rom.data.len()));
+                    ui.end_row();
+                }
+            });
+        let back = ui.button("Back").clicked();
+
+        if let Some(index) = chosen {
+            let mut pending = self.pending_archive.take().unwrap();
+            let rom = pending.roms.swap_remove(index);
+            self.start(pending.backend_name.as_deref(), rom, command_sender);
+        } else if back {
+            self.pending_archive = None;
+        }
+    }
+
+    fn browse_firmware(&mut self, key: &'static str, command_sender: &CommandSender) {
+        #[cfg(target_arch = "wasm32")]
+        {
+            let sender = command_sender.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                if let Some(handle) = rfd::AsyncFileDialog::new().pick_file().await {
+                    let data = handle.read().await;
+                    sender.send(AppCommand::LoadFirmware(key.to_string(), data));
+                }
+            });
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let _ = command_sender;
+            if let Some(path) = rfd::FileDialog::new().pick_file() {
+                match std::fs::read(path) {
+                    Ok(data) => self.load_firmware(key, data),
+                    Err(error) => log::error!("unable to read firmware: {}", error),
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "rom-library")]
+    fn draw_library(&mut self, command_sender: &CommandSender, ui: &mut egui::Ui) {
+        let mut chosen = None;
+        egui::CollapsingHeader::new("Built-in roms")
+            .default_open(true)
+            .show(ui, |ui| {
+                egui::Grid::new("rom_library")
+                    .num_columns(3)
+                    .show(ui, |ui| {
+                        for rom in crate::library::LIBRARY {
+                            if ui.button(rom.name).clicked() {
+                                chosen = Some(rom);
+                            }
+                            ui.label(rom.backend);
+                            ui.label(rom.description);
+                            ui.end_row();
+                        }
+                    });
+            });
+        if let Some(rom) = chosen {
+            self.rom_name = Some(rom.name.to_string());
+            self.launch(rom.backend, rom.data.to_vec(), command_sender);
+        }
+    }
+
+    fn pick_self_test_rom(&mut self, command_sender: &CommandSender) {
+        #[cfg(target_arch = "wasm32")]
+        {
+            let sender = command_sender.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                if let Some(handle) = rfd::AsyncFileDialog::new().pick_file().await {
+                    let data = handle.read().await;
+                    sender.send(AppCommand::RunSelfTest(data));
+                }
+            });
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let _ = command_sender;
+            if let Some(path) = rfd::FileDialog::new().pick_file() {
+                match std::fs::read(path) {
+                    Ok(data) => self.run_self_test(&data),
+                    Err(error) => log::error!("unable to read test rom: {}", error),
+                }
+            }
+        }
+    }
+
+    fn draw_options(&mut self, command_sender: &CommandSender, ui: &mut egui::Ui) {
+        let Some(pending) = self.pending_launch.as_mut() else {
+            return;
+        };
+
+        ui.heading(format!("{} options", pending.backend_name));
+        egui::Grid::new("backend_options")
+            .num_columns(2)
+            .show(ui, |ui| {
+                let palettes = pending.schema.palettes();
+                if !palettes.is_empty() {
+                    let label = ui.label("Palette");
+                    let selected = palettes
+                        .iter()
+                        .find(|palette| palette.is_applied(&pending.values))
+                        .map_or("Custom", |palette| palette.label);
+                    egui::ComboBox::from_id_salt("backend_palette")
+                        .selected_text(selected)
+                        .show_ui(ui, |ui| {
+                            for palette in palettes {
+                                if ui
+                                    .selectable_label(selected == palette.label, palette.label)
+                                    .clicked()
+                                {
+                                    palette.apply(&mut pending.values);
+                                }
+                            }
+                        })
+                        .response
+                        .labelled_by(label.id);
+                    ui.end_row();
+                }
+                for option in pending.schema.options() {
+                    let label = ui.label(option.label);
+                    if let Some(value) = pending.values.get_mut(option.key) {
+                        draw_option_value(ui, option.key, &option.kind, value, label.id);
+                    }
+                    ui.end_row();
+                }
+            });
+
+        let mut browse = None;
+        if !pending.firmware.is_empty() {
+            ui.separator();
+            ui.heading("Firmware");
+            egui::Grid::new("backend_firmware")
+                .num_columns(3)
+                .show(ui, |ui| {
+                    for firmware in pending.firmware {
+                        ui.label(firmware.label);
+                        let status = if pending.firmware_files.contains(firmware.key) {
+                            "found"
+                        } else if pending.rejected_firmware.contains(&firmware.key) {
+                            "unknown dump"
+                        } else if firmware.required {
+                            "missing"
+                        } else {
+                            "missing, using HLE boot"
+                        };
+                        ui.label(status);
+                        if ui.button("Browse").clicked() {
+                            browse = Some(firmware.key);
+                        }
+                        ui.end_row();
+                    }
+                });
+        }
+
+        let self_test = self
+            .registry
+            .get(&pending.backend_name)
+            .ok()
+            .and_then(|descriptor| descriptor.self_test);
+        let mut run_self_test = false;
+        if let Some(self_test) = self_test {
+            ui.separator();
+            ui.heading("Self-test");
+            run_self_test = ui
+                .button(format!("Run {}", self_test.label))
+                .on_hover_text("Runs a test rom with the options above and reports the results")
+                .clicked();
+            match &pending.self_test_report {
+                Some(Ok(report)) => draw_self_test_report(ui, report),
+                Some(Err(error)) => {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+                None => {}
+            }
+        }
+
+        let analysis = self
+            .registry
+            .get(&pending.backend_name)
+            .ok()
+            .and_then(|descriptor| descriptor.options_analysis);
+        let mut analyze = false;
+        if let Some(analysis) = analysis {
+            ui.separator();
+            ui.heading("Analysis");
+            analyze = ui
+                .button(format!("Run {}", analysis.label))
+                .on_hover_text(
+                    "Tries the rom with different options and applies the ones which work best",
+                )
+                .clicked();
+            match &pending.analysis_notes {
+                Some(Ok(notes)) => {
+                    for note in notes {
+                        ui.label(note);
+                    }
+                }
+                Some(Err(error)) => {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+                None => {}
+            }
+        }
+
+        let (mut back, mut launch, mut play_demo) = (false, false, false);
+        ui.horizontal(|ui| {
+            if ui.button("Reset to defaults").clicked() {
+                pending.values = pending.schema.defaults();
+            }
+            back = ui.button("Back").clicked();
+            launch = ui
+                .add_enabled(pending.is_ready(), egui::Button::new("Launch"))
+                .clicked();
+            if pending.has_demo {
+                play_demo = ui
+                    .add_enabled(pending.is_ready(), egui::Button::new("Play demo"))
+                    .on_hover_text("Runs the rom with the input recorded for it")
+                    .clicked();
+            }
+        });
+
+        if let Some(key) = browse {
+            self.browse_firmware(key, command_sender);
+        } else if run_self_test {
+            self.pick_self_test_rom(command_sender);
+        } else if analyze {
+            self.analyze_options();
+        } else if back {
+            self.pending_launch = None;
+        } else if launch || play_demo {
+            let pending = self.pending_launch.take().unwrap();
+            self.launch_reply = Some(command_sender.request(AppCommand::InitBackendWithRom(
+                pending.backend_name,
+                pending.rom_data,
+                pending.values,
+                pending.firmware_files,
+                play_demo,
+            )));
+        }
+    }
+
+    pub fn update(&mut self, _command_sender: &CommandSender, _ctx: &egui::Context) {
+        if let Some(reply) = self.launch_reply.as_ref().and_then(PendingReply::poll) {
+            self.launch_reply = None;
+            self.launch_error = reply.err();
+        }
+    }
 
     pub fn draw(
         &mut self,
-        command_sender: &mpsc::Sender<AppCommand>,
+        command_sender: &CommandSender,
         _ctx: &egui::Context,
         ui: &mut egui::Ui,
     ) {
+        if self.pending_launch.is_some() {
+            self.draw_options(command_sender, ui);
+            return;
+        }
+        if self.pending_detection.is_some() {
+            self.draw_detection(command_sender, ui);
+            return;
+        }
+        if self.pending_integrity.is_some() {
+            self.draw_integrity(command_sender, ui);
+            return;
+        }
+        if self.pending_archive.is_some() {
+            self.draw_archive(command_sender, ui);
+            return;
+        }
+
         ui.heading("Emulator Selection");
+        if let Some(error) = &self.launch_error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+        if let Some(session) = self.last_session.as_mut() {
+            ui.horizontal(|ui| {
+                if ui
+                    .button(format!("Continue {}", session.label()))
+                    .on_hover_text("Starts the last rom again with its options")
+                    .clicked()
+                {
+                    self.launch_reply = Some(command_sender.request(AppCommand::ContinueSession));
+                }
+                if ui
+                    .checkbox(&mut session.resume, "Resume where I left off")
+                    .on_hover_text("Keeps the state of the rom when it stops")
+                    .changed()
+                {
+                    command_sender.send(AppCommand::SetSessionResume(session.resume));
+                }
+            });
+        }
         egui::ComboBox::from_label("Select emulator backend")
-            .selected_text(format!("{:?}", self.emulator_backend_selection))
+            .selected_text(self.emulator_backend_selection.clone())
             .show_ui(ui, |ui| {
-                ui.selectable_value(
-                    &mut self.emulator_backend_selection,
-                    AvailableBackends::Chip8,
-                    "Chip8",
-                );
-                ui.selectable_value(
-                    &mut self.emulator_backend_selection,
-                    AvailableBackends::SuperChip,
-                    "SuperChip",
-                );
+                for descriptor in self.registry.descriptors() {
+                    ui.selectable_value(
+                        &mut self.emulator_backend_selection,
+                        descriptor.name.to_string(),
+                        descriptor.name,
+                    );
+                }
             });
-        if ui.button("Select rom").clicked() {
+
+        #[cfg(not(target_arch = "wasm32"))]
+        ui.horizontal(|ui| {
+            ui.label("Firmware directory");
+            if ui
+                .text_edit_singleline(&mut self.firmware_directory)
+                .changed()
+            {
+                command_sender.send(AppCommand::SetFirmwareDirectory(
+                    self.firmware_directory.clone(),
+                ));
+            }
+            if ui.button("Choose").clicked() {
+                if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                    self.firmware_directory = path.to_string_lossy().to_string();
+                    command_sender.send(AppCommand::SetFirmwareDirectory(
+                        self.firmware_directory.clone(),
+                    ));
+                }
+            }
+        });
+
+        #[cfg(target_arch = "wasm32")]
+        if ui.button("Import overrides").clicked() {
+            let sender = command_sender.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                if let Some(handle) = rfd::AsyncFileDialog::new()
+                    .add_filter("JSON", &["json"])
+                    .pick_file()
+                    .await
+                {
+                    let content = String::from_utf8_lossy(&handle.read().await).to_string();
+                    sender.send(AppCommand::ImportOverrides(content));
+                }
+            });
+        }
+
+        self.draw_patch(command_sender, ui);
+
+        if ui.button("Open rom with any backend").clicked() {
+            self.pick_rom(command_sender);
+        }
+
+        #[cfg(feature = "rom-library")]
+        self.draw_library(command_sender, ui);
+
+        #[cfg(not(target_arch = "wasm32"))]
+        self.draw_browser(command_sender, ui);
+
+        let Ok(descriptor) = self.registry.get(&self.emulator_backend_selection) else {
+            return;
+        };
+
+        if descriptor.extensions.is_empty() {
+            if ui.button("Start").clicked() {
+                self.rom_name = None;
+                self.launch_reply = Some(command_sender.request(AppCommand::ConfigureBackend(
+                    self.emulator_backend_selection.clone(),
+                    vec![],
+                )));
+            }
+        } else if ui.button("Select rom").clicked() {
+            let dialog_filter_name = format!("{} roms", descriptor.name);
+            let extensions = descriptor.extensions;
             #[cfg(target_arch = "wasm32")]
             {
                 let sender = command_sender.clone();
                 let selection = self.emulator_backend_selection.clone();
                 wasm_bindgen_futures::spawn_local(async move {
-                    if let Some(handle) = rfd::AsyncFileDialog::new().pick_file().await {
-                        let rom = handle.read().await;
-                        sender
-                            .send(AppCommand::InitBackendWithRom(selection, rom))
-                            .unwrap();
+                    if let Some(handle) = rfd::AsyncFileDialog::new()
+                        .add_filter(dialog_filter_name, extensions)
+                        .add_filter("Archives", ARCHIVE_EXTENSIONS)
+                        .add_filter("All files", &["*"])
+                        .pick_file()
+                        .await
+                    {
+                        let data = handle.read().await;
+                        let rom = Rom::new(Some(handle.file_name()), data);
+                        sender.send(AppCommand::OpenRomWithBackend(selection, rom));
                     }
                 });
             }
             #[cfg(not(target_arch = "wasm32"))]
             {
-                if let Some(path) = rfd::FileDialog::new().pick_file() {
-                    let rom = std::fs::read(path).expect("unable to read rom");
-                    command_sender
-                        .send(AppCommand::InitBackendWithRom(
-                            self.emulator_backend_selection,
-                            rom,
-                        ))
-                        .unwrap();
+                if let Some(path) = rfd::FileDialog::new()
+                    .add_filter(dialog_filter_name, extensions)
+                    .add_filter("Archives", ARCHIVE_EXTENSIONS)
+                    .add_filter("All files", &["*"])
+                    .pick_file()
+                {
+                    match read_rom_file(&path) {
+                        Ok(rom) => {
+                            let backend_name = self.emulator_backend_selection.clone();
+                            self.open_rom_with(Some(&backend_name), rom, command_sender);
+                        }
+                        Err(error) => {
+                            self.launch_error = Some(format!("unable to read rom: {}", error))
+                        }
+                    }
                 }
             }
         }
     }
 }
+
+// Reads a rom together with a patch of the same name next to it, e.g. game.ips for game.ch8
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_rom_file(path: &std::path::Path) -> std::io::Result<Rom> {
+    let data = std::fs::read(path)?;
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string());
+    let patch = PATCH_EXTENSIONS.iter().find_map(|extension| {
+        let patch_path = path.with_extension(extension);
+        let data = std::fs::read(&patch_path).ok()?;
+        let file_name = patch_path.file_name()?.to_string_lossy().to_string();
+        Some(RomPatch::new(file_name, data))
+    });
+    Ok(Rom::new(file_name, data).with_patch(patch))
+}
+
+// Files named like the firmware are checked first, any other file in the directory is only taken
+// if it matches one of the known hashes
+#[cfg(not(target_arch = "wasm32"))]
+fn locate_firmware(directory: &str, descriptor: &FirmwareDescriptor) -> Option<Vec<u8>> {
+    if directory.is_empty() {
+        return None;
+    }
+    let mut candidates: Vec<(bool, std::path::PathBuf)> = std::fs::read_dir(directory)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .map(|path| {
+            let named = path.file_name().is_some_and(|file_name| {
+                descriptor
+                    .file_names
+                    .iter()
+                    .any(|name| file_name.eq_ignore_ascii_case(name))
+            });
+            (named, path)
+        })
+        .filter(|(named, _)| *named || !descriptor.hashes.is_empty())
+        .collect();
+    candidates.sort_by_key(|(named, _)| !named);
+    candidates
+        .into_iter()
+        .filter_map(|(_, path)| std::fs::read(path).ok())
+        .find(|data| descriptor.verify(data))
+}
+
+fn draw_self_test_report(ui: &mut egui::Ui, report: &SelfTestReport) {
+    egui::Grid::new("self_test_report")
+        .num_columns(2)
+        .striped(true)
+        .show(ui, |ui| {
+            for result in report {
+                ui.label(&result.name);
+                match result.verdict {
+                    SelfTestVerdict::Pass => ui.label("pass"),
+                    SelfTestVerdict::Fail => ui.colored_label(ui.visuals().error_fg_color, "fail"),
+                    SelfTestVerdict::Unknown => ui
+                        .colored_label(ui.visuals().warn_fg_color, "unknown")
+                        .on_hover_text("The result could not be read from the screen"),
+                };
+                ui.end_row();
+            }
+        });
+}
+
+#[cfg(target_arch = "wasm32")]
+fn locate_firmware(_directory: &str, _descriptor: &FirmwareDescriptor) -> Option<Vec<u8>> {
+    None
+}
+
+// The label is what screen readers announce for the widget
+fn draw_option_value(
+    ui: &mut egui::Ui,
+    key: &str,
+    kind: &OptionKind,
+    value: &mut OptionValue,
+    label: egui::Id,
+) {
+    match (kind, value) {
+        (OptionKind::Bool, OptionValue::Bool(value)) => {
+            ui.checkbox(value, "").labelled_by(label);
+        }
+        (OptionKind::Int { min, max }, OptionValue::Int(value)) => {
+            ui.add(egui::DragValue::new(value).range(*min..=*max))
+                .labelled_by(label);
+        }
+        (OptionKind::Enum { variants }, OptionValue::Enum(value)) => {
+            egui::ComboBox::from_id_salt(key)
+                .selected_text(value.clone())
+                .show_ui(ui, |ui| {
+                    for variant in variants {
+                        ui.selectable_value(value, variant.to_string(), *variant);
+                    }
+                })
+                .response
+                .labelled_by(label);
+        }
+        (OptionKind::Color, OptionValue::Color(value)) => {
+            let mut rgb = [value.0, value.1, value.2];
+            if ui
+                .color_edit_button_srgb(&mut rgb)
+                .labelled_by(label)
+                .changed()
+            {
+                *value = (rgb[0], rgb[1], rgb[2], value.3);
+            }
+        }
+        (OptionKind::DipSwitches { settings }, OptionValue::Int(value)) => {
+            draw_dip_switches(ui, key, settings, value);
+        }
+        _ => {
+            ui.label("invalid value");
+        }
+    }
+}
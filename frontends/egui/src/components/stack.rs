@@ -0,0 +1,129 @@
+use axwemulator_core::backend::component::{MemoryAddress, StackFrame};
+use egui::RichText;
+
+use crate::{app::AppCommand, command::CommandSender};
+
+use super::Component;
+
+#[derive(Default)]
+pub struct StackComponent {
+    selected_component: String,
+}
+
+// Return addresses are shown relative to the start of the subroutine they are in
+fn symbolize(function: Option<MemoryAddress>, address: MemoryAddress) -> String {
+    match function {
+        Some(function) if address >= function => {
+            format!("sub_{:04X}+{:#X}", function, address - function)
+        }
+        _ => format!("{:#06X}", address),
+    }
+}
+
+impl StackComponent {
+    pub fn new() -> Self {
+        Self {
+            selected_component: "".to_string(),
+        }
+    }
+
+    fn draw_frames(
+        &mut self,
+        command_sender: &CommandSender,
+        ui: &mut egui::Ui,
+        frames: &[StackFrame],
+    ) {
+        if frames.is_empty() {
+            ui.label("No subroutine is running");
+            return;
+        }
+        egui::Grid::new("stack_frames")
+            .num_columns(3)
+            .striped(true)
+            .show(ui, |ui| {
+                for (depth, frame) in frames.iter().enumerate() {
+                    // The caller of this frame is the subroutine of the next outer one
+                    let caller = frames.get(depth + 1).and_then(|outer| outer.function);
+                    ui.label(RichText::new(format!("#{}", depth)).monospace());
+                    let function = frame
+                        .function
+                        .map_or("?".to_string(), |function| format!("sub_{:04X}", function));
+                    if ui
+                        .link(RichText::new(function).monospace())
+                        .on_hover_text("Show the subroutine in the memory view")
+                        .clicked()
+                    {
+                        if let Some(function) = frame.function {
+                            command_sender.send(AppCommand::ShowMemory(function));
+                        }
+                    }
+                    if ui
+                        .link(
+                            RichText::new(format!(
+                                "returns to {}",
+                                symbolize(caller, frame.return_address)
+                            ))
+                            .monospace(),
+                        )
+                        .on_hover_text(format!("{:#06X}", frame.return_address))
+                        .clicked()
+                    {
+                        command_sender.send(AppCommand::ShowMemory(frame.return_address));
+                    }
+                    ui.end_row();
+                }
+            });
+    }
+}
+
+impl Component for StackComponent {
+    fn update(
+        &mut self,
+        _emulator: &super::emulator::EmulatorComponent,
+        _command_sender: &CommandSender,
+        _ctx: &egui::Context,
+    ) {
+    }
+
+    fn draw(
+        &mut self,
+        emulator: &super::emulator::EmulatorComponent,
+        command_sender: &CommandSender,
+        _ctx: &egui::Context,
+        ui: &mut egui::Ui,
+    ) {
+        let backend = emulator.get_backend();
+        let mut debuggable: Vec<String> = backend
+            .get_all_components()
+            .iter()
+            .filter(|(_, component)| component.borrow_mut().as_debuggable().is_some())
+            .map(|(name, _)| name.clone())
+            .collect();
+        debuggable.sort();
+        if !debuggable.contains(&self.selected_component) {
+            match debuggable.first() {
+                Some(name) => self.selected_component = name.clone(),
+                None => {
+                    ui.label("No component of this backend has a call stack");
+                    return;
+                }
+            }
+        }
+        egui::ComboBox::from_label("Stack")
+            .selected_text(self.selected_component.to_string())
+            .show_ui(ui, |ui| {
+                for name in debuggable.iter() {
+                    ui.selectable_value(&mut self.selected_component, name.clone(), name);
+                }
+            });
+
+        let Ok(component) = backend.get_component(&self.selected_component) else {
+            return;
+        };
+        let frames = component
+            .borrow_mut()
+            .as_debuggable()
+            .map_or(vec![], |debuggable| debuggable.stack_frames());
+        self.draw_frames(command_sender, ui, &frames);
+    }
+}
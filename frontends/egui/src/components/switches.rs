@@ -0,0 +1,121 @@
+use axwemulator_core::backend::options::{
+    DipSetting, OptionDescriptor, OptionKind, OptionValue, OptionValues,
+};
+
+use crate::{app::AppCommand, command::CommandSender};
+
+use super::Component;
+
+// The settings of a bank as combo boxes, followed by the eight switches themselves. Returns
+// whether the bank changed.
+pub fn draw_dip_switches(
+    ui: &mut egui::Ui,
+    key: &str,
+    settings: &[DipSetting],
+    value: &mut i64,
+) -> bool {
+    let mut bank = *value as u8;
+    ui.vertical(|ui| {
+        for setting in settings {
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_salt((key, setting.label))
+                    .selected_text(setting.selected(bank).unwrap_or("custom"))
+                    .show_ui(ui, |ui| {
+                        for (label, choice) in setting.choices.iter() {
+                            if ui
+                                .selectable_label(setting.selected(bank) == Some(*label), *label)
+                                .clicked()
+                            {
+                                bank = setting.select(bank, *choice);
+                            }
+                        }
+                    });
+                ui.label(setting.label);
+            });
+        }
+        ui.horizontal(|ui| {
+            for switch in 0..8 {
+                let mut on = bank & (1 << switch) != 0;
+                if ui
+                    .toggle_value(&mut on, format!("{}", switch + 1))
+                    .on_hover_text(format!("Switch {}", switch + 1))
+                    .changed()
+                {
+                    bank ^= 1 << switch;
+                }
+            }
+        });
+    });
+    let changed = bank as i64 != *value;
+    *value = bank as i64;
+    changed
+}
+
+// The DIP switches of the running rom. Like on the real board they are only read when the machine
+// starts, so changes are stored with the rom options and apply the next time it is started.
+pub struct SwitchesComponent {
+    options: Vec<OptionDescriptor>,
+    values: OptionValues,
+    changed: bool,
+}
+
+impl SwitchesComponent {
+    // None if the backend has no switches
+    pub fn new(options: &[OptionDescriptor], values: OptionValues) -> Option<Self> {
+        let options: Vec<OptionDescriptor> = options
+            .iter()
+            .filter(|option| matches!(option.kind, OptionKind::DipSwitches { .. }))
+            .cloned()
+            .collect();
+        if options.is_empty() {
+            return None;
+        }
+        Some(Self {
+            options,
+            values,
+            changed: false,
+        })
+    }
+}
+
+impl Component for SwitchesComponent {
+    fn update(
+        &mut self,
+        _emulator: &super::emulator::EmulatorComponent,
+        _command_sender: &CommandSender,
+        _ctx: &egui::Context,
+    ) {
+    }
+
+    fn draw(
+        &mut self,
+        _emulator: &super::emulator::EmulatorComponent,
+        command_sender: &CommandSender,
+        _ctx: &egui::Context,
+        ui: &mut egui::Ui,
+    ) {
+        for option in self.options.iter() {
+            let OptionKind::DipSwitches { settings } = &option.kind else {
+                continue;
+            };
+            // The values were resolved against the schema when the rom started
+            let mut bank = self.values.get_int(option.key).unwrap_or_default();
+            ui.label(option.label);
+            if draw_dip_switches(ui, option.key, settings, &mut bank) {
+                self.values.set(option.key, OptionValue::Int(bank));
+                command_sender.send(AppCommand::SetRomOption(
+                    option.key.to_string(),
+                    OptionValue::Int(bank),
+                ));
+                self.changed = true;
+            }
+            ui.separator();
+        }
+        if self.changed {
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                "Takes effect when the rom is started again",
+            );
+        }
+    }
+}
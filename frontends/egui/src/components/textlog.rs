@@ -0,0 +1,70 @@
+use std::collections::VecDeque;
+
+use axwemulator_core::frontend::text::TextReceiver;
+use egui::{RichText, ScrollArea, TextStyle};
+
+use crate::command::CommandSender;
+
+use super::Component;
+
+const MAX_LOG_LINES: usize = 10_000;
+
+// Lines the backend printed, e.g. by homebrew roms through the debug port, newest last
+pub struct TextLogComponent {
+    receiver: TextReceiver,
+    lines: VecDeque<String>,
+}
+
+impl TextLogComponent {
+    pub fn new(receiver: TextReceiver) -> Self {
+        Self {
+            receiver,
+            lines: VecDeque::new(),
+        }
+    }
+}
+
+impl Component for TextLogComponent {
+    fn update(
+        &mut self,
+        _emulator: &super::emulator::EmulatorComponent,
+        _command_sender: &CommandSender,
+        _ctx: &egui::Context,
+    ) {
+        while let Some((_, line)) = self.receiver.pop() {
+            if self.lines.len() == MAX_LOG_LINES {
+                self.lines.pop_front();
+            }
+            self.lines.push_back(line);
+        }
+    }
+
+    fn draw(
+        &mut self,
+        _emulator: &super::emulator::EmulatorComponent,
+        _command_sender: &CommandSender,
+        _ctx: &egui::Context,
+        ui: &mut egui::Ui,
+    ) {
+        ui.horizontal(|ui| {
+            if ui.small_button("Clear").clicked() {
+                self.lines.clear();
+            }
+            if ui.small_button("Copy").clicked() {
+                ui.ctx()
+                    .copy_text(self.lines.iter().cloned().collect::<Vec<_>>().join("\n"));
+            }
+        });
+        let row_height = ui.text_style_height(&TextStyle::Monospace);
+        ScrollArea::both().stick_to_bottom(true).show_rows(
+            ui,
+            row_height,
+            self.lines.len(),
+            |ui, rows| {
+                for line in self.lines.range(rows) {
+                    ui.label(RichText::new(line).monospace());
+                }
+            },
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use std::collections::VecDeque;
+
+use axwemulator_core::frontend::trace::TraceReceiver;
+use egui::{RichText, ScrollArea, TextStyle};
+
+use crate::command::CommandSender;
+
+use super::Component;
+
+const MAX_LOG_LINES: usize = 10_000;
+
+// Instructions the cpu executed while recording, newest last
+pub struct TraceLogComponent {
+    receiver: TraceReceiver,
+    lines: VecDeque<String>,
+}
+
+impl TraceLogComponent {
+    pub fn new(receiver: TraceReceiver) -> Self {
+        Self {
+            receiver,
+            lines: VecDeque::with_capacity(MAX_LOG_LINES),
+        }
+    }
+}
+
+impl Component for TraceLogComponent {
+    fn update(
+        &mut self,
+        _emulator: &super::emulator::EmulatorComponent,
+        _command_sender: &CommandSender,
+        _ctx: &egui::Context,
+    ) {
+        for (_, entry) in self.receiver.pop_all() {
+            if self.lines.len() == MAX_LOG_LINES {
+                self.lines.pop_front();
+            }
+            self.lines.push_back(entry.to_string());
+        }
+    }
+
+    fn draw(
+        &mut self,
+        _emulator: &super::emulator::EmulatorComponent,
+        _command_sender: &CommandSender,
+        _ctx: &egui::Context,
+        ui: &mut egui::Ui,
+    ) {
+        ui.horizontal(|ui| {
+            let mut recording = self.receiver.is_enabled();
+            if ui
+                .checkbox(&mut recording, "Record")
+                .on_hover_text("Recording every instruction slows down the emulation")
+                .changed()
+            {
+                self.receiver.set_enabled(recording);
+            }
+            if ui.small_button("Clear").clicked() {
+                self.lines.clear();
+            }
+            if ui.small_button("Copy").clicked() {
+                ui.ctx()
+                    .copy_text(self.lines.iter().cloned().collect::<Vec<_>>().join("\n"));
+            }
+        });
+        ui.label(format!("{} instructions", self.lines.len()));
+        let row_height = ui.text_style_height(&TextStyle::Monospace);
+        ScrollArea::both().stick_to_bottom(true).show_rows(
+            ui,
+            row_height,
+            self.lines.len(),
+            |ui, rows| {
+                for line in self.lines.range(rows) {
+                    ui.label(RichText::new(line).monospace());
+                }
+            },
+        );
+    }
+}
@@ -0,0 +1,404 @@
+use std::collections::HashMap;
+
+use axwemulator_core::{
+    backend::trigger::{TriggerCondition, TriggerEvent, TriggerId, ValueCondition, Watchpoint},
+    frontend::{graphics::Frame, storage::StorageProvider},
+};
+use egui::RichText;
+use femtos::{Duration, Instant};
+
+use crate::{app::AppCommand, command::CommandSender};
+
+use super::{Component, emulator::REPLAY_FRAMES};
+
+const MAX_CAPTURES: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TriggerKind {
+    Clock,
+    Write,
+    Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Any,
+    Equal,
+    NotEqual,
+    Less,
+    Greater,
+}
+
+impl Comparison {
+    const ALL: [Comparison; 5] = [
+        Comparison::Any,
+        Comparison::Equal,
+        Comparison::NotEqual,
+        Comparison::Less,
+        Comparison::Greater,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            Comparison::Any => "any value",
+            Comparison::Equal => "==",
+            Comparison::NotEqual => "!=",
+            Comparison::Less => "<",
+            Comparison::Greater => ">",
+        }
+    }
+}
+
+// What happens when a trigger fires, next to stopping the run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerAction {
+    Screenshot,
+    // Into the trigger slot of the savestates, where it can be loaded from
+    SaveState,
+    ScreenshotAndSaveState,
+}
+
+impl TriggerAction {
+    const ALL: [TriggerAction; 3] = [
+        TriggerAction::Screenshot,
+        TriggerAction::SaveState,
+        TriggerAction::ScreenshotAndSaveState,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            TriggerAction::Screenshot => "screenshot",
+            TriggerAction::SaveState => "save state",
+            TriggerAction::ScreenshotAndSaveState => "screenshot and save state",
+        }
+    }
+
+    pub fn takes_screenshot(&self) -> bool {
+        *self != TriggerAction::SaveState
+    }
+
+    pub fn saves_state(&self) -> bool {
+        *self != TriggerAction::Screenshot
+    }
+}
+
+struct Capture {
+    event: TriggerEvent,
+    action: TriggerAction,
+    frame: Option<Frame>,
+    saved_to: Option<String>,
+    state_saved: bool,
+}
+
+pub struct TriggersComponent {
+    kind: TriggerKind,
+    clock_secs: f64,
+    address: String,
+    value: u8,
+    write_size: usize,
+    write_comparison: Comparison,
+    write_value: u64,
+    action: TriggerAction,
+    // Triggers restored from the debugger layout have no entry and take screenshots
+    actions: HashMap<TriggerId, TriggerAction>,
+    captures: Vec<Capture>,
+}
+
+impl TriggersComponent {
+    pub fn new() -> Self {
+        Self {
+            kind: TriggerKind::Clock,
+            clock_secs: 1.0,
+            address: "0x200".to_string(),
+            value: 0,
+            write_size: 1,
+            write_comparison: Comparison::Any,
+            write_value: 0,
+            action: TriggerAction::Screenshot,
+            actions: HashMap::new(),
+            captures: vec![],
+        }
+    }
+
+    pub fn set_action(&mut self, id: TriggerId, action: TriggerAction) {
+        self.actions.insert(id, action);
+    }
+
+    pub fn action(&self, id: TriggerId) -> TriggerAction {
+        self.actions
+            .get(&id)
+            .copied()
+            .unwrap_or(TriggerAction::Screenshot)
+    }
+
+    // The state is saved by the caller, which has access to the savestates
+    pub fn capture(
+        &mut self,
+        storage: &mut dyn StorageProvider,
+        backend_name: &str,
+        event: TriggerEvent,
+        frame: Option<&Frame>,
+        state_saved: bool,
+        command_sender: &CommandSender,
+    ) {
+        let action = self.action(event.id);
+        let frame = frame.filter(|_| action.takes_screenshot());
+        let saved_to =
+            frame.and_then(|frame| save_screenshot(storage, backend_name, &event, frame));
+        let message = match (saved_to.is_some(), state_saved) {
+            (true, true) => Some("Screenshot and state saved"),
+            (true, false) => Some("Screenshot saved"),
+            (false, true) => Some("State saved to the trigger slot"),
+            (false, false) => None,
+        };
+        if let Some(message) = message {
+            command_sender.send(AppCommand::ShowMessage(message.to_string()));
+        }
+        self.captures.push(Capture {
+            event,
+            action,
+            frame: frame.cloned(),
+            saved_to,
+            state_saved,
+        });
+        if self.captures.len() > MAX_CAPTURES {
+            self.captures.remove(0);
+        }
+    }
+
+    fn condition(&self) -> Option<TriggerCondition> {
+        let address =
+            || usize::from_str_radix(self.address.trim().trim_start_matches("0x"), 16).ok();
+        match self.kind {
+            TriggerKind::Clock => Some(TriggerCondition::Clock(
+                Instant::START + Duration::from_nanos((self.clock_secs * 1e9) as u64),
+            )),
+            TriggerKind::Write => address().map(|a| {
+                let watchpoint = Watchpoint::new(a).with_size(self.write_size);
+                let value = match self.write_comparison {
+                    Comparison::Any => None,
+                    Comparison::Equal => Some(ValueCondition::Equal(self.write_value)),
+                    Comparison::NotEqual => Some(ValueCondition::NotEqual(self.write_value)),
+                    Comparison::Less => Some(ValueCondition::Less(self.write_value)),
+                    Comparison::Greater => Some(ValueCondition::Greater(self.write_value)),
+                };
+                TriggerCondition::Write(match value {
+                    Some(value) => watchpoint.with_value(value),
+                    None => watchpoint,
+                })
+            }),
+            TriggerKind::Value => address().map(|a| TriggerCondition::Value(a, self.value)),
+        }
+    }
+}
+
+impl Default for TriggersComponent {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn describe(condition: &TriggerCondition) -> String {
+    match condition {
+        TriggerCondition::Clock(instant) => {
+            format!("at {:.6}s", instant.as_duration().as_nanos() as f64 / 1e9)
+        }
+        TriggerCondition::Write(watchpoint) => {
+            let mut text = format!("write to {:#06x}", watchpoint.address);
+            if watchpoint.size > 1 {
+                text.push_str(&format!(" ({} bytes)", watchpoint.size));
+            }
+            match watchpoint.value {
+                Some(ValueCondition::Equal(value)) => text.push_str(&format!(" == {:#x}", value)),
+                Some(ValueCondition::NotEqual(value)) => {
+                    text.push_str(&format!(" != {:#x}", value))
+                }
+                Some(ValueCondition::Less(value)) => text.push_str(&format!(" < {:#x}", value)),
+                Some(ValueCondition::Greater(value)) => text.push_str(&format!(" > {:#x}", value)),
+                None => {}
+            }
+            text
+        }
+        TriggerCondition::Value(address, value) => {
+            format!("{:#06x} becomes {:#04x}", address, value)
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn save_screenshot(
+    storage: &mut dyn StorageProvider,
+    backend_name: &str,
+    event: &TriggerEvent,
+    frame: &Frame,
+) -> Option<String> {
+    let key = format!(
+        "screenshots/{}-{}.png",
+        backend_name.to_lowercase(),
+        event.clock.as_duration().as_nanos()
+    );
+    let result = axwemulator_core::frontend::screenshot::encode_png(frame)
+        .and_then(|data| storage.write(&key, &data));
+    match result {
+        Ok(()) => Some(storage.location(&key)),
+        Err(error) => {
+            log::error!("unable to save screenshot: {}", error);
+            None
+        }
+    }
+}
+
+// Browsers have no place to put the files unattended, the captures are only kept in memory
+#[cfg(target_arch = "wasm32")]
+fn save_screenshot(
+    _storage: &mut dyn StorageProvider,
+    _backend_name: &str,
+    _event: &TriggerEvent,
+    _frame: &Frame,
+) -> Option<String> {
+    None
+}
+
+impl Component for TriggersComponent {
+    fn update(
+        &mut self,
+        _emulator: &super::emulator::EmulatorComponent,
+        _command_sender: &CommandSender,
+        _ctx: &egui::Context,
+    ) {
+    }
+
+    fn draw(
+        &mut self,
+        emulator: &super::emulator::EmulatorComponent,
+        command_sender: &CommandSender,
+        _ctx: &egui::Context,
+        ui: &mut egui::Ui,
+    ) {
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt("trigger_kind")
+                .selected_text(format!("{:?}", self.kind))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.kind, TriggerKind::Clock, "Clock");
+                    ui.selectable_value(&mut self.kind, TriggerKind::Write, "Write");
+                    ui.selectable_value(&mut self.kind, TriggerKind::Value, "Value");
+                });
+            match self.kind {
+                TriggerKind::Clock => {
+                    ui.add(
+                        egui::DragValue::new(&mut self.clock_secs)
+                            .range(0.0..=f64::MAX)
+                            .speed(0.01)
+                            .suffix("s"),
+                    );
+                }
+                TriggerKind::Write => {
+                    ui.add(egui::TextEdit::singleline(&mut self.address).desired_width(60.0));
+                    egui::ComboBox::from_id_salt("trigger_write_size")
+                        .selected_text(format!("{} bytes", self.write_size))
+                        .width(70.0)
+                        .show_ui(ui, |ui| {
+                            for size in [1, 2, 4, 8] {
+                                ui.selectable_value(
+                                    &mut self.write_size,
+                                    size,
+                                    format!("{} bytes", size),
+                                );
+                            }
+                        });
+                    egui::ComboBox::from_id_salt("trigger_write_comparison")
+                        .selected_text(self.write_comparison.label())
+                        .width(70.0)
+                        .show_ui(ui, |ui| {
+                            for comparison in Comparison::ALL {
+                                ui.selectable_value(
+                                    &mut self.write_comparison,
+                                    comparison,
+                                    comparison.label(),
+                                );
+                            }
+                        });
+                    if self.write_comparison != Comparison::Any {
+                        ui.add(egui::DragValue::new(&mut self.write_value).hexadecimal(
+                            self.write_size * 2,
+                            false,
+                            true,
+                        ));
+                    }
+                }
+                TriggerKind::Value => {
+                    ui.add(egui::TextEdit::singleline(&mut self.address).desired_width(60.0));
+                    ui.add(egui::DragValue::new(&mut self.value).hexadecimal(2, false, true));
+                }
+            }
+            egui::ComboBox::from_id_salt("trigger_action")
+                .selected_text(self.action.label())
+                .show_ui(ui, |ui| {
+                    for action in TriggerAction::ALL {
+                        ui.selectable_value(&mut self.action, action, action.label());
+                    }
+                });
+            let condition = self.condition();
+            if ui
+                .add_enabled(condition.is_some(), egui::Button::new("Add"))
+                .clicked()
+            {
+                command_sender.send(AppCommand::AddTrigger(condition.unwrap(), self.action));
+            }
+        });
+
+        ui.separator();
+        for trigger in emulator.get_backend().get_triggers() {
+            ui.horizontal(|ui| {
+                ui.label(format!(
+                    "{}: {}",
+                    describe(&trigger.condition),
+                    self.action(trigger.id).label()
+                ));
+                if ui.small_button("Remove").clicked() {
+                    command_sender.send(AppCommand::RemoveTrigger(trigger.id));
+                }
+            });
+        }
+
+        ui.separator();
+        ui.label(format!("Captures ({})", self.captures.len()));
+        for capture in self.captures.iter().rev() {
+            let clock = capture.event.clock.as_duration().as_nanos() as f64 / 1e9;
+            let mut results = vec![];
+            if capture.action.takes_screenshot() {
+                results.push(match (&capture.frame, &capture.saved_to) {
+                    (None, _) => "no frame".to_string(),
+                    (Some(_), Some(path)) => path.clone(),
+                    (Some(_), None) => "kept in memory".to_string(),
+                });
+            }
+            if capture.action.saves_state() {
+                results.push(if capture.state_saved {
+                    "state in the trigger slot".to_string()
+                } else {
+                    "state not saved".to_string()
+                });
+            }
+            let text = format!(
+                "{:.6}s {}: {}",
+                clock,
+                describe(&capture.event.condition),
+                results.join(", ")
+            );
+            ui.horizontal(|ui| {
+                ui.label(RichText::new(text).monospace());
+                if ui
+                    .small_button("Trace")
+                    .on_hover_text(format!(
+                        "Rewind {} frames before the capture and trace up to it",
+                        REPLAY_FRAMES
+                    ))
+                    .clicked()
+                {
+                    command_sender.send(AppCommand::TraceBefore(None, capture.event.clock));
+                }
+            });
+        }
+    }
+}
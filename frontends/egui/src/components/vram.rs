@@ -0,0 +1,222 @@
+use std::ops::Deref;
+
+use axwemulator_core::backend::component::{Addressable, MemoryAddress};
+use egui::{Color32, ColorImage, TextureHandle, TextureOptions, Vec2};
+
+use crate::command::CommandSender;
+
+use super::Component;
+
+const BITS_PER_PIXEL: [usize; 4] = [1, 2, 4, 8];
+// Custom palettes are only offered up to 4 bpp, larger ones are shown in grayscale
+const MAX_CUSTOM_COLORS: usize = 16;
+const MAX_DIMENSION: usize = 1024;
+
+// Shows a range of memory as a bitmap, for looking at video memory of backends without any
+// structured graphics inspection. Pixels are packed with the most significant bits first.
+pub struct VramComponent {
+    selected_component: Option<String>,
+    address_text: String,
+    address: MemoryAddress,
+    width: usize,
+    height: usize,
+    bits_per_pixel: usize,
+    custom_palette: Option<Vec<Color32>>,
+    texture: Option<TextureHandle>,
+}
+
+impl Default for VramComponent {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VramComponent {
+    pub fn new() -> Self {
+        Self {
+            selected_component: None,
+            address_text: "0x0000".to_string(),
+            address: 0,
+            width: 64,
+            height: 32,
+            bits_per_pixel: 1,
+            custom_palette: None,
+            texture: None,
+        }
+    }
+
+    fn byte_count(&self) -> usize {
+        (self.width * self.height * self.bits_per_pixel).div_ceil(8)
+    }
+
+    fn read_memory<T>(&self, addressable: &T) -> Vec<u8>
+    where
+        T: Addressable + ?Sized,
+    {
+        let mut data = vec![0; self.byte_count()];
+        if addressable.peek(self.address, &mut data).is_err() {
+            // The range crosses unmapped memory, which is shown as zero
+            for (offset, byte) in data.iter_mut().enumerate() {
+                let mut buffer = [0];
+                if addressable.peek(self.address + offset, &mut buffer).is_ok() {
+                    *byte = buffer[0];
+                }
+            }
+        }
+        data
+    }
+
+    fn palette(&self) -> Vec<Color32> {
+        let colors = 1usize << self.bits_per_pixel;
+        match &self.custom_palette {
+            Some(palette) if colors <= MAX_CUSTOM_COLORS => palette[..colors].to_vec(),
+            _ => grayscale(colors),
+        }
+    }
+
+    fn draw_settings(&mut self, emulator: &super::emulator::EmulatorComponent, ui: &mut egui::Ui) {
+        egui::ComboBox::from_label("Source")
+            .selected_text(
+                self.selected_component
+                    .clone()
+                    .unwrap_or(String::from("Bus")),
+            )
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut self.selected_component, None, "Bus");
+                for (name, component) in emulator.get_backend().get_all_components() {
+                    if component.borrow_mut().as_addressable().is_some() {
+                        ui.selectable_value(&mut self.selected_component, Some(name.clone()), name);
+                    }
+                }
+            });
+
+        egui::Grid::new("vram_settings")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Address");
+                if ui.text_edit_singleline(&mut self.address_text).changed() {
+                    let text = self.address_text.trim().trim_start_matches("0x");
+                    if let Ok(address) = usize::from_str_radix(text, 16) {
+                        self.address = address;
+                    }
+                }
+                ui.end_row();
+                ui.label("Width");
+                ui.add(egui::DragValue::new(&mut self.width).range(1..=MAX_DIMENSION));
+                ui.end_row();
+                ui.label("Height");
+                ui.add(egui::DragValue::new(&mut self.height).range(1..=MAX_DIMENSION));
+                ui.end_row();
+                ui.label("Bits per pixel");
+                ui.horizontal(|ui| {
+                    for bits in BITS_PER_PIXEL {
+                        ui.selectable_value(&mut self.bits_per_pixel, bits, bits.to_string());
+                    }
+                });
+                ui.end_row();
+            });
+
+        let colors = 1usize << self.bits_per_pixel;
+        let mut custom = self.custom_palette.is_some();
+        if ui.checkbox(&mut custom, "Custom palette").changed() {
+            // Starts from the colors currently shown
+            self.custom_palette = custom.then(|| {
+                let mut palette = grayscale(colors.min(MAX_CUSTOM_COLORS));
+                palette.resize(MAX_CUSTOM_COLORS, Color32::WHITE);
+                palette
+            });
+        }
+        if let Some(palette) = self.custom_palette.as_mut() {
+            if colors > MAX_CUSTOM_COLORS {
+                ui.label("Shown in grayscale above 4 bits per pixel");
+            } else {
+                ui.horizontal_wrapped(|ui| {
+                    for color in palette.iter_mut().take(colors) {
+                        ui.color_edit_button_srgba(color);
+                    }
+                });
+            }
+        }
+        ui.label(format!(
+            "{:#06X} - {:#06X}",
+            self.address,
+            self.address + self.byte_count() - 1
+        ));
+    }
+}
+
+fn grayscale(colors: usize) -> Vec<Color32> {
+    (0..colors)
+        .map(|value| Color32::from_gray((value * 255 / (colors - 1)) as u8))
+        .collect()
+}
+
+fn decode_bitmap(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    bits_per_pixel: usize,
+    palette: &[Color32],
+) -> ColorImage {
+    let mask = (1usize << bits_per_pixel) - 1;
+    let mut image = ColorImage::new([width, height], Color32::BLACK);
+    for (index, pixel) in image.pixels.iter_mut().enumerate() {
+        let bit = index * bits_per_pixel;
+        let Some(byte) = data.get(bit / 8) else {
+            break;
+        };
+        let shift = 8 - bits_per_pixel - bit % 8;
+        *pixel = palette[(*byte as usize >> shift) & mask];
+    }
+    image
+}
+
+impl Component for VramComponent {
+    fn update(
+        &mut self,
+        emulator: &super::emulator::EmulatorComponent,
+        _command_sender: &CommandSender,
+        ctx: &egui::Context,
+    ) {
+        let data = match &self.selected_component {
+            None => self.read_memory(emulator.get_backend().get_bus().deref()),
+            Some(name) => {
+                let Ok(component) = emulator.get_backend().get_component(name) else {
+                    return;
+                };
+                let mut component = component.borrow_mut();
+                let Some(addressable) = component.as_addressable() else {
+                    return;
+                };
+                self.read_memory(addressable)
+            }
+        };
+        let image = decode_bitmap(
+            &data,
+            self.width,
+            self.height,
+            self.bits_per_pixel,
+            &self.palette(),
+        );
+        self.texture = Some(ctx.load_texture("vram", image, TextureOptions::NEAREST));
+    }
+
+    fn draw(
+        &mut self,
+        emulator: &super::emulator::EmulatorComponent,
+        _command_sender: &CommandSender,
+        _ctx: &egui::Context,
+        ui: &mut egui::Ui,
+    ) {
+        self.draw_settings(emulator, ui);
+        ui.separator();
+        if let Some(texture) = self.texture.as_ref() {
+            let width = ui.available_width();
+            let size = texture.size_vec2();
+            ui.add(
+                egui::Image::new(texture)
+                    .fit_to_exact_size(Vec2::new(width, width * size.y / size.x)),
+            );
+        }
+    }
+}
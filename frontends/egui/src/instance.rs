@@ -0,0 +1,272 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+use interprocess::local_socket::{ListenerOptions, Name, prelude::*};
+
+use crate::{app::AppCommand, command::CommandSender, components::selection::read_rom_file};
+
+// Roms opened while an instance is running are handed over to it through a local socket of the
+// user, so opening files from the file manager does not start a new window each time
+const HANDOVER_GREETING: &str = "axwemulator open";
+const HANDOVER_ACK: &str = "ok";
+const HANDOVER_TIMEOUT: Duration = Duration::from_secs(1);
+
+// A unix domain socket in the private runtime directory of the user, or in the app storage when
+// there is none
+#[cfg(unix)]
+fn instance_path() -> Option<PathBuf> {
+    let directory = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .or_else(|| eframe::storage_dir(crate::app::APP_ID))?;
+    Some(directory.join("axwemulator-instance.sock"))
+}
+
+#[cfg(unix)]
+fn instance_name() -> io::Result<Name<'static>> {
+    use interprocess::local_socket::GenericFilePath;
+
+    let path = instance_path()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no directory for the socket"))?;
+    path.to_fs_name::<GenericFilePath>()
+}
+
+// A named pipe, which other users may not write to. The user name keeps the pipes of users apart.
+#[cfg(not(unix))]
+fn instance_name() -> io::Result<Name<'static>> {
+    use interprocess::local_socket::GenericNamespaced;
+
+    let user = std::env::var("USERNAME").unwrap_or_default();
+    format!("axwemulator-instance-{}", user).to_ns_name::<GenericNamespaced>()
+}
+
+#[cfg(unix)]
+fn bind() -> io::Result<LocalSocketListener> {
+    use interprocess::os::unix::local_socket::ListenerOptionsExt;
+
+    let bind = || {
+        ListenerOptions::new()
+            .name(instance_name()?)
+            .mode(0o600)
+            .create_sync()
+    };
+    match bind() {
+        // Left behind by an instance which did not exit cleanly, a running one took the rom already
+        Err(error) if error.kind() == io::ErrorKind::AddrInUse => {
+            if let Some(path) = instance_path() {
+                std::fs::remove_file(path)?;
+            }
+            bind()
+        }
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            if let Some(directory) = instance_path().as_deref().and_then(Path::parent) {
+                std::fs::create_dir_all(directory)?;
+            }
+            bind()
+        }
+        result => result,
+    }
+}
+
+#[cfg(not(unix))]
+fn bind() -> io::Result<LocalSocketListener> {
+    ListenerOptions::new().name(instance_name()?).create_sync()
+}
+
+// Returns true when a running instance accepted the rom
+pub fn hand_over(path: &Path) -> bool {
+    let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let (result_sender, result_receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let send = || -> io::Result<bool> {
+            let mut stream = LocalSocketStream::connect(instance_name()?)?;
+            writeln!(stream, "{} {}", HANDOVER_GREETING, path.display())?;
+            let mut reply = String::new();
+            BufReader::new(stream).read_line(&mut reply)?;
+            Ok(reply.trim() == HANDOVER_ACK)
+        };
+        let _ = result_sender.send(send().unwrap_or(false));
+    });
+    // Local sockets have no timeouts, an instance which does not answer in time counts as none
+    result_receiver
+        .recv_timeout(HANDOVER_TIMEOUT)
+        .unwrap_or(false)
+}
+
+// Accepts roms from later instances until the app exits. Fails if the socket cannot be created,
+// the app then simply runs without handover.
+pub fn listen(command_sender: CommandSender) {
+    let listener = match bind() {
+        Ok(listener) => listener,
+        Err(error) => {
+            log::warn!("unable to accept roms from other instances: {}", error);
+            return;
+        }
+    };
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            // A request which never ends must not hold up the next ones
+            let command_sender = command_sender.clone();
+            thread::spawn(move || {
+                if let Err(error) = accept(stream, &command_sender) {
+                    log::warn!("unable to accept rom from other instance: {}", error);
+                }
+            });
+        }
+    });
+}
+
+fn accept(stream: LocalSocketStream, command_sender: &CommandSender) -> io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    let Some(path) = request
+        .trim_end_matches(['\r', '\n'])
+        .strip_prefix(HANDOVER_GREETING)
+        .map(|path| PathBuf::from(path.trim_start()))
+    else {
+        return Ok(());
+    };
+    writeln!(reader.get_mut(), "{}", HANDOVER_ACK)?;
+    open_rom_file(&path, command_sender);
+    Ok(())
+}
+
+// Opens the rom with backend detection, like a rom picked in the selection screen
+pub fn open_rom_file(path: &Path, command_sender: &CommandSender) {
+    match read_rom_file(path) {
+        Ok(rom) => command_sender.send(AppCommand::OpenRom(rom)),
+        Err(error) => log::error!("unable to read rom {}: {}", path.display(), error),
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub use open_documents::{forward_open_documents, receive_open_documents};
+
+// The finder opens files with an open documents apple event instead of the command line, also when
+// it starts the app for them
+#[cfg(target_os = "macos")]
+mod open_documents {
+    use std::{path::PathBuf, sync::Mutex};
+
+    use objc2::{
+        ClassType, DeclaredClass, declare_class, msg_send, msg_send_id, mutability,
+        rc::Retained,
+        runtime::{NSObject, NSObjectProtocol},
+        sel,
+    };
+    use objc2_foundation::{
+        NSAppleEventDescriptor, NSAppleEventManager, NSNotification, NSNotificationCenter, NSString,
+    };
+
+    use super::open_rom_file;
+    use crate::command::CommandSender;
+
+    // Four character codes of the apple event and its list of files
+    const CORE_EVENT_CLASS: u32 = u32::from_be_bytes(*b"aevt");
+    const OPEN_DOCUMENTS: u32 = u32::from_be_bytes(*b"odoc");
+    const DIRECT_OBJECT: u32 = u32::from_be_bytes(*b"----");
+
+    // Files arrive before the app is created when the finder starts it, they wait for the sender
+    enum OpenedFiles {
+        Pending(Vec<PathBuf>),
+        Forwarded(CommandSender),
+    }
+
+    static OPENED_FILES: Mutex<OpenedFiles> = Mutex::new(OpenedFiles::Pending(Vec::new()));
+
+    declare_class!(
+        struct OpenDocumentsHandler;
+
+        unsafe impl ClassType for OpenDocumentsHandler {
+            type Super = NSObject;
+            type Mutability = mutability::InteriorMutable;
+            const NAME: &'static str = "AXWEmulatorOpenDocumentsHandler";
+        }
+
+        impl DeclaredClass for OpenDocumentsHandler {}
+
+        unsafe impl OpenDocumentsHandler {
+            // AppKit installs its own handlers when launching, so ours goes in right after
+            #[method(applicationWillFinishLaunching:)]
+            fn will_finish_launching(&self, _notification: &NSNotification) {
+                unsafe {
+                    let manager = NSAppleEventManager::sharedAppleEventManager();
+                    let _: () = msg_send![
+                        &manager,
+                        setEventHandler: self,
+                        andSelector: sel!(handleOpenDocuments:withReplyEvent:),
+                        forEventClass: CORE_EVENT_CLASS,
+                        andEventID: OPEN_DOCUMENTS
+                    ];
+                }
+            }
+
+            #[method(handleOpenDocuments:withReplyEvent:)]
+            fn handle_open_documents(
+                &self,
+                event: &NSAppleEventDescriptor,
+                _reply: &NSAppleEventDescriptor,
+            ) {
+                for path in document_paths(event) {
+                    let mut opened = OPENED_FILES.lock().unwrap();
+                    match &mut *opened {
+                        OpenedFiles::Pending(paths) => paths.push(path),
+                        OpenedFiles::Forwarded(command_sender) => {
+                            open_rom_file(&path, command_sender)
+                        }
+                    }
+                }
+            }
+        }
+
+        unsafe impl NSObjectProtocol for OpenDocumentsHandler {}
+    );
+
+    fn document_paths(event: &NSAppleEventDescriptor) -> Vec<PathBuf> {
+        unsafe {
+            let files: Option<Retained<NSAppleEventDescriptor>> =
+                msg_send_id![event, paramDescriptorForKeyword: DIRECT_OBJECT];
+            let Some(files) = files else {
+                return vec![];
+            };
+            (1..=files.numberOfItems())
+                .filter_map(|index| files.descriptorAtIndex(index)?.fileURLValue()?.path())
+                .map(|path| PathBuf::from(path.to_string()))
+                .collect()
+        }
+    }
+
+    // Has to run before the event loop starts, to also see the files the app was started for
+    pub fn receive_open_documents() {
+        let handler: Retained<OpenDocumentsHandler> =
+            unsafe { msg_send_id![super(OpenDocumentsHandler::alloc().set_ivars(())), init] };
+        let name = NSString::from_str("NSApplicationWillFinishLaunchingNotification");
+        unsafe {
+            NSNotificationCenter::defaultCenter().addObserver_selector_name_object(
+                &handler,
+                sel!(applicationWillFinishLaunching:),
+                Some(&name),
+                None,
+            );
+        }
+        // Neither the notification center nor the event manager keep the handler alive, it has
+        // to live as long as the app
+        std::mem::forget(handler);
+    }
+
+    // Opens the files received so far and hands the later ones over as they arrive
+    pub fn forward_open_documents(command_sender: CommandSender) {
+        let mut opened = OPENED_FILES.lock().unwrap();
+        if let OpenedFiles::Pending(paths) = &*opened {
+            for path in paths {
+                open_rom_file(path, &command_sender);
+            }
+        }
+        *opened = OpenedFiles::Forwarded(command_sender);
+    }
+}
@@ -0,0 +1,51 @@
+use axwemulator_core::backend::{
+    component::MemoryAddress,
+    trigger::{TriggerCondition, Watchpoint},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::app::SidepanelContent;
+
+// The debugging context of a rom, restored when the rom is launched again
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DebuggerLayout {
+    pub sidepanel: SidepanelContent,
+    pub inspector_component: String,
+    // None selects the bus
+    pub memory_component: Option<String>,
+    pub memory_scroll_offset: f32,
+    pub watchpoints: Vec<Watchpoint>,
+    pub value_triggers: Vec<(MemoryAddress, u8)>,
+    pub frozen_memory: Vec<(MemoryAddress, u8)>,
+}
+
+impl DebuggerLayout {
+    // Clock triggers are bound to a single run, so only memory triggers are kept
+    pub fn set_triggers<'a>(&mut self, conditions: impl Iterator<Item = &'a TriggerCondition>) {
+        self.watchpoints.clear();
+        self.value_triggers.clear();
+        for condition in conditions {
+            match *condition {
+                TriggerCondition::Write(watchpoint) => self.watchpoints.push(watchpoint),
+                TriggerCondition::Value(address, value) => {
+                    self.value_triggers.push((address, value))
+                }
+                TriggerCondition::Clock(_) => {}
+            }
+        }
+    }
+
+    pub fn triggers(&self) -> impl Iterator<Item = TriggerCondition> + '_ {
+        let writes = self
+            .watchpoints
+            .iter()
+            .copied()
+            .map(TriggerCondition::Write);
+        let values = self
+            .value_triggers
+            .iter()
+            .map(|(address, value)| TriggerCondition::Value(*address, *value));
+        writes.chain(values)
+    }
+}
@@ -1,5 +1,16 @@
 #![warn(clippy::all, rust_2018_idioms)]
 
+#[cfg(feature = "alloc-metrics")]
+pub mod allocations;
 pub mod app;
+pub mod appearance;
+pub mod command;
 pub mod components;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod instance;
+pub mod layout;
+#[cfg(feature = "rom-library")]
+pub mod library;
+pub mod overrides;
+pub mod session;
 pub mod utils;
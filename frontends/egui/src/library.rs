@@ -0,0 +1,22 @@
+// Roms shipped with the frontend, see assets/roms for their licenses
+pub struct LibraryRom {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub backend: &'static str,
+    pub data: &'static [u8],
+}
+
+pub const LIBRARY: &[LibraryRom] = &[
+    LibraryRom {
+        name: "Bounce",
+        description: "A ball bouncing off the screen edges",
+        backend: "Chip8",
+        data: include_bytes!("../assets/roms/bounce.ch8"),
+    },
+    LibraryRom {
+        name: "Keypad",
+        description: "Shows the last key pressed, try the keys 1-4, Q-R, A-F and Z-V",
+        backend: "Chip8",
+        data: include_bytes!("../assets/roms/keypad.ch8"),
+    },
+];
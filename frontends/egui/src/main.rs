@@ -4,8 +4,18 @@
 // When compiling natively:
 #[cfg(not(target_arch = "wasm32"))]
 fn main() -> eframe::Result {
+    use axwemulator_frontends_egui::instance;
+
     env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
 
+    // A rom given on the command line, e.g. by the file manager through the file association
+    let rom_path = std::env::args_os().nth(1).map(std::path::PathBuf::from);
+    if rom_path.as_deref().is_some_and(instance::hand_over) {
+        return Ok(());
+    }
+    #[cfg(target_os = "macos")]
+    instance::receive_open_documents();
+
     let native_options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([800.0, 400.0])
@@ -18,12 +28,17 @@ fn main() -> eframe::Result {
         ..Default::default()
     };
     eframe::run_native(
-        "eframe template",
+        axwemulator_frontends_egui::app::APP_ID,
         native_options,
         Box::new(|cc| {
-            Ok(Box::new(axwemulator_frontends_egui::app::EmulatorApp::new(
-                cc,
-            )))
+            let app = axwemulator_frontends_egui::app::EmulatorApp::new(cc);
+            instance::listen(app.command_sender());
+            #[cfg(target_os = "macos")]
+            instance::forward_open_documents(app.command_sender());
+            if let Some(path) = rom_path {
+                instance::open_rom_file(&path, &app.command_sender());
+            }
+            Ok(Box::new(app))
         }),
     )
 }
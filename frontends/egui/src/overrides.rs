@@ -0,0 +1,154 @@
+use std::collections::{BTreeMap, HashMap};
+
+use axwemulator_core::backend::options::{OptionKind, OptionValue, OptionValues, OptionsSchema};
+#[cfg(not(target_arch = "wasm32"))]
+use axwemulator_core::frontend::storage::StorageProvider;
+use serde::Deserialize;
+
+pub const OVERRIDES_FILE_NAME: &str = "overrides.toml";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum OverrideValue {
+    Bool(bool),
+    Int(i64),
+    Text(String),
+}
+
+impl OverrideValue {
+    fn to_option_value(&self, kind: &OptionKind) -> Option<OptionValue> {
+        match (kind, self) {
+            (OptionKind::Bool, OverrideValue::Bool(value)) => Some(OptionValue::Bool(*value)),
+            (OptionKind::Int { .. }, OverrideValue::Int(value)) => Some(OptionValue::Int(*value)),
+            // Switch banks are written as a number, e.g. 0b0000_1010
+            (OptionKind::DipSwitches { .. }, OverrideValue::Int(value)) => {
+                Some(OptionValue::Int(*value))
+            }
+            (OptionKind::Enum { .. }, OverrideValue::Text(value)) => {
+                Some(OptionValue::Enum(value.clone()))
+            }
+            (OptionKind::Color, OverrideValue::Text(value)) => parse_color(value),
+            _ => None,
+        }
+    }
+}
+
+// Colors are written as "#rrggbb" or "#rrggbbaa"
+fn parse_color(value: &str) -> Option<OptionValue> {
+    let hex = value.strip_prefix('#')?;
+    if hex.len() != 6 && hex.len() != 8 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok();
+    let alpha = if hex.len() == 8 { channel(3)? } else { 255 };
+    Some(OptionValue::Color((
+        channel(0)?,
+        channel(1)?,
+        channel(2)?,
+        alpha,
+    )))
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RomOverride {
+    // Restricts the override to a single backend, e.g. when a rom runs on Chip8 and SuperChip
+    pub backend: Option<String>,
+    #[serde(default)]
+    pub options: BTreeMap<String, OverrideValue>,
+    // Maps host keys to the keys the backend receives, both given by their egui key names
+    #[serde(default)]
+    pub keybindings: BTreeMap<String, String>,
+}
+
+impl RomOverride {
+    fn applies_to(&self, backend_name: &str) -> bool {
+        self.backend
+            .as_ref()
+            .is_none_or(|backend| backend == backend_name)
+    }
+}
+
+// Overrides keyed by the sha1 hash of the rom
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub struct Overrides(HashMap<String, RomOverride>);
+
+impl Overrides {
+    pub fn from_toml(content: &str) -> Result<Self, String> {
+        toml::from_str(content).map_err(|error| error.to_string())
+    }
+
+    pub fn from_json(content: &str) -> Result<Self, String> {
+        serde_json::from_str(content).map_err(|error| error.to_string())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(storage: &dyn StorageProvider) -> Self {
+        let content = match storage.read(OVERRIDES_FILE_NAME) {
+            Ok(Some(content)) => content,
+            Ok(None) => return Self::default(),
+            Err(error) => {
+                log::error!("unable to read overrides: {}", error);
+                return Self::default();
+            }
+        };
+        Self::from_toml(&String::from_utf8_lossy(&content)).unwrap_or_else(|error| {
+            log::error!(
+                "unable to parse {}: {}",
+                storage.location(OVERRIDES_FILE_NAME),
+                error
+            );
+            Self::default()
+        })
+    }
+
+    fn get(&self, backend_name: &str, rom_hash: &str) -> Option<&RomOverride> {
+        self.0
+            .get(rom_hash)
+            .filter(|rom_override| rom_override.applies_to(backend_name))
+    }
+
+    pub fn apply_options(
+        &self,
+        backend_name: &str,
+        rom_hash: &str,
+        schema: &OptionsSchema,
+        values: &mut OptionValues,
+    ) {
+        let Some(rom_override) = self.get(backend_name, rom_hash) else {
+            return;
+        };
+        for option in schema.options() {
+            let Some(value) = rom_override.options.get(option.key) else {
+                continue;
+            };
+            match value.to_option_value(&option.kind) {
+                Some(value) if option.accepts(&value) => values.set(option.key, value),
+                _ => log::warn!("ignoring invalid override for option {}", option.key),
+            }
+        }
+    }
+
+    pub fn keybindings(&self, backend_name: &str, rom_hash: &str) -> HashMap<egui::Key, egui::Key> {
+        let mut result = HashMap::new();
+        let Some(rom_override) = self.get(backend_name, rom_hash) else {
+            return result;
+        };
+        for (host_key, emulated_key) in &rom_override.keybindings {
+            match (
+                egui::Key::from_name(host_key),
+                egui::Key::from_name(emulated_key),
+            ) {
+                (Some(host_key), Some(emulated_key)) => {
+                    result.insert(host_key, emulated_key);
+                }
+                _ => log::warn!(
+                    "ignoring invalid keybinding {} = {}",
+                    host_key,
+                    emulated_key
+                ),
+            }
+        }
+        result
+    }
+}
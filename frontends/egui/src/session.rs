@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+
+// Keys of the app storage, the rom is kept since it may not be around on disk anymore
+pub const SESSION_ROM_KEY: &str = "session/rom";
+pub const SESSION_STATE_KEY: &str = "session/state";
+
+// The rom launched last, which the selection screen offers to continue with
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LastSession {
+    pub backend_name: String,
+    pub rom_name: Option<String>,
+    pub rom_hash: String,
+    // Stores the state when the rom stops and loads it on continue, otherwise it starts over
+    pub resume: bool,
+}
+
+impl LastSession {
+    pub fn label(&self) -> String {
+        match self.rom_name.as_ref() {
+            Some(rom_name) => format!("{} ({})", rom_name, self.backend_name),
+            None => self.backend_name.clone(),
+        }
+    }
+}
@@ -38,6 +38,8 @@ pub fn translate_egui_key_to_frontend_key(egui_key: egui::Key) -> Option<Keyboar
         egui::Key::Num7 => Some(KeyboardEventKey::Number7),
         egui::Key::Num8 => Some(KeyboardEventKey::Number8),
         egui::Key::Num9 => Some(KeyboardEventKey::Number9),
+        egui::Key::Space => Some(KeyboardEventKey::Space),
+        egui::Key::Enter => Some(KeyboardEventKey::Enter),
         _ => None,
     }
 }